@@ -38,6 +38,11 @@ impl OrthoCamera2D {
     pub fn aspect_ratio(&self) -> f32 {
         self.viewport_size.w as f32 / self.viewport_size.h as f32
     }
+    /// How many pixels one world unit spans on screen, at the current zoom.
+    pub fn pixels_per_world_unit(&self) -> f32 {
+        let scale = self.xform.scale.x.abs().max(self.xform.scale.y.abs());
+        scale * self.viewport_size.h as f32 / (self.frustum.top - self.frustum.bottom)
+    }
     pub fn proj_matrix(&self) -> Mat4<f32> {
         Mat4::orthographic_lh_no(self.frustum)
     }
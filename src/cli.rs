@@ -0,0 +1,107 @@
+// Command-line tools, run instead of the game when arguments are given.
+//
+// Usage: grisui-prelude-game <command> [args...]
+
+use std::path::Path;
+use std::fs::File;
+use paths::Paths;
+use shape::svg::ImportError;
+
+const USAGE: &str = "\
+Usage: grisui-prelude-game [<command> [args...]]
+
+Without a command, runs the game.
+
+Commands:
+    import-svg <file.svg>...    Converts each <path> of the SVG files into
+                                `.shape` files, saved in `res/shapes/`.
+    export-svg shape <name> <out.svg>
+                                Exports a shape from `res/shapes/`.
+    export-svg scene <name> <out.svg>
+                                Exports a scene from `res/scenes/`, with all
+                                of its shape instances.
+    help                        Prints this message.";
+
+/// Runs the command given by `args` (without the program name), and
+/// returns the process exit code.
+pub fn run(args: &[String]) -> i32 {
+    match args[0].as_str() {
+        "import-svg" => import_svg(&args[1..]),
+        "export-svg" => export_svg(&args[1..]),
+        "help" | "-h" | "--help" => {
+            println!("{}", USAGE);
+            0
+        },
+        cmd => {
+            eprintln!("Unknown command `{}`.\n\n{}", cmd, USAGE);
+            2
+        },
+    }
+}
+
+fn import_svg(files: &[String]) -> i32 {
+    if files.is_empty() {
+        eprintln!("import-svg: Missing SVG file path.\n\n{}", USAGE);
+        return 2;
+    }
+    let paths = Paths::new();
+    let mut status = 0;
+    for file in files {
+        match paths.import_svg(Path::new(file)) {
+            Ok(shapes) => for (name, _) in shapes {
+                println!("{}", paths.shape_path_from_name(&name).display());
+            },
+            Err(ImportError::Svg(e)) => {
+                eprintln!("import-svg: {}", e);
+                status = 1;
+            },
+            Err(e) => {
+                eprintln!("import-svg: {}: {}", file, e);
+                status = 1;
+            },
+        }
+    }
+    status
+}
+
+fn export_svg(args: &[String]) -> i32 {
+    if args.len() != 3 || (args[0] != "shape" && args[0] != "scene") {
+        eprintln!("export-svg: Expected `shape` or `scene`, a name, and an output file.\n\n{}", USAGE);
+        return 2;
+    }
+    let (kind, name, out) = (&args[0], &args[1], &args[2]);
+    let paths = Paths::new();
+    let shapes = paths.load_shapes();
+    let mut f = match File::create(out) {
+        Ok(f) => f,
+        Err(e) => {
+            eprintln!("export-svg: {}: {}", out, e);
+            return 1;
+        },
+    };
+    let result = if kind == "shape" {
+        match shapes.get(name) {
+            Some(shape) => shape.save_svg(&mut f, name),
+            None => {
+                eprintln!("export-svg: There is no shape named `{}`", name);
+                return 1;
+            },
+        }
+    } else {
+        let scenes = paths.load_scenes();
+        match scenes.get(name) {
+            Some(scene) => scene.save_svg(&mut f, &shapes, &scenes),
+            None => {
+                eprintln!("export-svg: There is no scene named `{}`", name);
+                return 1;
+            },
+        }
+    };
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("export-svg: {}: {}", out, e);
+            1
+        },
+    }
+}
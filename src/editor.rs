@@ -1,15 +1,24 @@
 use std::ptr;
+use std::mem;
 use std::fs::{self, File};
+use std::collections::{BTreeSet, HashMap};
 use gl;
 use gx::{Object, BufferUsage};
 use system::*;
-use v::{Vec3, Rgba, Mat4};
+use v::{Vec3, Vec4, Rgba, Mat4, Aabr};
 use camera::OrthoCamera2D;
 use mesh::{self, vertex_array, color_mesh::{self, Vertex}};
 use duration_ext::DurationExt;
 use text::Text;
 use font::FontID;
-use shape::Shape;
+use shape::{Shape, Primitive, GradientKind, GradientSpread, GradientStop, REFERENCE_PIXELS_PER_UNIT, gradient};
+use shape::boolean::{self, BooleanOp};
+use shape::{simplify, offset};
+use shape::edit::{PathPoint, PointKind, SegmentKind};
+use snap::{self, Snapping, SnapKind, SnapTargets, Snap, Guide};
+use history::{History, Checkpoint};
+use scene::{Scene, ShapeInstance, Source, Override, ZOrder};
+use xform::Xform2D;
 
 type ColorVertexArray = vertex_array::VertexArray<color_mesh::Program>;
 
@@ -51,6 +60,12 @@ impl From<Rgba<f32>> for Hsva<f32> {
     }
 }
 
+// The flattening tolerance for commands which turn curves into lines.
+// Fine enough to still look smooth when zooming in a few times.
+fn editing_tolerance() -> f32 {
+    ::shape::flattening_tolerance(REFERENCE_PIXELS_PER_UNIT * 4.)
+}
+
 fn rgba_from_hsva(hsva: Hsva<f32>) -> Rgba<f32> {
     let Hsva { h, s, v, a } = hsva;
     let c = v * s; // chroma
@@ -219,6 +234,169 @@ impl HsvaSliders {
 }
 
 
+/// What the left mouse button does.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum Tool {
+    /// Each click appends a line; dragging turns it into a quadratic curve.
+    Polygon,
+    /// Dragging draws a freehand subpath, smoothed into cubic curves on release.
+    Pencil,
+    /// Dragging draws a primitive from corner to corner.
+    Rect,
+    RoundedRect,
+    Ellipse,
+    /// Dragging draws a primitive from its center to its first vertex.
+    RegularPolygon,
+    Star,
+    /// Clicking selects nodes and control points, and dragging moves them.
+    Nodes,
+}
+
+impl Tool {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Tool::Polygon => "polygon",
+            Tool::Pencil => "pencil",
+            Tool::Rect => "rect",
+            Tool::RoundedRect => "rounded_rect",
+            Tool::Ellipse => "ellipse",
+            Tool::RegularPolygon => "regular_polygon",
+            Tool::Star => "star",
+            Tool::Nodes => "nodes",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "polygon" => Some(Tool::Polygon),
+            "pencil" => Some(Tool::Pencil),
+            "rect" => Some(Tool::Rect),
+            "rounded_rect" => Some(Tool::RoundedRect),
+            "ellipse" => Some(Tool::Ellipse),
+            "regular_polygon" => Some(Tool::RegularPolygon),
+            "star" => Some(Tool::Star),
+            "nodes" => Some(Tool::Nodes),
+            _ => None,
+        }
+    }
+}
+
+/// What dragging with the nodes tool does. Positions are in world space.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum NodeDrag {
+    /// Moves the selected points; `last` is where the picked one was moved last.
+    Points { last: Vec2<f32> },
+    /// Selects the nodes within a box.
+    Box { start: Vec2<f32>, end: Vec2<f32> },
+}
+
+/// What the editor edits. F9 switches between them.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum Mode {
+    /// The shape of the current tab.
+    Shape,
+    /// The shape instances of a scene.
+    Scene,
+}
+
+impl Mode {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Mode::Shape => "shape",
+            Mode::Scene => "scene",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "shape" => Some(Mode::Shape),
+            "scene" => Some(Mode::Scene),
+            _ => None,
+        }
+    }
+}
+
+/// What dragging the selected shape instance does, depending on where it was grabbed.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+enum Gizmo {
+    /// Moves the instance which was just placed.
+    Place,
+    Move,
+    /// Rotates around the instance's position, from the handle above it.
+    Rotate,
+    /// Scales from the instance's position, from a corner of its bounds.
+    Scale,
+}
+
+impl Gizmo {
+    fn name(&self) -> &'static str {
+        match *self {
+            Gizmo::Place => "place",
+            Gizmo::Move => "move",
+            Gizmo::Rotate => "rotate",
+            Gizmo::Scale => "scale",
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct InstanceDrag {
+    gizmo: Gizmo,
+    /// Where the mouse was pressed, in world space.
+    start: Vec2<f32>,
+    /// The instance's transform back then.
+    start_xform: Xform2D,
+}
+
+// The corners of the instance's bounds in world space, counter-clockwise
+// from the bottom left one in the instance's space. `aabr` is what
+// `ShapeInstance::local_aabr()` gives.
+fn instance_corners(aabr: Aabr<f32>, xform: &Xform2D) -> [Vec2<f32>; 4] {
+    let (min, max) = (aabr.min, aabr.max);
+    [
+        xform.transform_point(min),
+        xform.transform_point(Vec2::new(max.x, min.y)),
+        xform.transform_point(max),
+        xform.transform_point(Vec2::new(min.x, max.y)),
+    ]
+}
+
+// Whether `p`, in world space, is within the bounds of the instance, give
+// or take `margin` world units.
+fn instance_contains(aabr: Aabr<f32>, xform: &Xform2D, p: Vec2<f32>, margin: f32) -> bool {
+    let p = xform.inverse_transform_point(p);
+    let margin = xform.scale.map(|s| if s == 0. { 0. } else { margin / s.abs() });
+    p.x >= aabr.min.x - margin.x && p.x <= aabr.max.x + margin.x
+        && p.y >= aabr.min.y - margin.y && p.y <= aabr.max.y + margin.y
+}
+
+// How `:place` and the list of placeable things name a source: groups
+// are prefixed with `@`.
+fn source_label(source: &Source) -> String {
+    match *source {
+        Source::Shape(ref name) => name.clone(),
+        Source::Group(ref name) => format!("@{}", name),
+    }
+}
+
+fn source_from_label(label: &str) -> Source {
+    if label.starts_with('@') {
+        Source::Group(label[1 ..].to_owned())
+    } else {
+        Source::Shape(label.to_owned())
+    }
+}
+
+/// A shape open in a tab of the editor. For the current document, the
+/// camera, selection and history are those of `EditorSystem` instead, and
+/// are swapped with these when switching to another one.
+struct Document {
+    shape_name: String,
+    camera: OrthoCamera2D,
+    selection: BTreeSet<PathPoint>,
+    history: History,
+    /// The shape as it was last loaded or saved, to tell whether it has unsaved changes.
+    saved: Shape,
+}
+
 pub struct EditorSystem {
     camera: OrthoCamera2D,
     grid_origin_vertices: ColorVertexArray,
@@ -241,6 +419,59 @@ pub struct EditorSystem {
     hsva_sliding_speed: Hsva<f32>,
     is_entering_command: bool,
     command_text: Text,
+    tool: Tool,
+    /// How far, in screen pixels, the curves fitted by the pencil may stray from the samples.
+    pencil_error: f32,
+    /// World-space mouse positions recorded since the pencil was pressed.
+    pencil_samples: Vec<Vec2<f32>>,
+    /// Where the primitive being dragged started, in world space.
+    primitive_drag_start: Option<Vec2<f32>>,
+    /// In pixels, as for the stroke thickness.
+    rounded_rect_radius: f32,
+    regular_polygon_sides: u32,
+    star_points: u32,
+    /// The inner radius of stars, relative to their outer radius.
+    star_inner_ratio: f32,
+    /// Points of the working shape, selected with the nodes tool.
+    selection: BTreeSet<PathPoint>,
+    /// The point which the nodes tool would pick.
+    hovered_point: Option<PathPoint>,
+    node_drag: Option<NodeDrag>,
+    node_overlay_points: ColorVertexArray,
+    node_overlay_lines: ColorVertexArray,
+    snapping: Snapping,
+    /// The scene whose shape instances have their nodes snapped to.
+    snapping_scene_name: String,
+    /// The nodes which positions snap to, in world space, refreshed every tick.
+    snap_nodes: Vec<Vec2<f32>>,
+    /// Where the mouse was snapped to last, for the indicator.
+    last_snap: Option<Snap>,
+    snap_overlay_points: ColorVertexArray,
+    snap_overlay_lines: ColorVertexArray,
+    history: History,
+    next_merge_key: u32,
+    /// Merges the steps of the history recorded while a mouse button is held.
+    drag_merge_key: Option<u32>,
+    /// Merges the steps of the history recorded while the stroke thickness is changing.
+    thickness_merge_key: Option<u32>,
+    is_showing_history: bool,
+    history_text: Text,
+    documents: Vec<Document>,
+    current_document: usize,
+    tabs_text: Text,
+    mode: Mode,
+    /// The scene edited in scene mode.
+    scene_name: String,
+    /// The scenes opened in scene mode, as they were last loaded or saved.
+    saved_scenes: HashMap<String, Scene>,
+    selected_instance: Option<String>,
+    /// The instance which clicking would select.
+    hovered_instance: Option<String>,
+    instance_drag: Option<InstanceDrag>,
+    /// The shape or group which clicking on nothing places an instance of.
+    placed_source: Option<Source>,
+    scene_overlay_points: ColorVertexArray,
+    scene_overlay_lines: ColorVertexArray,
 }
 
 fn create_grid_vertices(color_mesh_gl_program: &color_mesh::Program, size: Extent2<usize>, color: Rgba<f32>, scale: Extent2<f32>) -> ColorVertexArray {
@@ -280,6 +511,19 @@ impl EditorSystem {
     const CAMERA_Z_ROTATION_SPEED_DEGREES: f32 = 90.;
     pub const CAMERA_NEAR: f32 = 0.; // It does work for an orthographic camera.
     pub const CAMERA_FAR: f32 = 1024.;
+    const DEFAULT_PENCIL_ERROR: f32 = 4.;
+    /// A freehand subpath which ends closer than this to its start, in screen pixels, gets closed.
+    const PENCIL_CLOSING_DISTANCE: f32 = 8.;
+    /// How close to a point the mouse must be for the nodes tool to pick it, in screen pixels.
+    const NODE_PICKING_DISTANCE: f32 = 8.;
+    /// How close to a target the mouse must be to snap to it, in screen pixels.
+    const SNAPPING_DISTANCE: f32 = 8.;
+    /// How many steps of the history are listed on each side of the current one.
+    const HISTORY_LINES: usize = 12;
+    /// How close to a handle of a gizmo the mouse must be to grab it, in screen pixels.
+    const GIZMO_PICKING_DISTANCE: f32 = 8.;
+    /// How far above the bounds of the selected instance its rotation handle is, in screen pixels.
+    const ROTATION_HANDLE_DISTANCE: f32 = 32.;
 
     pub fn new(color_mesh_gl_program: &color_mesh::Program, text_gl_program: &mesh::text::Program, viewport_size: Extent2<u32>) -> Self {
         let grid_vertices_1 = create_grid_vertices(color_mesh_gl_program, Extent2::new(8, 8), Rgba::white(), Extent2::one());
@@ -298,6 +542,12 @@ impl EditorSystem {
                 */
             ]
         );
+        let node_overlay_points = ColorVertexArray::from_vertices(&color_mesh_gl_program, "Node Overlay Points", BufferUsage::DynamicDraw, vec![]);
+        let node_overlay_lines = ColorVertexArray::from_vertices(&color_mesh_gl_program, "Node Overlay Lines", BufferUsage::DynamicDraw, vec![]);
+        let snap_overlay_points = ColorVertexArray::from_vertices(&color_mesh_gl_program, "Snap Overlay Points", BufferUsage::DynamicDraw, vec![]);
+        let snap_overlay_lines = ColorVertexArray::from_vertices(&color_mesh_gl_program, "Snap Overlay Lines", BufferUsage::DynamicDraw, vec![]);
+        let scene_overlay_points = ColorVertexArray::from_vertices(&color_mesh_gl_program, "Scene Overlay Points", BufferUsage::DynamicDraw, vec![]);
+        let scene_overlay_lines = ColorVertexArray::from_vertices(&color_mesh_gl_program, "Scene Overlay Lines", BufferUsage::DynamicDraw, vec![]);
         let text = Text::new(text_gl_program, "Editor Text");
         let hsva_sliders = HsvaSliders::new(&color_mesh_gl_program);
         let camera = OrthoCamera2D::new(viewport_size, Self::CAMERA_NEAR, Self::CAMERA_FAR);
@@ -319,6 +569,43 @@ impl EditorSystem {
             hsva_sliding_speed: Hsva { h: 0., s: 0., v: 0., a: 0. },
             is_entering_command: false,
             command_text: Text::new(text_gl_program, "Editor Command Text"),
+            tool: Tool::Polygon,
+            pencil_error: Self::DEFAULT_PENCIL_ERROR,
+            pencil_samples: vec![],
+            primitive_drag_start: None,
+            rounded_rect_radius: 24.,
+            regular_polygon_sides: 6,
+            star_points: 5,
+            star_inner_ratio: 0.5,
+            selection: BTreeSet::new(),
+            hovered_point: None,
+            node_drag: None,
+            node_overlay_points,
+            node_overlay_lines,
+            snapping: Snapping::default(),
+            snapping_scene_name: "default".to_owned(),
+            snap_nodes: vec![],
+            last_snap: None,
+            snap_overlay_points,
+            snap_overlay_lines,
+            history: History::default(),
+            next_merge_key: 0,
+            drag_merge_key: None,
+            thickness_merge_key: None,
+            is_showing_history: false,
+            history_text: Text::new(text_gl_program, "Editor History Text"),
+            documents: vec![],
+            current_document: 0,
+            tabs_text: Text::new(text_gl_program, "Editor Tabs Text"),
+            mode: Mode::Shape,
+            scene_name: "default".to_owned(),
+            saved_scenes: HashMap::new(),
+            selected_instance: None,
+            hovered_instance: None,
+            instance_drag: None,
+            placed_source: None,
+            scene_overlay_points,
+            scene_overlay_lines,
         }
     }
     pub const CLEAR_COLOR: Rgba<f32> = Rgba {
@@ -327,185 +614,1913 @@ impl EditorSystem {
     fn primary_color(&self) -> Rgba<f32> {
         rgba_from_hsva(self.hsva_sliders.hsva)
     }
-    fn on_enter_editor(&mut self, g: &Game) {
-        debug_assert!(!self.is_active);
-        self.is_active = true;
-        unsafe {
-            let Rgba { r, g, b, a } = Self::CLEAR_COLOR;
-            gl::ClearColor(r, g, b, a);
+    fn on_enter_editor(&mut self, g: &Game) {
+        debug_assert!(!self.is_active);
+        self.is_active = true;
+        unsafe {
+            let Rgba { r, g, b, a } = Self::CLEAR_COLOR;
+            gl::ClearColor(r, g, b, a);
+        }
+        g.platform.cursors.crosshair.set();
+        if self.documents.is_empty() {
+            let shape = g.loaded_shapes.borrow_mut().entry(self.working_shape_name.clone()).or_insert_with(Shape::new).clone();
+            let name = self.working_shape_name.clone();
+            self.open_document(&name, shape);
+        }
+        self.text.string = "If the universe is infinite,\nthere is an infinite number of worlds\nwhere this story takes place.".to_owned();
+        self.text.update_gl(&g.fonts.fonts[&self.font_id]);
+    }
+    fn on_leave_editor(&mut self, g: &Game) {
+        debug_assert!(self.is_active);
+        self.is_active = false;
+        unsafe {
+            gl::ClearColor(1., 1., 1., 1.);
+        }
+        g.platform.cursors.normal.set();
+    }
+
+    fn add_vertex_at_current_mouse_position(&mut self, g: &Game, is_down: bool) {
+        debug_assert!(self.is_active);
+        let mut loaded_shapes = g.loaded_shapes.borrow_mut();
+        let working_shape = match loaded_shapes.get_mut(&self.working_shape_name) {
+            Some(s) => s,
+            None => {
+                error!("Editor: No shape to edit");
+                return;
+            },
+        };
+        let is_drawing_subpath = match working_shape.path.subpaths.last() {
+            Some(subpath) => !subpath.is_closed,
+            None => false,
+        };
+
+        // Angles snap around the node before the one being placed.
+        let origin = working_shape.path.subpaths.last().filter(|_| is_drawing_subpath).and_then(|subpath| {
+            let n = subpath.cmds.len();
+            let back = if is_down { 1 } else { 2 };
+            if n >= back { Some(subpath.cmds[n - back].end()) } else { None }
+        });
+
+        if let Some(pos) = g.input.mouse_position() {
+            let position = self.snap_mouse(g, pos, origin);
+            use ::shape::{PathCmd, SubPath};
+            if is_down {
+                // Clicking after the current subpath was closed starts a new one (e.g a hole).
+                if !is_drawing_subpath {
+                    working_shape.path.subpaths.push(SubPath::new(position.into()));
+                } else {
+                    working_shape.path.current_subpath_mut().unwrap().cmds.push(PathCmd::Line { end: position.into() });
+                }
+            } else {
+                if !is_drawing_subpath {
+                    return;
+                }
+                let subpath = working_shape.path.current_subpath_mut().unwrap();
+                let cmd = if subpath.cmds.len() == 1 {
+                    subpath.cmds[0]
+                } else {
+                    subpath.cmds.pop().unwrap()
+                };
+                let ctrl = match cmd {
+                    PathCmd::Start(p) => p,
+                    PathCmd::Line { end } => end,
+                    _ => panic!(),
+                };
+                subpath.cmds.push(PathCmd::Quadratic { ctrl, end: position.into() });
+            }
+        }
+    }
+
+    // While the pencil is held, the samples are shown as a polyline in
+    // the working shape's last subpath; releasing it replaces that by the
+    // fitted curves.
+    fn press_pencil(&mut self, g: &Game) {
+        debug_assert!(self.is_active);
+        let mut loaded_shapes = g.loaded_shapes.borrow_mut();
+        let working_shape = match loaded_shapes.get_mut(&self.working_shape_name) {
+            Some(s) => s,
+            None => {
+                error!("Editor: No shape to edit");
+                return;
+            },
+        };
+        // Only where the stroke starts is snapped; the rest is freehand.
+        if let Some(pos) = g.input.mouse_position() {
+            let position = self.snap_mouse(g, pos, None);
+            self.pencil_samples = vec![position];
+            working_shape.path.subpaths.push(::shape::SubPath::new(position));
+        }
+    }
+    fn drag_pencil(&mut self, g: &Game, pos: Vec2<i32>) {
+        debug_assert!(self.is_active);
+        let position: Vec2<f32> = self.camera.viewport_to_world(pos, 0.).into();
+        let is_far_enough = match self.pencil_samples.last() {
+            Some(&last) => last.distance(position) * self.camera.pixels_per_world_unit() >= 1.,
+            None => return,
+        };
+        if !is_far_enough {
+            return;
+        }
+        self.pencil_samples.push(position);
+        let mut loaded_shapes = g.loaded_shapes.borrow_mut();
+        if let Some(subpath) = loaded_shapes.get_mut(&self.working_shape_name).and_then(|s| s.path.current_subpath_mut()) {
+            subpath.cmds.push(::shape::PathCmd::Line { end: position });
+        }
+    }
+    fn release_pencil(&mut self, g: &Game) {
+        debug_assert!(self.is_active);
+        let mut samples = ::std::mem::replace(&mut self.pencil_samples, vec![]);
+        let mut loaded_shapes = g.loaded_shapes.borrow_mut();
+        let working_shape = match loaded_shapes.get_mut(&self.working_shape_name) {
+            Some(s) => s,
+            None => return,
+        };
+        if samples.is_empty() {
+            return;
+        }
+        working_shape.path.subpaths.pop();
+        samples.dedup();
+        if samples.len() < 2 {
+            return;
+        }
+        let pixels_per_unit = self.camera.pixels_per_world_unit();
+        let (start, end) = (samples[0], samples[samples.len() - 1]);
+        let is_closed = samples.len() > 2 && start.distance(end) * pixels_per_unit <= Self::PENCIL_CLOSING_DISTANCE;
+        if is_closed {
+            *samples.last_mut().unwrap() = start;
+        }
+        let mut subpath = ::shape::SubPath::new(start);
+        subpath.cmds.extend(simplify::fit_cubics(&samples, is_closed, self.pencil_error / pixels_per_unit).into_iter().map(|c| {
+            ::shape::PathCmd::Cubic { ctrl0: c.ctrl0, ctrl1: c.ctrl1, end: c.end }
+        }));
+        subpath.is_closed = is_closed;
+        info!("Editor: Fitted {} samples with {} curves", samples.len(), subpath.cmds.len() - 1);
+        working_shape.path.subpaths.push(subpath);
+    }
+
+    // The primitive which the current tool draws when dragging from `from` to `to`.
+    fn dragged_primitive(&self, from: Vec2<f32>, to: Vec2<f32>) -> Primitive {
+        let (min, max) = (Vec2::partial_min(from, to), Vec2::partial_max(from, to));
+        let (radius, rotation_radians) = ((to - from).magnitude(), (to.y - from.y).atan2(to.x - from.x));
+        match self.tool {
+            Tool::Rect => Primitive::Rect { min, max },
+            Tool::RoundedRect => Primitive::RoundedRect { min, max, radius: self.rounded_rect_radius / REFERENCE_PIXELS_PER_UNIT },
+            Tool::Ellipse => Primitive::Ellipse { center: (min + max) / 2., radii: (max - min) / 2. },
+            Tool::RegularPolygon => Primitive::RegularPolygon {
+                center: from, radius, sides: self.regular_polygon_sides, rotation_radians,
+            },
+            Tool::Star => Primitive::Star {
+                center: from, outer_radius: radius, inner_radius: radius * self.star_inner_ratio,
+                points: self.star_points, rotation_radians,
+            },
+            Tool::Polygon | Tool::Pencil | Tool::Nodes => unreachable!(),
+        }
+    }
+    // As with the pencil, the primitive is shown in the working shape's last
+    // subpath while dragging.
+    fn press_primitive(&mut self, g: &Game) {
+        debug_assert!(self.is_active);
+        let mut loaded_shapes = g.loaded_shapes.borrow_mut();
+        let working_shape = match loaded_shapes.get_mut(&self.working_shape_name) {
+            Some(s) => s,
+            None => {
+                error!("Editor: No shape to edit");
+                return;
+            },
+        };
+        if let Some(pos) = g.input.mouse_position() {
+            let position = self.snap_mouse(g, pos, None);
+            self.primitive_drag_start = Some(position);
+            working_shape.path.subpaths.push(self.dragged_primitive(position, position).to_subpath());
+        }
+    }
+    fn drag_primitive(&mut self, g: &Game, pos: Vec2<i32>) {
+        debug_assert!(self.is_active);
+        let start = match self.primitive_drag_start {
+            Some(start) => start,
+            None => return,
+        };
+        let end = self.snap_mouse(g, pos, Some(start));
+        let subpath = self.dragged_primitive(start, end).to_subpath();
+        let mut loaded_shapes = g.loaded_shapes.borrow_mut();
+        if let Some(last) = loaded_shapes.get_mut(&self.working_shape_name).and_then(|s| s.path.current_subpath_mut()) {
+            *last = subpath;
+        }
+    }
+    fn release_primitive(&mut self, g: &Game) {
+        debug_assert!(self.is_active);
+        let start = match self.primitive_drag_start.take() {
+            Some(start) => start,
+            None => return,
+        };
+        let end = match g.input.mouse_position() {
+            Some(pos) => self.snap_mouse(g, pos, Some(start)),
+            None => start,
+        };
+        let mut loaded_shapes = g.loaded_shapes.borrow_mut();
+        let working_shape = match loaded_shapes.get_mut(&self.working_shape_name) {
+            Some(s) => s,
+            None => return,
+        };
+        // A click without dragging would only leave a speck.
+        if start.distance(end) * self.camera.pixels_per_world_unit() < 2. {
+            working_shape.path.subpaths.pop();
+            return;
+        }
+        let primitive = self.dragged_primitive(start, end);
+        info!("Editor: Added {:?}", primitive);
+        *working_shape.path.current_subpath_mut().unwrap() = primitive.to_subpath();
+    }
+
+    // The nodes tool picks the point under the mouse, or starts a selection
+    // box. Shift adds to the selection instead of replacing it.
+    fn press_nodes(&mut self, g: &Game) {
+        debug_assert!(self.is_active);
+        let position: Vec2<f32> = match g.input.mouse_position() {
+            Some(pos) => self.camera.viewport_to_world(pos, 0.).into(),
+            None => return,
+        };
+        let is_adding = g.input.key(Keycode::LShift).is_down() || g.input.key(Keycode::RShift).is_down();
+        match self.hovered_point {
+            Some(p) => {
+                if is_adding {
+                    if !self.selection.remove(&p) {
+                        self.selection.insert(p);
+                    }
+                } else if !self.selection.contains(&p) {
+                    self.selection.clear();
+                    self.selection.insert(p);
+                }
+                let last = g.loaded_shapes.borrow().get(&self.working_shape_name).and_then(|s| s.path.point(p));
+                self.node_drag = Some(NodeDrag::Points { last: last.unwrap_or(position) });
+            },
+            None => {
+                if !is_adding {
+                    self.selection.clear();
+                }
+                self.node_drag = Some(NodeDrag::Box { start: position, end: position });
+            },
+        };
+    }
+    // Alt breaks the tangents at a node apart, by moving one of its control
+    // points without the other.
+    fn drag_nodes(&mut self, g: &Game, pos: Vec2<i32>) {
+        debug_assert!(self.is_active);
+        let last = match self.node_drag {
+            Some(NodeDrag::Points { last }) => last,
+            Some(NodeDrag::Box { start, .. }) => {
+                self.node_drag = Some(NodeDrag::Box { start, end: self.camera.viewport_to_world(pos, 0.).into() });
+                return;
+            },
+            None => return,
+        };
+        let mut loaded_shapes = g.loaded_shapes.borrow_mut();
+        let path = match loaded_shapes.get_mut(&self.working_shape_name) {
+            Some(s) => &mut s.path,
+            None => return,
+        };
+        // Angles snap around the previous node, or around the node of a control point.
+        let origin = match self.selection.iter().next() {
+            Some(&p) if self.selection.len() == 1 => match p.kind {
+                PointKind::Node if p.cmd > 0 => path.point(PathPoint { cmd: p.cmd - 1, ..p }),
+                PointKind::Node => None,
+                _ => path.handle_node(p).and_then(|node| path.point(node)),
+            },
+            _ => None,
+        };
+        let position = self.snap_mouse(g, pos, origin);
+        self.node_drag = Some(NodeDrag::Points { last: position });
+        let is_breaking = g.input.key(Keycode::LAlt).is_down() || g.input.key(Keycode::RAlt).is_down();
+        // Turning the opposite control point only makes sense when it isn't being moved as well.
+        let keep_aligned = !is_breaking && self.selection.len() == 1;
+        let delta = position - last;
+        for &p in self.selection.iter() {
+            // Already carried by their node.
+            if path.handle_node(p).map_or(false, |node| self.selection.contains(&node)) {
+                continue;
+            }
+            if let Some(old) = path.point(p) {
+                path.move_point(p, old + delta, keep_aligned);
+            }
+        }
+    }
+    fn release_nodes(&mut self, g: &Game) {
+        debug_assert!(self.is_active);
+        let (start, end) = match self.node_drag.take() {
+            Some(NodeDrag::Box { start, end }) => (start, end),
+            _ => return,
+        };
+        let (min, max) = (Vec2::partial_min(start, end), Vec2::partial_max(start, end));
+        if let Some(working_shape) = g.loaded_shapes.borrow().get(&self.working_shape_name) {
+            let is_inside = |p: Vec2<f32>| p.x >= min.x && p.y >= min.y && p.x <= max.x && p.y <= max.y;
+            self.selection.extend(working_shape.path.points().into_iter()
+                .filter(|&(p, position)| p.kind == PointKind::Node && is_inside(position))
+                .map(|(p, _)| p));
+        }
+    }
+    // Refreshes the hovered point and the overlay of the nodes tool: nodes,
+    // control points with lines to their nodes, and the selection box.
+    fn update_node_overlay(&mut self, g: &Game) {
+        self.node_overlay_points.vertices.clear();
+        self.node_overlay_lines.vertices.clear();
+        let loaded_shapes = g.loaded_shapes.borrow();
+        let path = match (self.tool, loaded_shapes.get(&self.working_shape_name)) {
+            (Tool::Nodes, Some(s)) => &s.path,
+            _ => {
+                self.hovered_point = None;
+                return;
+            },
+        };
+        // Selections outlive the points they refer to when nodes are
+        // removed by other means.
+        self.selection.retain(|&p| path.point(p).is_some());
+        self.hovered_point = match (self.node_drag, g.input.mouse_position()) {
+            (None, Some(pos)) => {
+                let position = self.camera.viewport_to_world(pos, 0.).into();
+                path.nearest_point(position, Self::NODE_PICKING_DISTANCE / self.camera.pixels_per_world_unit())
+            },
+            (Some(NodeDrag::Points { .. }), _) => self.hovered_point,
+            _ => None,
+        };
+
+        let vertex = |p: Vec2<f32>, color| Vertex { position: Vec3::from(p), color };
+        let handle_color = Rgba::new(0.6, 0.6, 0.6, 1.);
+        for (p, position) in path.points() {
+            let color = if self.selection.contains(&p) {
+                Rgba::new(1., 0.8, 0., 1.)
+            } else if self.hovered_point == Some(p) {
+                Rgba::new(1., 0.4, 0., 1.)
+            } else if p.kind == PointKind::Node {
+                Rgba::white()
+            } else {
+                handle_color
+            };
+            self.node_overlay_points.vertices.push(vertex(position, color));
+            let subpath = &path.subpaths[p.subpath];
+            let nodes = match p.kind {
+                PointKind::Node => continue,
+                // A quadratic curve's control point gives the tangents at both of its nodes.
+                PointKind::Ctrl0 => match subpath.cmds[p.cmd] {
+                    ::shape::PathCmd::Quadratic { end, .. } => vec![subpath.cmds[p.cmd - 1].end(), end],
+                    _ => vec![subpath.cmds[p.cmd - 1].end()],
+                },
+                PointKind::Ctrl1 => vec![subpath.cmds[p.cmd].end()],
+            };
+            for node in nodes {
+                self.node_overlay_lines.vertices.push(vertex(node, handle_color));
+                self.node_overlay_lines.vertices.push(vertex(position, handle_color));
+            }
+        }
+        if let Some(NodeDrag::Box { start, end }) = self.node_drag {
+            let corners = [start, Vec2::new(end.x, start.y), end, Vec2::new(start.x, end.y)];
+            for i in 0 .. 4 {
+                self.node_overlay_lines.vertices.push(vertex(corners[i], Rgba::white()));
+                self.node_overlay_lines.vertices.push(vertex(corners[(i + 1) % 4], Rgba::white()));
+            }
+        }
+        self.node_overlay_points.update_and_resize_vbo();
+        self.node_overlay_lines.update_and_resize_vbo();
+    }
+
+    // Where the mouse at `pos` snaps to, in world space, remembering it for
+    // the indicator. `origin` is the previous node, for angle snapping.
+    // Holding Ctrl suspends snapping.
+    fn snap_mouse(&mut self, g: &Game, pos: Vec2<i32>, origin: Option<Vec2<f32>>) -> Vec2<f32> {
+        let position = self.camera.viewport_to_world(pos, 0.).into();
+        self.snap_position(g, position, origin)
+    }
+    // Same as `snap_mouse()`, for a position in world space.
+    fn snap_position(&mut self, g: &Game, position: Vec2<f32>, origin: Option<Vec2<f32>>) -> Vec2<f32> {
+        let pixels_per_unit = self.camera.pixels_per_world_unit();
+        self.last_snap = if Self::is_snapping_suspended(g) {
+            None
+        } else {
+            self.snapping.snap(position, &SnapTargets {
+                nodes: &self.snap_nodes,
+                origin,
+                grid_step: snap::grid_step(pixels_per_unit),
+                max_distance: Self::SNAPPING_DISTANCE / pixels_per_unit,
+            })
+        };
+        self.last_snap.map_or(position, |snap| snap.position())
+    }
+    fn is_snapping_suspended(g: &Game) -> bool {
+        g.input.key(Keycode::LCtrl).is_down() || g.input.key(Keycode::RCtrl).is_down()
+    }
+    fn update_snap_nodes(&mut self, g: &Game) {
+        self.snap_nodes.clear();
+        let loaded_shapes = g.loaded_shapes.borrow();
+        // In scene mode, only the scene is on screen, and the instance being
+        // dragged would snap to itself.
+        let (scene_name, dragged_instance) = match self.mode {
+            Mode::Shape => (&self.snapping_scene_name, None),
+            Mode::Scene => (&self.scene_name, self.selected_instance.as_ref().filter(|_| self.instance_drag.is_some())),
+        };
+        if let (Mode::Shape, Some(working_shape)) = (self.mode, loaded_shapes.get(&self.working_shape_name)) {
+            // What is being dragged would snap to itself.
+            let last = working_shape.path.subpaths.len().wrapping_sub(1);
+            let is_last_dragged = self.primitive_drag_start.is_some() || !self.pencil_samples.is_empty();
+            for (p, position) in working_shape.path.points() {
+                let is_dragged = (is_last_dragged && p.subpath == last) || (self.node_drag.is_some() && self.selection.contains(&p));
+                if p.kind == PointKind::Node && !is_dragged {
+                    self.snap_nodes.push(position);
+                }
+            }
+        }
+        let loaded_scenes = g.loaded_scenes.borrow();
+        if let Some(scene) = loaded_scenes.get(scene_name) {
+            for instance in scene.shape_instances.iter().filter(|i| Some(&i.name) != dragged_instance) {
+                for placement in instance.placements(&loaded_scenes) {
+                    let shape = match loaded_shapes.get(&placement.shape_name) {
+                        Some(shape) => shape,
+                        None => continue,
+                    };
+                    for subpath in shape.path.subpaths.iter() {
+                        for cmd in subpath.cmds.iter() {
+                            let p = placement.model * Vec4::from_point(Vec3::from(cmd.end()));
+                            self.snap_nodes.push(Vec2::new(p.x, p.y));
+                        }
+                    }
+                }
+            }
+        }
+    }
+    // Refreshes the guides, and the indicator of where the mouse snapped to:
+    // a point colored by the kind of snap, along with the line from the
+    // origin for angles, and the guide highlighted for guides.
+    fn update_snap_overlay(&mut self) {
+        self.snap_overlay_points.vertices.clear();
+        self.snap_overlay_lines.vertices.clear();
+        let vertex = |p: Vec2<f32>, color| Vertex { position: Vec3::from(p), color };
+        let color = |kind| match kind {
+            SnapKind::Grid => Rgba::white(),
+            SnapKind::Node => Rgba::new(1., 0.4, 0., 1.),
+            SnapKind::Angle => Rgba::new(1., 0., 1., 1.),
+            SnapKind::Guide => Rgba::new(0., 1., 1., 1.),
+        };
+        // The guides go across the visible part of the world, whichever way the camera turned.
+        let size = self.camera.viewport_size().map(|x| x as i32);
+        let corners: Vec<Vec2<f32>> = [(0, 0), (size.w, 0), (0, size.h), (size.w, size.h)].iter()
+            .map(|&(x, y)| self.camera.viewport_to_world(Vec2::new(x, y), 0.).into())
+            .collect();
+        let min = corners.iter().fold(corners[0], |a, &b| Vec2::partial_min(a, b));
+        let max = corners.iter().fold(corners[0], |a, &b| Vec2::partial_max(a, b));
+        let snapped_guide = match self.last_snap {
+            Some(Snap::Guide { guide, .. }) => Some(guide),
+            _ => None,
+        };
+        if self.snapping.is_enabled(SnapKind::Guide) {
+            for (i, guide) in self.snapping.guides.iter().enumerate() {
+                let mut c = color(SnapKind::Guide);
+                if snapped_guide != Some(i) {
+                    c.a = 0.4;
+                }
+                let (a, b) = match *guide {
+                    Guide::Horizontal { y } => (Vec2::new(min.x, y), Vec2::new(max.x, y)),
+                    Guide::Vertical { x } => (Vec2::new(x, min.y), Vec2::new(x, max.y)),
+                };
+                self.snap_overlay_lines.vertices.push(vertex(a, c));
+                self.snap_overlay_lines.vertices.push(vertex(b, c));
+            }
+        }
+        if let Some(snap) = self.last_snap {
+            let c = color(snap.kind());
+            self.snap_overlay_points.vertices.push(vertex(snap.position(), c));
+            if let Snap::Angle { origin, position } = snap {
+                self.snap_overlay_lines.vertices.push(vertex(origin, c));
+                self.snap_overlay_lines.vertices.push(vertex(position, c));
+            }
+        }
+        self.snap_overlay_points.update_and_resize_vbo();
+        self.snap_overlay_lines.update_and_resize_vbo();
+    }
+
+    // Every action which changes shapes or scenes is recorded as a step of
+    // the history, by comparing them with a checkpoint taken before it.
+    fn checkpoint(&self, g: &Game) -> Checkpoint {
+        Checkpoint {
+            shapes: g.loaded_shapes.borrow().clone(),
+            scenes: g.loaded_scenes.borrow().clone(),
+        }
+    }
+    fn record(&mut self, g: &Game, label: &str, merge_key: Option<u32>, before: &Checkpoint) {
+        let after = self.checkpoint(g);
+        if self.history.record(label, merge_key, before, &after) {
+            *g.quit_warning.borrow_mut() = None;
+        }
+    }
+    // Drags, keys and the like happen all the time, and only change the
+    // shape or the scene being edited, so only those are compared.
+    fn drag_checkpoint(&self, g: &Game) -> Checkpoint {
+        let mut checkpoint = Checkpoint { shapes: HashMap::new(), scenes: HashMap::new() };
+        if let Some(shape) = g.loaded_shapes.borrow().get(&self.working_shape_name) {
+            checkpoint.shapes.insert(self.working_shape_name.clone(), shape.clone());
+        }
+        if let Some(scene) = g.loaded_scenes.borrow().get(&self.scene_name) {
+            checkpoint.scenes.insert(self.scene_name.clone(), scene.clone());
+        }
+        checkpoint
+    }
+    // Compares the same documents as `before`, even if e.g another tab
+    // became the current one meanwhile.
+    fn record_drag(&mut self, g: &Game, label: &str, merge_key: Option<u32>, before: &Checkpoint) {
+        let after = {
+            let (loaded_shapes, loaded_scenes) = (g.loaded_shapes.borrow(), g.loaded_scenes.borrow());
+            Checkpoint {
+                shapes: before.shapes.keys().filter_map(|name| loaded_shapes.get(name).map(|s| (name.clone(), s.clone()))).collect(),
+                scenes: before.scenes.keys().filter_map(|name| loaded_scenes.get(name).map(|s| (name.clone(), s.clone()))).collect(),
+            }
+        };
+        if self.history.record(label, merge_key, before, &after) {
+            *g.quit_warning.borrow_mut() = None;
+        }
+    }
+    fn new_merge_key(&mut self) -> u32 {
+        self.next_merge_key = self.next_merge_key.wrapping_add(1);
+        self.next_merge_key
+    }
+    // Whatever was being dragged refers to a shape which is going away.
+    fn cancel_drags(&mut self) {
+        self.pencil_samples.clear();
+        self.primitive_drag_start = None;
+        self.node_drag = None;
+        self.instance_drag = None;
+    }
+    // What dragging the mouse does, for the history.
+    fn mouse_action_label(&self, is_right_button: bool) -> String {
+        match (self.mode, self.instance_drag) {
+            (Mode::Scene, Some(drag)) => format!("{} instance", drag.gizmo.name()),
+            (Mode::Scene, None) => "scene".to_owned(),
+            (Mode::Shape, _) if is_right_button => "fill gradient".to_owned(),
+            (Mode::Shape, _) => format!("{} tool", self.tool.name()),
+        }
+    }
+    fn undo(&mut self, g: &Game) {
+        self.cancel_drags();
+        self.selection.clear();
+        match self.history.undo(&mut g.loaded_shapes.borrow_mut(), &mut g.loaded_scenes.borrow_mut()) {
+            Some(label) => {
+                info!("Editor: Undid `{}`", label);
+                *g.quit_warning.borrow_mut() = None;
+            },
+            None => info!("Editor: Nothing to undo"),
+        };
+        self.close_documents_without_shapes(g);
+    }
+    fn redo(&mut self, g: &Game) {
+        self.cancel_drags();
+        self.selection.clear();
+        match self.history.redo(&mut g.loaded_shapes.borrow_mut(), &mut g.loaded_scenes.borrow_mut()) {
+            Some(label) => {
+                info!("Editor: Redid `{}`", label);
+                *g.quit_warning.borrow_mut() = None;
+            },
+            None => info!("Editor: Nothing to redo"),
+        };
+        self.close_documents_without_shapes(g);
+    }
+
+    fn swap_current_document(&mut self) {
+        let document = &mut self.documents[self.current_document];
+        mem::swap(&mut self.camera, &mut document.camera);
+        mem::swap(&mut self.selection, &mut document.selection);
+        mem::swap(&mut self.history, &mut document.history);
+    }
+    fn switch_to_document(&mut self, i: usize) {
+        if i == self.current_document || i >= self.documents.len() {
+            return;
+        }
+        self.cancel_drags();
+        self.swap_current_document();
+        self.current_document = i;
+        self.swap_current_document();
+        self.working_shape_name = self.documents[i].shape_name.clone();
+    }
+    // Opens a new tab for the shape named `name`, and switches to it.
+    fn open_document(&mut self, name: &str, saved: Shape) {
+        let camera = OrthoCamera2D::new(self.camera.viewport_size(), Self::CAMERA_NEAR, Self::CAMERA_FAR);
+        self.documents.push(Document {
+            shape_name: name.to_owned(),
+            camera,
+            selection: BTreeSet::new(),
+            history: History::default(),
+            saved,
+        });
+        if self.documents.len() == 1 {
+            self.current_document = 0;
+            self.swap_current_document();
+            self.working_shape_name = name.to_owned();
+        } else {
+            let i = self.documents.len() - 1;
+            self.switch_to_document(i);
+        }
+    }
+    // Switches to the tab of the shape named `name`, which a command just
+    // changed, opening one if needed. `previous` is the shape as it was
+    // loaded before, if it was.
+    fn show_document(&mut self, name: &str, previous: Option<Shape>) {
+        match self.document_index(name) {
+            Some(i) => self.switch_to_document(i),
+            None => self.open_document(name, previous.unwrap_or_else(Shape::new)),
+        };
+        self.selection.clear();
+    }
+    fn document_index(&self, name: &str) -> Option<usize> {
+        self.documents.iter().position(|d| d.shape_name == name)
+    }
+    fn is_document_unsaved(&self, g: &Game, i: usize) -> bool {
+        let document = &self.documents[i];
+        g.loaded_shapes.borrow().get(&document.shape_name) != Some(&document.saved)
+    }
+    // `:close` closes the current tab, unless its shape has unsaved changes;
+    // `:close!` closes it anyway, putting the shape back as it was saved.
+    fn close_current_document(&mut self, g: &Game, is_forced: bool) {
+        if self.documents.len() <= 1 {
+            error!("Editor: Can't close the last tab");
+            return;
+        }
+        let closing = self.current_document;
+        if self.is_document_unsaved(g, closing) {
+            if !is_forced {
+                error!("Editor: `{}` has unsaved changes; save them with `:w`, or discard them with `:close!`", self.working_shape_name);
+                return;
+            }
+            let document = &self.documents[closing];
+            g.loaded_shapes.borrow_mut().insert(document.shape_name.clone(), document.saved.clone());
+        }
+        self.remove_document(closing);
+    }
+    // Closes tab `i`, switching to the next one (or the previous one, for
+    // the last) if it is the current one. There must be another tab.
+    fn remove_document(&mut self, i: usize) {
+        if i == self.current_document {
+            let next = if i + 1 < self.documents.len() { i + 1 } else { i - 1 };
+            self.switch_to_document(next);
+        }
+        self.documents.remove(i);
+        if self.current_document > i {
+            self.current_document -= 1;
+        }
+    }
+    // Undoing or redoing a step of one tab can unload a shape which is open
+    // in another, e.g one which was imported; such tabs are closed. The last
+    // tab is kept, and draws nothing until its shape comes back.
+    fn close_documents_without_shapes(&mut self, g: &Game) {
+        for i in (0 .. self.documents.len()).rev() {
+            let is_loaded = g.loaded_shapes.borrow().contains_key(&self.documents[i].shape_name);
+            if !is_loaded && self.documents.len() > 1 {
+                info!("Editor: Closed `{}`, which isn't loaded anymore", self.documents[i].shape_name);
+                self.remove_document(i);
+            }
+        }
+    }
+    // Shows the open shapes side by side, the current one in brackets, with
+    // a star after those which have unsaved changes. Also lets `QuitSystem`
+    // know about them. In scene mode, shows the scene instead, followed by
+    // the loaded shapes, the one to place in brackets.
+    fn update_tabs_text(&mut self, g: &Game) {
+        let mut unsaved_document_names = vec![];
+        let mut tabs = vec![];
+        for (i, document) in self.documents.iter().enumerate() {
+            let is_unsaved = self.is_document_unsaved(g, i);
+            if is_unsaved {
+                unsaved_document_names.push(document.shape_name.clone());
+            }
+            let name = format!("{}{}", document.shape_name, if is_unsaved { "*" } else { "" });
+            tabs.push(if i == self.current_document { format!("[{}]", name) } else { format!(" {} ", name) });
+        }
+        let mut unsaved_scene_names: Vec<&String> = {
+            let loaded_scenes = g.loaded_scenes.borrow();
+            self.saved_scenes.iter().filter(|&(name, saved)| loaded_scenes.get(name).map_or(false, |loaded| loaded != saved)).map(|(name, _)| name).collect()
+        };
+        unsaved_scene_names.sort();
+        unsaved_document_names.extend(unsaved_scene_names.iter().map(|name| format!("{}.scene", name)));
+        *g.unsaved_document_names.borrow_mut() = unsaved_document_names;
+        let string = match self.mode {
+            Mode::Shape => tabs.join(" "),
+            Mode::Scene => {
+                let is_unsaved = unsaved_scene_names.contains(&&self.scene_name);
+                let shapes: Vec<String> = self.placeable_sources(g).into_iter().map(|source| match self.placed_source {
+                    Some(ref placed) if *placed == source => format!("[{}]", source_label(&source)),
+                    _ => source_label(&source),
+                }).collect();
+                format!("[{}.scene{}]  shapes: {}", self.scene_name, if is_unsaved { "*" } else { "" }, shapes.join(" "))
+            },
+        };
+        if string != self.tabs_text.string {
+            self.tabs_text.string = string;
+            self.tabs_text.update_gl(&g.fonts.fonts[&FontID::Debug]);
+        }
+    }
+    // Lists the steps around the current one, which is marked; the ones
+    // after it were undone.
+    fn update_history_text(&mut self, g: &Game) {
+        let mut string = format!("History ({} KiB)\n", (self.history.memory_used() + 1023) / 1024);
+        let done = self.history.done_labels();
+        let undone = self.history.undone_labels();
+        let skipped = done.len().saturating_sub(Self::HISTORY_LINES);
+        if skipped > 0 {
+            string += &format!("  ({} more)\n", skipped);
+        }
+        for (i, label) in done.iter().enumerate().skip(skipped) {
+            string += &format!("{} {}\n", if i + 1 == done.len() { ">" } else { " " }, label);
+        }
+        for label in undone.iter().take(Self::HISTORY_LINES) {
+            string += &format!("  ({})\n", label);
+        }
+        if undone.len() > Self::HISTORY_LINES {
+            string += &format!("  ({} more)\n", undone.len() - Self::HISTORY_LINES);
+        }
+        if string != self.history_text.string {
+            self.history_text.string = string;
+            self.history_text.update_gl(&g.fonts.fonts[&FontID::Debug]);
+        }
+    }
+
+    fn set_fill_gradient_extrema_to_current_mouse_position(&mut self, g: &Game, is_down: bool) {
+        debug_assert!(self.is_active);
+        let mut loaded_shapes = g.loaded_shapes.borrow_mut();
+        let working_shape = match loaded_shapes.get_mut(&self.working_shape_name) {
+            Some(s) => s,
+            None => {
+                error!("Editor: No shape to edit");
+                return;
+            },
+        };
+
+        if let Some(pos) = g.input.mouse_position() {
+            let origin = if is_down { None } else { Some(working_shape.style.fill_gradient.start) };
+            let position = self.snap_mouse(g, pos, origin);
+            if is_down {
+                working_shape.style.fill_gradient.start = position.into();
+            } else {
+                working_shape.style.fill_gradient.end = position.into();
+            }
+        }
+    }
+
+    fn deleted_selected(&mut self, g: &Game) {
+        debug_assert!(self.is_active);
+        let mut loaded_shapes = g.loaded_shapes.borrow_mut();
+        let working_shape = match loaded_shapes.get_mut(&self.working_shape_name) {
+            Some(s) => s,
+            None => {
+                error!("Editor: No shape to edit");
+                return;
+            },
+        };
+        if self.selection.is_empty() {
+            working_shape.path.subpaths.clear();
+            return;
+        }
+        // From the last node on, so that deleting one doesn't move the others.
+        for p in self.selection.iter().rev().filter(|p| p.kind == PointKind::Node) {
+            working_shape.path.delete_node(p.subpath, p.cmd);
+        }
+        self.selection.clear();
+    }
+
+    fn end_polygon(&mut self, g: &Game) {
+        debug_assert!(self.is_active);
+        let mut loaded_shapes = g.loaded_shapes.borrow_mut();
+        let working_shape = match loaded_shapes.get_mut(&self.working_shape_name) {
+            Some(s) => s,
+            None => {
+                error!("Editor: No shape to edit");
+                return;
+            },
+        };
+        if let Some(subpath) = working_shape.path.current_subpath_mut() {
+            subpath.is_closed = true;
+        }
+    }
+    fn toggle_select_all(&mut self, g: &Game) {
+        debug_assert!(self.is_active);
+        if !self.selection.is_empty() {
+            self.selection.clear();
+            return;
+        }
+        if let Some(working_shape) = g.loaded_shapes.borrow().get(&self.working_shape_name) {
+            self.selection = working_shape.path.points().into_iter()
+                .map(|(p, _)| p)
+                .filter(|p| p.kind == PointKind::Node)
+                .collect();
+        }
+    }
+    fn execute_current_command(&mut self, g: &Game) {
+        let cmd = self.command_text.string.clone();
+        self.execute_command(g, &cmd);
+    }
+    fn execute_command(&mut self, g: &Game, cmd: &str) {
+        let line = Self::command_components(cmd);
+        if line.is_empty() {
+            return;
+        }
+        let mut before = self.checkpoint(g);
+        let cmd = &line[0];
+        let args = &line[1..];
+        match *cmd {
+            "w" => if self.is_about_scene(args) {
+                self.save_scene(g, args);
+            } else {
+                self.save_working_shape_with_name(g, args);
+            },
+            "e" => match args.get(0) {
+                Some(arg) if arg.ends_with(".scene") => self.open_scene(g, arg.trim_end_matches(".scene")),
+                _ => self.load_working_shape_by_name(g, args),
+            },
+            "import" => self.import_svg(g, args),
+            "export" => self.export_working_shape_as_svg(g, args),
+            "export-scene" => self.export_scene_as_svg(g, args),
+            "gradient" => self.edit_working_shape_gradient(g, args),
+            "stroke" => self.edit_working_shape_stroke(g, args),
+            "union" | "intersection" | "difference" | "xor" => self.combine_working_shape(g, BooleanOp::from_name(cmd).unwrap(), args),
+            "simplify" | "fit" | "offset" | "cubics" => self.reshape_working_shape(g, cmd, args),
+            "tool" => self.select_tool(args),
+            "node" => self.edit_selected_nodes(g, args),
+            "snap" => self.configure_snapping(args),
+            "guide" => self.edit_guides(g, args),
+            "undo" => self.undo(g),
+            "redo" => self.redo(g),
+            "history" => self.is_showing_history = !self.is_showing_history,
+            "close" => self.close_current_document(g, false),
+            "close!" => self.close_current_document(g, true),
+            "mode" => match args.get(0).map(|arg| Mode::from_name(arg)) {
+                Some(Some(mode)) => self.set_mode(g, mode),
+                Some(None) => error!("Editor: Unknown mode `{}`: expected `shape` or `scene`", args[0]),
+                None => {
+                    let mode = if self.mode == Mode::Shape { Mode::Scene } else { Mode::Shape };
+                    self.set_mode(g, mode);
+                },
+            },
+            "place" => self.set_placed_source(g, args),
+            "rename" => self.rename_selected_instance(g, args),
+            "group" => self.group_instances(g, args),
+            "override" => self.override_in_selected_instance(g, args),
+            "z" => self.edit_z_order(g, args),
+            _ => error!("Editor: `{}` is not recognized as an editor command", cmd),
+        };
+        // Saving and closing change which shapes are open, not the shapes
+        // themselves.
+        let is_recorded = match *cmd {
+            "undo" | "redo" | "w" | "close" | "close!" | "mode" | "place" => false,
+            _ => true,
+        };
+        if is_recorded {
+            // Loading the shape of a new tab isn't undoable either, since
+            // undoing it would leave the tab without its shape.
+            for document in self.documents.iter() {
+                if !before.shapes.contains_key(&document.shape_name) {
+                    if let Some(shape) = g.loaded_shapes.borrow().get(&document.shape_name) {
+                        before.shapes.insert(document.shape_name.clone(), shape.clone());
+                    }
+                }
+            }
+            self.record(g, &format!(":{}", line.join(" ")), None, &before);
+        }
+    }
+    fn command_components(mut cmd: &str) -> Vec<&str> {
+        if cmd.is_empty() {
+            return vec![];
+        }
+        if cmd.chars().nth(0).unwrap() == ':' {
+            cmd = &cmd[1..];
+        }
+        cmd.lines().next().unwrap().split_whitespace().collect()
+    }
+    fn load_working_shape_by_name(&mut self, g: &Game, args: &[&str]) {
+        if args.is_empty() {
+            error!("Editor: Not enough arguments for command 'e': missing shape name.");
+            return;
+        }
+        let name = args[0];
+        self.mode = Mode::Shape;
+        if let Some(i) = self.document_index(name) {
+            self.switch_to_document(i);
+            return;
+        }
+        let path = g.paths.shape_path_from_name(&name);
+        let shape = match File::open(&path) {
+            Ok(mut f) => match Shape::load(&mut f) {
+                Ok(shape) => shape,
+                Err(::shape::LoadError::Parse(e)) => {
+                    error!("Editor: Can't load shape: {}", e.in_file(path));
+                    return;
+                },
+                Err(e) => {
+                    error!("Editor: Can't load shape `{}`: {}", path.display(), e);
+                    return;
+                },
+            },
+            Err(_) => Shape::new(),
+        };
+        g.loaded_shapes.borrow_mut().insert(name.to_owned(), shape.clone());
+        self.open_document(name, shape);
+    }
+    fn import_svg(&mut self, g: &Game, args: &[&str]) {
+        if args.is_empty() {
+            error!("Editor: Not enough arguments for command 'import': missing SVG file path.");
+            return;
+        }
+        let shapes = match g.paths.import_svg(::std::path::Path::new(args[0])) {
+            Ok(shapes) => shapes,
+            Err(::shape::svg::ImportError::Svg(e)) => {
+                error!("Editor: Can't import SVG: {}", e);
+                return;
+            },
+            Err(e) => {
+                error!("Editor: Can't import `{}`: {}", args[0], e);
+                return;
+            },
+        };
+        for (name, shape) in shapes {
+            info!("Editor: Imported shape `{}`", name);
+            let previous = g.loaded_shapes.borrow_mut().insert(name.clone(), shape);
+            self.show_document(&name, previous);
+        }
+    }
+    fn export_working_shape_as_svg(&mut self, g: &Game, args: &[&str]) {
+        if args.is_empty() {
+            error!("Editor: Not enough arguments for command 'export': missing SVG file path.");
+            return;
+        }
+        let loaded_shapes = g.loaded_shapes.borrow();
+        let shape = match loaded_shapes.get(&self.working_shape_name) {
+            Some(s) => s,
+            None => {
+                error!("Editor: No shape to export");
+                return;
+            },
+        };
+        let result = File::create(args[0]).and_then(|mut f| shape.save_svg(&mut f, &self.working_shape_name));
+        match result {
+            Ok(()) => info!("Editor: Exported `{}` to `{}`", self.working_shape_name, args[0]),
+            Err(e) => error!("Editor: Can't export to `{}`: {}", args[0], e),
+        };
+    }
+    fn export_scene_as_svg(&mut self, g: &Game, args: &[&str]) {
+        if args.len() < 2 {
+            error!("Editor: Not enough arguments for command 'export-scene': expected a scene name and an SVG file path.");
+            return;
+        }
+        let loaded_scenes = g.loaded_scenes.borrow();
+        let scene = match loaded_scenes.get(args[0]) {
+            Some(s) => s,
+            None => {
+                error!("Editor: There is no scene named `{}`", args[0]);
+                return;
+            },
+        };
+        let result = File::create(args[1]).and_then(|mut f| scene.save_svg(&mut f, &g.loaded_shapes.borrow(), &loaded_scenes));
+        match result {
+            Ok(()) => info!("Editor: Exported scene `{}` to `{}`", args[0], args[1]),
+            Err(e) => error!("Editor: Can't export to `{}`: {}", args[1], e),
+        };
+    }
+    // `:gradient linear|radial|conic` sets the kind;
+    // `:gradient pad|repeat|reflect` sets the spread;
+    // `:gradient stop <offset>` sets a stop to the primary color;
+    // `:gradient remove <offset>` removes a stop;
+    // `:gradient none` removes all stops.
+    fn edit_working_shape_gradient(&mut self, g: &Game, args: &[&str]) {
+        if args.is_empty() {
+            error!("Editor: Not enough arguments for command 'gradient': expected a kind, a spread, `stop`, `remove` or `none`.");
+            return;
+        }
+        let color = self.primary_color();
+        let mut loaded_shapes = g.loaded_shapes.borrow_mut();
+        let gradient = match loaded_shapes.get_mut(&self.working_shape_name) {
+            Some(s) => &mut s.style.fill_gradient,
+            None => {
+                error!("Editor: No shape to edit");
+                return;
+            },
+        };
+        let offset = || match args.get(1).map(|s| s.parse::<f32>()) {
+            Some(Ok(offset)) => Some(offset),
+            _ => {
+                error!("Editor: `gradient {}` expects an offset between 0 and 1", args[0]);
+                None
+            },
+        };
+        if let Some(kind) = GradientKind::from_name(args[0]) {
+            gradient.kind = kind;
+        } else if let Some(spread) = GradientSpread::from_name(args[0]) {
+            gradient.spread = spread;
+        } else {
+            match args[0] {
+                "stop" => if let Some(offset) = offset() {
+                    gradient.set_stop(GradientStop { offset, color });
+                },
+                "remove" => if let Some(offset) = offset() {
+                    gradient.stops.retain(|s| s.offset != offset);
+                },
+                "none" => gradient.stops.clear(),
+                _ => {
+                    error!("Editor: `{}` is not a gradient kind, spread or operation", args[0]);
+                    return;
+                },
+            };
+        }
+        let stops = gradient.stops.iter().map(|s| s.offset.to_string()).collect::<Vec<_>>();
+        info!("Editor: Gradient is now {} ({}), with stops at [{}]", gradient.kind.name(), gradient.spread.name(), stops.join(", "));
+    }
+    // `:stroke stop <offset>` sets a stop of the stroke gradient to the primary color;
+    // `:stroke remove <offset>` removes a stop;
+    // `:stroke none` removes all stops, going back to the plain stroke color;
+    // `:stroke node color` sets the color at the last node to the primary color;
+    // `:stroke node thickness <t>` sets the thickness at the last node;
+    // `:stroke node clear` removes the overrides at the last node;
+    // `:stroke taper <start> <end>` narrows open strokes down over these lengths, in pixels.
+    fn edit_working_shape_stroke(&mut self, g: &Game, args: &[&str]) {
+        if args.is_empty() {
+            error!("Editor: Not enough arguments for command 'stroke': expected `stop`, `remove`, `none`, `node` or `taper`.");
+            return;
+        }
+        let color = self.primary_color();
+        let mut loaded_shapes = g.loaded_shapes.borrow_mut();
+        let working_shape = match loaded_shapes.get_mut(&self.working_shape_name) {
+            Some(s) => s,
+            None => {
+                error!("Editor: No shape to edit");
+                return;
+            },
+        };
+        let number = |i: usize, what: &str| match args.get(i).map(|s| s.parse::<f32>()) {
+            Some(Ok(x)) => Some(x),
+            _ => {
+                error!("Editor: `stroke {}` expects {}", args[..i].join(" "), what);
+                None
+            },
+        };
+        match args[0] {
+            "stop" => if let Some(offset) = number(1, "an offset between 0 and 1") {
+                gradient::set_stop(&mut working_shape.style.stroke_gradient, GradientStop { offset, color });
+            },
+            "remove" => if let Some(offset) = number(1, "an offset between 0 and 1") {
+                working_shape.style.stroke_gradient.retain(|s| s.offset != offset);
+            },
+            "none" => working_shape.style.stroke_gradient.clear(),
+            "taper" => {
+                let (start, end) = match number(1, "two lengths in pixels").and_then(|start| number(2, "a second length in pixels").map(|end| (start, end))) {
+                    Some((start, end)) => (start.max(0.), end.max(0.)),
+                    None => return,
+                };
+                working_shape.style.stroke_taper_start = start;
+                working_shape.style.stroke_taper_end = end;
+                info!("Editor: Stroke now tapers over {} pixels at the start and {} at the end", start, end);
+                return;
+            },
+            "node" => {
+                let subpath = match working_shape.path.current_subpath_mut() {
+                    Some(s) => s,
+                    None => {
+                        error!("Editor: `stroke node` needs a node to edit");
+                        return;
+                    },
+                };
+                let i = subpath.cmds.len() - 1;
+                match args.get(1).cloned() {
+                    Some("color") => subpath.node_style_mut(i).stroke_color = Some(color),
+                    Some("thickness") => if let Some(t) = number(2, "a thickness") {
+                        subpath.node_style_mut(i).stroke_thickness = Some(t);
+                    },
+                    Some("clear") => *subpath.node_style_mut(i) = Default::default(),
+                    _ => {
+                        error!("Editor: `stroke node` expects `color`, `thickness` or `clear`");
+                        return;
+                    },
+                };
+                info!("Editor: Node {} now has {:?}", i, subpath.node_style(i));
+                return;
+            },
+            _ => {
+                error!("Editor: `{}` is not a stroke operation", args[0]);
+                return;
+            },
+        };
+        let stops = working_shape.style.stroke_gradient.iter().map(|s| s.offset.to_string()).collect::<Vec<_>>();
+        info!("Editor: Stroke gradient now has stops at [{}]", stops.join(", "));
+    }
+    // `:union|intersection|difference|xor <shape> [<result>]` combines the
+    // working shape's fill with that of another loaded shape, in local
+    // coordinates. The result keeps the working shape's style, and replaces
+    // it unless a name is given for it, in which case its tab becomes the current one.
+    fn combine_working_shape(&mut self, g: &Game, op: BooleanOp, args: &[&str]) {
+        if args.is_empty() {
+            error!("Editor: Not enough arguments for command '{}': missing shape name.", op.name());
+            return;
+        }
+        let result = {
+            let loaded_shapes = g.loaded_shapes.borrow();
+            let (working_shape, other) = match (loaded_shapes.get(&self.working_shape_name), loaded_shapes.get(args[0])) {
+                (Some(a), Some(b)) => (a, b),
+                (None, _) => {
+                    error!("Editor: No shape to edit");
+                    return;
+                },
+                (_, None) => {
+                    error!("Editor: There is no loaded shape named `{}`", args[0]);
+                    return;
+                },
+            };
+            let path = boolean::combine(
+                &working_shape.path, working_shape.style.fill_rule,
+                &other.path, other.style.fill_rule,
+                op, editing_tolerance()
+            );
+            Shape { path, style: working_shape.style.clone() }
+        };
+        info!("Editor: The {} of `{}` and `{}` has {} subpaths", op.name(), self.working_shape_name, args[0], result.path.subpaths.len());
+        let name = args.get(1).map_or(self.working_shape_name.clone(), |name| name.to_string());
+        let previous = g.loaded_shapes.borrow_mut().insert(name.clone(), result);
+        self.show_document(&name, previous);
+    }
+    // `:tool polygon` selects the click-to-add-nodes tool;
+    // `:tool pencil [<px>]` selects the freehand tool, optionally with how
+    // far, in screen pixels, its curves may stray from the mouse;
+    // `:tool rect|ellipse` selects a tool which drags primitives from corner to corner;
+    // `:tool rounded_rect [<radius>]` does too, optionally with the corner radius in pixels;
+    // `:tool regular_polygon [<sides>]` drags polygons from their center;
+    // `:tool star [<points> [<inner ratio>]]` drags stars from their center, optionally
+    // with the inner radius relative to the outer one;
+    // `:tool nodes` selects the tool which picks and drags nodes and control points.
+    fn select_tool(&mut self, args: &[&str]) {
+        let tool = match args.get(0).map(|name| (name, Tool::from_name(name))) {
+            Some((_, Some(tool))) => tool,
+            Some((name, None)) => {
+                error!("Editor: `{}` is not a tool", name);
+                return;
+            },
+            None => {
+                error!("Editor: Not enough arguments for command 'tool': expected `polygon`, `pencil`, `rect`, `rounded_rect`, `ellipse`, `regular_polygon`, `star` or `nodes`.");
+                return;
+            },
+        };
+        fn setting<T: ::std::str::FromStr + PartialOrd>(args: &[&str], i: usize, min: T, what: &str) -> Result<Option<T>, ()> {
+            match args.get(i).map(|s| s.parse::<T>()) {
+                None => Ok(None),
+                Some(Ok(x)) if x >= min => Ok(Some(x)),
+                _ => {
+                    error!("Editor: `tool {}` expects {}", args[0], what);
+                    Err(())
+                },
+            }
+        }
+        let is_ok = match tool {
+            Tool::Pencil => setting(args, 1, 0_f32, "a distance in pixels").map(|x| if let Some(x) = x {
+                self.pencil_error = x;
+            }),
+            Tool::RoundedRect => setting(args, 1, 0_f32, "a radius in pixels").map(|x| if let Some(x) = x {
+                self.rounded_rect_radius = x;
+            }),
+            Tool::RegularPolygon => setting(args, 1, 3, "a number of sides, at least 3").map(|x| if let Some(x) = x {
+                self.regular_polygon_sides = x;
+            }),
+            Tool::Star => setting(args, 1, 2, "a number of points, at least 2").and_then(|points| {
+                setting(args, 2, 0_f32, "an inner ratio, at least 0").map(|ratio| {
+                    self.star_points = points.unwrap_or(self.star_points);
+                    self.star_inner_ratio = ratio.unwrap_or(self.star_inner_ratio);
+                })
+            }),
+            Tool::Polygon | Tool::Rect | Tool::Ellipse | Tool::Nodes => Ok(()),
+        }.is_ok();
+        if !is_ok {
+            return;
+        }
+        self.tool = tool;
+        match tool {
+            Tool::Pencil => info!("Editor: Tool is now pencil, within {} pixels", self.pencil_error),
+            Tool::RoundedRect => info!("Editor: Tool is now rounded_rect, with a radius of {} pixels", self.rounded_rect_radius),
+            Tool::RegularPolygon => info!("Editor: Tool is now regular_polygon, with {} sides", self.regular_polygon_sides),
+            Tool::Star => info!("Editor: Tool is now star, with {} points and an inner ratio of {}", self.star_points, self.star_inner_ratio),
+            _ => info!("Editor: Tool is now {}", tool.name()),
+        };
+    }
+    // `:simplify [<px>]` drops the nodes which are less than <px> away from the outline (2 by default);
+    // `:fit [<px>]` replaces the path by cubic curves, at most <px> away from it (2 by default);
+    // `:offset <px>` grows the fill by <px>, or shrinks it if negative;
+    // `:cubics` turns quadratic curves into cubic ones.
+    // Distances are in pixels, as for the stroke thickness.
+    fn reshape_working_shape(&mut self, g: &Game, cmd: &str, args: &[&str]) {
+        let px = match args.get(0).map(|s| s.parse::<f32>()) {
+            Some(Ok(x)) => Some(x),
+            None => None,
+            Some(Err(_)) => {
+                error!("Editor: `{}` expects a distance in pixels", cmd);
+                return;
+            },
+        };
+        let distance = |default: f32| px.unwrap_or(default) / REFERENCE_PIXELS_PER_UNIT;
+        let mut loaded_shapes = g.loaded_shapes.borrow_mut();
+        let working_shape = match loaded_shapes.get_mut(&self.working_shape_name) {
+            Some(s) => s,
+            None => {
+                error!("Editor: No shape to edit");
+                return;
+            },
+        };
+        let path = match cmd {
+            "simplify" => simplify::simplify(&working_shape.path, distance(2.), editing_tolerance()),
+            "fit" => simplify::fit_curves(&working_shape.path, distance(2.), editing_tolerance()),
+            "offset" => {
+                if px.is_none() {
+                    error!("Editor: Not enough arguments for command 'offset': missing distance.");
+                    return;
+                }
+                offset::offset(&working_shape.path, working_shape.style.fill_rule, distance(0.), editing_tolerance())
+            },
+            "cubics" => working_shape.path.to_cubics(),
+            _ => unreachable!(),
+        };
+        let node_count = |path: &::shape::Path| path.subpaths.iter().map(|s| s.cmds.len()).sum::<usize>();
+        info!("Editor: `{}` went from {} to {} nodes", cmd, node_count(&working_shape.path), node_count(&path));
+        working_shape.path = path;
+        self.selection.clear();
+    }
+    // `:node insert [<t>]` splits the selected segments at <t> (0.5 by default);
+    // `:node delete` removes the selected nodes, keeping the outline as close as possible;
+    // `:node align` makes the tangents at the selected nodes line up;
+    // `:node line|quadratic|cubic` converts the selected segments.
+    // A segment is selected when the nodes at both of its ends are, or when
+    // it starts at the only selected node.
+    fn edit_selected_nodes(&mut self, g: &Game, args: &[&str]) {
+        let op = match args.get(0) {
+            Some(&op) if op == "insert" || op == "delete" || op == "align" || SegmentKind::from_name(op).is_some() => op,
+            Some(op) => {
+                error!("Editor: `{}` is not a node operation", op);
+                return;
+            },
+            None => {
+                error!("Editor: Not enough arguments for command 'node': expected `insert`, `delete`, `align`, `line`, `quadratic` or `cubic`.");
+                return;
+            },
+        };
+        let t = match (op, args.get(1).map(|s| s.parse::<f32>())) {
+            ("insert", None) => 0.5,
+            ("insert", Some(Ok(t))) if t > 0. && t < 1. => t,
+            ("insert", _) => {
+                error!("Editor: `node insert` expects a parameter between 0 and 1, exclusive");
+                return;
+            },
+            _ => 0.,
+        };
+        let mut loaded_shapes = g.loaded_shapes.borrow_mut();
+        let path = match loaded_shapes.get_mut(&self.working_shape_name) {
+            Some(s) => &mut s.path,
+            None => {
+                error!("Editor: No shape to edit");
+                return;
+            },
+        };
+        let nodes: Vec<PathPoint> = self.selection.iter().cloned().filter(|p| p.kind == PointKind::Node).collect();
+        let mut segments = vec![];
+        for (i, subpath) in path.subpaths.iter().enumerate() {
+            for cmd in 1 .. subpath.cmds.len() + 1 {
+                let is_selected = match path.segment_ends(i, cmd) {
+                    Some((a, b)) => (nodes.contains(&a) && nodes.contains(&b)) || nodes == [a],
+                    None => false,
+                };
+                if is_selected {
+                    segments.push((i, cmd));
+                }
+            }
+        }
+        // From the last one on, so that editing one doesn't move the others.
+        match op {
+            "insert" => for &(subpath, cmd) in segments.iter().rev() {
+                path.insert_node(subpath, cmd, t);
+            },
+            "delete" => for p in nodes.iter().rev() {
+                path.delete_node(p.subpath, p.cmd);
+            },
+            "align" => for p in nodes.iter() {
+                path.align_tangents(p.subpath, p.cmd);
+            },
+            kind => for &(subpath, cmd) in segments.iter() {
+                path.convert_segment(subpath, cmd, SegmentKind::from_name(kind).unwrap());
+            },
+        };
+        match op {
+            "align" => info!("Editor: Aligned the tangents at {} nodes", nodes.len()),
+            "delete" => info!("Editor: Deleted {} nodes", nodes.len()),
+            _ => info!("Editor: `node {}` changed {} segments", op, segments.len()),
+        };
+        if op == "insert" || op == "delete" {
+            self.selection.clear();
+        }
+    }
+    // `:snap grid|nodes|angles|guides [on|off]` toggles or sets snapping to that kind of target
+    // (also F1, F2, F3 and F4; holding Ctrl suspends snapping altogether);
+    // `:snap step <degrees>` sets the angle increment;
+    // `:snap scene <name>` sets the scene whose shape instances have their nodes snapped to.
+    fn configure_snapping(&mut self, args: &[&str]) {
+        match (args.get(0).map(|s| *s), args.get(1)) {
+            (Some("step"), Some(degrees)) => match degrees.parse::<f32>() {
+                Ok(degrees) if degrees > 0. => {
+                    self.snapping.angle_step_radians = degrees.to_radians();
+                    info!("Editor: Angles now snap every {} degrees", degrees);
+                },
+                _ => error!("Editor: `snap step` expects a positive angle in degrees"),
+            },
+            (Some("scene"), Some(name)) => {
+                self.snapping_scene_name = name.to_string();
+                info!("Editor: Now snapping to the nodes of scene `{}`", name);
+            },
+            (Some(name), state) => {
+                let kind = match SnapKind::from_name(name) {
+                    Some(kind) => kind,
+                    None => {
+                        error!("Editor: `{}` is not something to snap to", name);
+                        return;
+                    },
+                };
+                let is_enabled = match state.map(|s| *s) {
+                    None => !self.snapping.is_enabled(kind),
+                    Some("on") => true,
+                    Some("off") => false,
+                    Some(state) => {
+                        error!("Editor: `snap {}` expects `on` or `off`, not `{}`", name, state);
+                        return;
+                    },
+                };
+                self.snapping.set_enabled(kind, is_enabled);
+                info!("Editor: Snapping to {} is now {}", kind.name(), if is_enabled { "on" } else { "off" });
+            },
+            (None, _) => {
+                let enabled: Vec<_> = SnapKind::ALL.iter().filter(|&&k| self.snapping.is_enabled(k)).map(|k| k.name()).collect();
+                info!("Editor: Snapping to {:?}, every {} degrees", enabled, self.snapping.angle_step_radians.to_degrees());
+            },
+        };
+    }
+    // `:guide h|v [<coordinate>]` adds a horizontal or vertical guide, at
+    // the given world coordinate or through where the mouse snaps to;
+    // `:guide clear` removes all guides.
+    fn edit_guides(&mut self, g: &Game, args: &[&str]) {
+        let is_horizontal = match args.get(0) {
+            Some(&"h") => true,
+            Some(&"v") => false,
+            Some(&"clear") => {
+                self.snapping.guides.clear();
+                return;
+            },
+            _ => {
+                error!("Editor: Not enough arguments for command 'guide': expected `h`, `v` or `clear`.");
+                return;
+            },
+        };
+        let at = match args.get(1).map(|s| s.parse::<f32>()) {
+            Some(Ok(x)) => x,
+            Some(Err(_)) => {
+                error!("Editor: `guide` expects a coordinate in world units");
+                return;
+            },
+            None => match g.input.mouse_position() {
+                Some(pos) => {
+                    let p = self.snap_mouse(g, pos, None);
+                    if is_horizontal { p.y } else { p.x }
+                },
+                None => return,
+            },
+        };
+        let guide = if is_horizontal { Guide::Horizontal { y: at } } else { Guide::Vertical { x: at } };
+        info!("Editor: Added {:?}", guide);
+        self.snapping.guides.push(guide);
+    }
+    fn save_working_shape_with_name(&mut self, g: &Game, args: &[&str]) {
+        let name = if args.is_empty() {
+            self.working_shape_name.to_owned()
+        } else {
+            args[0].to_owned()
+        };
+        let is_renaming = name != self.working_shape_name;
+        if is_renaming && self.document_index(&name).is_some() {
+            error!("Editor: `{}` is open in another tab", name);
+            return;
+        }
+        let path = g.paths.shape_path_from_name(&name);
+        let shape = match g.loaded_shapes.borrow().get(&self.working_shape_name) {
+            Some(shape) => shape.clone(),
+            None => {
+                error!("Editor: No shape to save");
+                return;
+            },
+        };
+        shape.save(&mut File::create(path).unwrap()).unwrap();
+        let mut loaded_shapes = g.loaded_shapes.borrow_mut();
+        let document = &mut self.documents[self.current_document];
+        if is_renaming {
+            // The tab now edits the copy; the original goes back to how it was
+            // saved, and the history, which is about the original, is dropped.
+            loaded_shapes.insert(document.shape_name.clone(), document.saved.clone());
+            document.shape_name = name.clone();
+            self.working_shape_name = name.clone();
+            self.history = History::default();
+        }
+        document.saved = shape.clone();
+        loaded_shapes.insert(name, shape);
+    }
+
+
+    fn set_mode(&mut self, g: &Game, mode: Mode) {
+        self.cancel_drags();
+        self.mode = mode;
+        if mode == Mode::Scene {
+            let name = self.scene_name.clone();
+            self.open_scene(g, &name);
+        }
+        info!("Editor: Now editing the {}", match mode {
+            Mode::Shape => format!("shape `{}`", self.working_shape_name),
+            Mode::Scene => format!("scene `{}`", self.scene_name),
+        });
+    }
+    // Loads the scene from disk unless it is loaded already, and edits it in scene mode.
+    fn open_scene(&mut self, g: &Game, name: &str) {
+        let mut loaded_scenes = g.loaded_scenes.borrow_mut();
+        if !loaded_scenes.contains_key(name) {
+            let path = Some(g.paths.scene_path_from_name(name))
+                .filter(|path| path.exists())
+                .or_else(|| Some(g.paths.legacy_scene_path_from_name(name)).filter(|path| path.exists()));
+            let scene = match path {
+                Some(path) => match g.paths.load_scene(&path) {
+                    Ok(scene) => scene,
+                    Err(e) => {
+                        error!("Editor: Can't load scene `{}`: {}", path.display(), e);
+                        return;
+                    },
+                },
+                None => Scene::default(),
+            };
+            loaded_scenes.insert(name.to_owned(), scene);
+        }
+        if !self.saved_scenes.contains_key(name) {
+            self.saved_scenes.insert(name.to_owned(), loaded_scenes[name].clone());
+        }
+        if self.scene_name != name {
+            self.cancel_drags();
+            self.selected_instance = None;
+            self.scene_name = name.to_owned();
+        }
+        self.mode = Mode::Scene;
+    }
+    // `:w` in scene mode, or `:w name.scene`, saves the scene. As for shapes,
+    // saving it under another name goes on editing the copy.
+    fn save_scene(&mut self, g: &Game, args: &[&str]) {
+        let name = match args.get(0) {
+            Some(arg) => arg.trim_end_matches(".scene").to_owned(),
+            None => self.scene_name.clone(),
+        };
+        let mut loaded_scenes = g.loaded_scenes.borrow_mut();
+        let is_renaming = name != self.scene_name;
+        if is_renaming && loaded_scenes.contains_key(&name) {
+            error!("Editor: There is already a scene named `{}`", name);
+            return;
+        }
+        let scene = match loaded_scenes.get(&self.scene_name) {
+            Some(scene) => scene.clone(),
+            None => {
+                error!("Editor: No scene to save");
+                return;
+            },
+        };
+        let path = g.paths.scene_path_from_name(&name);
+        if let Err(e) = File::create(&path).and_then(|mut f| scene.save(&mut f)) {
+            error!("Editor: Can't save scene `{}`: {}", path.display(), e);
+            return;
+        }
+        if is_renaming {
+            if let Some(saved) = self.saved_scenes.get(&self.scene_name) {
+                loaded_scenes.insert(self.scene_name.clone(), saved.clone());
+            }
+            self.scene_name = name.clone();
+        }
+        self.saved_scenes.insert(name.clone(), scene.clone());
+        loaded_scenes.insert(name, scene);
+    }
+    // Whether `:e` and `:w` with these arguments are about the scene rather than the shape.
+    fn is_about_scene(&self, args: &[&str]) -> bool {
+        match args.get(0) {
+            Some(arg) => arg.ends_with(".scene"),
+            None => self.mode == Mode::Scene,
         }
-        g.platform.cursors.crosshair.set();
-        self.text.string = "If the universe is infinite,\nthere is an infinite number of worlds\nwhere this story takes place.".to_owned();
-        self.text.update_gl(&g.fonts.fonts[&self.font_id]);
     }
-    fn on_leave_editor(&mut self, g: &Game) {
-        debug_assert!(self.is_active);
-        self.is_active = false;
-        unsafe {
-            gl::ClearColor(1., 1., 1., 1.);
+    // The frontmost instance of the scene which is under `p`, in world space.
+    fn instance_under(&self, g: &Game, p: Vec2<f32>) -> Option<String> {
+        let loaded_scenes = g.loaded_scenes.borrow();
+        let loaded_shapes = g.loaded_shapes.borrow();
+        let scene = loaded_scenes.get(&self.scene_name)?;
+        let margin = Self::GIZMO_PICKING_DISTANCE / self.camera.pixels_per_world_unit();
+        scene.z_order().into_iter().rev().map(|i| &scene.shape_instances[i]).find(|instance| {
+            instance.local_aabr(&loaded_shapes, &loaded_scenes).map_or(false, |aabr| instance_contains(aabr, &instance.xform, p, margin))
+        }).map(|instance| instance.name.clone())
+    }
+    // The corners of the selected instance's bounds, and its rotation handle, in world space.
+    fn selected_instance_handles(&self, g: &Game) -> Option<([Vec2<f32>; 4], Vec2<f32>)> {
+        let loaded_scenes = g.loaded_scenes.borrow();
+        let loaded_shapes = g.loaded_shapes.borrow();
+        let scene = loaded_scenes.get(&self.scene_name)?;
+        let instance = &scene.shape_instances[scene.instance_index(self.selected_instance.as_ref()?)?];
+        let corners = instance_corners(instance.local_aabr(&loaded_shapes, &loaded_scenes)?, &instance.xform);
+        let top = (corners[2] + corners[3]) / 2.;
+        let center = (corners[0] + corners[2]) / 2.;
+        let up = if top == center { instance.xform.up() } else { (top - center).normalized() };
+        Some((corners, top + up * Self::ROTATION_HANDLE_DISTANCE / self.camera.pixels_per_world_unit()))
+    }
+    // The part of the selected instance's gizmo which is under `p`, in world space.
+    fn gizmo_under(&self, g: &Game, p: Vec2<f32>) -> Option<Gizmo> {
+        let (corners, rotation_handle) = self.selected_instance_handles(g)?;
+        let max_distance = Self::GIZMO_PICKING_DISTANCE / self.camera.pixels_per_world_unit();
+        if rotation_handle.distance(p) <= max_distance {
+            return Some(Gizmo::Rotate);
         }
-        g.platform.cursors.normal.set();
+        if corners.iter().any(|c| c.distance(p) <= max_distance) {
+            return Some(Gizmo::Scale);
+        }
+        if self.instance_under(g, p).as_ref() == self.selected_instance.as_ref() {
+            return Some(Gizmo::Move);
+        }
+        None
     }
-
-    fn add_vertex_at_current_mouse_position(&mut self, g: &Game, is_down: bool) {
+    // Grabs the gizmo of the selected instance, or else selects the instance
+    // under the mouse, or else places a new one if there is a shape to place.
+    fn press_scene(&mut self, g: &Game) {
         debug_assert!(self.is_active);
-        let mut loaded_shapes = g.loaded_shapes.borrow_mut();
-        let working_shape = match loaded_shapes.get_mut(&self.working_shape_name) {
-            Some(s) => s,
-            None => {
-                error!("Editor: No shape to edit");
-                return;
+        let pos = match g.input.mouse_position() {
+            Some(pos) => pos,
+            None => return,
+        };
+        let mouse = self.camera.viewport_to_world(pos, 0.).into();
+        let gizmo = match self.gizmo_under(g, mouse) {
+            Some(gizmo) => gizmo,
+            None => match self.instance_under(g, mouse) {
+                Some(name) => {
+                    self.selected_instance = Some(name);
+                    Gizmo::Move
+                },
+                None => match self.placed_source.clone() {
+                    Some(source) => {
+                        let position = self.snap_position(g, mouse, None);
+                        let mut loaded_scenes = g.loaded_scenes.borrow_mut();
+                        let scene = loaded_scenes.entry(self.scene_name.clone()).or_insert_with(Scene::default);
+                        let name = scene.unique_instance_name(source.name());
+                        let xform = Xform2D { position: Vec3::new(position.x, position.y, scene.front_z()), .. Xform2D::default() };
+                        scene.shape_instances.push(ShapeInstance { source, name: name.clone(), xform, .. ShapeInstance::default() });
+                        self.selected_instance = Some(name);
+                        Gizmo::Place
+                    },
+                    None => {
+                        self.selected_instance = None;
+                        return;
+                    },
+                },
             },
         };
-        if working_shape.path.is_closed {
-            return;
+        let loaded_scenes = g.loaded_scenes.borrow();
+        let scene = &loaded_scenes[&self.scene_name];
+        if let Some(i) = self.selected_instance.as_ref().and_then(|name| scene.instance_index(name)) {
+            self.instance_drag = Some(InstanceDrag { gizmo, start: mouse, start_xform: scene.shape_instances[i].xform });
         }
-
-        if let Some(pos) = g.input.mouse_position() {
-            let mut position = self.camera.viewport_to_world(pos, 0.);
-            if is_down {
-                if working_shape.path.cmds.is_empty() {
-                    working_shape.path.cmds.push(::shape::PathCmd::Start(position.into()));
-                } else {
-                    working_shape.path.cmds.push(::shape::PathCmd::Line { end: position.into() });
+    }
+    fn drag_scene(&mut self, g: &Game, pos: Vec2<i32>) {
+        debug_assert!(self.is_active);
+        let drag = match self.instance_drag {
+            Some(drag) => drag,
+            None => return,
+        };
+        let mouse: Vec2<f32> = self.camera.viewport_to_world(pos, 0.).into();
+        let origin = Vec2::from(drag.start_xform.position);
+        let mut xform = drag.start_xform;
+        match drag.gizmo {
+            Gizmo::Place | Gizmo::Move => {
+                let position = self.snap_position(g, origin + mouse - drag.start, None);
+                xform.position.x = position.x;
+                xform.position.y = position.y;
+            },
+            // Rotations snap to the angle step, as lines do.
+            Gizmo::Rotate => {
+                let angle = |p: Vec2<f32>| {
+                    let d = p - origin;
+                    d.y.atan2(d.x)
+                };
+                let step = self.snapping.angle_step_radians;
+                xform.rotation_z_radians += angle(mouse) - angle(drag.start);
+                if self.snapping.is_enabled(SnapKind::Angle) && step > 0. && !Self::is_snapping_suspended(g) {
+                    xform.rotation_z_radians = (xform.rotation_z_radians / step).round() * step;
                 }
-            } else {
-                use ::shape::PathCmd;
-                let cmd = if working_shape.path.cmds.len() == 1 {
-                    working_shape.path.cmds[0]
+            },
+            // Holding Shift keeps the proportions.
+            Gizmo::Scale => {
+                let unrotated = |p: Vec2<f32>| (p - origin).rotated_z(-drag.start_xform.rotation_z_radians);
+                let (from, to) = (unrotated(drag.start), unrotated(mouse));
+                let ratio = if g.input.key(Keycode::LShift).is_down() || g.input.key(Keycode::RShift).is_down() {
+                    Vec2::broadcast(if from == Vec2::zero() { 1. } else { to.dot(from) / from.dot(from) })
                 } else {
-                    working_shape.path.cmds.pop().unwrap()
-                };
-                let ctrl = match cmd {
-                    PathCmd::Start(p) => p,
-                    PathCmd::Line { end } => end,
-                    _ => panic!(),
+                    Vec2::new(
+                        if from.x == 0. { 1. } else { to.x / from.x },
+                        if from.y == 0. { 1. } else { to.y / from.y },
+                    )
                 };
-                working_shape.path.cmds.push(::shape::PathCmd::Quadratic { ctrl, end: position.into() });
-            }
-            working_shape.update_vertices_gl();
+                xform.scale = drag.start_xform.scale * ratio;
+            },
+        };
+        let mut loaded_scenes = g.loaded_scenes.borrow_mut();
+        let scene = match loaded_scenes.get_mut(&self.scene_name) {
+            Some(scene) => scene,
+            None => return,
+        };
+        if let Some(i) = self.selected_instance.as_ref().and_then(|name| scene.instance_index(name)) {
+            scene.shape_instances[i].xform = xform;
         }
     }
-
-    fn set_fill_gradient_extrema_to_current_mouse_position(&mut self, g: &Game, is_down: bool) {
-        debug_assert!(self.is_active);
-        let mut loaded_shapes = g.loaded_shapes.borrow_mut();
-        let working_shape = match loaded_shapes.get_mut(&self.working_shape_name) {
-            Some(s) => s,
+    fn release_scene(&mut self) {
+        self.instance_drag = None;
+    }
+    fn delete_selected_instance(&mut self, g: &Game) {
+        let mut loaded_scenes = g.loaded_scenes.borrow_mut();
+        let scene = match loaded_scenes.get_mut(&self.scene_name) {
+            Some(scene) => scene,
+            None => return,
+        };
+        if let Some(i) = self.selected_instance.take().and_then(|name| scene.instance_index(&name)) {
+            scene.shape_instances.remove(i);
+        }
+    }
+    // The loaded shapes, then the scenes which can be placed as groups in the
+    // current one, each sorted by name. Scenes which contain the current one
+    // can't, since it would then contain itself.
+    fn placeable_sources(&self, g: &Game) -> Vec<Source> {
+        let loaded_scenes = g.loaded_scenes.borrow();
+        let mut shape_names: Vec<&String> = Vec::new();
+        let loaded_shapes = g.loaded_shapes.borrow();
+        shape_names.extend(loaded_shapes.keys());
+        shape_names.sort();
+        let mut group_names: Vec<&String> = loaded_scenes.iter()
+            .filter(|&(name, scene)| *name != self.scene_name && !scene.contains_group(&self.scene_name, &loaded_scenes))
+            .map(|(name, _)| name)
+            .collect();
+        group_names.sort();
+        shape_names.into_iter().map(|name| Source::Shape(name.clone()))
+            .chain(group_names.into_iter().map(|name| Source::Group(name.clone())))
+            .collect()
+    }
+    // Picks what to place among `placeable_sources()`, and nothing at all
+    // before the first one.
+    fn cycle_placed_source(&mut self, g: &Game, is_forwards: bool) {
+        let mut sources: Vec<Option<Source>> = self.placeable_sources(g).into_iter().map(Some).collect();
+        sources.insert(0, None);
+        let n = sources.len();
+        let i = sources.iter().position(|source| *source == self.placed_source).unwrap_or(0);
+        self.placed_source = sources[(i + if is_forwards { 1 } else { n - 1 }) % n].clone();
+    }
+    // `:place shape` makes clicking on nothing place instances of `shape`,
+    // and `:place @scene` instances of `scene` as a group; `:place` alone
+    // stops doing so.
+    fn set_placed_source(&mut self, g: &Game, args: &[&str]) {
+        let source = match args.get(0) {
+            Some(label) => source_from_label(label),
             None => {
-                error!("Editor: No shape to edit");
+                self.placed_source = None;
                 return;
             },
         };
-
-        if let Some(pos) = g.input.mouse_position() {
-            let mut position = self.camera.viewport_to_world(pos, 0.);
-            if is_down {
-                working_shape.style.fill_gradient.start.position = position;
-            } else {
-                working_shape.style.fill_gradient.end.position = position;
-            }
-            working_shape.update_gradient_fill_strip_gl();
+        if self.placeable_sources(g).contains(&source) {
+            self.placed_source = Some(source);
+            return;
         }
+        match source {
+            Source::Shape(name) => error!("Editor: There is no shape named `{}`", name),
+            Source::Group(ref name) if !g.loaded_scenes.borrow().contains_key(name) => error!("Editor: There is no scene named `{}`", name),
+            Source::Group(name) => error!("Editor: Can't place `{}` in `{}`, which it contains", name, self.scene_name),
+        };
     }
-
-    fn deleted_selected(&mut self, g: &Game) {
-        debug_assert!(self.is_active);
-        let mut loaded_shapes = g.loaded_shapes.borrow_mut();
-        let working_shape = match loaded_shapes.get_mut(&self.working_shape_name) {
-            Some(s) => s,
+    // `:group name [instance...]` moves the given instances of the scene, or
+    // else the selected one, into a new scene named `name`, and puts an
+    // instance of it where they were. They keep their xforms, since the new
+    // instance has none.
+    fn group_instances(&mut self, g: &Game, args: &[&str]) {
+        let group_name = match args.get(0) {
+            Some(arg) => arg.trim_end_matches(".scene").to_owned(),
             None => {
-                error!("Editor: No shape to edit");
+                error!("Editor: Not enough arguments for command 'group': missing group name.");
                 return;
             },
         };
-        working_shape.path.cmds.clear();
-        working_shape.update_vertices_gl();
-        working_shape.path.is_closed = false;
+        // The group's instance is named after it, as with `:rename`.
+        if group_name.contains('/') {
+            error!("Editor: Shape instance names can't contain `/`");
+            return;
+        }
+        let instance_names: Vec<String> = match (&args[1..], self.selected_instance.as_ref()) {
+            (&[], Some(selected)) => vec![selected.clone()],
+            (&[], None) => {
+                error!("Editor: No shape instance is selected");
+                return;
+            },
+            (names, _) => names.iter().map(|name| name.to_string()).collect(),
+        };
+        let mut loaded_scenes = g.loaded_scenes.borrow_mut();
+        if loaded_scenes.contains_key(&group_name) {
+            error!("Editor: There is already a scene named `{}`", group_name);
+            return;
+        }
+        let scene = match loaded_scenes.get_mut(&self.scene_name) {
+            Some(scene) => scene,
+            None => return,
+        };
+        if let Some(name) = instance_names.iter().find(|name| scene.instance_index(name).is_none()) {
+            error!("Editor: There is no shape instance named `{}`", name);
+            return;
+        }
+        let (grouped, kept) = scene.shape_instances.drain(..).partition(|i| instance_names.contains(&i.name));
+        scene.shape_instances = kept;
+        let name = scene.unique_instance_name(&group_name);
+        scene.shape_instances.push(ShapeInstance { source: Source::Group(group_name.clone()), name: name.clone(), .. ShapeInstance::default() });
+        self.selected_instance = Some(name);
+        // It isn't saved yet.
+        self.saved_scenes.insert(group_name.clone(), Scene::default());
+        loaded_scenes.insert(group_name.clone(), Scene { shape_instances: grouped, .. Scene::default() });
+        info!("Editor: Grouped {} instances into `{}`", instance_names.len(), group_name);
     }
-
-    fn end_polygon(&mut self, g: &Game) {
-        debug_assert!(self.is_active);
-        let mut loaded_shapes = g.loaded_shapes.borrow_mut();
-        let working_shape = match loaded_shapes.get_mut(&self.working_shape_name) {
-            Some(s) => s,
+    // `:override path hide|show|reset` changes the instance at `path` within
+    // the selected group instance, for it only; `reset` undoes its changes.
+    fn override_in_selected_instance(&mut self, g: &Game, args: &[&str]) {
+        let (path, action) = match (args.get(0), args.get(1)) {
+            (Some(path), Some(action)) => (path.to_string(), *action),
+            _ => {
+                error!("Editor: Not enough arguments for command 'override': expected a path and `hide`, `show` or `reset`.");
+                return;
+            },
+        };
+        let mut loaded_scenes = g.loaded_scenes.borrow_mut();
+        let scene = match loaded_scenes.get_mut(&self.scene_name) {
+            Some(scene) => scene,
+            None => return,
+        };
+        let instance = match self.selected_instance.as_ref().and_then(|name| scene.instance_index(name)) {
+            Some(i) => &mut scene.shape_instances[i],
             None => {
-                error!("Editor: No shape to edit");
+                error!("Editor: No shape instance is selected");
                 return;
             },
         };
-        working_shape.path.is_closed = true;
-    }
-    fn toggle_select_all(&mut self, _g: &Game) {
-        debug_assert!(self.is_active);
-        unimplemented!{}
+        if let Source::Shape(_) = instance.source {
+            error!("Editor: `{}` is not an instance of a group", instance.name);
+            return;
+        }
+        match action {
+            "hide" | "show" => instance.overrides.entry(path).or_insert_with(Override::default).is_hidden = action == "hide",
+            "reset" => if instance.overrides.remove(&path).is_none() {
+                warn!("Editor: `{}` overrides nothing at `{}`", instance.name, path);
+            },
+            _ => error!("Editor: Unknown override `{}`: expected `hide`, `show` or `reset`", action),
+        };
     }
-    fn execute_current_command(&mut self, g: &Game) {
-        let cmd = self.command_text.string.clone();
-        self.execute_command(g, &cmd);
+    // `:rename name` renames the selected instance.
+    fn rename_selected_instance(&mut self, g: &Game, args: &[&str]) {
+        let new_name = match args.get(0) {
+            Some(name) => name.to_string(),
+            None => {
+                error!("Editor: Not enough arguments for command 'rename': missing instance name.");
+                return;
+            },
+        };
+        let mut loaded_scenes = g.loaded_scenes.borrow_mut();
+        let scene = match loaded_scenes.get_mut(&self.scene_name) {
+            Some(scene) => scene,
+            None => return,
+        };
+        let i = match self.selected_instance.as_ref().and_then(|name| scene.instance_index(name)) {
+            Some(i) => i,
+            None => {
+                error!("Editor: No shape instance is selected");
+                return;
+            },
+        };
+        if scene.instance_index(&new_name).is_some() {
+            error!("Editor: There is already a shape instance named `{}`", new_name);
+            return;
+        }
+        // It separates the names in the paths of overrides.
+        if new_name.contains('/') {
+            error!("Editor: Shape instance names can't contain `/`");
+            return;
+        }
+        scene.shape_instances[i].name = new_name.clone();
+        self.selected_instance = Some(new_name);
     }
-    fn execute_command(&mut self, g: &Game, cmd: &str) {
-        let line = Self::command_components(cmd);
-        let cmd = &line[0];
-        let args = &line[1..];
-        match *cmd {
-            "w" => self.save_working_shape_with_name(g, args),
-            "e" => self.load_working_shape_by_name(g, args),
-            _ => error!("Editor: `{}` is not recognized as an editor command", cmd),
+    // `:z up|down|front|back` moves the selected instance among the others;
+    // `:z depth` sets its Z value, smaller being closer.
+    fn edit_z_order(&mut self, g: &Game, args: &[&str]) {
+        let arg = match args.get(0) {
+            Some(arg) => *arg,
+            None => {
+                error!("Editor: Not enough arguments for command 'z': expected `up`, `down`, `front`, `back` or a depth.");
+                return;
+            },
+        };
+        let mut loaded_scenes = g.loaded_scenes.borrow_mut();
+        let scene = match loaded_scenes.get_mut(&self.scene_name) {
+            Some(scene) => scene,
+            None => return,
+        };
+        let i = match self.selected_instance.as_ref().and_then(|name| scene.instance_index(name)) {
+            Some(i) => i,
+            None => {
+                error!("Editor: No shape instance is selected");
+                return;
+            },
+        };
+        match (ZOrder::from_name(arg), arg.parse::<f32>()) {
+            (Some(to), _) => if !scene.move_in_z_order(i, to) {
+                info!("Editor: `{}` can't move {}", scene.shape_instances[i].name, to.name());
+            },
+            (None, Ok(z)) if z >= Self::CAMERA_NEAR && z <= Self::CAMERA_FAR => scene.shape_instances[i].xform.position.z = z,
+            _ => error!("Editor: Invalid depth `{}`: expected `up`, `down`, `front`, `back` or a number between {} and {}", arg, Self::CAMERA_NEAR, Self::CAMERA_FAR),
         };
     }
-    fn command_components(mut cmd: &str) -> Vec<&str> {
-        if cmd.is_empty() {
-            return vec![];
-        }
-        if cmd.chars().nth(0).unwrap() == ':' {
-            cmd = &cmd[1..];
+    // Keys which do something else in scene mode. Returns the label for the
+    // history if the key is one of them.
+    fn on_scene_key(&mut self, g: &Game, key: Key) -> Option<&'static str> {
+        let keycode = key.code.unwrap();
+        let label = match keycode {
+            Keycode::Backspace | Keycode::Delete | Keycode::X => "delete instance",
+            Keycode::PageUp | Keycode::PageDown => "z-order",
+            Keycode::Up | Keycode::Down => "shape to place",
+            Keycode::Escape => "deselect",
+            _ => return None,
+        };
+        if !key.is_down() {
+            return Some(label);
         }
-        cmd.lines().next().unwrap().split_whitespace().collect()
+        match keycode {
+            Keycode::Backspace | Keycode::Delete | Keycode::X => self.delete_selected_instance(g),
+            Keycode::PageUp => self.edit_z_order(g, &["up"]),
+            Keycode::PageDown => self.edit_z_order(g, &["down"]),
+            Keycode::Up => self.cycle_placed_source(g, false),
+            Keycode::Down => self.cycle_placed_source(g, true),
+            _ => {
+                self.selected_instance = None;
+                self.placed_source = None;
+            },
+        };
+        Some(label)
     }
-    fn load_working_shape_by_name(&mut self, g: &Game, args: &[&str]) {
-        if args.is_empty() {
-            error!("Editor: Not enough arguments for command 'e': missing shape name.");
+    // Outlines the hovered and selected instances, and draws the handles of
+    // the selected one's gizmo.
+    fn update_scene_overlay(&mut self, g: &Game) {
+        self.scene_overlay_points.vertices.clear();
+        self.scene_overlay_lines.vertices.clear();
+        if self.mode != Mode::Scene {
+            self.hovered_instance = None;
             return;
         }
-        let name = args[0];
-        self.working_shape_name = name.to_owned();
-        let path = g.paths.shape_path_from_name(&name);
-        let shape = match File::open(&path) {
-            Ok(mut f) => Shape::load(&g.color_mesh_gl_program, &mut f).unwrap(),
-            Err(_) => Shape::new(&g.color_mesh_gl_program),
+        {
+            // Selections outlive the instances they refer to when those are
+            // removed by other means, e.g undoing.
+            let loaded_scenes = g.loaded_scenes.borrow();
+            let is_selection_valid = loaded_scenes.get(&self.scene_name)
+                .and_then(|scene| scene.instance_index(self.selected_instance.as_ref()?))
+                .is_some();
+            if !is_selection_valid {
+                self.selected_instance = None;
+                self.instance_drag = None;
+            }
+        }
+        self.hovered_instance = match (self.instance_drag, g.input.mouse_position()) {
+            (None, Some(pos)) => self.instance_under(g, self.camera.viewport_to_world(pos, 0.).into()),
+            _ => None,
         };
-        g.loaded_shapes.borrow_mut().insert(name.to_owned(), shape);
-    }
-    fn save_working_shape_with_name(&mut self, g: &Game, args: &[&str]) {
-        let name = if args.is_empty() {
-            self.working_shape_name.to_owned()
-        } else {
-            args[0].to_owned()
+
+        let vertex = |p: Vec2<f32>, color| Vertex { position: Vec3::from(p), color };
+        let hovered_corners = {
+            let loaded_scenes = g.loaded_scenes.borrow();
+            let loaded_shapes = g.loaded_shapes.borrow();
+            loaded_scenes.get(&self.scene_name).and_then(|scene| {
+                let instance = &scene.shape_instances[scene.instance_index(self.hovered_instance.as_ref()?)?];
+                Some(instance_corners(instance.local_aabr(&loaded_shapes, &loaded_scenes)?, &instance.xform))
+            })
         };
-        let path = g.paths.shape_path_from_name(&name);
-        let mut shape = Shape::new(&g.color_mesh_gl_program);
-        {
-            let source_shape = &g.loaded_shapes.borrow()[&self.working_shape_name];
-            let &Shape {
-                ref path,
-                ref style, 
-                vertices: _,
-                solid_fill_strip: _,
-                gradient_fill_strip: _,
-            } = source_shape;
-            shape.path = path.clone();
-            shape.style = style.clone();
-            shape.update_vertices_gl();
-            shape.update_solid_fill_strip_gl();
-            shape.update_gradient_fill_strip_gl();
+        let outline = |lines: &mut Vec<Vertex>, corners: &[Vec2<f32>; 4], color| for i in 0 .. 4 {
+            lines.push(vertex(corners[i], color));
+            lines.push(vertex(corners[(i + 1) % 4], color));
+        };
+        if let Some(corners) = hovered_corners {
+            outline(&mut self.scene_overlay_lines.vertices, &corners, Rgba::new(1., 0.4, 0., 1.));
         }
-        shape.save(&mut File::create(path).unwrap()).unwrap();
-        self.working_shape_name = name.clone();
-        g.loaded_shapes.borrow_mut().insert(self.working_shape_name.clone(), shape);
+        if let Some((corners, rotation_handle)) = self.selected_instance_handles(g) {
+            let color = Rgba::new(1., 0.8, 0., 1.);
+            outline(&mut self.scene_overlay_lines.vertices, &corners, color);
+            self.scene_overlay_lines.vertices.push(vertex((corners[2] + corners[3]) / 2., color));
+            self.scene_overlay_lines.vertices.push(vertex(rotation_handle, color));
+            for &corner in corners.iter() {
+                self.scene_overlay_points.vertices.push(vertex(corner, Rgba::white()));
+            }
+            self.scene_overlay_points.vertices.push(vertex(rotation_handle, color));
+        }
+        self.scene_overlay_points.update_and_resize_vbo();
+        self.scene_overlay_lines.update_and_resize_vbo();
     }
 
-
     fn autocomplete_command(&mut self, g: &Game) {
         let candidates = { // 'words' borrows from command_text; Scope its lifetime.
             let words = Self::command_components(&self.command_text.string);
@@ -581,6 +2596,9 @@ impl System for EditorSystem {
     }
     fn on_canvas_resized(&mut self, _: &Game, size: Extent2<u32>, _by_user: bool) {
         self.camera.set_viewport_size(size);
+        for document in self.documents.iter_mut() {
+            document.camera.set_viewport_size(size);
+        }
         self.text_position = (self.camera.viewport_size() / 2).map(|x| x as i32).into();
         self.text_position.y -= 1;
     }
@@ -596,6 +2614,21 @@ impl System for EditorSystem {
                 self.camera.xform.position.z = 0.;
             }
         }
+        let before = match self.drag_merge_key {
+            Some(_) => Some(self.drag_checkpoint(g)),
+            None => None,
+        };
+        match (self.mode, self.tool) {
+            (Mode::Scene, _) => self.drag_scene(g, pos),
+            (_, Tool::Polygon) => (),
+            (_, Tool::Pencil) => self.drag_pencil(g, pos),
+            (_, Tool::Nodes) => self.drag_nodes(g, pos),
+            _ => self.drag_primitive(g, pos),
+        };
+        if let Some(before) = before {
+            let (label, merge_key) = (self.mouse_action_label(false), self.drag_merge_key);
+            self.record_drag(g, &label, merge_key, &before);
+        }
     }
     fn on_mouse_scroll(&mut self, _: &Game, delta: Vec2<i32>) {
         if !self.is_active {
@@ -649,9 +2682,29 @@ impl System for EditorSystem {
         }
 
 
+        let keycode = key.code.unwrap();
+        if keycode == Keycode::Z && (g.input.key(Keycode::LCtrl).is_down() || g.input.key(Keycode::RCtrl).is_down()) {
+            if key.is_down() {
+                if g.input.key(Keycode::LShift).is_down() || g.input.key(Keycode::RShift).is_down() {
+                    self.redo(g);
+                } else {
+                    self.undo(g);
+                }
+            }
+            return;
+        }
+        let before = self.drag_checkpoint(g);
+
+        if self.mode == Mode::Scene {
+            if let Some(label) = self.on_scene_key(g, key) {
+                self.record_drag(g, label, None, &before);
+                return;
+            }
+        }
+
         let normal_camera_rotation_speed = Self::CAMERA_Z_ROTATION_SPEED_DEGREES.to_radians();
 
-        match key.code.unwrap() {
+        match keycode {
             Keycode::Colon => if key.is_down() {
                 self.is_entering_command = true;
             },
@@ -678,6 +2731,17 @@ impl System for EditorSystem {
             Keycode::Backspace | Keycode::Delete | Keycode::X => if key.is_down() {
                 self.deleted_selected(g);
             },
+            Keycode::F1 | Keycode::F2 | Keycode::F3 | Keycode::F4 => if key.is_down() {
+                let kind = match key.code.unwrap() {
+                    Keycode::F1 => SnapKind::Grid,
+                    Keycode::F2 => SnapKind::Node,
+                    Keycode::F3 => SnapKind::Angle,
+                    _ => SnapKind::Guide,
+                };
+                let is_enabled = !self.snapping.is_enabled(kind);
+                self.snapping.set_enabled(kind, is_enabled);
+                info!("Editor: Snapping to {} is now {}", kind.name(), if is_enabled { "on" } else { "off" });
+            },
             Keycode::J => self.hsva_sliding_speed.v =  1. * key.is_down() as i32 as f32,
             Keycode::K => self.hsva_sliding_speed.v = -1. * key.is_down() as i32 as f32,
             Keycode::L => self.hsva_sliding_speed.s = -1. * key.is_down() as i32 as f32,
@@ -687,41 +2751,85 @@ impl System for EditorSystem {
             Keycode::O => self.hsva_sliding_speed.a =  1. * key.is_down() as i32 as f32,
             Keycode::P => self.hsva_sliding_speed.a = -1. * key.is_down() as i32 as f32,
             Keycode::S => {
-                let mut loaded_shapes = g.loaded_shapes.borrow_mut();
-                let working_shape = loaded_shapes.get_mut(&self.working_shape_name).unwrap();
-                working_shape.style.stroke_color = self.primary_color();
-                working_shape.update_vertices_gl();
+                if let Some(working_shape) = g.loaded_shapes.borrow_mut().get_mut(&self.working_shape_name) {
+                    working_shape.style.stroke_color = self.primary_color();
+                }
             },
             Keycode::D => {
-                let mut loaded_shapes = g.loaded_shapes.borrow_mut();
-                let working_shape = loaded_shapes.get_mut(&self.working_shape_name).unwrap();
-                working_shape.style.fill_color = self.primary_color();
-                working_shape.update_solid_fill_strip_gl();
+                if let Some(working_shape) = g.loaded_shapes.borrow_mut().get_mut(&self.working_shape_name) {
+                    working_shape.style.fill_color = self.primary_color();
+                }
+            },
+            Keycode::N => if key.is_down() {
+                use ::shape::FillRule;
+                if let Some(working_shape) = g.loaded_shapes.borrow_mut().get_mut(&self.working_shape_name) {
+                    working_shape.style.fill_rule = match working_shape.style.fill_rule {
+                        FillRule::EvenOdd => FillRule::NonZero,
+                        FillRule::NonZero => FillRule::EvenOdd,
+                    };
+                    info!("Editor: Fill rule is now `{}`", working_shape.style.fill_rule.name());
+                }
             },
             Keycode::Left => {
-                let mut loaded_shapes = g.loaded_shapes.borrow_mut();
-                let working_shape = loaded_shapes.get_mut(&self.working_shape_name).unwrap();
-                working_shape.style.fill_gradient.start.color = self.primary_color();
-                working_shape.update_gradient_fill_strip_gl();
+                if let Some(working_shape) = g.loaded_shapes.borrow_mut().get_mut(&self.working_shape_name) {
+                    working_shape.style.fill_gradient.set_stop(GradientStop { offset: 0., color: self.primary_color() });
+                }
             },
             Keycode::Right => {
-                let mut loaded_shapes = g.loaded_shapes.borrow_mut();
-                let working_shape = loaded_shapes.get_mut(&self.working_shape_name).unwrap();
-                working_shape.style.fill_gradient.end.color = self.primary_color();
-                working_shape.update_gradient_fill_strip_gl();
+                if let Some(working_shape) = g.loaded_shapes.borrow_mut().get_mut(&self.working_shape_name) {
+                    working_shape.style.fill_gradient.set_stop(GradientStop { offset: 1., color: self.primary_color() });
+                }
+            },
+            Keycode::H => if key.is_down() {
+                self.is_showing_history = !self.is_showing_history;
+            },
+            Keycode::F9 => if key.is_down() {
+                let mode = if self.mode == Mode::Shape { Mode::Scene } else { Mode::Shape };
+                self.set_mode(g, mode);
+            },
+            Keycode::Tab => if key.is_down() && self.documents.len() > 1 {
+                let n = self.documents.len();
+                let is_backwards = g.input.key(Keycode::LShift).is_down() || g.input.key(Keycode::RShift).is_down();
+                let i = (self.current_document + if is_backwards { n - 1 } else { 1 }) % n;
+                self.switch_to_document(i);
             },
             _ => (),
         };
+
+        let label = match keycode {
+            Keycode::Return => "close subpath".to_owned(),
+            Keycode::Backspace | Keycode::Delete | Keycode::X => "delete".to_owned(),
+            Keycode::S => "stroke color".to_owned(),
+            Keycode::D => "fill color".to_owned(),
+            Keycode::N => "fill rule".to_owned(),
+            Keycode::Left | Keycode::Right => "gradient stop".to_owned(),
+            _ => format!("{:?} key", keycode),
+        };
+        self.record_drag(g, &label, None, &before);
     }
     fn on_mouse_button(&mut self, g: &Game, btn: MouseButton) {
         if !self.is_active {
             return;
         }
+        if btn.is_down() {
+            self.drag_merge_key = Some(self.new_merge_key());
+        }
+        let before = self.drag_checkpoint(g);
         match btn.button {
-            Sdl2MouseButton::Left => if btn.is_down() {
-                self.add_vertex_at_current_mouse_position(g, true);
+            Sdl2MouseButton::Left if self.mode == Mode::Scene => if btn.is_down() {
+                self.press_scene(g);
             } else {
-                self.add_vertex_at_current_mouse_position(g, false);
+                self.release_scene();
+            },
+            Sdl2MouseButton::Right if self.mode == Mode::Scene => {},
+            Sdl2MouseButton::Left => match (self.tool, btn.is_down()) {
+                (Tool::Polygon, is_down) => self.add_vertex_at_current_mouse_position(g, is_down),
+                (Tool::Pencil, true) => self.press_pencil(g),
+                (Tool::Pencil, false) => self.release_pencil(g),
+                (Tool::Nodes, true) => self.press_nodes(g),
+                (Tool::Nodes, false) => self.release_nodes(g),
+                (_, true) => self.press_primitive(g),
+                (_, false) => self.release_primitive(g),
             },
             Sdl2MouseButton::Middle => {},
             Sdl2MouseButton::Right => if btn.is_down() {
@@ -733,6 +2841,12 @@ impl System for EditorSystem {
             Sdl2MouseButton::X1 => {},
             Sdl2MouseButton::X2 => {},
         };
+        let label = self.mouse_action_label(btn.button == Sdl2MouseButton::Right);
+        let merge_key = self.drag_merge_key;
+        self.record_drag(g, &label, merge_key, &before);
+        if !btn.is_down() {
+            self.drag_merge_key = None;
+        }
     }
     fn on_message(&mut self, g: &Game, msg: &Message) {
         match *msg {
@@ -762,6 +2876,9 @@ impl System for EditorSystem {
 
         self.text.string.clear();
         //self.text.string = format!("{:?}", self.hsva_sliders.hsva);
+        if let Some(ref warning) = *g.quit_warning.borrow() {
+            self.text.string.push_str(warning);
+        }
         self.text.update_gl(&g.fonts.fonts[&self.font_id]);
 
         let cursor_color = rgba_from_hsva(self.hsva_sliders.hsva);
@@ -770,6 +2887,48 @@ impl System for EditorSystem {
         }
         self.cursor_vertices.update_vbo_range(0..1);
 
+        self.update_node_overlay(g);
+        self.update_scene_overlay(g);
+        self.update_snap_nodes(g);
+        // Between drags, the indicator shows where a click would go.
+        let is_dragging = self.primitive_drag_start.is_some() || !self.pencil_samples.is_empty() || self.instance_drag.is_some() || match self.node_drag {
+            Some(NodeDrag::Points { .. }) => true,
+            _ => false,
+        };
+        if !is_dragging {
+            let is_placing = self.placed_source.is_some() && self.hovered_instance.is_none();
+            match (self.mode, self.tool, g.input.mouse_position()) {
+                (Mode::Scene, _, Some(pos)) if is_placing => { self.snap_mouse(g, pos, None); },
+                (Mode::Scene, _, _) | (_, Tool::Nodes, _) | (_, _, None) => self.last_snap = None,
+                (_, tool, Some(pos)) => {
+                    let origin = match tool {
+                        Tool::Polygon => g.loaded_shapes.borrow().get(&self.working_shape_name)
+                            .and_then(|s| s.path.subpaths.last().filter(|s| !s.is_closed).map(|s| s.cmds[s.cmds.len() - 1].end())),
+                        _ => None,
+                    };
+                    self.snap_mouse(g, pos, origin);
+                },
+            };
+        }
+        self.update_snap_overlay();
+
+        self.update_tabs_text(g);
+        if self.is_showing_history {
+            self.update_history_text(g);
+        }
+
+        let mut direction = 0.;
+        direction += g.input.key(Keycode::KpPlus).is_down() as i32 as f32;
+        direction -= g.input.key(Keycode::KpMinus).is_down() as i32 as f32;
+        direction *= 16.;
+        if direction == 0. {
+            self.thickness_merge_key = None;
+            return;
+        }
+        if self.thickness_merge_key.is_none() {
+            self.thickness_merge_key = Some(self.new_merge_key());
+        }
+        let before = self.drag_checkpoint(g);
         {
             let mut loaded_shapes = g.loaded_shapes.borrow_mut();
             let working_shape = match loaded_shapes.get_mut(&self.working_shape_name) {
@@ -779,15 +2938,13 @@ impl System for EditorSystem {
                     return;
                 },
             };
-            let mut direction = 0.;
-            direction += g.input.key(Keycode::KpPlus).is_down() as i32 as f32;
-            direction -= g.input.key(Keycode::KpMinus).is_down() as i32 as f32;
-            direction *= 16.;
             working_shape.style.stroke_thickness += dt * direction;
             if working_shape.style.stroke_thickness < 0.1 {
                 working_shape.style.stroke_thickness = 0.1;
             }
         }
+        let merge_key = self.thickness_merge_key;
+        self.record_drag(g, "stroke thickness", merge_key, &before);
     }
 
     fn draw(&mut self, g: &Game, gfx_interp: f64) {
@@ -812,11 +2969,72 @@ impl System for EditorSystem {
                 */
             };
 
-            let draw_working_shape = || ::gameplay::draw_shape_instance(g, &self.camera, &::scene::ShapeInstance {
-                source_shape_name: self.working_shape_name.clone(),
-                name: "Editor Shape".to_owned(),
-                xform: ::xform::Xform2D::default(),
-            });
+            let draw_working_shape = || ::gameplay::draw_shape(g, &self.camera, &self.working_shape_name, &Mat4::identity());
+
+            let draw_scene = || {
+                let loaded_scenes = g.loaded_scenes.borrow();
+                if let Some(scene) = loaded_scenes.get(&self.scene_name) {
+                    let loaded_shapes = g.loaded_shapes.borrow();
+                    for placement in scene.placements(&loaded_scenes) {
+                        if loaded_shapes.contains_key(&placement.shape_name) {
+                            ::gameplay::draw_shape(g, &self.camera, &placement.shape_name, &placement.model);
+                        }
+                    }
+                }
+            };
+
+            let draw_document = || match self.mode {
+                Mode::Shape => draw_working_shape(),
+                Mode::Scene => draw_scene(),
+            };
+
+            let draw_scene_overlay = || if self.mode == Mode::Scene {
+                gl::Disable(gl::DEPTH_TEST);
+                gl::DepthMask(gl::FALSE);
+                g.color_mesh_gl_program.set_uniform_mvp(&self.camera.view_proj_matrix());
+                g.color_mesh_gl_program.set_uniform_is_drawing_points(false);
+                gl::LineWidth(1.);
+                gl::BindVertexArray(self.scene_overlay_lines.vao().gl_id());
+                gl::DrawArrays(gl::LINES, 0, self.scene_overlay_lines.vertices.len() as _);
+                gl::PointSize(8.);
+                g.color_mesh_gl_program.set_uniform_is_drawing_points(true);
+                gl::BindVertexArray(self.scene_overlay_points.vao().gl_id());
+                gl::DrawArrays(gl::POINTS, 0, self.scene_overlay_points.vertices.len() as _);
+                gl::DepthMask(gl::TRUE);
+                gl::Enable(gl::DEPTH_TEST);
+            };
+
+            let draw_node_overlay = || if self.mode == Mode::Shape && self.tool == Tool::Nodes {
+                gl::Disable(gl::DEPTH_TEST);
+                gl::DepthMask(gl::FALSE);
+                g.color_mesh_gl_program.set_uniform_mvp(&self.camera.view_proj_matrix());
+                g.color_mesh_gl_program.set_uniform_is_drawing_points(false);
+                gl::LineWidth(1.);
+                gl::BindVertexArray(self.node_overlay_lines.vao().gl_id());
+                gl::DrawArrays(gl::LINES, 0, self.node_overlay_lines.vertices.len() as _);
+                gl::PointSize(8.);
+                g.color_mesh_gl_program.set_uniform_is_drawing_points(true);
+                gl::BindVertexArray(self.node_overlay_points.vao().gl_id());
+                gl::DrawArrays(gl::POINTS, 0, self.node_overlay_points.vertices.len() as _);
+                gl::DepthMask(gl::TRUE);
+                gl::Enable(gl::DEPTH_TEST);
+            };
+
+            let draw_snap_overlay = || {
+                gl::Disable(gl::DEPTH_TEST);
+                gl::DepthMask(gl::FALSE);
+                g.color_mesh_gl_program.set_uniform_mvp(&self.camera.view_proj_matrix());
+                g.color_mesh_gl_program.set_uniform_is_drawing_points(false);
+                gl::LineWidth(1.);
+                gl::BindVertexArray(self.snap_overlay_lines.vao().gl_id());
+                gl::DrawArrays(gl::LINES, 0, self.snap_overlay_lines.vertices.len() as _);
+                gl::PointSize(10.);
+                g.color_mesh_gl_program.set_uniform_is_drawing_points(true);
+                gl::BindVertexArray(self.snap_overlay_points.vao().gl_id());
+                gl::DrawArrays(gl::POINTS, 0, self.snap_overlay_points.vertices.len() as _);
+                gl::DepthMask(gl::TRUE);
+                gl::Enable(gl::DEPTH_TEST);
+            };
 
             let draw_grid = || {
                 if self.do_draw_grid {
@@ -914,12 +3132,18 @@ impl System for EditorSystem {
                 if self.draw_grid_first {
                     draw_grid();
                     draw_cursor();
-                    draw_working_shape();
+                    draw_document();
+                    draw_node_overlay();
+                    draw_scene_overlay();
+                    draw_snap_overlay();
                     draw_hsva_sliders();
                 } else {
                     draw_cursor();
-                    draw_working_shape();
+                    draw_document();
                     draw_grid();
+                    draw_node_overlay();
+                    draw_scene_overlay();
+                    draw_snap_overlay();
                     draw_hsva_sliders();
                 }
 
@@ -940,6 +3164,30 @@ impl System for EditorSystem {
                 gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.text.indices.ibo().gl_id());
                 gl::DrawElements(gl::TRIANGLES, self.text.indices.indices.len() as _, gl::UNSIGNED_SHORT, ptr::null_mut());
                 gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
+
+                // Tabs on the first line, then the history below.
+                let line_height = g.fonts.fonts[&FontID::Debug].height as i32;
+                let debug_text_mvp = |line: i32| {
+                    let Extent2 { w, h } = g.fonts.fonts[&FontID::Debug].texture_size.map(|x| x as f32) * 2. / self.camera.viewport_size().map(|x| x as f32);
+                    let t = self.camera.viewport_to_ugly_ndc(Vec2::new(0, line_height * line));
+                    Mat4::<f32>::translation_3d(t) * Mat4::scaling_3d(Vec3::new(w, h, 1.))
+                };
+                g.text_gl_program.set_uniform_mvp(&debug_text_mvp(1));
+                g.text_gl_program.set_uniform_font_atlas_via_font_id(FontID::Debug);
+                g.text_gl_program.set_uniform_color(Rgba::white());
+                gl::BindVertexArray(self.tabs_text.vertices.vao().gl_id());
+                gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.tabs_text.indices.ibo().gl_id());
+                gl::DrawElements(gl::TRIANGLES, self.tabs_text.indices.indices.len() as _, gl::UNSIGNED_SHORT, ptr::null_mut());
+                gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
+
+                if self.is_showing_history {
+                    g.text_gl_program.set_uniform_mvp(&debug_text_mvp(2));
+                    gl::BindVertexArray(self.history_text.vertices.vao().gl_id());
+                    gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.history_text.indices.ibo().gl_id());
+                    gl::DrawElements(gl::TRIANGLES, self.history_text.indices.indices.len() as _, gl::UNSIGNED_SHORT, ptr::null_mut());
+                    gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
+                }
+
                 gl::BindVertexArray(0);
                 gl::Enable(gl::DEPTH_TEST);
             }
@@ -36,18 +36,57 @@ pub struct Game {
     pub fonts: font::Fonts,
     pub loaded_scenes: RefCell<HashMap<String, scene::Scene>>,
     pub loaded_shapes: RefCell<HashMap<String, shape::Shape>>,
+    /// Shapes and scenes with changes which aren't saved to disk yet, as reported by the editor.
+    pub unsaved_document_names: RefCell<Vec<String>>,
+    /// The warning which `QuitSystem` gives before discarding unsaved changes,
+    /// for the editor to show. The editor clears it on the next edit.
+    pub quit_warning: RefCell<Option<String>>,
+    pub shape_meshes: RefCell<mesh::shape_mesh::ShapeMeshes>,
     pub color_mesh_gl_program: mesh::color_mesh::Program,
     pub text_gl_program: mesh::text::Program,
 }
 
-pub struct QuitSystem;
+#[derive(Debug, Default)]
+pub struct QuitSystem {
+    now: Duration,
+    /// When the warning about unsaved documents was given, if it still stands.
+    warned_at: Option<Duration>,
+}
+
+impl QuitSystem {
+    const WARNING_SECONDS: u64 = 5;
+}
 
 impl System for QuitSystem {
     fn name(&self) -> &str { "QuitSystem" }
     fn on_quit_requested(&mut self, g: &Game) {
         info!("{}: Received 'Quit' event", self.name());
+        // Quitting again confirms that unsaved changes are to be lost.
+        let unsaved_document_names = g.unsaved_document_names.borrow();
+        if !unsaved_document_names.is_empty() && self.warned_at.is_none() {
+            let warning = format!("Unsaved changes to {}; quit again to discard them", unsaved_document_names.join(", "));
+            warn!("{}: {}", self.name(), warning);
+            *g.quit_warning.borrow_mut() = Some(warning);
+            self.warned_at = Some(self.now);
+            return;
+        }
         g.wants_to_quit.set(true);
     }
+    fn tick(&mut self, g: &Game, t: Duration, _dt: Duration) {
+        self.now = t;
+        // The warning stands for a few seconds, until the next edit, or
+        // until everything is saved; then quitting warns again.
+        let is_stale = match self.warned_at {
+            Some(warned_at) => t > warned_at + Duration::from_secs(Self::WARNING_SECONDS)
+                || g.quit_warning.borrow().is_none()
+                || g.unsaved_document_names.borrow().is_empty(),
+            None => false,
+        };
+        if is_stale {
+            self.warned_at = None;
+            *g.quit_warning.borrow_mut() = None;
+        }
+    }
 }
 
 impl Game {
@@ -64,15 +103,16 @@ impl Game {
         let paths = paths::Paths::new();
         let fonts = font::Fonts::from_path(&paths.fonts).unwrap();
         let loaded_scenes = RefCell::new(paths.load_scenes());
-        let loaded_shapes = RefCell::new(paths.load_shapes(&color_mesh_gl_program));
+        let loaded_shapes = RefCell::new(paths.load_shapes());
+        let shape_meshes = RefCell::new(mesh::shape_mesh::ShapeMeshes::new());
         let viewport_size = platform.canvas_size();
 
         let systems = RefCell::new(vec![
             Box::new(InputSystem) as Box<System>,
             Box::new(PlatformSystem),
             Box::new(editor::EditorSystem::new(&color_mesh_gl_program, &text_gl_program, viewport_size)),
-            Box::new(gameplay::GameplaySystem::new(viewport_size)),
-            Box::new(QuitSystem),
+            Box::new(gameplay::GameplaySystem::new(&color_mesh_gl_program, &text_gl_program, viewport_size)),
+            Box::new(QuitSystem::default()),
         ]);
 
         info!("Game: ... Done initializing.");
@@ -91,6 +131,9 @@ impl Game {
             fonts,
             loaded_scenes,
             loaded_shapes,
+            unsaved_document_names: RefCell::new(vec![]),
+            quit_warning: RefCell::new(None),
+            shape_meshes,
             color_mesh_gl_program,
             text_gl_program,
         }
@@ -1,30 +1,99 @@
+use std::ptr;
 use gl;
 use system::*;
 use camera::OrthoCamera2D;
-use gx::Object;
-use shape::{self, Shape, Style};
-use scene::ShapeInstance;
+use v::{Vec3, Mat4, Rgba};
+use gx::{self, Object};
+use shape::{self, Shape, Style, FillRule};
+use mesh::shape_mesh::ShapeMesh;
+use mesh::text::Program as TextProgram;
+use text::Text;
+use font::FontID;
+use scene::{Action, Transition, TransitionKind};
+use mesh::{vertex_array, color_mesh::{self, Vertex}};
+use xform::Xform2D;
+use progress::Progress;
+use save::{self, Save, Thumbnail};
+use duration_ext::DurationExt;
+
+type ColorVertexArray = vertex_array::VertexArray<color_mesh::Program>;
+
+// What the action being run waits for before the next one starts.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Wait {
+    Nothing,
+    Click,
+    Animation { from: Xform2D, to: Xform2D, elapsed: f32, duration: f32 },
+    Transition,
+}
+
+// The actions of what the player clicked on, being run in order.
+#[derive(Debug)]
+struct ActionChain {
+    scene_name: String,
+    /// The path of the instance which they act on; `None` for hotspots.
+    instance_path: Option<String>,
+    actions: Vec<Action>,
+    next: usize,
+    wait: Wait,
+}
+
+// Going from the scene named `from` to the current one.
+#[derive(Debug)]
+struct SceneTransition {
+    from: String,
+    transition: Transition,
+    elapsed: f32,
+}
+
+impl SceneTransition {
+    // From 0 at the start to 1 at the end.
+    fn progress(&self) -> f32 {
+        (self.elapsed / self.transition.seconds).min(1.)
+    }
+}
 
 #[derive(Debug)]
 pub struct GameplaySystem {
     is_active: bool,
-    current_scene_name: String,
+    progress: Progress,
     camera: OrthoCamera2D,
+    action_chain: Option<ActionChain>,
+    scene_transition: Option<SceneTransition>,
+    /// Dialogues and the inventory.
+    message_text: Text,
+    /// Full-screen backgrounds of pushed scenes, and transition effects.
+    screen_vertices: ColorVertexArray,
+    /// The slot to save to once the scene is drawn, for its thumbnail.
+    save_slot: Option<u32>,
+}
+
+fn send(g: &Game, message: Message) {
+    g.messages.borrow_mut().push_back(message);
 }
 
 impl GameplaySystem {
     const CAMERA_NEAR: f32 = ::editor::EditorSystem::CAMERA_NEAR;
     const CAMERA_FAR: f32 = ::editor::EditorSystem::CAMERA_FAR;
-    pub fn new(viewport_size: Extent2<u32>) -> Self {
+    // How many sides the circle of the iris transition has.
+    const IRIS_SIDES: usize = 64;
+    // How wide the thumbnails of saves are at most, in pixels.
+    const THUMBNAIL_MAX_WIDTH: u32 = 160;
+    pub fn new(color_mesh_gl_program: &color_mesh::Program, text_gl_program: &TextProgram, viewport_size: Extent2<u32>) -> Self {
         Self {
             is_active: false,
-            current_scene_name: "default".to_owned(),
+            progress: Progress::new("default"),
             camera: OrthoCamera2D::new(viewport_size, Self::CAMERA_NEAR, Self::CAMERA_FAR),
+            action_chain: None,
+            scene_transition: None,
+            message_text: Text::new(text_gl_program, "Gameplay Message Text"),
+            screen_vertices: ColorVertexArray::from_vertices(color_mesh_gl_program, "Gameplay Screen Vertices", gx::BufferUsage::DynamicDraw, vec![]),
+            save_slot: None,
         }
     }
     fn on_enter_gameplay(&mut self, g: &Game) {
-        unsafe {
-            gl::ClearColor(0.2, 0.6, 1., 1.);
+        if !g.loaded_scenes.borrow().contains_key(&self.progress.scene_name) {
+            error!("Gameplay: There is no scene named `{}`", self.progress.scene_name);
         }
         g.platform.cursors.normal.set();
         self.is_active = true;
@@ -36,6 +105,358 @@ impl GameplaySystem {
         g.platform.cursors.normal.set();
         self.is_active = false;
     }
+    /// The scene which reacts to clicks: the one on top of the stack.
+    pub fn top_scene_name(&self) -> &str {
+        self.progress.pushed_scene_names.last().unwrap_or(&self.progress.scene_name)
+    }
+    /// Leaves the current scene, and those pushed over it, for the one named `name`.
+    pub fn jump_to_scene(&mut self, g: &Game, name: &str, transition: Transition) {
+        if !g.loaded_scenes.borrow().contains_key(name) {
+            error!("Gameplay: There is no scene named `{}`", name);
+            return;
+        }
+        for popped in self.progress.pushed_scene_names.drain(..).rev() {
+            send(g, Message::ScenePopped(popped));
+        }
+        let from = ::std::mem::replace(&mut self.progress.scene_name, name.to_owned());
+        send(g, Message::SceneTransitionStarted { from: from.clone(), to: name.to_owned(), transition });
+        if transition.kind == TransitionKind::Cut || transition.seconds <= 0. {
+            self.scene_transition = None;
+            send(g, Message::SceneTransitionEnded { to: name.to_owned() });
+        } else {
+            self.scene_transition = Some(SceneTransition { from, transition, elapsed: 0. });
+        }
+    }
+    /// Shows the scene named `name` over the current one.
+    pub fn push_scene(&mut self, g: &Game, name: &str) {
+        if !g.loaded_scenes.borrow().contains_key(name) {
+            error!("Gameplay: There is no scene named `{}`", name);
+            return;
+        }
+        self.progress.pushed_scene_names.push(name.to_owned());
+        send(g, Message::ScenePushed(name.to_owned()));
+    }
+    /// Hides the scene on top, if it was pushed.
+    pub fn pop_scene(&mut self, g: &Game) {
+        match self.progress.pushed_scene_names.pop() {
+            Some(name) => send(g, Message::ScenePopped(name)),
+            None => warn!("Gameplay: No scene to pop"),
+        };
+    }
+    fn save(&mut self, g: &Game, slot: u32, thumbnail: Option<&Thumbnail>) {
+        let save = Save::new(&self.progress);
+        match g.paths.write_save(slot, &save, thumbnail) {
+            Ok(()) => info!("Gameplay: Saved to slot {}", slot),
+            Err(e) => error!("Gameplay: Could not save to slot {}: {}", slot, e),
+        };
+    }
+    // Replaces the progress with the one saved in `slot`, dropping whatever
+    // was going on.
+    fn load(&mut self, g: &Game, slot: u32) {
+        let save = match g.paths.load_save(slot) {
+            Ok(save) => save,
+            Err(e) => {
+                error!("Gameplay: Could not load slot {}: {}", slot, e);
+                return;
+            },
+        };
+        if !g.loaded_scenes.borrow().contains_key(&save.progress.scene_name) {
+            error!("Gameplay: There is no scene named `{}`", save.progress.scene_name);
+        }
+        self.progress = save.progress;
+        self.action_chain = None;
+        self.scene_transition = None;
+        self.message_text.string.clear();
+        info!("Gameplay: Loaded slot {}", slot);
+    }
+    // Lists the saves, from their metadata.
+    fn show_save_slots(&mut self, g: &Game) {
+        let slots = g.paths.load_save_slots();
+        let now = save::timestamp_secs_now();
+        let message = if slots.is_empty() {
+            "No saves".to_owned()
+        } else {
+            slots.iter().map(|(slot, metadata)| {
+                let elapsed = metadata.elapsed_secs as u64;
+                let age = now.saturating_sub(metadata.timestamp_secs);
+                format!("{}: {} ({}:{:02}:{:02} played, saved {} minutes ago)", slot, metadata.scene_name,
+                    elapsed / 3600, elapsed / 60 % 60, elapsed % 60, age / 60)
+            }).collect::<Vec<_>>().join("\n")
+        };
+        self.show_message(g, &message);
+    }
+    // Reads what was just drawn, from the bottom row up.
+    unsafe fn capture_thumbnail(&self) -> Thumbnail {
+        let size = self.camera.viewport_size();
+        let mut rgb = vec![0_u8; (size.w * size.h * 3) as usize];
+        gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+        gl::ReadPixels(0, 0, size.w as _, size.h as _, gl::RGB, gl::UNSIGNED_BYTE, rgb.as_mut_ptr() as *mut _);
+        Thumbnail::from_bottom_up_rgb(size, &rgb, Self::THUMBNAIL_MAX_WIDTH)
+    }
+    fn show_message(&mut self, g: &Game, message: &str) {
+        self.message_text.string = message.to_owned();
+        self.message_text.update_gl(&g.fonts.fonts[&FontID::Debug]);
+    }
+    // Starts running the actions of what is under the mouse in the scene on
+    // top: the last hotspot there, or else the frontmost shape there.
+    fn click(&mut self, g: &Game) {
+        let pos = match g.input.mouse_position() {
+            Some(pos) => pos,
+            None => return,
+        };
+        let scenes = g.loaded_scenes.borrow();
+        let scene_name = self.top_scene_name().to_owned();
+        let scene = match scenes.get(&scene_name) {
+            Some(scene) => scene,
+            None => return,
+        };
+        self.camera.xform = scene.camera;
+        let p: Vec2<f32> = self.camera.viewport_to_world(pos, 0.).into();
+        let hotspot = scene.hotspots.iter().rev().find(|h| {
+            !h.on_click.is_empty() && p.x >= h.area.min.x && p.x <= h.area.max.x && p.y >= h.area.min.y && p.y <= h.area.max.y
+        });
+        let (instance_path, actions) = match hotspot {
+            Some(hotspot) => (None, hotspot.on_click.clone()),
+            None => {
+                let no_overrides = Default::default();
+                let overrides = self.progress.overrides.get(&scene_name).unwrap_or(&no_overrides);
+                let placement = scene.placement_at(p, &g.loaded_shapes.borrow(), &scenes, overrides);
+                let path = match placement.and_then(|p| p.on_click_path) {
+                    Some(path) => path,
+                    None => return,
+                };
+                let actions = match scene.instance_at_path(&path, &scenes) {
+                    Some(instance) => instance.on_click.clone(),
+                    None => return,
+                };
+                (Some(path), actions)
+            },
+        };
+        self.action_chain = Some(ActionChain { scene_name, instance_path, actions, next: 0, wait: Wait::Nothing });
+    }
+    // Runs the actions of the chain until one of them has to wait.
+    fn run_action_chain(&mut self, g: &Game) {
+        loop {
+            let (action, scene_name, instance_path) = match self.action_chain {
+                Some(ref mut chain) if chain.wait == Wait::Nothing => match chain.actions.get(chain.next) {
+                    Some(action) => {
+                        chain.next += 1;
+                        (action.clone(), chain.scene_name.clone(), chain.instance_path.clone())
+                    },
+                    None => {
+                        self.action_chain = None;
+                        return;
+                    },
+                },
+                _ => return,
+            };
+            let wait = self.run_action(g, &action, &scene_name, instance_path.as_ref().map(String::as_str));
+            if let Some(ref mut chain) = self.action_chain {
+                chain.wait = wait;
+            }
+        }
+    }
+    fn run_action(&mut self, g: &Game, action: &Action, scene_name: &str, instance_path: Option<&str>) -> Wait {
+        let instance_path = match (action, instance_path) {
+            (&Action::Animate { .. }, None) | (&Action::Disappear, None) => {
+                warn!("Gameplay: Skipping `{:?}`, which only instances can do", action);
+                return Wait::Nothing;
+            },
+            (_, path) => path.unwrap_or(""),
+        };
+        match *action {
+            Action::Dialogue(ref text) => {
+                self.show_message(g, text);
+                Wait::Click
+            },
+            Action::Animate { to, seconds } => {
+                let from = {
+                    let scenes = g.loaded_scenes.borrow();
+                    let instance = scenes.get(scene_name).and_then(|scene| scene.instance_at_path(instance_path, &scenes));
+                    let current = self.progress.overrides.get(scene_name).and_then(|o| o.get(instance_path)).and_then(|o| o.xform);
+                    match (current, instance) {
+                        (Some(xform), _) => xform,
+                        (None, Some(instance)) => instance.xform,
+                        (None, None) => return Wait::Nothing,
+                    }
+                };
+                self.progress.override_mut(scene_name, instance_path).xform = Some(if seconds > 0. { from } else { to });
+                if seconds > 0. {
+                    Wait::Animation { from, to, elapsed: 0., duration: seconds }
+                } else {
+                    Wait::Nothing
+                }
+            },
+            Action::JumpToScene { ref scene, transition } => {
+                self.jump_to_scene(g, scene, transition);
+                if self.scene_transition.is_some() { Wait::Transition } else { Wait::Nothing }
+            },
+            Action::PushScene(ref name) => {
+                self.push_scene(g, name);
+                Wait::Nothing
+            },
+            Action::PopScene => {
+                self.pop_scene(g);
+                Wait::Nothing
+            },
+            Action::Disappear => {
+                self.progress.override_mut(scene_name, instance_path).is_hidden = true;
+                Wait::Nothing
+            },
+            Action::PickUp(ref item) => {
+                if !self.progress.inventory.contains(item) {
+                    self.progress.inventory.push(item.clone());
+                }
+                Wait::Nothing
+            },
+            Action::ShowInventory => {
+                let items = if self.progress.inventory.is_empty() { "(nothing)".to_owned() } else { self.progress.inventory.join(", ") };
+                self.show_message(g, &format!("Inventory: {}", items));
+                Wait::Click
+            },
+            Action::SetProperty(ref name, ref value) => {
+                self.progress.properties.insert(name.clone(), value.clone());
+                Wait::Nothing
+            },
+        }
+    }
+    fn animate(&mut self, dt: f32) {
+        let chain = match self.action_chain {
+            Some(ref mut chain) => chain,
+            None => return,
+        };
+        let (from, to, elapsed, duration) = match chain.wait {
+            Wait::Animation { from, to, elapsed, duration } => (from, to, elapsed + dt, duration),
+            _ => return,
+        };
+        let t = (elapsed / duration).min(1.);
+        let path = chain.instance_path.clone().unwrap_or_default();
+        self.progress.override_mut(&chain.scene_name, &path).xform = Some(from.lerp(&to, t));
+        chain.wait = if t >= 1. { Wait::Nothing } else { Wait::Animation { from, to, elapsed, duration } };
+    }
+    fn update_scene_transition(&mut self, g: &Game, dt: f32) {
+        let is_over = match self.scene_transition {
+            Some(ref mut transition) => {
+                transition.elapsed += dt;
+                transition.progress() >= 1.
+            },
+            None => return,
+        };
+        if !is_over {
+            return;
+        }
+        self.scene_transition = None;
+        send(g, Message::SceneTransitionEnded { to: self.progress.scene_name.clone() });
+        if let Some(ref mut chain) = self.action_chain {
+            if chain.wait == Wait::Transition {
+                chain.wait = Wait::Nothing;
+            }
+        }
+    }
+    // Draws the scene named `name`, over what is already drawn. Unless it is
+    // `is_opaque`, its background is blended with it.
+    unsafe fn draw_scene(&mut self, g: &Game, name: &str, is_opaque: bool) {
+        let scenes = g.loaded_scenes.borrow();
+        let scene = match scenes.get(name) {
+            Some(scene) => scene,
+            None => return,
+        };
+        gl::Clear(gl::DEPTH_BUFFER_BIT);
+        if is_opaque {
+            let Rgba { r, g, b, .. } = scene.background_color;
+            gl::ClearColor(r, g, b, 1.);
+            gl::Clear(gl::COLOR_BUFFER_BIT);
+        } else {
+            let vertices = Self::screen_quad(scene.background_color);
+            self.draw_screen_vertices(g, vertices);
+        }
+        self.camera.xform = scene.camera;
+        let loaded_shapes = g.loaded_shapes.borrow();
+        let no_overrides = Default::default();
+        let overrides = self.progress.overrides.get(name).unwrap_or(&no_overrides);
+        for placement in scene.placements_with_overrides(&scenes, overrides) {
+            if loaded_shapes.contains_key(&placement.shape_name) {
+                draw_shape(g, &self.camera, &placement.shape_name, &placement.model);
+            }
+        }
+    }
+    fn screen_quad(color: Rgba<f32>) -> Vec<Vertex> {
+        [(-1., -1.), (1., -1.), (-1., 1.), (1., 1.)].iter()
+            .map(|&(x, y)| Vertex { position: Vec3::new(x, y, 0.), color })
+            .collect()
+    }
+    // A black ring, as a triangle strip, which leaves a hole of `radius`
+    // pixels at the center of the screen.
+    fn iris_ring(&self, radius: f32) -> Vec<Vertex> {
+        let size = self.camera.viewport_size().map(|x| x as f32);
+        let outer_radius = size.w.hypot(size.h);
+        let black = Rgba::new(0., 0., 0., 1.);
+        let mut vertices = Vec::with_capacity(2 * (Self::IRIS_SIDES + 1));
+        for i in 0 ..= Self::IRIS_SIDES {
+            let angle = i as f32 * 2. * ::std::f32::consts::PI / Self::IRIS_SIDES as f32;
+            let direction = Vec2::new(angle.cos() * 2. / size.w, angle.sin() * 2. / size.h);
+            for &r in [radius, outer_radius].iter() {
+                let p = direction * r;
+                vertices.push(Vertex { position: Vec3::new(p.x, p.y, 0.), color: black });
+            }
+        }
+        vertices
+    }
+    // Draws a triangle strip whose positions are in normalized device coordinates.
+    unsafe fn draw_screen_vertices(&mut self, g: &Game, vertices: Vec<Vertex>) {
+        self.screen_vertices.vertices = vertices;
+        self.screen_vertices.update_and_resize_vbo();
+        g.color_mesh_gl_program.set_uniform_mvp(&Mat4::identity());
+        g.color_mesh_gl_program.set_uniform_is_drawing_points(false);
+        g.color_mesh_gl_program.set_uniform_gradient(None);
+        gl::Disable(gl::DEPTH_TEST);
+        gl::Disable(gl::CULL_FACE);
+        gl::BindVertexArray(self.screen_vertices.vao().gl_id());
+        gl::DrawArrays(gl::TRIANGLE_STRIP, 0, self.screen_vertices.vertices.len() as _);
+        gl::Enable(gl::CULL_FACE);
+        gl::Enable(gl::DEPTH_TEST);
+    }
+    // Draws the current scene, or the previous and current ones as the
+    // transition between them goes.
+    unsafe fn draw_current_scene(&mut self, g: &Game) {
+        let current = self.progress.scene_name.clone();
+        let (from, transition, t) = match self.scene_transition {
+            Some(ref transition) => (transition.from.clone(), transition.transition, transition.progress()),
+            None => return self.draw_scene(g, &current, true),
+        };
+        let vp = self.camera.viewport_size();
+        match transition.kind {
+            TransitionKind::Cut => self.draw_scene(g, &current, true),
+            TransitionKind::Fade => {
+                self.draw_scene(g, if t < 0.5 { &from } else { &current }, true);
+                let alpha = 1. - (2. * t - 1.).abs();
+                self.draw_screen_vertices(g, Self::screen_quad(Rgba::new(0., 0., 0., alpha)));
+            },
+            TransitionKind::Crossfade => {
+                self.draw_scene(g, &from, true);
+                // The current scene is blended as a whole, background
+                // included; its own shapes blend over each other too, until
+                // the end.
+                gl::BlendColor(0., 0., 0., t);
+                gl::BlendFunc(gl::CONSTANT_ALPHA, gl::ONE_MINUS_CONSTANT_ALPHA);
+                self.draw_scene(g, &current, false);
+                gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+            },
+            TransitionKind::Wipe => {
+                self.draw_scene(g, &from, true);
+                gl::Enable(gl::SCISSOR_TEST);
+                gl::Scissor(0, 0, (vp.w as f32 * t) as _, vp.h as _);
+                self.draw_scene(g, &current, true);
+                gl::Disable(gl::SCISSOR_TEST);
+            },
+            TransitionKind::Iris => {
+                self.draw_scene(g, if t < 0.5 { &from } else { &current }, true);
+                let max_radius = (vp.w as f32).hypot(vp.h as f32) / 2.;
+                let ring = self.iris_ring(max_radius * (2. * t - 1.).abs());
+                self.draw_screen_vertices(g, ring);
+            },
+        };
+    }
 }
 
 impl System for GameplaySystem {
@@ -52,6 +473,58 @@ impl System for GameplaySystem {
             _ => (),
         };
     }
+    fn on_key(&mut self, g: &Game, key: Key) {
+        if !self.is_active || !key.is_down() {
+            return;
+        }
+        // Closes inventories, maps and such, unless they are busy.
+        if key.code == Some(Keycode::Escape) && self.action_chain.is_none() && !self.progress.pushed_scene_names.is_empty() {
+            self.pop_scene(g);
+        }
+        if key.code == Some(Keycode::Tab) {
+            self.show_save_slots(g);
+        }
+        // Ctrl+1 saves to slot 1, Shift+1 loads it, and so on.
+        let slot = match key.code {
+            Some(Keycode::Num1) => 1,
+            Some(Keycode::Num2) => 2,
+            Some(Keycode::Num3) => 3,
+            Some(Keycode::Num4) => 4,
+            Some(Keycode::Num5) => 5,
+            Some(Keycode::Num6) => 6,
+            Some(Keycode::Num7) => 7,
+            Some(Keycode::Num8) => 8,
+            Some(Keycode::Num9) => 9,
+            _ => return,
+        };
+        if g.input.key(Keycode::LCtrl).is_down() || g.input.key(Keycode::RCtrl).is_down() {
+            self.save_slot = Some(slot);
+        } else if g.input.key(Keycode::LShift).is_down() || g.input.key(Keycode::RShift).is_down() {
+            self.load(g, slot);
+        }
+    }
+    fn on_mouse_button(&mut self, g: &Game, btn: MouseButton) {
+        if !self.is_active || !btn.is_left() || !btn.is_down() || self.scene_transition.is_some() {
+            return;
+        }
+        match self.action_chain {
+            Some(ref mut chain) => if chain.wait == Wait::Click {
+                chain.wait = Wait::Nothing;
+                self.message_text.string.clear();
+            },
+            None => self.click(g),
+        };
+    }
+    fn tick(&mut self, g: &Game, _t: Duration, dt: Duration) {
+        if !self.is_active {
+            return;
+        }
+        self.progress.elapsed_secs += dt.to_f64_seconds();
+        let dt = dt.to_f64_seconds() as f32;
+        self.update_scene_transition(g, dt);
+        self.animate(dt);
+        self.run_action_chain(g);
+    }
     fn draw(&mut self, g: &Game, _gfx_interp: f64) {
         if !self.is_active {
             return;
@@ -64,13 +537,36 @@ impl System for GameplaySystem {
 
             gl::UseProgram(g.color_mesh_gl_program.program().gl_id());
 
-            {
-                let mut scenes = g.loaded_scenes.borrow_mut();
-                let scene = scenes.get_mut(&self.current_scene_name).unwrap();
-                scene.sort_shape_instances_by_z();
-                for shape_instance in scene.shape_instances.iter() {
-                    draw_shape_instance(g, &self.camera, shape_instance);
-                }
+            self.draw_current_scene(g);
+            for name in self.progress.pushed_scene_names.clone() {
+                self.draw_scene(g, &name, false);
+            }
+
+            if let Some(slot) = self.save_slot.take() {
+                let thumbnail = self.capture_thumbnail();
+                self.save(g, slot, Some(&thumbnail));
+            }
+
+            // Messages at the bottom, over the scene.
+            if !self.message_text.string.is_empty() {
+                gl::Disable(gl::DEPTH_TEST);
+                gl::UseProgram(g.text_gl_program.program().gl_id());
+                let font = &g.fonts.fonts[&FontID::Debug];
+                let lines = self.message_text.string.lines().count() as i32;
+                let mvp = {
+                    let Extent2 { w, h } = font.texture_size.map(|x| x as f32) * 2. / self.camera.viewport_size().map(|x| x as f32);
+                    let y = self.camera.viewport_size().h as i32 - font.height as i32 * lines;
+                    let t = self.camera.viewport_to_ugly_ndc(Vec2::new(font.height as i32, y));
+                    Mat4::<f32>::translation_3d(t) * Mat4::scaling_3d(Vec3::new(w, h, 1.))
+                };
+                g.text_gl_program.set_uniform_mvp(&mvp);
+                g.text_gl_program.set_uniform_font_atlas_via_font_id(FontID::Debug);
+                g.text_gl_program.set_uniform_color(Rgba::white());
+                gl::BindVertexArray(self.message_text.vertices.vao().gl_id());
+                gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.message_text.indices.ibo().gl_id());
+                gl::DrawElements(gl::TRIANGLES, self.message_text.indices.indices.len() as _, gl::UNSIGNED_SHORT, ptr::null_mut());
+                gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
+                gl::Enable(gl::DEPTH_TEST);
             }
 
             gl::BindVertexArray(0);
@@ -80,78 +576,124 @@ impl System for GameplaySystem {
 }
 
 pub static mut DO_DRAW_SHAPE_STROKE_LINES: bool = true;
-pub static mut DO_DRAW_SHAPE_STROKE_POINTS: bool = true;
+// When false, fills always use the stencil, even if they could be triangulated.
+pub static mut DO_TRIANGULATE_SHAPE_FILLS: bool = true;
+// The vertices of the outline; for debugging the flattening of curves.
+pub static mut DO_DRAW_SHAPE_STROKE_POINTS: bool = false;
 
-pub unsafe fn draw_shape_instance(g: &Game, camera: &OrthoCamera2D, shape_instance: &ShapeInstance) {
-    let &ShapeInstance {
-        ref source_shape_name, name: _, xform,
-    } = shape_instance;
-    
+/// Draws the shape named `shape_name`, which `model` puts in the world.
+/// Does nothing if it isn't loaded.
+pub unsafe fn draw_shape(g: &Game, camera: &OrthoCamera2D, shape_name: &str, model: &Mat4<f32>) {
+    let loaded_shapes = g.loaded_shapes.borrow();
+    let shape = match loaded_shapes.get(shape_name) {
+        Some(shape) => shape,
+        None => return,
+    };
     let &Shape {
-        path: shape::Path {
-            is_closed, cmds: _,
-        },
+        path: _,
         style: Style {
-            stroke_thickness, stroke_color: _, fill_color: _,
-            fill_gradient: _,
+            fill_rule, ref fill_gradient, ..
         },
+    } = shape;
+
+    let scale = Vec2::new(model.cols.x.x, model.cols.x.y).magnitude().max(Vec2::new(model.cols.y.x, model.cols.y.y).magnitude());
+    let pixels_per_unit = camera.pixels_per_world_unit() * scale;
+    let tolerance = shape::flattening_tolerance(pixels_per_unit);
+
+    let mut shape_meshes = g.shape_meshes.borrow_mut();
+    let &ShapeMesh {
         ref vertices,
-        ref solid_fill_strip,
-        ref gradient_fill_strip,
-    } = &g.loaded_shapes.borrow()[source_shape_name];
+        ref subpaths,
+        ref stroke,
+        ref stroke_strips,
+        is_fill_triangulated,
+        ref fill,
+        ref fill_indices,
+        ref fill_cover_strip,
+        ..
+    } = shape_meshes.get(&g.color_mesh_gl_program, shape_name, shape, tolerance);
 
     // Set MVP once, first.
-    let mvp = camera.view_proj_matrix() * xform.model_matrix();
+    let mvp = camera.view_proj_matrix() * *model;
     g.color_mesh_gl_program.set_uniform_mvp(&mvp);
     g.color_mesh_gl_program.set_uniform_is_drawing_points(false);
 
     // Fill
-    {
+    g.color_mesh_gl_program.set_uniform_gradient(Some(fill_gradient));
+    if is_fill_triangulated && DO_TRIANGULATE_SHAPE_FILLS {
+        gl::Disable(gl::DEPTH_TEST);
+        // Triangles of mirrored instances wind the other way.
+        gl::Disable(gl::CULL_FACE);
+        gl::BindVertexArray(fill.vao().gl_id());
+        gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, fill_indices.ibo().gl_id());
+        gl::DrawElements(gl::TRIANGLES, fill_indices.indices.len() as _, gl::UNSIGNED_INT, ptr::null_mut());
+        gl::Enable(gl::CULL_FACE);
+        gl::Enable(gl::DEPTH_TEST);
+    } else {
+        // Fallback for outlines which intersect: mark covered pixels in the
+        // stencil, then cover the outline's bounds where they are marked.
         gl::Enable(gl::STENCIL_TEST);
         gl::Disable(gl::DEPTH_TEST);
         gl::Disable(gl::CULL_FACE);
 
         gl::ClearStencil(0x0); // Set clear value
+        gl::StencilMask(0xff);
         gl::Clear(gl::STENCIL_BUFFER_BIT);
         gl::ColorMask(gl::FALSE, gl::FALSE, gl::FALSE, gl::FALSE);
         gl::DepthMask(gl::FALSE);
-        gl::StencilFunc(gl::ALWAYS, 0, 1);
-        gl::StencilOp(gl::KEEP, gl::KEEP, gl::INVERT);
-        gl::StencilMask(1);
+        gl::StencilFunc(gl::ALWAYS, 0, 0xff);
+        let coverage_mask = match fill_rule {
+            // Each fan toggles the parity of the pixels it covers.
+            FillRule::EvenOdd => {
+                gl::StencilOp(gl::KEEP, gl::KEEP, gl::INVERT);
+                gl::StencilMask(1);
+                1
+            },
+            // Each fan adds its winding number (+1 or -1 depending on orientation) to the pixels it covers.
+            FillRule::NonZero => {
+                gl::StencilOpSeparate(gl::FRONT, gl::KEEP, gl::KEEP, gl::INCR_WRAP);
+                gl::StencilOpSeparate(gl::BACK, gl::KEEP, gl::KEEP, gl::DECR_WRAP);
+                0xff
+            },
+        };
 
         gl::BindVertexArray(vertices.vao().gl_id());
-        gl::DrawArrays(gl::TRIANGLE_FAN, 0, vertices.vertices.len() as _);
+        for &(ref range, _is_closed) in subpaths.iter() {
+            gl::DrawArrays(gl::TRIANGLE_FAN, range.start as _, (range.end - range.start) as _);
+        }
 
         gl::ColorMask(gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE);
         gl::DepthMask(gl::TRUE);
-        gl::StencilFunc(gl::EQUAL, 1, 1);
+        gl::StencilFunc(gl::NOTEQUAL, 0, coverage_mask);
         gl::StencilOp(gl::KEEP, gl::KEEP, gl::KEEP);
 
-        gl::BindVertexArray(solid_fill_strip.vao().gl_id());
-        gl::DrawArrays(gl::TRIANGLE_STRIP, 0, solid_fill_strip.vertices.len() as _);
-        gl::BindVertexArray(gradient_fill_strip.vao().gl_id());
-        gl::DrawArrays(gl::TRIANGLE_STRIP, 0, gradient_fill_strip.vertices.len() as _);
+        gl::BindVertexArray(fill_cover_strip.vao().gl_id());
+        gl::DrawArrays(gl::TRIANGLE_STRIP, 0, fill_cover_strip.vertices.len() as _);
 
         gl::Enable(gl::CULL_FACE);
         gl::Enable(gl::DEPTH_TEST);
         gl::Disable(gl::STENCIL_TEST);
     }
 
+    g.color_mesh_gl_program.set_uniform_gradient(None);
+
     // Stroke
     {
-        let topology = if is_closed { gl::LINE_LOOP } else { gl::LINE_STRIP };
-
-        gl::BindVertexArray(vertices.vao().gl_id());
-        gl::PointSize(stroke_thickness);
-        gl::LineWidth(stroke_thickness);
+        if DO_DRAW_SHAPE_STROKE_LINES {
+            // Strips of mirrored instances wind the other way.
+            gl::Disable(gl::CULL_FACE);
+            gl::BindVertexArray(stroke.vao().gl_id());
+            for range in stroke_strips.iter() {
+                gl::DrawArrays(gl::TRIANGLE_STRIP, range.start as _, (range.end - range.start) as _);
+            }
+            gl::Enable(gl::CULL_FACE);
+        }
         if DO_DRAW_SHAPE_STROKE_POINTS {
+            gl::BindVertexArray(vertices.vao().gl_id());
+            gl::PointSize(4.);
             g.color_mesh_gl_program.set_uniform_is_drawing_points(true);
             gl::DrawArrays(gl::POINTS, 0, vertices.vertices.len() as _);
             g.color_mesh_gl_program.set_uniform_is_drawing_points(false);
         }
-        if DO_DRAW_SHAPE_STROKE_LINES {
-            gl::DrawArrays(topology, 0, vertices.vertices.len() as _);
-        }
     }
 }
-
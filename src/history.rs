@@ -0,0 +1,304 @@
+// Undo and redo for the editor.
+//
+// Each step of the history is a command which can be undone and redone: it
+// holds the shapes and scenes which it changed, as they were before and
+// after. The editor takes a `Checkpoint` before each action, and records a
+// step from it afterwards if anything changed.
+//
+// Consecutive steps recorded with the same merge key become a single one,
+// so that e.g a whole drag is undone at once. The oldest steps are
+// forgotten when the history takes more memory than its budget.
+
+use std::mem;
+use std::collections::{HashMap, VecDeque};
+use shape::{Shape, SubPath, PathCmd, NodeStyle, GradientStop};
+use scene::{Scene, ShapeInstance, Override, Hotspot, Action, Property, Properties};
+
+pub const DEFAULT_MEMORY_BUDGET: usize = 16 << 20;
+
+/// Roughly how many bytes a document takes in memory.
+trait MemorySize {
+    fn memory_size(&self) -> usize;
+}
+
+impl MemorySize for Shape {
+    fn memory_size(&self) -> usize {
+        let style = &self.style;
+        let stops = style.stroke_gradient.len() + style.fill_gradient.stops.len();
+        let subpaths: usize = self.path.subpaths.iter().map(|s| {
+            mem::size_of::<SubPath>() + s.cmds.len() * mem::size_of::<PathCmd>() + s.node_styles.len() * mem::size_of::<NodeStyle>()
+        }).sum();
+        mem::size_of::<Shape>() + stops * mem::size_of::<GradientStop>() + style.stroke_dashes.len() * mem::size_of::<f32>() + subpaths
+    }
+}
+
+impl MemorySize for Scene {
+    fn memory_size(&self) -> usize {
+        let properties = |p: &Properties| p.iter().map(|(k, v)| mem::size_of::<Property>() + k.len() + match *v {
+            Property::Text(ref t) => t.len(),
+            _ => 0,
+        }).sum::<usize>();
+        // Texts and names within actions aren't counted.
+        let actions = |a: &[Action]| mem::size_of_val(a);
+        mem::size_of::<Scene>() + properties(&self.properties) + self.shape_instances.iter().map(|i| {
+            mem::size_of::<ShapeInstance>() + i.name.len() + i.source.name().len() + properties(&i.properties) + actions(&i.on_click) + i.overrides.iter().map(|(path, o)| {
+                mem::size_of::<Override>() + path.len() + properties(&o.properties)
+            }).sum::<usize>()
+        }).sum::<usize>() + self.hotspots.iter().map(|h| {
+            mem::size_of::<Hotspot>() + h.name.len() + properties(&h.properties) + actions(&h.on_click)
+        }).sum::<usize>()
+    }
+}
+
+/// A document, before and after a step. `None` means that it wasn't loaded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change<T> {
+    pub name: String,
+    pub before: Option<T>,
+    pub after: Option<T>,
+}
+
+fn change_memory_size<T: MemorySize>(change: &Change<T>) -> usize {
+    let size = |x: &Option<T>| x.as_ref().map_or(0, MemorySize::memory_size);
+    mem::size_of::<Change<T>>() + change.name.len() + size(&change.before) + size(&change.after)
+}
+
+// The changes between two versions of a set of documents.
+fn changes<T: Clone + PartialEq>(before: &HashMap<String, T>, after: &HashMap<String, T>) -> Vec<Change<T>> {
+    let mut names: Vec<&String> = before.keys().chain(after.keys().filter(|name| !before.contains_key(*name))).collect();
+    names.sort();
+    names.into_iter().filter(|name| before.get(*name) != after.get(*name)).map(|name| Change {
+        name: name.clone(),
+        before: before.get(name).cloned(),
+        after: after.get(name).cloned(),
+    }).collect()
+}
+
+// Adds `new` on top of `changes`, which happened first.
+fn merge_changes<T>(changes: &mut Vec<Change<T>>, new: Vec<Change<T>>) {
+    for change in new {
+        match changes.iter_mut().find(|c| c.name == change.name) {
+            Some(c) => c.after = change.after,
+            None => changes.push(change),
+        };
+    }
+}
+
+fn set<T: Clone>(documents: &mut HashMap<String, T>, name: &str, document: &Option<T>) {
+    match *document {
+        Some(ref d) => { documents.insert(name.to_owned(), d.clone()); },
+        None => { documents.remove(name); },
+    };
+}
+
+/// All documents which the editor can change, as they were at some point.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Checkpoint {
+    pub shapes: HashMap<String, Shape>,
+    pub scenes: HashMap<String, Scene>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Step {
+    label: String,
+    merge_key: Option<u32>,
+    shapes: Vec<Change<Shape>>,
+    scenes: Vec<Change<Scene>>,
+}
+
+impl Step {
+    fn memory_size(&self) -> usize {
+        mem::size_of::<Self>() + self.label.len()
+            + self.shapes.iter().map(change_memory_size).sum::<usize>()
+            + self.scenes.iter().map(change_memory_size).sum::<usize>()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct History {
+    /// Oldest first.
+    done: VecDeque<Step>,
+    /// Most recently undone last.
+    undone: Vec<Step>,
+    memory_budget: usize,
+    memory_used: usize,
+    /// Whether the next step may merge into the last one; not after undoing or redoing.
+    can_merge: bool,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new(DEFAULT_MEMORY_BUDGET)
+    }
+}
+
+impl History {
+    pub fn new(memory_budget: usize) -> Self {
+        Self {
+            done: VecDeque::new(),
+            undone: vec![],
+            memory_budget,
+            memory_used: 0,
+            can_merge: false,
+        }
+    }
+    pub fn memory_used(&self) -> usize {
+        self.memory_used
+    }
+    /// Labels of the steps which can be undone, oldest first.
+    pub fn done_labels(&self) -> Vec<&str> {
+        self.done.iter().map(|s| s.label.as_str()).collect()
+    }
+    /// Labels of the steps which can be redone, next one first.
+    pub fn undone_labels(&self) -> Vec<&str> {
+        self.undone.iter().rev().map(|s| s.label.as_str()).collect()
+    }
+    /// Records what changed from `before` to `after` as a new step, and
+    /// returns whether anything did. The steps which were undone can't be
+    /// redone anymore.
+    ///
+    /// If `merge_key` is the same as the last step's, the change is added to
+    /// that step instead, which keeps its label.
+    pub fn record(&mut self, label: &str, merge_key: Option<u32>, before: &Checkpoint, after: &Checkpoint) -> bool {
+        let shapes = changes(&before.shapes, &after.shapes);
+        let scenes = changes(&before.scenes, &after.scenes);
+        if shapes.is_empty() && scenes.is_empty() {
+            return false;
+        }
+        self.undone.clear();
+        let is_merging = self.can_merge && merge_key.is_some() && self.done.back().map(|s| s.merge_key) == Some(merge_key);
+        if is_merging {
+            let last = self.done.back_mut().unwrap();
+            merge_changes(&mut last.shapes, shapes);
+            merge_changes(&mut last.scenes, scenes);
+        } else {
+            self.done.push_back(Step {
+                label: label.to_owned(),
+                merge_key,
+                shapes,
+                scenes,
+            });
+        }
+        self.can_merge = true;
+        self.forget_oldest_steps();
+        true
+    }
+    // Recomputes the memory in use, dropping the oldest steps while it is
+    // over budget. The last step is always kept.
+    fn forget_oldest_steps(&mut self) {
+        self.memory_used = self.done.iter().chain(self.undone.iter()).map(Step::memory_size).sum();
+        while self.memory_used > self.memory_budget && self.done.len() > 1 {
+            self.memory_used -= self.done.pop_front().unwrap().memory_size();
+        }
+    }
+    /// Puts the documents back as they were before the last step, and
+    /// returns its label.
+    pub fn undo(&mut self, shapes: &mut HashMap<String, Shape>, scenes: &mut HashMap<String, Scene>) -> Option<String> {
+        let step = self.done.pop_back()?;
+        for c in step.shapes.iter() {
+            set(shapes, &c.name, &c.before);
+        }
+        for c in step.scenes.iter() {
+            set(scenes, &c.name, &c.before);
+        }
+        let label = step.label.clone();
+        self.undone.push(step);
+        self.can_merge = false;
+        Some(label)
+    }
+    /// Does again the last step which was undone, and returns its label.
+    pub fn redo(&mut self, shapes: &mut HashMap<String, Shape>, scenes: &mut HashMap<String, Scene>) -> Option<String> {
+        let step = self.undone.pop()?;
+        for c in step.shapes.iter() {
+            set(shapes, &c.name, &c.after);
+        }
+        for c in step.scenes.iter() {
+            set(scenes, &c.name, &c.after);
+        }
+        let label = step.label.clone();
+        self.done.push_back(step);
+        self.can_merge = false;
+        Some(label)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use v::Vec2;
+    use shape::Path;
+
+    fn checkpoint(shapes: &[(&str, &Shape)]) -> Checkpoint {
+        Checkpoint {
+            shapes: shapes.iter().map(|&(name, shape)| (name.to_owned(), shape.clone())).collect(),
+            scenes: HashMap::new(),
+        }
+    }
+
+    fn square(size: f32) -> Shape {
+        Shape { path: Path::rect(Vec2::zero(), Vec2::broadcast(size)), ..Shape::default() }
+    }
+
+    #[test]
+    fn undo_and_redo_swap_the_changed_documents() {
+        let (a, b) = (square(1.), square(2.));
+        let mut history = History::default();
+        assert!(history.record("grow", None, &checkpoint(&[("x", &a)]), &checkpoint(&[("x", &b), ("y", &a)])));
+        let mut shapes = checkpoint(&[("x", &b), ("y", &a)]).shapes;
+        let mut scenes = HashMap::new();
+        assert_eq!(history.undo(&mut shapes, &mut scenes), Some("grow".to_owned()));
+        assert_eq!(shapes, checkpoint(&[("x", &a)]).shapes);
+        assert_eq!(history.undo(&mut shapes, &mut scenes), None);
+        history.redo(&mut shapes, &mut scenes).unwrap();
+        assert_eq!(shapes, checkpoint(&[("x", &b), ("y", &a)]).shapes);
+    }
+
+    #[test]
+    fn nothing_is_recorded_without_changes() {
+        let a = square(1.);
+        let mut history = History::default();
+        assert!(!history.record("nothing", None, &checkpoint(&[("x", &a)]), &checkpoint(&[("x", &a)])));
+        assert!(history.done_labels().is_empty());
+    }
+
+    #[test]
+    fn steps_with_the_same_merge_key_merge() {
+        let shapes: Vec<Shape> = (1 .. 5).map(|i| square(i as f32)).collect();
+        let mut history = History::default();
+        history.record("drag", Some(1), &checkpoint(&[("x", &shapes[0])]), &checkpoint(&[("x", &shapes[1])]));
+        history.record("drag", Some(1), &checkpoint(&[("x", &shapes[1])]), &checkpoint(&[("x", &shapes[2])]));
+        history.record("drag", Some(2), &checkpoint(&[("x", &shapes[2])]), &checkpoint(&[("x", &shapes[3])]));
+        assert_eq!(history.done_labels(), vec!["drag", "drag"]);
+        let mut current = checkpoint(&[("x", &shapes[3])]).shapes;
+        history.undo(&mut current, &mut HashMap::new());
+        history.undo(&mut current, &mut HashMap::new());
+        assert_eq!(current["x"], shapes[0]);
+        assert_eq!(history.undone_labels().len(), 2);
+    }
+
+    #[test]
+    fn recording_forgets_undone_steps() {
+        let (a, b, c) = (square(1.), square(2.), square(3.));
+        let mut history = History::default();
+        history.record("b", None, &checkpoint(&[("x", &a)]), &checkpoint(&[("x", &b)]));
+        let mut current = checkpoint(&[("x", &b)]).shapes;
+        history.undo(&mut current, &mut HashMap::new());
+        history.record("c", None, &checkpoint(&[("x", &a)]), &checkpoint(&[("x", &c)]));
+        assert!(history.undone_labels().is_empty());
+        assert_eq!(history.done_labels(), vec!["c"]);
+    }
+
+    #[test]
+    fn oldest_steps_are_forgotten_over_budget() {
+        let shapes: Vec<Shape> = (1 .. 12).map(|i| square(i as f32)).collect();
+        let mut one_step = History::default();
+        one_step.record("0", None, &checkpoint(&[("x", &shapes[0])]), &checkpoint(&[("x", &shapes[1])]));
+        let mut history = History::new(one_step.memory_used() * 3);
+        for (i, w) in shapes.windows(2).enumerate() {
+            history.record(&i.to_string(), None, &checkpoint(&[("x", &w[0])]), &checkpoint(&[("x", &w[1])]));
+        }
+        assert_eq!(history.done_labels(), vec!["7", "8", "9"]);
+        assert!(history.memory_used() <= one_step.memory_used() * 3);
+    }
+}
@@ -75,6 +75,11 @@ impl System for InputSystem {
                     },
                 });
             },
+            Keycode::F5 => if key.is_down() {
+                unsafe {
+                    ::gameplay::DO_TRIANGULATE_SHAPE_FILLS = !::gameplay::DO_TRIANGULATE_SHAPE_FILLS;
+                }
+            },
             Keycode::F6 => if key.is_down() {
                 unsafe {
                     ::gameplay::DO_DRAW_SHAPE_STROKE_LINES = !::gameplay::DO_DRAW_SHAPE_STROKE_LINES;
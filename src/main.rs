@@ -9,6 +9,7 @@ extern crate alto;
 extern crate freetype_sys;
 #[macro_use] #[allow(unused_imports)]
 extern crate serde;
+extern crate ron;
 #[macro_use]
 extern crate log;
 extern crate env_logger;
@@ -26,8 +27,12 @@ pub mod grx;
 pub mod gx;
 pub mod mesh;
 pub mod editor;
+pub mod snap;
+pub mod history;
 pub mod gameplay;
 pub mod scene;
+pub mod progress;
+pub mod save;
 pub mod shape;
 pub mod camera;
 pub mod xform;
@@ -35,6 +40,7 @@ pub mod message;
 pub mod paths;
 pub mod font;
 pub mod text;
+pub mod cli;
 
 use std::time::Duration;
 use game::Game;
@@ -45,6 +51,11 @@ fn main() {
     early::setup_env();
     early::setup_log();
 
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        std::process::exit(cli::run(&args));
+    }
+
     let g = Game::new("Grisui - Prelude", 800, 480);
     let mut time = TimeManager::with_fixed_dt_and_frame_time_ceil(
         Duration::from_millis(50),
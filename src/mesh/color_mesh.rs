@@ -3,6 +3,7 @@ use gx;
 use grx;
 use gl::{self, types::*};
 use v::{Vec3, Rgba, Mat4};
+use shape::{Gradient, GradientKind, GradientSpread};
 use super::{ProgramAttribs, Attrib};
 
 
@@ -11,6 +12,13 @@ pub struct Program {
     program: gx::Program,
     u_mvp: GLint,
     u_is_drawing_points: GLint,
+    u_gradient_kind: GLint,
+    u_gradient_spread: GLint,
+    u_gradient_start: GLint,
+    u_gradient_end: GLint,
+    u_gradient_stop_count: GLint,
+    u_gradient_offsets: GLint,
+    u_gradient_colors: GLint,
     a_position: GLuint,
     a_color: GLuint,
 }
@@ -56,18 +64,65 @@ uniform mat4 u_mvp;
 in vec3 a_position;
 in vec4 a_color;
 out vec4 v_color;
+out vec2 v_position;
 void main() {
     gl_Position = u_mvp * vec4(a_position, 1.0);
     v_color = a_color;
+    v_position = a_position.xy;
 }
 \0";
 
 
+    // The gradient is blended over the vertex color. Keep in sync with `shape::Gradient::color_at()`.
+    // The size of the stop arrays is `shape::gradient::MAX_STOPS`.
     const FS: &'static [u8] = b"
 #version 130
 uniform bool u_is_drawing_points;
+uniform int u_gradient_kind; // 0: none, 1: linear, 2: radial, 3: conic
+uniform int u_gradient_spread; // 0: pad, 1: repeat, 2: reflect
+uniform vec2 u_gradient_start;
+uniform vec2 u_gradient_end;
+uniform int u_gradient_stop_count;
+uniform float u_gradient_offsets[16];
+uniform vec4 u_gradient_colors[16];
 in vec4 v_color;
+in vec2 v_position;
 out vec4 f_color;
+
+float gradient_offset() {
+    vec2 d = u_gradient_end - u_gradient_start;
+    vec2 p = v_position - u_gradient_start;
+    float t;
+    if(u_gradient_kind == 1) {
+        t = dot(p, d) / max(dot(d, d), 1e-12f);
+    } else if(u_gradient_kind == 2) {
+        t = length(p) / max(length(d), 1e-6f);
+    } else {
+        t = atan(d.x * p.y - d.y * p.x, dot(d, p)) / 6.28318530718f;
+        if(t < 0.f)
+            t += 1.f;
+    }
+    if(u_gradient_spread == 1)
+        return fract(t);
+    if(u_gradient_spread == 2)
+        return 1.f - abs(mod(t, 2.f) - 1.f);
+    return clamp(t, 0.f, 1.f);
+}
+
+vec4 gradient_color(float t) {
+    if(t <= u_gradient_offsets[0])
+        return u_gradient_colors[0];
+    for(int i = 1; i < u_gradient_stop_count; ++i) {
+        float a = u_gradient_offsets[i - 1];
+        float b = u_gradient_offsets[i];
+        if(t <= b) {
+            float s = b > a ? (t - a) / (b - a) : 1.f;
+            return mix(u_gradient_colors[i - 1], u_gradient_colors[i], s);
+        }
+    }
+    return u_gradient_colors[u_gradient_stop_count - 1];
+}
+
 void main() {
     if(u_is_drawing_points) {
         vec2 from_center = gl_PointCoord - vec2(0.5f);
@@ -76,6 +131,11 @@ void main() {
             discard;
     }
     f_color = v_color;
+    if(u_gradient_kind != 0 && u_gradient_stop_count > 0) {
+        vec4 g = gradient_color(gradient_offset());
+        float a = g.a + f_color.a * (1.f - g.a);
+        f_color = a > 0.f ? vec4((g.rgb * g.a + f_color.rgb * f_color.a * (1.f - g.a)) / a, a) : vec4(0.f);
+    }
 }
 \0";
 
@@ -112,9 +172,19 @@ void main() {
         let a_color = program.attrib_location(b"a_color\0").unwrap() as _;
         let u_mvp = program.uniform_location(b"u_mvp\0").unwrap();
         let u_is_drawing_points = program.uniform_location(b"u_is_drawing_points\0").unwrap();
+        let u_gradient_kind = program.uniform_location(b"u_gradient_kind\0").unwrap();
+        let u_gradient_spread = program.uniform_location(b"u_gradient_spread\0").unwrap();
+        let u_gradient_start = program.uniform_location(b"u_gradient_start\0").unwrap();
+        let u_gradient_end = program.uniform_location(b"u_gradient_end\0").unwrap();
+        let u_gradient_stop_count = program.uniform_location(b"u_gradient_stop_count\0").unwrap();
+        let u_gradient_offsets = program.uniform_location(b"u_gradient_offsets\0").unwrap();
+        let u_gradient_colors = program.uniform_location(b"u_gradient_colors\0").unwrap();
 
         Self {
-            program, u_mvp, u_is_drawing_points, a_position, a_color,
+            program, u_mvp, u_is_drawing_points,
+            u_gradient_kind, u_gradient_spread, u_gradient_start, u_gradient_end,
+            u_gradient_stop_count, u_gradient_offsets, u_gradient_colors,
+            a_position, a_color,
         }
     }
     pub fn set_uniform_mvp(&self, m: &Mat4<f32>) {
@@ -128,5 +198,39 @@ void main() {
             gl::Uniform1i(self.u_is_drawing_points, yes as _);
         }
     }
+    /// Blends the given gradient over vertex colors, or stops doing so if `None`.
+    pub fn set_uniform_gradient(&self, gradient: Option<&Gradient>) {
+        let gradient = match gradient {
+            Some(g) if !g.is_none() => g,
+            _ => {
+                unsafe {
+                    gl::Uniform1i(self.u_gradient_kind, 0);
+                }
+                return;
+            },
+        };
+        let kind = match gradient.kind {
+            GradientKind::Linear => 1,
+            GradientKind::Radial => 2,
+            GradientKind::Conic => 3,
+        };
+        let spread = match gradient.spread {
+            GradientSpread::Pad => 0,
+            GradientSpread::Repeat => 1,
+            GradientSpread::Reflect => 2,
+        };
+        let stops = gradient.normalized_stops();
+        let offsets = stops.iter().map(|s| s.offset).collect::<Vec<_>>();
+        let colors = stops.iter().map(|s| s.color).collect::<Vec<Rgba<f32>>>();
+        unsafe {
+            gl::Uniform1i(self.u_gradient_kind, kind);
+            gl::Uniform1i(self.u_gradient_spread, spread);
+            gl::Uniform2f(self.u_gradient_start, gradient.start.x, gradient.start.y);
+            gl::Uniform2f(self.u_gradient_end, gradient.end.x, gradient.end.y);
+            gl::Uniform1i(self.u_gradient_stop_count, stops.len() as _);
+            gl::Uniform1fv(self.u_gradient_offsets, offsets.len() as _, offsets.as_ptr());
+            gl::Uniform4fv(self.u_gradient_colors, colors.len() as _, colors.as_ptr() as *const GLfloat);
+        }
+    }
 }
 
@@ -4,6 +4,7 @@ pub mod vertex_array;
 pub mod index_array;
 pub mod color_mesh;
 pub mod text;
+pub mod shape_mesh;
 
 
 pub trait ProgramAttribs {
@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+use std::ops::Range;
+use gx::BufferUsage;
+use shape::{Shape, Tessellation};
+use super::{vertex_array, index_array::IndexArray, color_mesh};
+
+type ColorVertexArray = vertex_array::VertexArray<color_mesh::Program>;
+
+/// GPU-side resources for drawing a `Shape`.
+///
+/// It remembers which `Shape` it was built from and with which flattening
+/// tolerance, so that it can be rebuilt lazily when the source shape is
+/// modified or when more precision is needed.
+#[derive(Debug)]
+pub struct ShapeMesh {
+    source: Shape,
+    tolerance: f32,
+    pub vertices: ColorVertexArray,
+    pub subpaths: Vec<(Range<usize>, bool)>,
+    pub stroke: ColorVertexArray,
+    pub stroke_strips: Vec<Range<usize>>,
+    pub is_fill_triangulated: bool,
+    pub fill: ColorVertexArray,
+    pub fill_indices: IndexArray<u32>,
+    pub fill_cover_strip: ColorVertexArray,
+}
+
+impl ShapeMesh {
+    pub fn new(color_mesh_gl_program: &color_mesh::Program, shape: &Shape, tolerance: f32) -> Self {
+        let Tessellation {
+            vertices, subpaths, stroke_vertices, stroke_strips,
+            is_fill_triangulated, fill_vertices, fill_indices,
+            fill_cover_strip,
+        } = shape.tessellate(tolerance);
+        let new_array = |label, vertices| ColorVertexArray::from_vertices(
+            color_mesh_gl_program, label, BufferUsage::DynamicDraw, vertices
+        );
+        Self {
+            source: shape.clone(),
+            tolerance,
+            vertices: new_array("Some Shape Vertices", vertices),
+            subpaths,
+            stroke: new_array("Some Shape Stroke", stroke_vertices),
+            stroke_strips,
+            is_fill_triangulated,
+            fill: new_array("Some Shape Fill", fill_vertices),
+            fill_indices: IndexArray::from_indices("Some Shape Fill", BufferUsage::DynamicDraw, fill_indices),
+            fill_cover_strip: new_array("Some Shape Fill Cover Strip", fill_cover_strip),
+        }
+    }
+    /// Is this mesh built from `shape`, and at least as precise as `tolerance` requires?
+    ///
+    /// A mesh that is more precise than needed is fine: this way, instances of
+    /// the same shape at different scales don't make it flip-flop every frame.
+    pub fn is_up_to_date(&self, shape: &Shape, tolerance: f32) -> bool {
+        self.source == *shape && self.tolerance <= tolerance
+    }
+    pub fn update_gl(&mut self, shape: &Shape, tolerance: f32) {
+        let Tessellation {
+            vertices, subpaths, stroke_vertices, stroke_strips,
+            is_fill_triangulated, fill_vertices, fill_indices,
+            fill_cover_strip,
+        } = shape.tessellate(tolerance);
+        self.vertices.vertices = vertices;
+        self.subpaths = subpaths;
+        self.vertices.update_and_resize_vbo();
+        self.stroke.vertices = stroke_vertices;
+        self.stroke_strips = stroke_strips;
+        self.stroke.update_and_resize_vbo();
+        self.is_fill_triangulated = is_fill_triangulated;
+        self.fill.vertices = fill_vertices;
+        self.fill.update_and_resize_vbo();
+        self.fill_indices.indices = fill_indices;
+        self.fill_indices.update_and_resize_ibo();
+        self.fill_cover_strip.vertices = fill_cover_strip;
+        self.fill_cover_strip.update_and_resize_vbo();
+        self.source = shape.clone();
+        self.tolerance = tolerance;
+    }
+}
+
+/// Cache of `ShapeMesh`es, keyed by shape name.
+#[derive(Debug, Default)]
+pub struct ShapeMeshes {
+    meshes: HashMap<String, ShapeMesh>,
+}
+
+impl ShapeMeshes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Gets the mesh for the given shape, creating or refreshing it as needed.
+    /// `tolerance` is in the shape's local units; see `shape::flattening_tolerance()`.
+    pub fn get(&mut self, color_mesh_gl_program: &color_mesh::Program, name: &str, shape: &Shape, tolerance: f32) -> &ShapeMesh {
+        if !self.meshes.contains_key(name) {
+            self.meshes.insert(name.to_owned(), ShapeMesh::new(color_mesh_gl_program, shape, tolerance));
+        }
+        let mesh = self.meshes.get_mut(name).unwrap();
+        if !mesh.is_up_to_date(shape, tolerance) {
+            // Don't lose precision that other instances may still need.
+            let tolerance = if mesh.source == *shape { tolerance.min(mesh.tolerance) } else { tolerance };
+            mesh.update_gl(shape, tolerance);
+        }
+        mesh
+    }
+    pub fn remove(&mut self, name: &str) {
+        self.meshes.remove(name);
+    }
+}
@@ -1,3 +1,5 @@
+use scene::Transition;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Message {
     EnterEditor,
@@ -5,5 +7,15 @@ pub enum Message {
 
     EnterGameplay,
     LeaveGameplay,
+
+    /// Gameplay started leaving the scene named `from` for the one named
+    /// `to`, which is current from now on.
+    SceneTransitionStarted { from: String, to: String, transition: Transition },
+    /// The transition to the scene is over.
+    SceneTransitionEnded { to: String },
+    /// A scene was shown over the current one.
+    ScenePushed(String),
+    /// The scene on top, which was pushed, was hidden.
+    ScenePopped(String),
 }
 
@@ -1,11 +1,12 @@
 use std::fs::{self, File, ReadDir, DirEntry};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::env;
-use std::collections::HashMap;
+use std::io::{self, Read};
+use std::collections::{HashMap, BTreeMap};
 
-use mesh::color_mesh;
 use scene;
 use shape;
+use save;
 
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
 pub struct Paths {
@@ -17,38 +18,130 @@ pub struct Paths {
 }
 
 impl Paths {
+    /// Loads `.ron` scenes, and legacy `.scene` ones which have no `.ron`
+    /// counterpart yet. The latter get converted when saved from the editor.
     pub fn load_scenes(&self) -> HashMap<String, scene::Scene> {
         let mut scenes = HashMap::new();
         for entry in fs::read_dir(&self.scenes).unwrap().filter_map(Result::ok) {
-            if let Some(ext) = entry.path().extension() {
-                if ext == "scene" {
-                    let scene = scene::Scene::load(&mut File::open(entry.path()).unwrap()).unwrap();
-                    let name = entry.path().file_stem().unwrap().to_str().unwrap().to_owned();
-                    scenes.insert(name, scene);
-                }
+            let path = entry.path();
+            let name = path.file_stem().unwrap().to_str().unwrap().to_owned();
+            let is_scene = match path.extension() {
+                Some(ext) if ext == "ron" => true,
+                Some(ext) if ext == "scene" => !self.scene_path_from_name(&name).exists(),
+                _ => false,
+            };
+            if !is_scene {
+                continue;
             }
+            match self.load_scene(&path) {
+                Ok(scene) => { scenes.insert(name, scene); },
+                Err(e) => error!("Paths: Skipping scene `{}`: {}", path.display(), e),
+            };
         }
         scenes
     }
-    pub fn load_shapes(&self, color_mesh_gl_program: &color_mesh::Program) -> HashMap<String, shape::Shape> {
+    /// Loads the scene file at `path`, in the legacy format if its extension is `.scene`.
+    pub fn load_scene(&self, path: &Path) -> Result<scene::Scene, scene::LoadError> {
+        let mut f = File::open(path)?;
+        match path.extension() {
+            Some(ext) if ext == "scene" => scene::Scene::load_legacy(&mut f),
+            _ => scene::Scene::load(&mut f),
+        }
+    }
+    pub fn load_shapes(&self) -> HashMap<String, shape::Shape> {
         let mut shapes = HashMap::new();
         for entry in fs::read_dir(&self.shapes).unwrap().filter_map(Result::ok) {
             if let Some(ext) = entry.path().extension() {
                 if ext == "shape" {
-                    let shape = shape::Shape::load(color_mesh_gl_program, &mut File::open(entry.path()).unwrap()).unwrap();
-                    let name = entry.path().file_stem().unwrap().to_str().unwrap().to_owned();
+                    let path = entry.path();
+                    let shape = File::open(&path).map_err(shape::LoadError::from)
+                        .and_then(|mut f| shape::Shape::load(&mut f));
+                    let shape = match shape {
+                        Ok(shape) => shape,
+                        Err(shape::LoadError::Parse(e)) => {
+                            error!("Paths: Skipping shape: {}", e.in_file(path));
+                            continue;
+                        },
+                        Err(e) => {
+                            error!("Paths: Skipping shape `{}`: {}", path.display(), e);
+                            continue;
+                        },
+                    };
+                    let name = path.file_stem().unwrap().to_str().unwrap().to_owned();
                     shapes.insert(name, shape);
                 }
             }
         }
         shapes
     }
+    /// Imports the paths of an SVG file as shapes, and saves them in `self.shapes`.
+    /// Shapes are named after the SVG file; see `shape::svg::import()`.
+    pub fn import_svg(&self, svg_path: &Path) -> Result<Vec<(String, shape::Shape)>, shape::svg::ImportError> {
+        let mut src = String::new();
+        File::open(svg_path)?.read_to_string(&mut src)?;
+        let prefix = svg_path.file_stem().and_then(|s| s.to_str()).unwrap_or("svg");
+        let shapes = shape::svg::import(&src, prefix).map_err(|e| e.in_file(svg_path))?;
+        for &(ref name, ref shape) in &shapes {
+            shape.save(&mut File::create(self.shape_path_from_name(name))?)?;
+        }
+        Ok(shapes)
+    }
+    /// Loads the metadata of each save slot which holds a save, skipping
+    /// the ones which fail to load.
+    pub fn load_save_slots(&self) -> BTreeMap<u32, save::Metadata> {
+        let mut slots = BTreeMap::new();
+        for entry in fs::read_dir(&self.saves).unwrap().filter_map(Result::ok) {
+            let path = entry.path();
+            match path.extension() {
+                Some(ext) if ext == "ron" => (),
+                _ => continue,
+            };
+            let slot = match path.file_stem().and_then(|s| s.to_str()).and_then(|s| s.trim_start_matches("slot").parse().ok()) {
+                Some(slot) => slot,
+                None => continue,
+            };
+            match self.load_save(slot) {
+                Ok(save) => { slots.insert(slot, save.metadata); },
+                Err(e) => error!("Paths: Skipping save `{}`: {}", path.display(), e),
+            };
+        }
+        slots
+    }
+    pub fn load_save(&self, slot: u32) -> Result<save::Save, save::LoadError> {
+        save::Save::load(&mut File::open(self.save_path_from_slot(slot))?)
+    }
+    pub fn load_save_thumbnail(&self, slot: u32) -> Result<save::Thumbnail, save::LoadError> {
+        save::Thumbnail::load_ppm(&mut File::open(self.save_thumbnail_path_from_slot(slot))?)
+    }
+    /// Replaces the save in `slot`, if any. Each file is replaced as a whole,
+    /// the thumbnail first, so that the save never refers to a missing one.
+    pub fn write_save(&self, slot: u32, save: &save::Save, thumbnail: Option<&save::Thumbnail>) -> io::Result<()> {
+        if let Some(thumbnail) = thumbnail {
+            save::write_atomically(&self.save_thumbnail_path_from_slot(slot), |f| thumbnail.save_ppm(f))?;
+        }
+        save::write_atomically(&self.save_path_from_slot(slot), |f| save.save(f))
+    }
+    pub fn save_path_from_slot(&self, slot: u32) -> PathBuf {
+        let mut path = self.saves.clone();
+        path.push(format!("slot{}.ron", slot));
+        path
+    }
+    pub fn save_thumbnail_path_from_slot(&self, slot: u32) -> PathBuf {
+        let mut path = self.saves.clone();
+        path.push(format!("slot{}.ppm", slot));
+        path
+    }
     pub fn shape_path_from_name(&self, name: &str) -> PathBuf {
         let mut path = self.shapes.clone();
         path.push(format!("{}.shape", name));
         path
     }
     pub fn scene_path_from_name(&self, name: &str) -> PathBuf {
+        let mut path = self.scenes.clone();
+        path.push(format!("{}.ron", name));
+        path
+    }
+    pub fn legacy_scene_path_from_name(&self, name: &str) -> PathBuf {
         let mut path = self.scenes.clone();
         path.push(format!("{}.scene", name));
         path
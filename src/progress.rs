@@ -0,0 +1,34 @@
+// What the player changed in the world while playing, as opposed to the
+// scenes themselves, which gameplay never changes.
+
+use std::collections::BTreeMap;
+use scene::{Override, Properties};
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Progress {
+    pub scene_name: String,
+    /// The scenes shown over the current one, from bottom to top.
+    pub pushed_scene_names: Vec<String>,
+    pub properties: Properties,
+    /// The items which the player picked up, in order.
+    pub inventory: Vec<String>,
+    /// Changes to the instances of each scene, keyed by scene name, then by
+    /// the path of the instance; see `Scene::placements_with_overrides()`.
+    pub overrides: BTreeMap<String, BTreeMap<String, Override>>,
+    /// How long the game was played for, in seconds.
+    pub elapsed_secs: f64,
+}
+
+impl Progress {
+    /// The progress of a game which starts in the scene named `scene_name`.
+    pub fn new(scene_name: &str) -> Self {
+        Self { scene_name: scene_name.to_owned(), .. Self::default() }
+    }
+    /// The change to the instance at `path` in the scene named `scene_name`,
+    /// which is none at first.
+    pub fn override_mut(&mut self, scene_name: &str, path: &str) -> &mut Override {
+        self.overrides.entry(scene_name.to_owned()).or_insert_with(BTreeMap::new)
+            .entry(path.to_owned()).or_insert_with(Override::default)
+    }
+}
@@ -0,0 +1,301 @@
+// Saved games, one per numbered slot in the `saves/` folder.
+//
+// Each slot is a RON file (`slot1.ron`, ...) holding the metadata shown in
+// the list of slots, and the progress of the game:
+//
+// (
+//     version: 1,
+//     metadata: (timestamp_secs: 1700000000, scene_name: "hall", elapsed_secs: 754.2),
+//     progress: (scene_name: "hall", pushed_scene_names: [], properties: {}, inventory: ["key"], overrides: {}),
+// )
+//
+// along with a thumbnail of the screen as a binary PPM image (`slot1.ppm`).
+// Files are written to a temporary file first, then renamed over the
+// previous one, so that a crash while saving doesn't lose the slot.
+//
+// Version 0 is the unversioned `Save` of earlier prototypes, with a numeric
+// `scene_id` and a `has_unlocked_door` flag. It has no scene names, so it
+// starts in the "default" scene, and the flag becomes a property.
+
+use std::io;
+use std::fs::{self, File};
+use std::path::Path;
+use std::fmt::{self, Display, Formatter};
+use std::error::Error;
+use std::time::{SystemTime, UNIX_EPOCH};
+use ron;
+use v::Extent2;
+use scene::Property;
+use progress::Progress;
+
+/// The version of the format which `Save::save()` writes.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// What the list of slots shows about a save.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Metadata {
+    /// When it was saved, in seconds since the Unix epoch.
+    pub timestamp_secs: u64,
+    pub scene_name: String,
+    /// How long the game was played for.
+    pub elapsed_secs: f64,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Save {
+    pub metadata: Metadata,
+    pub progress: Progress,
+}
+
+/// A small picture of the screen, as it was when saving.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Thumbnail {
+    pub size: Extent2<u32>,
+    /// Rows from top to bottom, 3 bytes per pixel.
+    pub rgb: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    Ron(ron::de::Error),
+    /// Most likely, the game was saved by a newer version of the game.
+    UnsupportedVersion(u32),
+    /// The thumbnail isn't a binary PPM image.
+    Thumbnail,
+}
+
+impl From<io::Error> for LoadError {
+    fn from(e: io::Error) -> Self {
+        LoadError::Io(e)
+    }
+}
+
+impl From<ron::de::Error> for LoadError {
+    fn from(e: ron::de::Error) -> Self {
+        LoadError::Ron(e)
+    }
+}
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            LoadError::Io(ref e) => write!(f, "I/O error: {}", e),
+            LoadError::Ron(ref e) => write!(f, "{}", e),
+            LoadError::UnsupportedVersion(v) => write!(f, "unsupported version {} (the latest is {})", v, FORMAT_VERSION),
+            LoadError::Thumbnail => write!(f, "malformed thumbnail"),
+        }
+    }
+}
+
+impl Error for LoadError {
+    fn description(&self) -> &str {
+        match *self {
+            LoadError::Io(_) => "I/O error while loading save",
+            LoadError::Ron(_) => "malformed save file",
+            LoadError::UnsupportedVersion(_) => "unsupported save version",
+            LoadError::Thumbnail => "malformed thumbnail",
+        }
+    }
+}
+
+// What a save file holds, whatever its version. Version 0 has none.
+#[derive(Deserialize)]
+struct FileVersion {
+    #[serde(default)]
+    version: u32,
+}
+
+#[derive(Serialize)]
+struct FileRef<'a> {
+    version: u32,
+    metadata: &'a Metadata,
+    progress: &'a Progress,
+}
+
+// Version 1, the current one. When the format changes, the structs of the
+// previous version go here along with a conversion to the next one, and
+// `Save::load()` chains them.
+#[derive(Deserialize)]
+struct FileV1 {
+    metadata: Metadata,
+    progress: Progress,
+}
+
+#[derive(Deserialize)]
+struct FileV0 {
+    #[serde(rename = "scene_id")]
+    _scene_id: u32,
+    has_unlocked_door: bool,
+}
+
+impl From<FileV0> for FileV1 {
+    fn from(v0: FileV0) -> Self {
+        let mut progress = Progress::new("default");
+        progress.properties.insert("has_unlocked_door".to_owned(), Property::Bool(v0.has_unlocked_door));
+        let metadata = Metadata { scene_name: progress.scene_name.clone(), .. Metadata::default() };
+        FileV1 { metadata, progress }
+    }
+}
+
+/// Creates or replaces the file at `path` with what `write` writes, as a
+/// whole: it goes to a temporary file next to it first.
+pub fn write_atomically<F>(path: &Path, write: F) -> io::Result<()> where F: FnOnce(&mut File) -> io::Result<()> {
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let result = File::create(&tmp_path).and_then(|mut f| {
+        write(&mut f)?;
+        f.sync_all()
+    }).and_then(|()| fs::rename(&tmp_path, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+/// The current time, in seconds since the Unix epoch.
+pub fn timestamp_secs_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+impl Save {
+    /// A save of `progress`, as of now.
+    pub fn new(progress: &Progress) -> Self {
+        let metadata = Metadata {
+            timestamp_secs: timestamp_secs_now(),
+            scene_name: progress.scene_name.clone(),
+            elapsed_secs: progress.elapsed_secs,
+        };
+        Self { metadata, progress: progress.clone() }
+    }
+    /// Writes the save in the current version of the format.
+    pub fn save(&self, f: &mut io::Write) -> io::Result<()> {
+        let file = FileRef { version: FORMAT_VERSION, metadata: &self.metadata, progress: &self.progress };
+        let data = ron::ser::to_string_pretty(&file, ron::ser::PrettyConfig::default())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        writeln!(f, "{}", data)
+    }
+    /// Reads a save written by `save()`, in any version of the format.
+    pub fn load(f: &mut io::Read) -> Result<Self, LoadError> {
+        let data = {
+            let mut buf = String::new();
+            f.read_to_string(&mut buf)?;
+            buf
+        };
+        let file = match ron::de::from_str::<FileVersion>(&data)?.version {
+            0 => ron::de::from_str::<FileV0>(&data)?.into(),
+            FORMAT_VERSION => ron::de::from_str::<FileV1>(&data)?,
+            version => return Err(LoadError::UnsupportedVersion(version)),
+        };
+        let FileV1 { metadata, progress } = file;
+        Ok(Self { metadata, progress })
+    }
+}
+
+impl Thumbnail {
+    /// Picks every few pixels of `rgb`, whose rows go from bottom to top as
+    /// OpenGL reads them, so that the result is at most `max_width` pixels wide.
+    pub fn from_bottom_up_rgb(size: Extent2<u32>, rgb: &[u8], max_width: u32) -> Self {
+        let max_width = max_width.max(1);
+        let step = ((size.w + max_width - 1) / max_width).max(1);
+        let thumbnail_size = Extent2::new(size.w / step, size.h / step);
+        let mut thumbnail = Vec::with_capacity((thumbnail_size.w * thumbnail_size.h * 3) as usize);
+        for y in (0 .. thumbnail_size.h).rev() {
+            for x in 0 .. thumbnail_size.w {
+                let i = (((y * step) * size.w + x * step) * 3) as usize;
+                thumbnail.extend_from_slice(&rgb[i .. i + 3]);
+            }
+        }
+        Self { size: thumbnail_size, rgb: thumbnail }
+    }
+    /// Writes the thumbnail as a binary PPM image.
+    pub fn save_ppm(&self, f: &mut io::Write) -> io::Result<()> {
+        write!(f, "P6\n{} {}\n255\n", self.size.w, self.size.h)?;
+        f.write_all(&self.rgb)
+    }
+    /// Reads a thumbnail written by `save_ppm()`.
+    pub fn load_ppm(f: &mut io::Read) -> Result<Self, LoadError> {
+        let mut data = vec![];
+        f.read_to_end(&mut data)?;
+        // The header is 4 words separated by whitespace, then a single one.
+        let mut words = vec![];
+        let mut start = None;
+        let mut end = 0;
+        for (i, &c) in data.iter().enumerate() {
+            let is_space = (c as char).is_ascii_whitespace();
+            match (start, is_space) {
+                (None, false) => start = Some(i),
+                (Some(s), true) => {
+                    words.push(&data[s .. i]);
+                    start = None;
+                    if words.len() == 4 {
+                        end = i + 1;
+                        break;
+                    }
+                },
+                _ => (),
+            }
+        }
+        let number = |word: &[u8]| ::std::str::from_utf8(word).ok().and_then(|w| w.parse::<u32>().ok());
+        let (w, h) = match (words.first(), words.get(1), words.get(2), words.get(3)) {
+            (Some(&b"P6"), Some(w), Some(h), Some(&b"255")) => match (number(w), number(h)) {
+                (Some(w), Some(h)) => (w, h),
+                _ => return Err(LoadError::Thumbnail),
+            },
+            _ => return Err(LoadError::Thumbnail),
+        };
+        let rgb = &data[end ..];
+        let len = w.checked_mul(h).and_then(|n| n.checked_mul(3));
+        if end == 0 || len.map(|len| len as usize) != Some(rgb.len()) {
+            return Err(LoadError::Thumbnail);
+        }
+        Ok(Self { size: Extent2::new(w, h), rgb: rgb.to_vec() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saves_survive_saving_and_loading() {
+        let mut progress = Progress::new("hall");
+        progress.inventory.push("key".to_owned());
+        progress.elapsed_secs = 12.5;
+        progress.properties.insert("door".to_owned(), Property::Text("open".to_owned()));
+        let save = Save::new(&progress);
+        assert_eq!(save.metadata.scene_name, "hall");
+        assert_eq!(save.metadata.elapsed_secs, 12.5);
+        let mut data = vec![];
+        save.save(&mut data).unwrap();
+        assert_eq!(Save::load(&mut &data[..]).unwrap(), save);
+        match Save::load(&mut &b"(version: 2)"[..]) {
+            Err(LoadError::UnsupportedVersion(2)) => (),
+            other => panic!("{:?}", other),
+        };
+    }
+
+    #[test]
+    fn unversioned_saves_are_migrated() {
+        let save = Save::load(&mut &b"(scene_id: 3, has_unlocked_door: true)"[..]).unwrap();
+        assert_eq!(save.progress.scene_name, "default");
+        assert_eq!(save.progress.properties["has_unlocked_door"], Property::Bool(true));
+    }
+
+    #[test]
+    fn thumbnails_are_flipped_and_survive_saving_and_loading() {
+        // 4x2 pixels, the bottom row first; each pixel is (x, y, 0).
+        let rgb: Vec<u8> = [1, 0].iter().flat_map(|&y| (0 .. 4).flat_map(move |x| vec![x, 1 - y, 0])).collect();
+        let thumbnail = Thumbnail::from_bottom_up_rgb(Extent2::new(4, 2), &rgb, 2);
+        assert_eq!(thumbnail.size, Extent2::new(2, 1));
+        assert_eq!(thumbnail.rgb, vec![0, 0, 0, 2, 0, 0]);
+        let mut data = vec![];
+        thumbnail.save_ppm(&mut data).unwrap();
+        assert_eq!(Thumbnail::load_ppm(&mut &data[..]).unwrap(), thumbnail);
+        assert!(Thumbnail::load_ppm(&mut &b"P6\n2 2\n255\nab"[..]).is_err());
+        assert!(Thumbnail::load_ppm(&mut &b"P6\n4294967295 4294967295\n255\nab"[..]).is_err());
+        assert!(Thumbnail::load_ppm(&mut &b"P6\n65536 65536\n255\n"[..]).is_err());
+        assert_eq!(Thumbnail::from_bottom_up_rgb(Extent2::new(4, 2), &rgb, 0).size, Extent2::new(1, 0));
+    }
+}
@@ -1,6 +1,51 @@
 // Scene = Gameplay scene.
-
-// File format:
+//
+// Scenes are saved as RON (`.ron` files):
+//
+// (
+//     version: 3,
+//     scene: (
+//         camera: (position: (x: 0, y: 0, z: 0), rotation_z_radians: 0, scale: (x: 1, y: 1)),
+//         background_color: (r: 0.2, g: 0.6, b: 1, a: 1),
+//         shape_instances: [
+//             (source: Shape("sky"), name: "sky0", xform: (...), properties: {}),
+//             (source: Shape("key"), name: "key0", xform: (...), on_click: [
+//                 Animate(to: (...), seconds: 0.5),
+//                 Disappear,
+//                 PickUp("key"),
+//                 SetProperty("has_key", Bool(true)),
+//             ]),
+//             (source: Group("tree"), name: "tree0", xform: (...), overrides: {
+//                 "leaves": (is_hidden: true),
+//                 "trunk/bark": (xform: Some((...)), properties: {"color": Text("red")}),
+//             }),
+//         ],
+//         hotspots: [
+//             (name: "door", area: (min: (x: 1, y: -1), max: (x: 2, y: 0)), properties: {"locked": Bool(true)}, on_click: [
+//                 Dialogue("It's open."),
+//                 JumpToScene(scene: "hall", transition: (kind: Iris, seconds: 1.5)),
+//             ]),
+//         ],
+//         properties: {"music": Text("wind")},
+//     ),
+// )
+//
+// All fields of `scene` may be omitted, and default as in `Scene::default()`.
+//
+// Any scene can be used as a group (a "prefab") by others, which place
+// instances of it: its shape instances, and the groups among them, are then
+// drawn as part of theirs, relative to the xform of the group instance. Its
+// camera and background are not used then. Each group instance can override
+// the instances within it, which it names by their path.
+//
+// Clicking on an instance during gameplay runs the actions of the innermost
+// instance leading to the clicked shape which has any; see `Action`.
+//
+// Version 2 was the same, but each instance had a `source_shape_name` instead
+// of a `source`, and couldn't be of a group.
+//
+// Version 1 was the text format of `.scene` files, which are still loaded
+// with `Scene::load_legacy()`:
 //
 // I source_shape_name instance_name
 // P 1 2 3
@@ -12,95 +57,916 @@
 // R = Override rotation in degrees (convenience)
 // S = Override scale.
 //
-//
-// First :
-// - Create a Scene from memory;
-// - Switch to it via F8;
-// - Save it to disk.
+// Scenes are authored in the editor's scene mode (`:e name.scene`), and
+// played via F8.
 
 use std::io;
+use std::fmt::{self, Display, Formatter};
+use std::error::Error;
+use std::collections::{HashMap, BTreeMap};
+use ron;
 use xform::Xform2D;
-use v::{Vec2, Vec3};
+use v::{Vec2, Vec3, Vec4, Mat4, Rgba, Aabr};
+use shape::{self, Shape};
+
+/// The version of the format which `Scene::save()` writes.
+pub const FORMAT_VERSION: u32 = 3;
+
+/// The value of a property of a scene, shape instance or hotspot, for gameplay to use.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Property {
+    Bool(bool),
+    Int(i64),
+    Float(f32),
+    Text(String),
+}
+
+pub type Properties = BTreeMap<String, Property>;
+
+/// What a shape instance draws.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Source {
+    Shape(String),
+    /// The scene named so, used as a group.
+    Group(String),
+}
+
+impl Default for Source {
+    fn default() -> Self {
+        Source::Shape(String::new())
+    }
+}
+
+impl Source {
+    pub fn name(&self) -> &str {
+        match *self {
+            Source::Shape(ref name) | Source::Group(ref name) => name,
+        }
+    }
+}
+
+/// Something which happens when the player clicks on a shape instance or a
+/// hotspot. Each action starts once the previous one is done.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Action {
+    /// Shows the text, until the player clicks.
+    Dialogue(String),
+    /// Moves, turns and scales the instance to `to` over `seconds`. It then
+    /// stays so.
+    Animate { to: Xform2D, seconds: f32 },
+    /// Leaves the scene, and the scenes pushed over it, for another one.
+    JumpToScene {
+        scene: String,
+        #[serde(default)]
+        transition: Transition,
+    },
+    /// Shows the scene over the current one, e.g an inventory or a map. Only
+    /// the scene on top reacts to clicks.
+    PushScene(String),
+    /// Hides the scene on top, which was pushed.
+    PopScene,
+    /// Hides the instance for the rest of the game, as when it is picked up.
+    Disappear,
+    /// Puts the item in the inventory.
+    PickUp(String),
+    /// Shows what is in the inventory, until the player clicks.
+    ShowInventory,
+    /// Sets a property of the progress of the game.
+    SetProperty(String, Property),
+}
+
+/// How gameplay goes from one scene to the next.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TransitionKind {
+    /// At once.
+    Cut,
+    /// To black, then from black.
+    Fade,
+    /// The next scene shows through the previous one, more and more.
+    Crossfade,
+    /// The next scene replaces the previous one from left to right.
+    Wipe,
+    /// A circle closes on the previous scene, then opens on the next one.
+    Iris,
+}
+
+impl TransitionKind {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            TransitionKind::Cut => "cut",
+            TransitionKind::Fade => "fade",
+            TransitionKind::Crossfade => "crossfade",
+            TransitionKind::Wipe => "wipe",
+            TransitionKind::Iris => "iris",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "cut" => Some(TransitionKind::Cut),
+            "fade" => Some(TransitionKind::Fade),
+            "crossfade" => Some(TransitionKind::Crossfade),
+            "wipe" => Some(TransitionKind::Wipe),
+            "iris" => Some(TransitionKind::Iris),
+            _ => None,
+        }
+    }
+}
 
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Transition {
+    pub kind: TransitionKind,
+    pub seconds: f32,
+}
+
+impl Default for Transition {
+    fn default() -> Self {
+        Self { kind: TransitionKind::Fade, seconds: 1. }
+    }
+}
+
+/// A change which an instance of a group makes to one of the instances within.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Override {
+    /// Replaces the xform of the instance, which is relative to the group.
+    pub xform: Option<Xform2D>,
+    pub is_hidden: bool,
+    /// Added to the properties of the instance, replacing those of the same name.
+    pub properties: Properties,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ShapeInstance {
-    pub source_shape_name: String,
+    pub source: Source,
     pub name: String,
     pub xform: Xform2D,
+    #[serde(default)]
+    pub properties: Properties,
+    /// For instances of groups; keyed by the path of the instance within the
+    /// group, which is the names of the instances leading to it, joined by `/`.
+    #[serde(default)]
+    pub overrides: BTreeMap<String, Override>,
+    /// What clicking on it during gameplay does, in order.
+    #[serde(default)]
+    pub on_click: Vec<Action>,
+}
+
+/// A shape which a scene draws, once its groups are expanded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Placement {
+    /// The names of the instances leading to it from the scene, joined by `/`.
+    pub path: String,
+    pub shape_name: String,
+    pub model: Mat4<f32>,
+    /// Those of the instances leading to it, the outermost first, which
+    /// `model` is made of.
+    pub xforms: Vec<Xform2D>,
+    /// Those of the instance, with the overrides applied.
+    pub properties: Properties,
+    /// That of the innermost instance leading to it which has actions, if any.
+    pub on_click_path: Option<String>,
+}
+
+impl Placement {
+    /// Its Z value in the world.
+    pub fn z(&self) -> f32 {
+        self.model.cols.w.z
+    }
 }
 
-#[derive(Debug, Default, Clone)]
+/// An area of the scene which reacts to the mouse, without being drawn.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Hotspot {
+    pub name: String,
+    /// In world space.
+    pub area: Aabr<f32>,
+    #[serde(default)]
+    pub properties: Properties,
+    /// What clicking on it during gameplay does, in order. Those which act
+    /// on an instance are skipped.
+    #[serde(default)]
+    pub on_click: Vec<Action>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Scene {
+    /// Where gameplay looks at the scene from.
+    pub camera: Xform2D,
+    pub background_color: Rgba<f32>,
     pub shape_instances: Vec<ShapeInstance>,
+    pub hotspots: Vec<Hotspot>,
+    pub properties: Properties,
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        Self {
+            camera: Xform2D::default(),
+            background_color: Rgba::new(0.2, 0.6, 1., 1.),
+            shape_instances: vec![],
+            hotspots: vec![],
+            properties: Properties::new(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    Ron(ron::de::Error),
+    /// A line of a legacy `.scene` file which doesn't make sense.
+    Legacy { line: usize, message: String },
+    /// Most likely, the scene was saved by a newer version of the game.
+    UnsupportedVersion(u32),
+}
+
+impl From<io::Error> for LoadError {
+    fn from(e: io::Error) -> Self {
+        LoadError::Io(e)
+    }
+}
+
+impl From<ron::de::Error> for LoadError {
+    fn from(e: ron::de::Error) -> Self {
+        LoadError::Ron(e)
+    }
+}
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            LoadError::Io(ref e) => write!(f, "I/O error: {}", e),
+            LoadError::Ron(ref e) => write!(f, "{}", e),
+            LoadError::Legacy { line, ref message } => write!(f, "line {}: {}", line, message),
+            LoadError::UnsupportedVersion(v) => write!(f, "unsupported version {} (the latest is {})", v, FORMAT_VERSION),
+        }
+    }
+}
+
+impl Error for LoadError {
+    fn description(&self) -> &str {
+        match *self {
+            LoadError::Io(_) => "I/O error while loading scene",
+            LoadError::Ron(_) | LoadError::Legacy { .. } => "malformed scene file",
+            LoadError::UnsupportedVersion(_) => "unsupported scene version",
+        }
+    }
+}
+
+// What a scene file holds, whatever its version.
+#[derive(Deserialize)]
+struct FileVersion {
+    version: u32,
+}
+
+#[derive(Serialize)]
+struct FileRef<'a> {
+    version: u32,
+    scene: &'a Scene,
+}
+
+// Version 3, the current one. When the format changes, the structs of the
+// previous version go here along with a conversion to the next one, and
+// `Scene::load()` chains them.
+#[derive(Deserialize)]
+struct FileV3 {
+    scene: Scene,
+}
+
+#[derive(Deserialize)]
+struct FileV2 {
+    scene: SceneV2,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+struct SceneV2 {
+    camera: Xform2D,
+    background_color: Rgba<f32>,
+    shape_instances: Vec<ShapeInstanceV2>,
+    hotspots: Vec<Hotspot>,
+    properties: Properties,
+}
+
+#[derive(Deserialize)]
+struct ShapeInstanceV2 {
+    source_shape_name: String,
+    name: String,
+    xform: Xform2D,
+    #[serde(default)]
+    properties: Properties,
+}
+
+impl Default for SceneV2 {
+    fn default() -> Self {
+        let Scene { camera, background_color, hotspots, properties, .. } = Scene::default();
+        Self { camera, background_color, shape_instances: vec![], hotspots, properties }
+    }
+}
+
+impl From<SceneV2> for Scene {
+    fn from(v2: SceneV2) -> Self {
+        let SceneV2 { camera, background_color, shape_instances, hotspots, properties } = v2;
+        let shape_instances = shape_instances.into_iter().map(|i| ShapeInstance {
+            source: Source::Shape(i.source_shape_name),
+            name: i.name,
+            xform: i.xform,
+            properties: i.properties,
+            .. ShapeInstance::default()
+        }).collect();
+        Self { camera, background_color, shape_instances, hotspots, properties }
+    }
+}
+
+// Expands groups into the shapes which they are made of.
+struct Expansion<'a> {
+    scenes: &'a HashMap<String, Scene>,
+    // The groups being expanded, so that a group which contains itself
+    // stops there.
+    visiting: Vec<String>,
+    // Those of the groups being expanded.
+    xforms: Vec<Xform2D>,
+    placements: Vec<Placement>,
+}
+
+impl<'a> Expansion<'a> {
+    fn new(scenes: &'a HashMap<String, Scene>) -> Self {
+        Self { scenes, visiting: vec![], xforms: vec![], placements: vec![] }
+    }
+    // Adds what `instance` draws, within a group drawn with the `parent`
+    // matrix whose path is `prefix` (empty for the scene itself).
+    // `overrides` are those which apply within that group, and
+    // `on_click_path` is that of the innermost instance leading there which
+    // has actions.
+    fn instance(&mut self, instance: &ShapeInstance, parent: &Mat4<f32>, prefix: &str, overrides: &BTreeMap<String, Override>, on_click_path: Option<&str>) {
+        let own_override = overrides.get(&instance.name);
+        if own_override.map_or(false, |o| o.is_hidden) {
+            return;
+        }
+        let xform = own_override.and_then(|o| o.xform).unwrap_or(instance.xform);
+        let mut properties = instance.properties.clone();
+        if let Some(o) = own_override {
+            properties.extend(o.properties.clone());
+        }
+        let path = format!("{}{}", prefix, instance.name);
+        let on_click_path = if instance.on_click.is_empty() { on_click_path } else { Some(path.as_str()) };
+        // Model matrices flatten Z, so that instances within a group would
+        // all end up at the depth of the group; their Z values are relative
+        // to it instead.
+        let mut model = *parent * xform.model_matrix();
+        model.cols.w.z = parent.cols.w.z + xform.position.z;
+        let group_name = match instance.source {
+            Source::Shape(ref shape_name) => {
+                let on_click_path = on_click_path.map(|p| p.to_owned());
+                let mut xforms = self.xforms.clone();
+                xforms.push(xform);
+                self.placements.push(Placement { path, shape_name: shape_name.clone(), model, xforms, properties, on_click_path });
+                return;
+            },
+            Source::Group(ref group_name) => group_name,
+        };
+        let group = match self.scenes.get(group_name) {
+            Some(group) if !self.visiting.contains(group_name) => group,
+            _ => return,
+        };
+        // Those of the outer instances win over the instance's own.
+        let prefix = format!("{}/", instance.name);
+        let mut inner_overrides: BTreeMap<String, Override> = overrides.iter()
+            .filter(|&(path, _)| path.starts_with(prefix.as_str()))
+            .map(|(path, o)| (path[prefix.len() ..].to_owned(), o.clone()))
+            .collect();
+        for (path, o) in instance.overrides.iter() {
+            inner_overrides.entry(path.clone()).or_insert_with(|| o.clone());
+        }
+        self.visiting.push(group_name.clone());
+        self.xforms.push(xform);
+        let inner_prefix = format!("{}/", path);
+        for child in group.shape_instances.iter() {
+            self.instance(child, &model, &inner_prefix, &inner_overrides, on_click_path);
+        }
+        self.xforms.pop();
+        self.visiting.pop();
+    }
+}
+
+// Parses the text format of `.scene` files, which is version 1.
+fn parse_legacy(data: &str) -> Result<Scene, LoadError> {
+    let mut scene = Scene::default();
+    for (i, line) in data.lines().enumerate() {
+        let line_number = i + 1;
+        let error = |message: String| LoadError::Legacy { line: line_number, message };
+        let mut words = line.split_whitespace();
+        let cmd = match words.next() {
+            Some(cmd) => cmd,
+            None => continue,
+        };
+        if cmd == "I" {
+            let shape_name = words.next().ok_or_else(|| error("missing shape name".to_owned()))?.to_owned();
+            // The name is the rest of the line, spaces included.
+            let name = words.collect::<Vec<_>>().join(" ");
+            if name.is_empty() {
+                return Err(error("missing instance name".to_owned()));
+            }
+            scene.shape_instances.push(ShapeInstance { source: Source::Shape(shape_name), name, .. ShapeInstance::default() });
+            continue;
+        }
+        let numbers = words.map(|w| w.parse::<f32>().map_err(|_| error(format!("`{}` is not a number", w)))).collect::<Result<Vec<f32>, LoadError>>()?;
+        let expected = match cmd {
+            "P" => 3,
+            "R" => 1,
+            "S" => 2,
+            _ => return Err(error(format!("unknown command `{}`", cmd))),
+        };
+        if numbers.len() != expected {
+            return Err(error(format!("`{}` takes {} numbers, not {}", cmd, expected, numbers.len())));
+        }
+        let xform = match scene.shape_instances.last_mut() {
+            Some(instance) => &mut instance.xform,
+            None => return Err(error(format!("`{}` appears before any `I` command", cmd))),
+        };
+        match cmd {
+            "P" => xform.position = Vec3::new(numbers[0], numbers[1], numbers[2]),
+            "R" => xform.rotation_z_radians = numbers[0].to_radians(),
+            _ => xform.scale = Vec2::new(numbers[0], numbers[1]),
+        };
+    }
+    Ok(scene)
+}
+
+/// Where to move a shape instance among the others, from back to front.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum ZOrder {
+    Forward,
+    Backward,
+    Front,
+    Back,
+}
+
+impl ZOrder {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            ZOrder::Forward => "up",
+            ZOrder::Backward => "down",
+            ZOrder::Front => "front",
+            ZOrder::Back => "back",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "up" => Some(ZOrder::Forward),
+            "down" => Some(ZOrder::Backward),
+            "front" => Some(ZOrder::Front),
+            "back" => Some(ZOrder::Back),
+            _ => None,
+        }
+    }
+}
+
+impl ShapeInstance {
+    /// The shapes which the instance draws, in no particular order.
+    pub fn placements(&self, scenes: &HashMap<String, Scene>) -> Vec<Placement> {
+        let mut expansion = Expansion::new(scenes);
+        expansion.instance(self, &Mat4::identity(), "", &BTreeMap::new(), None);
+        expansion.placements
+    }
+    /// The bounds of what the instance draws, before its xform applies.
+    pub fn local_aabr(&self, shapes: &HashMap<String, Shape>, scenes: &HashMap<String, Scene>) -> Option<Aabr<f32>> {
+        let unmoved = ShapeInstance { xform: Xform2D::default(), .. self.clone() };
+        let mut bounds: Option<Aabr<f32>> = None;
+        for placement in unmoved.placements(scenes).iter() {
+            let aabr = match shapes.get(&placement.shape_name).and_then(|shape| shape.path.aabr()) {
+                Some(aabr) => aabr,
+                None => continue,
+            };
+            let (min, max) = (aabr.min, aabr.max);
+            for &corner in [min, Vec2::new(max.x, min.y), max, Vec2::new(min.x, max.y)].iter() {
+                let p = placement.model * Vec4::new(corner.x, corner.y, 0., 1.);
+                let p = Vec2::new(p.x, p.y);
+                bounds = Some(match bounds {
+                    None => Aabr { min: p, max: p },
+                    Some(b) => Aabr { min: Vec2::partial_min(b.min, p), max: Vec2::partial_max(b.max, p) },
+                });
+            }
+        }
+        bounds
+    }
 }
 
 impl Scene {
-    pub fn sort_shape_instances_by_z(&mut self) {
-        self.shape_instances.sort_by(|a, b| {
-            use ::std::cmp::Ordering;
-            let az = a.xform.position.z;
-            let bz = b.xform.position.z;
-            if az > bz {
-                Ordering::Less
-            } else {
-                Ordering::Greater
+    /// The shapes which the scene draws, groups expanded, from back to front.
+    pub fn placements(&self, scenes: &HashMap<String, Scene>) -> Vec<Placement> {
+        self.placements_with_overrides(scenes, &BTreeMap::new())
+    }
+    /// Same as `placements()`, with `overrides` applying to the instances of
+    /// the scene, which are keyed by their path from it; e.g those which
+    /// gameplay made.
+    pub fn placements_with_overrides(&self, scenes: &HashMap<String, Scene>, overrides: &BTreeMap<String, Override>) -> Vec<Placement> {
+        let mut expansion = Expansion::new(scenes);
+        for instance in self.shape_instances.iter() {
+            expansion.instance(instance, &Mat4::identity(), "", overrides, None);
+        }
+        let mut placements = expansion.placements;
+        placements.sort_by(|a, b| b.z().partial_cmp(&a.z()).unwrap_or(::std::cmp::Ordering::Equal));
+        placements
+    }
+    /// The frontmost shape drawn at `p`, in world space, according to its
+    /// actual geometry rather than its bounds.
+    pub fn placement_at(&self, p: Vec2<f32>, shapes: &HashMap<String, Shape>, scenes: &HashMap<String, Scene>, overrides: &BTreeMap<String, Override>) -> Option<Placement> {
+        self.placements_with_overrides(scenes, overrides).into_iter().rev().find(|placement| {
+            // Shapes which are scaled to nothing can't be hit.
+            if placement.xforms.iter().any(|x| x.scale.x == 0. || x.scale.y == 0.) {
+                return false;
+            }
+            match shapes.get(&placement.shape_name) {
+                Some(shape) => shape.hit_test(placement.xforms.iter().fold(p, |p, x| x.inverse_transform_point(p))),
+                None => false,
+            }
+        })
+    }
+    /// The instance at `path`, which is the names of the instances leading
+    /// to it, within the scene and then the groups they are of, joined by `/`.
+    pub fn instance_at_path<'a>(&'a self, path: &str, scenes: &'a HashMap<String, Scene>) -> Option<&'a ShapeInstance> {
+        let mut names = path.split('/');
+        let mut instance = &self.shape_instances[self.instance_index(names.next()?)?];
+        for name in names {
+            let group = match instance.source {
+                Source::Group(ref group_name) => scenes.get(group_name)?,
+                Source::Shape(_) => return None,
+            };
+            instance = &group.shape_instances[group.instance_index(name)?];
+        }
+        Some(instance)
+    }
+    /// Whether the scene has an instance of the group named `name`, or of a
+    /// group which has one, and so on. Placing such a group in the scene
+    /// named `name` would make it contain itself.
+    pub fn contains_group(&self, name: &str, scenes: &HashMap<String, Scene>) -> bool {
+        fn visit(scene: &Scene, name: &str, scenes: &HashMap<String, Scene>, visited: &mut Vec<String>) -> bool {
+            for instance in scene.shape_instances.iter() {
+                if let Source::Group(ref group_name) = instance.source {
+                    if group_name == name {
+                        return true;
+                    }
+                    if visited.contains(group_name) {
+                        continue;
+                    }
+                    visited.push(group_name.clone());
+                    if scenes.get(group_name).map_or(false, |group| visit(group, name, scenes, visited)) {
+                        return true;
+                    }
+                }
             }
+            false
+        }
+        visit(self, name, scenes, &mut vec![])
+    }
+    pub fn instance_index(&self, name: &str) -> Option<usize> {
+        self.shape_instances.iter().position(|i| i.name == name)
+    }
+    /// `base` followed by the smallest number which no instance is named with yet.
+    pub fn unique_instance_name(&self, base: &str) -> String {
+        (0..).map(|i| format!("{}{}", base, i)).find(|name| self.instance_index(name).is_none()).unwrap()
+    }
+    /// The indices of the instances, from back to front. Smaller Z values are
+    /// closer to the camera, which is at 0.
+    pub fn z_order(&self) -> Vec<usize> {
+        let mut order: Vec<usize> = (0 .. self.shape_instances.len()).collect();
+        order.sort_by(|&a, &b| {
+            let (az, bz) = (self.shape_instances[a].xform.position.z, self.shape_instances[b].xform.position.z);
+            bz.partial_cmp(&az).unwrap_or(::std::cmp::Ordering::Equal)
         });
+        order
+    }
+    /// A depth in front of all instances.
+    pub fn front_z(&self) -> f32 {
+        match self.z_order().last() {
+            Some(&i) => self.shape_instances[i].xform.position.z / 2.,
+            None => 0.,
+        }
     }
+    /// Changes the Z value of the instance at `index` so that it moves among
+    /// the others as told, and returns whether it did. Only that instance
+    /// changes: it goes halfway between the ones it ends up between, or one
+    /// unit behind the back one. It can't go in front of an instance which is
+    /// right at the camera.
+    pub fn move_in_z_order(&mut self, index: usize, to: ZOrder) -> bool {
+        let order = self.z_order();
+        let z = |i: usize| self.shape_instances[order[i]].xform.position.z;
+        let current = match order.iter().position(|&i| i == index) {
+            Some(current) => current,
+            None => return false,
+        };
+        let last = order.len() - 1;
+        let target = match to {
+            ZOrder::Forward if current < last => current + 1,
+            ZOrder::Backward if current > 0 => current - 1,
+            ZOrder::Front => last,
+            ZOrder::Back => 0,
+            _ => return false,
+        };
+        let new_z = if target > current {
+            let beyond = if target < last { z(target + 1) } else { 0. };
+            (z(target) + beyond) / 2.
+        } else if target < current {
+            let beyond = if target > 0 { z(target - 1) } else { z(target) + 2. };
+            (z(target) + beyond) / 2.
+        } else {
+            return false;
+        };
+        if new_z == z(target) {
+            return false;
+        }
+        self.shape_instances[index].xform.position.z = new_z;
+        true
+    }
+    /// Writes the scene in the current version of the format.
     pub fn save(&self, f: &mut io::Write) -> io::Result<()> {
-        for instance in self.shape_instances.iter() {
-            let &ShapeInstance {
-                ref source_shape_name, ref name,
-                xform: Xform2D {
-                    position: Vec3 { x, y, z },
-                    rotation_z_radians,
-                    scale: Vec2 { x: sx, y: sy },
-                },
-            } = instance;
-            writeln!(f, "I {} {}", source_shape_name, name)?;
-            writeln!(f, "P {} {} {}", x, y, z)?;
-            writeln!(f, "R {}", rotation_z_radians.to_degrees())?;
-            writeln!(f, "S {} {}", sx, sy)?;
-            writeln!(f)?;
-        }
-        Ok(())
-    }
-    pub fn load(f: &mut io::Read) -> io::Result<Self> {
+        let file = FileRef { version: FORMAT_VERSION, scene: self };
+        let data = ron::ser::to_string_pretty(&file, ron::ser::PrettyConfig::default())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        writeln!(f, "{}", data)
+    }
+    /// Exports the scene as a standalone SVG document, one `<path>` per
+    /// shape it draws, back to front.
+    pub fn save_svg(&self, f: &mut io::Write, shapes: &HashMap<String, Shape>, scenes: &HashMap<String, Scene>) -> io::Result<()> {
+        let placements = self.placements(scenes);
+        let mut items = vec![];
+        for placement in placements.iter() {
+            match shapes.get(&placement.shape_name) {
+                Some(shape) => items.push((placement.path.as_str(), shape, placement.model)),
+                None => warn!("Scene: Not exporting `{}`: there is no shape named `{}`", placement.path, placement.shape_name),
+            };
+        }
+        shape::svg::write(f, &items)
+    }
+    /// Reads a scene saved by `save()`, in any version of the format.
+    pub fn load(f: &mut io::Read) -> Result<Self, LoadError> {
         let data = {
             let mut buf = String::new();
             f.read_to_string(&mut buf)?;
             buf
         };
-        let mut scene = Self::default();
-        let mut words = data.split_whitespace();
-        while let Some(cmd) = words.next() {
-            match cmd {
-                "I" => {
-                    let source_shape_name = words.next().unwrap().to_owned();
-                    let name = words.next().unwrap().to_owned();
-                    scene.shape_instances.push(ShapeInstance {
-                        source_shape_name, name, xform: Xform2D::default(),
-                    });
-                },
-                "P" => {
-                    let p = &mut scene.shape_instances.last_mut().unwrap().xform.position;
-                    p.x = words.next().unwrap().parse().unwrap();
-                    p.y = words.next().unwrap().parse().unwrap();
-                    p.z = words.next().unwrap().parse().unwrap();
-                },
-                "R" => {
-                    let degrees: f32 = words.next().unwrap().parse().unwrap();
-                    scene.shape_instances.last_mut().unwrap().xform.rotation_z_radians = degrees.to_radians();
-                },
-                "S" => {
-                    let s = &mut scene.shape_instances.last_mut().unwrap().xform.scale;
-                    s.x = words.next().unwrap().parse().unwrap();
-                    s.y = words.next().unwrap().parse().unwrap();
-                },
-                whoops @ _ => panic!("Unknown command `{}`", whoops),
-            }
+        match ron::de::from_str::<FileVersion>(&data)?.version {
+            2 => Ok(ron::de::from_str::<FileV2>(&data)?.scene.into()),
+            FORMAT_VERSION => Ok(ron::de::from_str::<FileV3>(&data)?.scene),
+            version => Err(LoadError::UnsupportedVersion(version)),
         }
-        Ok(scene)
+    }
+    /// Reads a scene in the text format of `.scene` files.
+    pub fn load_legacy(f: &mut io::Read) -> Result<Self, LoadError> {
+        let data = {
+            let mut buf = String::new();
+            f.read_to_string(&mut buf)?;
+            buf
+        };
+        parse_legacy(&data)
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shape::{Path, SubPath, PathCmd};
+
+    fn scene(zs: &[f32]) -> Scene {
+        let shape_instances = zs.iter().enumerate().map(|(i, &z)| ShapeInstance {
+            source: Source::Shape("shape".to_owned()),
+            name: format!("shape{}", i),
+            xform: Xform2D { position: Vec3::new(0., 0., z), .. Xform2D::default() },
+            .. ShapeInstance::default()
+        }).collect();
+        Scene { shape_instances, .. Scene::default() }
+    }
+
+    #[test]
+    fn instances_move_in_z_order_one_at_a_time() {
+        let mut s = scene(&[10., 30., 20.]);
+        assert_eq!(s.z_order(), vec![1, 2, 0]);
+        assert!(s.move_in_z_order(1, ZOrder::Forward));
+        assert_eq!(s.z_order(), vec![2, 1, 0]);
+        assert!(s.move_in_z_order(1, ZOrder::Front));
+        assert_eq!(s.z_order(), vec![2, 0, 1]);
+        assert!(!s.move_in_z_order(1, ZOrder::Forward));
+        assert!(s.move_in_z_order(1, ZOrder::Back));
+        assert_eq!(s.z_order(), vec![1, 2, 0]);
+        assert_eq!(s.shape_instances[1].xform.position.z, 21.);
+        assert_eq!(s.shape_instances[0].xform.position.z, 10.);
+    }
+
+    #[test]
+    fn new_instance_names_are_unique() {
+        let s = scene(&[0., 0.]);
+        assert_eq!(s.unique_instance_name("shape"), "shape2");
+        assert_eq!(s.unique_instance_name("sky"), "sky0");
+    }
+
+    #[test]
+    fn scenes_survive_saving_and_loading() {
+        let mut s = scene(&[10., 20.]);
+        s.shape_instances[0].xform.rotation_z_radians = 1.5;
+        s.shape_instances[1].properties.insert("clicks".to_owned(), Property::Int(3));
+        s.hotspots.push(Hotspot {
+            name: "door".to_owned(),
+            area: Aabr { min: Vec2::new(-1., -2.), max: Vec2::new(1., 0.) },
+            properties: Properties::new(),
+            on_click: vec![Action::Dialogue("It's open.".to_owned()), Action::JumpToScene {
+                scene: "hall".to_owned(),
+                transition: Transition { kind: TransitionKind::Wipe, seconds: 0.5 },
+            }],
+        });
+        s.shape_instances[0].on_click.push(Action::Animate { to: Xform2D::default(), seconds: 0.5 });
+        s.properties.insert("music".to_owned(), Property::Text("wind".to_owned()));
+        let mut data = vec![];
+        s.save(&mut data).unwrap();
+        assert_eq!(Scene::load(&mut &data[..]).unwrap(), s);
+    }
+
+    #[test]
+    fn missing_fields_take_default_values() {
+        let s = Scene::load(&mut &b"(version: 2, scene: (properties: {\"x\": Bool(true)}))"[..]).unwrap();
+        assert_eq!(s.background_color, Scene::default().background_color);
+        assert_eq!(s.properties["x"], Property::Bool(true));
+        match Scene::load(&mut &b"(version: 4, scene: ())"[..]) {
+            Err(LoadError::UnsupportedVersion(4)) => (),
+            other => panic!("{:?}", other),
+        };
+    }
+
+    #[test]
+    fn version_2_scenes_are_migrated() {
+        let data = "(version: 2, scene: (shape_instances: [(source_shape_name: \"sky\", name: \"sky0\", xform: (position: (x: 1, y: 2, z: 3), rotation_z_radians: 0, scale: (x: 1, y: 1)))]))";
+        let s = Scene::load(&mut data.as_bytes()).unwrap();
+        assert_eq!(s.shape_instances[0].source, Source::Shape("sky".to_owned()));
+        assert_eq!(s.shape_instances[0].xform.position, Vec3::new(1., 2., 3.));
+        assert_eq!(s.background_color, Scene::default().background_color);
+    }
+
+    fn group_instance(group_name: &str, name: &str, x: f32) -> ShapeInstance {
+        ShapeInstance {
+            source: Source::Group(group_name.to_owned()),
+            name: name.to_owned(),
+            xform: Xform2D { position: Vec3::new(x, 0., 0.), .. Xform2D::default() },
+            .. ShapeInstance::default()
+        }
+    }
+
+    fn position(placement: &Placement) -> Vec2<f32> {
+        let p = placement.model * Vec4::new(0., 0., 0., 1.);
+        Vec2::new(p.x, p.y)
+    }
+
+    #[test]
+    fn groups_are_expanded_with_their_overrides() {
+        let mut scenes = HashMap::new();
+        scenes.insert("leaf".to_owned(), scene(&[5., 6.]));
+        let mut tree = scene(&[]);
+        tree.shape_instances.push(group_instance("leaf", "a", 10.));
+        tree.shape_instances.push(group_instance("leaf", "b", 20.));
+        tree.shape_instances[1].overrides.insert("shape0".to_owned(), Override { is_hidden: true, .. Override::default() });
+        scenes.insert("tree".to_owned(), tree);
+        let mut s = scene(&[]);
+        s.shape_instances.push(group_instance("tree", "tree0", 100.));
+        s.shape_instances[0].overrides.insert("a/shape1".to_owned(), Override {
+            xform: Some(Xform2D { position: Vec3::new(1., 0., 1.), .. Xform2D::default() }),
+            .. Override::default()
+        });
+        s.shape_instances[0].overrides.insert("b/shape0".to_owned(), Override { is_hidden: false, .. Override::default() });
+
+        let placements = s.placements(&scenes);
+        let paths: Vec<&str> = placements.iter().map(|p| p.path.as_str()).collect();
+        assert_eq!(paths, vec!["tree0/b/shape1", "tree0/a/shape0", "tree0/b/shape0", "tree0/a/shape1"]);
+        assert_eq!(position(&placements[0]), Vec2::new(120., 0.));
+        assert_eq!(position(&placements[3]), Vec2::new(111., 0.));
+        assert_eq!(placements[3].z(), 1.);
+    }
+
+    #[test]
+    fn groups_which_contain_themselves_stop_there() {
+        let mut scenes = HashMap::new();
+        let mut a = scene(&[0.]);
+        a.shape_instances.push(group_instance("b", "b0", 1.));
+        let mut b = scene(&[0.]);
+        b.shape_instances.push(group_instance("a", "a0", 1.));
+        scenes.insert("a".to_owned(), a.clone());
+        scenes.insert("b".to_owned(), b);
+        assert!(a.contains_group("a", &scenes));
+        assert!(!a.contains_group("c", &scenes));
+        assert_eq!(a.placements(&scenes).len(), 3);
+    }
+
+    #[test]
+    fn clicks_hit_the_frontmost_shape_under_them() {
+        let mut shapes = HashMap::new();
+        shapes.insert("shape".to_owned(), Shape { path: shape::Path::circle(Vec2::zero(), 1.), .. Shape::default() });
+        let mut scenes = HashMap::new();
+        let mut apple = scene(&[0.]);
+        apple.shape_instances[0].on_click.push(Action::Disappear);
+        scenes.insert("apple".to_owned(), apple);
+        let mut s = scene(&[10.]);
+        s.shape_instances.push(group_instance("apple", "apple0", 1.));
+        s.shape_instances[1].xform.position.z = 5.;
+
+        let hit = |s: &Scene, x: f32, overrides: &BTreeMap<String, Override>| s.placement_at(Vec2::new(x, 0.), &shapes, &scenes, overrides);
+        let no_overrides = BTreeMap::new();
+        let front = hit(&s, 0.5, &no_overrides).unwrap();
+        assert_eq!(front.path, "apple0/shape0");
+        assert_eq!(front.on_click_path.as_ref().map(String::as_str), Some("apple0/shape0"));
+        assert_eq!(s.instance_at_path("apple0/shape0", &scenes).unwrap().on_click, vec![Action::Disappear]);
+        assert_eq!(hit(&s, -0.5, &no_overrides).unwrap().path, "shape0");
+        // Within the bounds of the circle, but not the circle itself.
+        assert!(s.placement_at(Vec2::new(-0.8, 0.8), &shapes, &scenes, &no_overrides).is_none());
+
+        let mut overrides = BTreeMap::new();
+        overrides.insert("apple0/shape0".to_owned(), Override { is_hidden: true, .. Override::default() });
+        assert_eq!(hit(&s, 0.5, &overrides).unwrap().path, "shape0");
+    }
+
+    #[test]
+    fn clicks_go_through_the_transforms_of_groups() {
+        let mut shapes = HashMap::new();
+        shapes.insert("shape".to_owned(), Shape { path: shape::Path::circle(Vec2::zero(), 1.), .. Shape::default() });
+        let mut scenes = HashMap::new();
+        let mut ellipse = scene(&[0.]);
+        ellipse.shape_instances[0].xform.scale = Vec2::new(2., 0.5);
+        scenes.insert("ellipse".to_owned(), ellipse);
+        let mut s = Scene::default();
+        s.shape_instances.push(group_instance("ellipse", "ellipse0", 5.));
+        s.shape_instances[0].xform.rotation_z_radians = 90_f32.to_radians();
+
+        let hit = |s: &Scene, x: f32, y: f32| s.placement_at(Vec2::new(x, y), &shapes, &scenes, &BTreeMap::new()).is_some();
+        // Turned, the ellipse is tall and narrow.
+        assert!(hit(&s, 5., 1.5));
+        assert!(hit(&s, 5.4, 0.));
+        assert!(!hit(&s, 6.5, 0.));
+        assert!(!hit(&s, 5.6, 0.));
+        assert_eq!(s.placements(&scenes)[0].xforms.len(), 2);
+
+        s.shape_instances[0].xform.scale.x = 0.;
+        assert!(!hit(&s, 5., 0.));
+    }
+
+    #[test]
+    fn legacy_scenes_are_migrated() {
+        let data = "I sky the sky\nP 1 2 3\nR 90\nS 0.5 0.5\n\nI dune dune0\n";
+        let s = Scene::load_legacy(&mut data.as_bytes()).unwrap();
+        assert_eq!(s.shape_instances.len(), 2);
+        assert_eq!(s.shape_instances[0].name, "the sky");
+        assert_eq!(s.shape_instances[0].xform.position, Vec3::new(1., 2., 3.));
+        assert_eq!(s.shape_instances[0].xform.scale, Vec2::new(0.5, 0.5));
+        assert_eq!(s.shape_instances[1].xform, Xform2D::default());
+        match Scene::load_legacy(&mut "I sky sky0\nQ 1\n".as_bytes()) {
+            Err(LoadError::Legacy { line: 2, .. }) => (),
+            other => panic!("{:?}", other),
+        };
+    }
+
+    // The unit square, from the origin.
+    fn square() -> Path {
+        let mut subpath = SubPath::new(Vec2::zero());
+        for &(x, y) in &[(1., 0.), (1., 1.), (0., 1.)] {
+            subpath.cmds.push(PathCmd::Line { end: Vec2::new(x, y) });
+        }
+        subpath.is_closed = true;
+        Path { subpaths: vec![subpath] }
+    }
+
+    #[test]
+    fn svg_export_draws_placements_back_to_front() {
+        let mut shapes = HashMap::new();
+        let mut square = Shape { path: square(), .. Shape::default() };
+        square.style.stroke_thickness = 0.;
+        shapes.insert("shape".to_owned(), square);
+        let mut scenes = HashMap::new();
+        let mut group = scene(&[0.]);
+        group.shape_instances[0].xform.position.x = 1.;
+        scenes.insert("group".to_owned(), group);
+        let mut s = scene(&[1., 3.]);
+        s.shape_instances[0].xform.scale = Vec2::new(2., 1.);
+        s.shape_instances[1].xform.position = Vec3::new(-2., 0., 3.);
+        s.shape_instances.push(ShapeInstance {
+            xform: Xform2D { position: Vec3::new(0., 2., 2.), .. Xform2D::default() },
+            .. group_instance("group", "g", 0.)
+        });
+
+        let mut buf = vec![];
+        s.save_svg(&mut buf, &shapes, &scenes).unwrap();
+        let svg = String::from_utf8(buf).unwrap();
+        let at = |s: &str| svg.find(s).unwrap_or_else(|| panic!("No `{}` in {}", s, svg));
+        assert!(at(r#"id="shape1""#) < at(r#"id="g/shape0""#));
+        assert!(at(r#"id="g/shape0""#) < at(r#"id="shape0""#));
+        assert!(at(r#"matrix(1 0 0 1 -2 0)">"#) < at(r#"id="shape1""#));
+        assert!(at(r#"matrix(1 0 0 1 1 2)">"#) < at(r#"id="g/shape0""#));
+        assert!(at(r#"matrix(2 0 0 1 0 0)">"#) < at(r#"id="shape0""#));
+
+        // Import puts the scene's bounds, from (-2, 0) to (2, 3), in -1 to 1.
+        let imported = shape::svg::import(&svg, "t").unwrap();
+        let names: Vec<&str> = imported.iter().map(|&(ref name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["t_shape1", "t_g_shape0", "t_shape0"]);
+        let start = |i: usize| imported[i].1.path.subpaths[0].start();
+        assert_eq!(start(0), Vec2::new(-2., -1.5) / 2.);
+        assert_eq!(start(1), Vec2::new(1., 0.5) / 2.);
+        assert_eq!(start(2), Vec2::new(0., -1.5) / 2.);
+    }
+}
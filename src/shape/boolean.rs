@@ -0,0 +1,541 @@
+// Boolean operations on paths.
+//
+// Both paths are flattened, then their edges are cut wherever they cross or
+// touch, so that pieces only ever meet at their end points. A piece is kept
+// when the result is inside on one of its sides and outside on the other, and
+// it is oriented so that the inside is on its left. The kept pieces are then
+// chained back into closed subpaths.
+//
+// Which side of a piece is inside each operand is found from exact winding
+// numbers rather than by probing points next to it, so that coincident edges
+// (e.g a border shared by both paths) are handled.
+//
+// Curves come out as line segments, within the given tolerance of the
+// actual ones.
+
+use std::collections::HashMap;
+use v::Vec2;
+use super::{Path, SubPath, PathCmd, FillRule};
+
+/// How the fills of two paths are combined.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum BooleanOp {
+    /// Inside either path.
+    Union,
+    /// Inside both paths.
+    Intersection,
+    /// Inside the first path, but not the second.
+    Difference,
+    /// Inside exactly one of the paths.
+    Xor,
+}
+
+impl BooleanOp {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            BooleanOp::Union => "union",
+            BooleanOp::Intersection => "intersection",
+            BooleanOp::Difference => "difference",
+            BooleanOp::Xor => "xor",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "union" => Some(BooleanOp::Union),
+            "intersection" => Some(BooleanOp::Intersection),
+            "difference" => Some(BooleanOp::Difference),
+            "xor" => Some(BooleanOp::Xor),
+            _ => None,
+        }
+    }
+    fn apply(&self, a: bool, b: bool) -> bool {
+        match *self {
+            BooleanOp::Union => a || b,
+            BooleanOp::Intersection => a && b,
+            BooleanOp::Difference => a && !b,
+            BooleanOp::Xor => a != b,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Edge {
+    a: Vec2<f32>,
+    b: Vec2<f32>,
+    // 0 for the first path, 1 for the second.
+    operand: usize,
+}
+
+// Points are compared exactly: once edges are cut, pieces which meet share
+// the very same end points.
+type Key = (u32, u32);
+
+fn key(p: Vec2<f32>) -> Key {
+    // Adding zero turns -0 into +0.
+    ((p.x + 0.).to_bits(), (p.y + 0.).to_bits())
+}
+
+// The same key for both directions of a segment.
+fn segment_key(e: &Edge) -> (Key, Key) {
+    let (a, b) = (key(e.a), key(e.b));
+    if a <= b { (a, b) } else { (b, a) }
+}
+
+fn cross(u: Vec2<f32>, v: Vec2<f32>) -> f64 {
+    u.x as f64 * v.y as f64 - u.y as f64 * v.x as f64
+}
+
+fn is_inside(winding: i32, fill_rule: FillRule) -> bool {
+    match fill_rule {
+        FillRule::EvenOdd => winding % 2 != 0,
+        FillRule::NonZero => winding != 0,
+    }
+}
+
+// Subpaths are always closed here, since fills are.
+fn push_edges(edges: &mut Vec<Edge>, path: &Path, operand: usize, tolerance: f32) {
+    for subpath in &path.subpaths {
+        let points = subpath.generate_vertex_positions(tolerance);
+        for i in 0..points.len() {
+            let (a, b) = (points[i], points[(i + 1) % points.len()]);
+            if a != b {
+                edges.push(Edge { a, b, operand });
+            }
+        }
+    }
+}
+
+// Where `p` is along `e`, if it is strictly between its end points and at
+// most `epsilon` away from it.
+fn param_on_edge(p: Vec2<f32>, e: &Edge, epsilon: f32) -> Option<f32> {
+    let d = e.b - e.a;
+    let t = (p - e.a).dot(d) / d.dot(d);
+    let is_near = (e.a + d * t).distance(p) <= epsilon;
+    let is_end = p.distance(e.a) <= epsilon || p.distance(e.b) <= epsilon;
+    if t > 0. && t < 1. && is_near && !is_end { Some(t) } else { None }
+}
+
+// Finds where `e` and `f` must be cut, as `(t, point)` pairs for each.
+fn find_cuts(e: &Edge, f: &Edge, epsilon: f32, e_cuts: &mut Vec<(f32, Vec2<f32>)>, f_cuts: &mut Vec<(f32, Vec2<f32>)>) {
+    // An end point lying on the other edge, which also covers collinear overlaps.
+    let mut touches = false;
+    for &p in &[f.a, f.b] {
+        if let Some(t) = param_on_edge(p, e, epsilon) {
+            e_cuts.push((t, p));
+            touches = true;
+        }
+    }
+    for &p in &[e.a, e.b] {
+        if let Some(t) = param_on_edge(p, f, epsilon) {
+            f_cuts.push((t, p));
+            touches = true;
+        }
+    }
+    if touches {
+        return;
+    }
+    // A proper crossing; both edges are cut at the same point.
+    let (de, df) = (e.b - e.a, f.b - f.a);
+    let denom = cross(de, df);
+    if denom == 0. {
+        return;
+    }
+    let t = cross(f.a - e.a, df) / denom;
+    let u = cross(f.a - e.a, de) / denom;
+    if t <= 0. || t >= 1. || u <= 0. || u >= 1. {
+        return;
+    }
+    let p = e.a + de * t as f32;
+    if [e.a, e.b, f.a, f.b].iter().any(|q| q.distance(p) <= epsilon) {
+        return;
+    }
+    e_cuts.push((t as f32, p));
+    f_cuts.push((u as f32, p));
+}
+
+fn cut_edges(edges: &[Edge]) -> Vec<Edge> {
+    let extent = edges.iter().fold(0_f32, |m, e| m.max(e.a.x.abs()).max(e.a.y.abs()));
+    let epsilon = extent.max(1.) * 1e-6;
+
+    // Sweep along X, so that only edges which overlap on that axis are compared.
+    let min_x = |e: &Edge| e.a.x.min(e.b.x);
+    let mut order = (0..edges.len()).collect::<Vec<_>>();
+    order.sort_by(|&i, &j| min_x(&edges[i]).partial_cmp(&min_x(&edges[j])).unwrap());
+
+    let mut cuts = vec![vec![]; edges.len()];
+    for (n, &i) in order.iter().enumerate() {
+        let e = &edges[i];
+        let max_x = e.a.x.max(e.b.x) + epsilon;
+        let (min_y, max_y) = (e.a.y.min(e.b.y) - epsilon, e.a.y.max(e.b.y) + epsilon);
+        for &j in &order[n + 1 ..] {
+            let f = &edges[j];
+            if min_x(f) > max_x {
+                break;
+            }
+            if f.a.y.max(f.b.y) < min_y || f.a.y.min(f.b.y) > max_y {
+                continue;
+            }
+            let (mut e_cuts, mut f_cuts) = (vec![], vec![]);
+            find_cuts(e, f, epsilon, &mut e_cuts, &mut f_cuts);
+            cuts[i].extend(e_cuts);
+            cuts[j].extend(f_cuts);
+        }
+    }
+
+    let mut pieces = vec![];
+    for (e, mut cuts) in edges.iter().zip(cuts.into_iter()) {
+        cuts.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        let mut prev = e.a;
+        for (_, p) in cuts.into_iter().chain(Some((1., e.b))) {
+            if p != prev {
+                pieces.push(Edge { a: prev, b: p, operand: e.operand });
+                prev = p;
+            }
+        }
+    }
+    pieces
+}
+
+// Edges sorted into horizontal bands, for computing winding numbers quickly.
+struct Bands<'a> {
+    edges: &'a [Edge],
+    min_y: f32,
+    band_height: f32,
+    bands: Vec<Vec<usize>>,
+}
+
+impl<'a> Bands<'a> {
+    fn new(edges: &'a [Edge]) -> Self {
+        let min_y = edges.iter().fold(::std::f32::INFINITY, |m, e| m.min(e.a.y));
+        let max_y = edges.iter().fold(::std::f32::NEG_INFINITY, |m, e| m.max(e.a.y));
+        let count = ((edges.len() as f32).sqrt().ceil() as usize).max(1);
+        let band_height = ((max_y - min_y) / count as f32).max(::std::f32::MIN_POSITIVE);
+        let mut bands = Self { edges, min_y, band_height, bands: vec![vec![]; count] };
+        for (i, e) in edges.iter().enumerate() {
+            let (first, last) = (bands.band(e.a.y.min(e.b.y)), bands.band(e.a.y.max(e.b.y)));
+            for band in &mut bands.bands[first .. last + 1] {
+                band.push(i);
+            }
+        }
+        bands
+    }
+    fn band(&self, y: f32) -> usize {
+        let i = ((y - self.min_y) / self.band_height).floor().max(0.) as usize;
+        i.min(self.bands.len() - 1)
+    }
+    // The winding number of `operand` around `p`, ignoring the edges along `segment`.
+    fn winding_number(&self, p: Vec2<f32>, operand: usize, segment: (Key, Key)) -> i32 {
+        let mut winding = 0;
+        for &i in &self.bands[self.band(p.y)] {
+            let e = &self.edges[i];
+            if e.operand != operand || segment_key(e) == segment {
+                continue;
+            }
+            let side = cross(e.b - e.a, p - e.a);
+            if e.a.y <= p.y {
+                if e.b.y > p.y && side > 0. {
+                    winding += 1;
+                }
+            } else if e.b.y <= p.y && side < 0. {
+                winding -= 1;
+            }
+        }
+        winding
+    }
+}
+
+// Removes points in the middle of straight runs, such as those left where
+// a segment was cut.
+fn remove_collinear_points(points: Vec<Vec2<f32>>) -> Vec<Vec2<f32>> {
+    let is_straight = |a: Vec2<f32>, b: Vec2<f32>, c: Vec2<f32>| {
+        let (u, v) = (b - a, c - b);
+        cross(u, v).abs() <= 1e-6 * (u.magnitude() * v.magnitude()) as f64 && u.dot(v) > 0.
+    };
+    let mut out: Vec<Vec2<f32>> = vec![];
+    for p in points {
+        while out.len() >= 2 && is_straight(out[out.len() - 2], out[out.len() - 1], p) {
+            out.pop();
+        }
+        out.push(p);
+    }
+    while out.len() >= 3 {
+        let n = out.len();
+        if is_straight(out[n - 2], out[n - 1], out[0]) {
+            out.pop();
+        } else if is_straight(out[n - 1], out[0], out[1]) {
+            out.remove(0);
+        } else {
+            break;
+        }
+    }
+    out
+}
+
+/// Combines the fills of `a` and `b`, each with its own fill rule.
+///
+/// The result is made of closed subpaths of line segments, with the inside
+/// on their left (i.e outer boundaries are counter-clockwise). Subpaths never
+/// cross each other, so both fill rules give the same fill.
+///
+/// `tolerance` is in local units, and bounds the error of flattened curves.
+pub fn combine(a: &Path, a_fill_rule: FillRule, b: &Path, b_fill_rule: FillRule, op: BooleanOp, tolerance: f32) -> Path {
+    combine_by_winding(a, b, tolerance, &|winding| {
+        op.apply(is_inside(winding[0], a_fill_rule), is_inside(winding[1], b_fill_rule))
+    })
+}
+
+/// The area which `path` winds around counter-clockwise more often than
+/// clockwise, i.e where its winding number is positive. The result is as
+/// described for `combine()`.
+pub fn positive_area(path: &Path, tolerance: f32) -> Path {
+    combine_by_winding(path, &Path::default(), tolerance, &|winding| winding[0] > 0)
+}
+
+// `is_inside_result` tells from the winding numbers of `a` and `b` around a
+// point whether it is inside the result.
+fn combine_by_winding(a: &Path, b: &Path, tolerance: f32, is_inside_result: &Fn([i32; 2]) -> bool) -> Path {
+    let mut edges = vec![];
+    push_edges(&mut edges, a, 0, tolerance);
+    push_edges(&mut edges, b, 1, tolerance);
+    let pieces = cut_edges(&edges);
+    let bands = Bands::new(&pieces);
+
+    let mut coincident = HashMap::<(Key, Key), Vec<usize>>::new();
+    for (i, e) in pieces.iter().enumerate() {
+        coincident.entry(segment_key(e)).or_insert_with(Vec::new).push(i);
+    }
+
+    // Decide once for each distinct segment, from its first piece.
+    let mut kept = vec![];
+    for (i, e) in pieces.iter().enumerate() {
+        let segment = segment_key(e);
+        let others = &coincident[&segment];
+        if others[0] != i {
+            continue;
+        }
+        // Crossing the coincident pieces from right to left changes the winding number of their operand.
+        let mut crossed = [0, 0];
+        for &j in others {
+            crossed[pieces[j].operand] += if pieces[j].a == e.a { 1 } else { -1 };
+        }
+        // Without the coincident pieces, the ray cast from the middle sees the
+        // winding numbers just beside it along +X, or along +Y when it is horizontal.
+        let middle = (e.a + e.b) / 2.;
+        let beside = [bands.winding_number(middle, 0, segment), bands.winding_number(middle, 1, segment)];
+        let is_beside_on_left = if e.a.y == e.b.y { e.b.x > e.a.x } else { e.b.y < e.a.y };
+        let (left, right) = if is_beside_on_left {
+            (beside, [beside[0] - crossed[0], beside[1] - crossed[1]])
+        } else {
+            ([beside[0] + crossed[0], beside[1] + crossed[1]], beside)
+        };
+        let is_inside_on_left = is_inside_result(left);
+        let is_inside_on_right = is_inside_result(right);
+        match (is_inside_on_left, is_inside_on_right) {
+            (true, false) => kept.push((e.a, e.b)),
+            (false, true) => kept.push((e.b, e.a)),
+            _ => (),
+        };
+    }
+
+    // Every point has as many kept pieces leaving it as arriving to it, so
+    // following them always leads back to where we started. Where several
+    // leave the same point, the one just clockwise of where we came from
+    // keeps to the same area, so that areas which only touch at a point
+    // become separate subpaths.
+    let mut leaving = HashMap::<Key, Vec<usize>>::new();
+    for (i, &(a, _)) in kept.iter().enumerate() {
+        leaving.entry(key(a)).or_insert_with(Vec::new).push(i);
+    }
+    let mut is_used = vec![false; kept.len()];
+    let mut path = Path::default();
+    for first in 0..kept.len() {
+        if is_used[first] {
+            continue;
+        }
+        let mut points = vec![];
+        let mut i = first;
+        loop {
+            is_used[i] = true;
+            let (a, b) = kept[i];
+            points.push(a);
+            if key(b) == key(kept[first].0) {
+                break;
+            }
+            let back = a - b;
+            let clockwise_angle = |j: usize| {
+                let out = kept[j].1 - kept[j].0;
+                let angle = (-cross(back, out)).atan2(back.dot(out) as f64);
+                if angle <= 0. { angle + 2. * ::std::f64::consts::PI } else { angle }
+            };
+            let next = leaving[&key(b)].iter().cloned().filter(|&j| !is_used[j]).min_by(|&j, &k| {
+                clockwise_angle(j).partial_cmp(&clockwise_angle(k)).unwrap()
+            });
+            match next {
+                Some(j) => i = j,
+                None => break,
+            };
+        }
+        let points = remove_collinear_points(points);
+        if points.len() < 3 {
+            continue;
+        }
+        let mut subpath = SubPath::new(points[0]);
+        subpath.cmds.extend(points[1..].iter().map(|&end| PathCmd::Line { end }));
+        subpath.is_closed = true;
+        path.subpaths.push(subpath);
+    }
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOLERANCE: f32 = 1e-3;
+
+    // Counter-clockwise.
+    fn rect(min: (f32, f32), max: (f32, f32)) -> Path {
+        let mut subpath = SubPath::new(Vec2::new(min.0, min.1));
+        for &(x, y) in &[(max.0, min.1), (max.0, max.1), (min.0, max.1)] {
+            subpath.cmds.push(PathCmd::Line { end: Vec2::new(x, y) });
+        }
+        subpath.is_closed = true;
+        Path { subpaths: vec![subpath] }
+    }
+
+    // Made of 4 cubic curves.
+    fn circle(radius: f32) -> Path {
+        let k = 0.552_284_8 * radius;
+        let v = |x, y| Vec2::new(x, y);
+        let mut subpath = SubPath::new(v(radius, 0.));
+        subpath.cmds.extend_from_slice(&[
+            PathCmd::Cubic { ctrl0: v(radius, k), ctrl1: v(k, radius), end: v(0., radius) },
+            PathCmd::Cubic { ctrl0: v(-k, radius), ctrl1: v(-radius, k), end: v(-radius, 0.) },
+            PathCmd::Cubic { ctrl0: v(-radius, -k), ctrl1: v(-k, -radius), end: v(0., -radius) },
+            PathCmd::Cubic { ctrl0: v(k, -radius), ctrl1: v(radius, -k), end: v(radius, 0.) },
+        ]);
+        subpath.is_closed = true;
+        Path { subpaths: vec![subpath] }
+    }
+
+    fn points(subpath: &SubPath) -> Vec<Vec2<f32>> {
+        subpath.cmds.iter().map(|cmd| match *cmd {
+            PathCmd::Start(p) | PathCmd::Line { end: p } => p,
+            _ => panic!("Results are made of lines only, not {:?}", cmd),
+        }).collect()
+    }
+
+    // Positive for counter-clockwise subpaths.
+    fn signed_area(subpath: &SubPath) -> f32 {
+        assert!(subpath.is_closed);
+        let points = points(subpath);
+        let mut prev = points[points.len() - 1];
+        points.iter().map(|&p| {
+            let area = (prev.x * p.y - p.x * prev.y) / 2.;
+            prev = p;
+            area
+        }).sum()
+    }
+
+    fn areas(path: &Path) -> Vec<f32> {
+        path.subpaths.iter().map(signed_area).collect()
+    }
+
+    fn sorted_areas(path: &Path) -> Vec<f32> {
+        let mut areas = areas(path);
+        areas.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        areas
+    }
+
+    fn combine_rects(a: &Path, b: &Path, op: BooleanOp) -> Path {
+        combine(a, FillRule::NonZero, b, FillRule::NonZero, op, TOLERANCE)
+    }
+
+    #[test]
+    fn overlapping_squares() {
+        let (a, b) = (rect((0., 0.), (2., 2.)), rect((1., 1.), (3., 3.)));
+        let union = combine_rects(&a, &b, BooleanOp::Union);
+        assert_eq!(areas(&union), vec![7.]);
+        assert_eq!(points(&union.subpaths[0]).len(), 8);
+        assert_eq!(areas(&combine_rects(&a, &b, BooleanOp::Intersection)), vec![1.]);
+        let difference = combine_rects(&a, &b, BooleanOp::Difference);
+        assert_eq!(areas(&difference), vec![3.]);
+        assert!(points(&difference.subpaths[0]).contains(&Vec2::new(1., 1.)));
+        // The two parts only touch at their corners, so they are kept apart.
+        assert_eq!(areas(&combine_rects(&a, &b, BooleanOp::Xor)), vec![3., 3.]);
+        // Clockwise operands give the same results.
+        let mut b_clockwise = b.clone();
+        b_clockwise.subpaths[0].cmds = {
+            let mut points = points(&b.subpaths[0]);
+            points.reverse();
+            let mut cmds = vec![PathCmd::Start(points[0])];
+            cmds.extend(points[1..].iter().map(|&end| PathCmd::Line { end }));
+            cmds
+        };
+        assert_eq!(areas(&combine_rects(&a, &b_clockwise, BooleanOp::Union)), vec![7.]);
+    }
+
+    #[test]
+    fn holes_are_clockwise() {
+        let (a, b) = (rect((0., 0.), (4., 4.)), rect((1., 1.), (3., 3.)));
+        assert_eq!(sorted_areas(&combine_rects(&a, &b, BooleanOp::Difference)), vec![-4., 16.]);
+        assert_eq!(combine_rects(&b, &a, BooleanOp::Difference), Path::default());
+
+        // Fill rules apply to each operand.
+        let mut both = a.clone();
+        both.subpaths.extend(b.subpaths.iter().cloned());
+        assert_eq!(areas(&combine(&both, FillRule::NonZero, &Path::default(), FillRule::NonZero, BooleanOp::Union, TOLERANCE)), vec![16.]);
+        let even_odd = combine(&both, FillRule::EvenOdd, &Path::default(), FillRule::NonZero, BooleanOp::Union, TOLERANCE);
+        assert_eq!(sorted_areas(&even_odd), vec![-4., 16.]);
+    }
+
+    #[test]
+    fn squares_sharing_an_edge() {
+        let (a, b) = (rect((0., 0.), (1., 1.)), rect((1., 0.), (2., 1.)));
+        let union = combine_rects(&a, &b, BooleanOp::Union);
+        assert_eq!(areas(&union), vec![2.]);
+        // The shared edge is gone, and so are the points in the middle of the long edges.
+        assert_eq!(points(&union.subpaths[0]).len(), 4);
+        assert_eq!(combine_rects(&a, &b, BooleanOp::Intersection), Path::default());
+        assert_eq!(areas(&combine_rects(&a, &b, BooleanOp::Difference)), vec![1.]);
+        assert_eq!(areas(&combine_rects(&a, &b, BooleanOp::Xor)), vec![2.]);
+        // A square on top of itself.
+        assert_eq!(areas(&combine_rects(&a, &a, BooleanOp::Union)), vec![1.]);
+        assert_eq!(combine_rects(&a, &a, BooleanOp::Xor), Path::default());
+    }
+
+    #[test]
+    fn squares_touching_at_a_corner_stay_apart() {
+        let (a, b) = (rect((0., 0.), (1., 1.)), rect((1., 1.), (2., 2.)));
+        let union = combine_rects(&a, &b, BooleanOp::Union);
+        assert_eq!(areas(&union), vec![1., 1.]);
+        assert!(union.subpaths.iter().all(|s| points(s).len() == 4));
+        assert_eq!(combine_rects(&a, &b, BooleanOp::Intersection), Path::default());
+    }
+
+    #[test]
+    fn curves_are_flattened_within_tolerance() {
+        let circle = circle(1.);
+        let square = rect((0., 0.), (2., 2.));
+        let quarter = combine(&circle, FillRule::NonZero, &square, FillRule::NonZero, BooleanOp::Intersection, TOLERANCE);
+        assert_eq!(quarter.subpaths.len(), 1);
+        let area = signed_area(&quarter.subpaths[0]);
+        let max_error = (::std::f32::consts::PI / 2. + 2.) * TOLERANCE;
+        assert!((area - ::std::f32::consts::PI / 4.).abs() <= max_error, "{}", area);
+        for p in points(&quarter.subpaths[0]) {
+            let is_on_axis = p.x.abs() < 1e-6 || p.y.abs() < 1e-6;
+            let is_on_arc = (p.magnitude() - 1.).abs() <= TOLERANCE;
+            assert!(p.x >= -1e-6 && p.y >= -1e-6 && (is_on_axis || is_on_arc), "{:?}", p);
+        }
+
+        let rest = combine(&circle, FillRule::NonZero, &square, FillRule::NonZero, BooleanOp::Difference, TOLERANCE);
+        assert!((areas(&rest).iter().sum::<f32>() - ::std::f32::consts::PI * 3. / 4.).abs() <= 3. * max_error);
+    }
+
+    #[test]
+    fn positive_area_removes_clockwise_overlaps() {
+        let mut path = rect((0., 0.), (2., 2.));
+        path.subpaths.extend(rect((1., 1.), (3., 3.)).subpaths);
+        assert_eq!(areas(&positive_area(&path, TOLERANCE)), vec![7.]);
+    }
+}
@@ -0,0 +1,598 @@
+// Node-level editing of paths.
+//
+// A node is the end point of a command; `cmds[0]`'s is the start point of
+// the subpath. The tangents at a node are given by the control points next
+// to it: the last one of the command which ends there, and the first one of
+// the command which starts there.
+//
+// A closed subpath whose last command ends back at its start point has a
+// node there twice, once as `cmds[0]` and once as the last command. Both
+// are edited as a single node.
+//
+// Edits forget which primitive the subpath was generated from, if any.
+
+use v::{Vec2, LineSegment2, QuadraticBezier2, CubicBezier2};
+use super::{Path, SubPath, PathCmd, NodeStyle};
+use super::geometry::Segment;
+use super::simplify;
+
+/// Deleting a node refits the two segments around it from this many samples of each.
+const SAMPLES_PER_MERGED_SEGMENT: usize = 16;
+
+/// Which point of a command.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PointKind {
+    /// The end point.
+    Node,
+    /// The first control point of a cubic curve, or the one of a quadratic curve.
+    Ctrl0,
+    /// The second control point of a cubic curve.
+    Ctrl1,
+}
+
+/// A point of a path which can be moved: a node, or a control point.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PathPoint {
+    /// Index into `Path::subpaths`.
+    pub subpath: usize,
+    /// Index into `SubPath::cmds`.
+    pub cmd: usize,
+    pub kind: PointKind,
+}
+
+/// What a segment can be converted to.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum SegmentKind {
+    Line,
+    Quadratic,
+    Cubic,
+}
+
+impl SegmentKind {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            SegmentKind::Line => "line",
+            SegmentKind::Quadratic => "quadratic",
+            SegmentKind::Cubic => "cubic",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "line" => Some(SegmentKind::Line),
+            "quadratic" => Some(SegmentKind::Quadratic),
+            "cubic" => Some(SegmentKind::Cubic),
+            _ => None,
+        }
+    }
+}
+
+fn cmd_from_segment(segment: Segment) -> PathCmd {
+    match segment {
+        Segment::Line(l) => PathCmd::Line { end: l.end },
+        Segment::Quadratic(c) => PathCmd::Quadratic { ctrl: c.ctrl, end: c.end },
+        Segment::Cubic(c) => PathCmd::Cubic { ctrl0: c.ctrl0, ctrl1: c.ctrl1, end: c.end },
+    }
+}
+
+// The segment which `cmds[i]` draws, for `i > 0`.
+fn segment(subpath: &SubPath, i: usize) -> Segment {
+    let start = subpath.cmds[i - 1].end();
+    match subpath.cmds[i] {
+        PathCmd::Start(end) | PathCmd::Line { end } => Segment::Line(LineSegment2 { start, end }),
+        PathCmd::Quadratic { ctrl, end } => Segment::Quadratic(QuadraticBezier2 { start, ctrl, end }),
+        PathCmd::Cubic { ctrl0, ctrl1, end } => Segment::Cubic(CubicBezier2 { start, ctrl0, ctrl1, end }),
+    }
+}
+
+// A single segment which follows `a` then `b`, as closely as possible.
+fn merge_segments(a: Segment, b: Segment) -> Segment {
+    match (a, b) {
+        (Segment::Line(a), Segment::Line(b)) => return Segment::Line(LineSegment2 { start: a.start, end: b.end }),
+        _ => (),
+    };
+    let (start, end) = (a.start(), b.end());
+    let mut points = vec![];
+    for &s in &[a, b] {
+        for i in 0 .. SAMPLES_PER_MERGED_SEGMENT {
+            points.push(s.evaluate(i as f32 / SAMPLES_PER_MERGED_SEGMENT as f32));
+        }
+    }
+    points.push(end);
+    points.dedup();
+    let (tangent1, tangent2) = (a.tangent(0.), -b.tangent(1.));
+    if points.len() < 3 || tangent1 == Vec2::zero() || tangent2 == Vec2::zero() {
+        return Segment::Line(LineSegment2 { start, end });
+    }
+    Segment::Cubic(simplify::fit_cubic(&points, tangent1, tangent2))
+}
+
+impl SubPath {
+    // Whether `cmds[0]` and the last command are the same node.
+    fn has_twin_start_node(&self) -> bool {
+        self.is_closed && self.cmds.len() > 1 && self.cmds[self.cmds.len() - 1].end() == self.start()
+    }
+    // All indices of commands which end at node `i`.
+    fn node_twins(&self, i: usize) -> Vec<usize> {
+        let last = self.cmds.len() - 1;
+        match i {
+            0 if self.has_twin_start_node() => vec![0, last],
+            i if i == last && i > 0 && self.has_twin_start_node() => vec![0, last],
+            i => vec![i],
+        }
+    }
+    // Turns the implicit segment which closes this subpath into a command.
+    fn make_closing_segment_explicit(&mut self) {
+        if self.is_closed && !self.has_twin_start_node() {
+            let start = self.start();
+            self.cmds.push(PathCmd::Line { end: start });
+        }
+    }
+    /// The control points which give the tangents at node `i`: the one
+    /// before it, then the one after it. Quadratic curves' control points
+    /// count too, although they are shared with the neighbouring node.
+    pub fn tangent_handles(&self, i: usize) -> (Option<usize>, Option<usize>) {
+        let twins = self.node_twins(i);
+        let (incoming, outgoing) = (twins[twins.len() - 1], twins[0] + 1);
+        let incoming = match self.cmds[incoming] {
+            PathCmd::Quadratic { .. } | PathCmd::Cubic { .. } => Some(incoming),
+            _ => None,
+        };
+        let outgoing = match self.cmds.get(outgoing) {
+            Some(&PathCmd::Quadratic { .. }) | Some(&PathCmd::Cubic { .. }) => Some(outgoing),
+            _ => None,
+        };
+        (incoming, outgoing)
+    }
+    fn point(&self, cmd: usize, kind: PointKind) -> Option<Vec2<f32>> {
+        match (self.cmds.get(cmd), kind) {
+            (Some(cmd), PointKind::Node) => Some(cmd.end()),
+            (Some(&PathCmd::Quadratic { ctrl, .. }), PointKind::Ctrl0) => Some(ctrl),
+            (Some(&PathCmd::Cubic { ctrl0, .. }), PointKind::Ctrl0) => Some(ctrl0),
+            (Some(&PathCmd::Cubic { ctrl1, .. }), PointKind::Ctrl1) => Some(ctrl1),
+            _ => None,
+        }
+    }
+    fn point_mut(&mut self, cmd: usize, kind: PointKind) -> Option<&mut Vec2<f32>> {
+        match (self.cmds.get_mut(cmd), kind) {
+            (Some(&mut PathCmd::Start(ref mut p)), PointKind::Node) => Some(p),
+            (Some(&mut PathCmd::Line { ref mut end }), PointKind::Node) => Some(end),
+            (Some(&mut PathCmd::Quadratic { ref mut end, .. }), PointKind::Node) => Some(end),
+            (Some(&mut PathCmd::Cubic { ref mut end, .. }), PointKind::Node) => Some(end),
+            (Some(&mut PathCmd::Quadratic { ref mut ctrl, .. }), PointKind::Ctrl0) => Some(ctrl),
+            (Some(&mut PathCmd::Cubic { ref mut ctrl0, .. }), PointKind::Ctrl0) => Some(ctrl0),
+            (Some(&mut PathCmd::Cubic { ref mut ctrl1, .. }), PointKind::Ctrl1) => Some(ctrl1),
+            _ => None,
+        }
+    }
+    // The handle before node `i`, which is the last control point of the
+    // command ending there.
+    fn incoming_kind(&self, cmd: usize) -> PointKind {
+        match self.cmds[cmd] {
+            PathCmd::Cubic { .. } => PointKind::Ctrl1,
+            _ => PointKind::Ctrl0,
+        }
+    }
+    // The node which a cubic control point belongs to, along with the
+    // control point on the other side of it, if any.
+    fn cubic_handle_pair(&self, cmd: usize, kind: PointKind) -> Option<(usize, Option<(usize, PointKind)>)> {
+        match (self.cmds.get(cmd), kind) {
+            (Some(&PathCmd::Cubic { .. }), PointKind::Ctrl0) if cmd > 0 => {
+                let node = cmd - 1;
+                let (incoming, _) = self.tangent_handles(node);
+                Some((node, incoming.map(|i| (i, self.incoming_kind(i)))))
+            },
+            (Some(&PathCmd::Cubic { .. }), PointKind::Ctrl1) => {
+                let (_, outgoing) = self.tangent_handles(cmd);
+                Some((cmd, outgoing.map(|i| (i, PointKind::Ctrl0))))
+            },
+            _ => None,
+        }
+    }
+}
+
+impl Path {
+    /// Where `p` is, or `None` if the path has no such point.
+    pub fn point(&self, p: PathPoint) -> Option<Vec2<f32>> {
+        self.subpaths.get(p.subpath).and_then(|s| s.point(p.cmd, p.kind))
+    }
+    /// All nodes and control points, subpath by subpath and command by command.
+    /// Twin start nodes of closed subpaths are only given once, as `cmds[0]`.
+    pub fn points(&self) -> Vec<(PathPoint, Vec2<f32>)> {
+        let mut points = vec![];
+        for (i, subpath) in self.subpaths.iter().enumerate() {
+            let twin = if subpath.has_twin_start_node() { Some(subpath.cmds.len() - 1) } else { None };
+            for cmd in 0 .. subpath.cmds.len() {
+                for &kind in &[PointKind::Ctrl0, PointKind::Ctrl1, PointKind::Node] {
+                    if kind == PointKind::Node && Some(cmd) == twin {
+                        continue;
+                    }
+                    if let Some(position) = subpath.point(cmd, kind) {
+                        points.push((PathPoint { subpath: i, cmd, kind }, position));
+                    }
+                }
+            }
+        }
+        points
+    }
+    /// The point which is closest to `p`, if any is at most `max_distance`
+    /// away from it. Nodes win over control points at the same distance.
+    pub fn nearest_point(&self, p: Vec2<f32>, max_distance: f32) -> Option<PathPoint> {
+        let mut nearest: Option<(f32, PathPoint)> = None;
+        for (point, position) in self.points() {
+            let d = position.distance(p);
+            if d > max_distance {
+                continue;
+            }
+            let is_nearer = match nearest {
+                None => true,
+                Some((best, best_point)) => d < best || (d == best && point.kind == PointKind::Node && best_point.kind != PointKind::Node),
+            };
+            if is_nearer {
+                nearest = Some((d, point));
+            }
+        }
+        nearest.map(|(_, point)| point)
+    }
+    /// The node which the cubic control point `p` moves along with, as
+    /// given by `points()`, or `None` if `p` is not a cubic control point.
+    pub fn handle_node(&self, p: PathPoint) -> Option<PathPoint> {
+        let s = self.subpaths.get(p.subpath)?;
+        let (node, _) = s.cubic_handle_pair(p.cmd, p.kind)?;
+        Some(PathPoint { subpath: p.subpath, cmd: s.node_twins(node)[0], kind: PointKind::Node })
+    }
+    /// The nodes at both ends of the segment drawn by `cmds[cmd]` of the
+    /// given subpath, as given by `points()`. `cmd` may also be `cmds.len()`
+    /// for the segment which closes a closed subpath, if it is implicit.
+    pub fn segment_ends(&self, subpath: usize, cmd: usize) -> Option<(PathPoint, PathPoint)> {
+        let s = self.subpaths.get(subpath)?;
+        let node = |i: usize| PathPoint { subpath, cmd: s.node_twins(i)[0], kind: PointKind::Node };
+        if cmd > 0 && cmd < s.cmds.len() {
+            Some((node(cmd - 1), node(cmd)))
+        } else if cmd > 0 && cmd == s.cmds.len() && s.is_closed && !s.has_twin_start_node() {
+            Some((node(cmd - 1), node(0)))
+        } else {
+            None
+        }
+    }
+    /// Moves `p` to `position`.
+    ///
+    /// The cubic control points next to a node move along with it. When
+    /// moving a cubic control point, the one on the other side of its node
+    /// turns with it if `keep_aligned` is set and both were aligned, so that
+    /// the curve stays smooth there.
+    pub fn move_point(&mut self, p: PathPoint, position: Vec2<f32>, keep_aligned: bool) {
+        let subpath = match self.subpaths.get_mut(p.subpath) {
+            Some(s) => s,
+            None => return,
+        };
+        let old = match subpath.point(p.cmd, p.kind) {
+            Some(old) => old,
+            None => return,
+        };
+        subpath.primitive = None;
+        let delta = position - old;
+        match p.kind {
+            PointKind::Node => {
+                let (incoming, outgoing) = subpath.tangent_handles(p.cmd);
+                for twin in subpath.node_twins(p.cmd) {
+                    *subpath.point_mut(twin, PointKind::Node).unwrap() = position;
+                }
+                let handles = incoming.map(|i| (i, PointKind::Ctrl1)).into_iter().chain(outgoing.map(|i| (i, PointKind::Ctrl0)));
+                for (i, kind) in handles {
+                    if let PathCmd::Cubic { .. } = subpath.cmds[i] {
+                        *subpath.point_mut(i, kind).unwrap() += delta;
+                    }
+                }
+            },
+            kind => {
+                let pair = subpath.cubic_handle_pair(p.cmd, kind);
+                *subpath.point_mut(p.cmd, kind).unwrap() = position;
+                if let (true, Some((node, Some((other, other_kind))))) = (keep_aligned, pair) {
+                    let center = subpath.cmds[node].end();
+                    let opposite = subpath.point(other, other_kind).unwrap();
+                    let (u, v) = (old - center, opposite - center);
+                    let are_aligned = u.magnitude() > 0. && v.magnitude() > 0.
+                        && (u.x * v.y - u.y * v.x).abs() <= 1e-4 * u.magnitude() * v.magnitude() && u.dot(v) < 0.;
+                    let direction = position - center;
+                    if are_aligned && direction.magnitude() > 0. {
+                        *subpath.point_mut(other, other_kind).unwrap() = center - direction.normalized() * v.magnitude();
+                    }
+                }
+            },
+        };
+    }
+    /// Splits the segment drawn by `cmds[cmd]` of the given subpath at `t`,
+    /// and returns the node in between. `cmd` may also be `cmds.len()` for
+    /// the segment which closes a closed subpath.
+    ///
+    /// The new node has no style overrides.
+    pub fn insert_node(&mut self, subpath: usize, cmd: usize, t: f32) -> Option<PathPoint> {
+        let s = self.subpaths.get_mut(subpath)?;
+        if cmd == s.cmds.len() && s.is_closed {
+            s.make_closing_segment_explicit();
+        }
+        if cmd == 0 || cmd >= s.cmds.len() {
+            return None;
+        }
+        s.primitive = None;
+        let (a, b) = segment(s, cmd).split(t);
+        s.cmds[cmd] = cmd_from_segment(b);
+        s.cmds.insert(cmd, cmd_from_segment(a));
+        if s.node_styles.len() > cmd {
+            s.node_styles.insert(cmd, NodeStyle::default());
+        }
+        Some(PathPoint { subpath, cmd, kind: PointKind::Node })
+    }
+    /// Removes the node at the end of `cmds[cmd]` of the given subpath.
+    /// The two segments around it become a single one, which follows them
+    /// as closely as possible. A subpath left without any node is removed.
+    pub fn delete_node(&mut self, subpath: usize, cmd: usize) {
+        {
+            let s = match self.subpaths.get_mut(subpath) {
+                Some(s) => s,
+                None => return,
+            };
+            if cmd >= s.cmds.len() {
+                return;
+            }
+            s.primitive = None;
+            // So that the node before the start point has a segment to merge.
+            s.make_closing_segment_explicit();
+            let n = s.cmds.len();
+            if n > 2 && s.node_twins(cmd).len() > 1 {
+                // Merge the segments on both sides of the start point; the
+                // new start point is the node which followed it.
+                let merged = merge_segments(segment(s, n - 1), segment(s, 1));
+                let style = |i: usize| s.node_styles.get(i).cloned().unwrap_or_default();
+                let mut node_styles = vec![style(1)];
+                node_styles.extend((2 .. n - 1).map(&style));
+                node_styles.push(style(1));
+                let mut cmds = vec![PathCmd::Start(s.cmds[1].end())];
+                cmds.extend_from_slice(&s.cmds[2 .. n - 1]);
+                cmds.push(cmd_from_segment(merged));
+                s.cmds = cmds;
+                s.node_styles = node_styles;
+            } else if cmd == 0 {
+                if n > 1 {
+                    s.cmds[0] = PathCmd::Start(s.cmds[1].end());
+                    s.cmds.remove(1);
+                } else {
+                    s.cmds.clear();
+                }
+                if !s.node_styles.is_empty() {
+                    s.node_styles.remove(0);
+                }
+            } else if cmd == n - 1 {
+                s.cmds.pop();
+                s.node_styles.truncate(cmd);
+            } else {
+                let merged = merge_segments(segment(s, cmd), segment(s, cmd + 1));
+                s.cmds[cmd + 1] = cmd_from_segment(merged);
+                s.cmds.remove(cmd);
+                if s.node_styles.len() > cmd {
+                    s.node_styles.remove(cmd);
+                }
+            }
+        }
+        if self.subpaths[subpath].cmds.is_empty() {
+            self.subpaths.remove(subpath);
+        }
+    }
+    /// Turns the segment drawn by `cmds[cmd]` of the given subpath into
+    /// another kind. Lines become straight curves and cubic curves become
+    /// quadratic ones as closely as possible; other conversions are exact,
+    /// apart from turning curves into lines. `cmd` may also be `cmds.len()`
+    /// for the segment which closes a closed subpath.
+    pub fn convert_segment(&mut self, subpath: usize, cmd: usize, kind: SegmentKind) {
+        let s = match self.subpaths.get_mut(subpath) {
+            Some(s) => s,
+            None => return,
+        };
+        if cmd == s.cmds.len() && s.is_closed {
+            s.make_closing_segment_explicit();
+        }
+        if cmd == 0 || cmd >= s.cmds.len() {
+            return;
+        }
+        s.primitive = None;
+        let (start, end) = (s.cmds[cmd - 1].end(), s.cmds[cmd].end());
+        s.cmds[cmd] = match (segment(s, cmd), kind) {
+            (_, SegmentKind::Line) => PathCmd::Line { end },
+            (Segment::Line(_), SegmentKind::Quadratic) => PathCmd::Quadratic { ctrl: (start + end) / 2., end },
+            (Segment::Line(_), SegmentKind::Cubic) => PathCmd::Cubic {
+                ctrl0: start + (end - start) / 3., ctrl1: end - (end - start) / 3., end,
+            },
+            (Segment::Quadratic(c), SegmentKind::Cubic) => {
+                let c = c.into_cubic();
+                PathCmd::Cubic { ctrl0: c.ctrl0, ctrl1: c.ctrl1, end }
+            },
+            // The control point of the quadratic curve which has the same
+            // derivative at its middle.
+            (Segment::Cubic(c), SegmentKind::Quadratic) => PathCmd::Quadratic {
+                ctrl: ((c.ctrl0 + c.ctrl1) * 3. - start - end) / 4., end,
+            },
+            (_, SegmentKind::Quadratic) | (_, SegmentKind::Cubic) => s.cmds[cmd],
+        };
+    }
+    /// Turns the control points on both sides of a node so that they are
+    /// aligned, keeping their distances to it. The curve becomes smooth there.
+    pub fn align_tangents(&mut self, subpath: usize, cmd: usize) {
+        let s = match self.subpaths.get_mut(subpath) {
+            Some(s) => s,
+            None => return,
+        };
+        if cmd >= s.cmds.len() {
+            return;
+        }
+        let (incoming, outgoing) = match s.tangent_handles(cmd) {
+            (Some(i), Some(o)) => ((i, s.incoming_kind(i)), (o, PointKind::Ctrl0)),
+            _ => return,
+        };
+        let center = s.cmds[cmd].end();
+        let (a, b) = (s.point(incoming.0, incoming.1).unwrap(), s.point(outgoing.0, outgoing.1).unwrap());
+        let direction = b - a;
+        if direction.magnitude() <= 0. {
+            return;
+        }
+        s.primitive = None;
+        let direction = direction.normalized();
+        *s.point_mut(incoming.0, incoming.1).unwrap() = center - direction * a.distance(center);
+        *s.point_mut(outgoing.0, outgoing.1).unwrap() = center + direction * b.distance(center);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v(x: f32, y: f32) -> Vec2<f32> {
+        Vec2::new(x, y)
+    }
+
+    fn open(cmds: &[PathCmd]) -> Path {
+        let mut s = SubPath::new(v(0., 0.));
+        s.cmds.extend_from_slice(cmds);
+        Path { subpaths: vec![s] }
+    }
+
+    fn node(cmd: usize) -> PathPoint {
+        PathPoint { subpath: 0, cmd, kind: PointKind::Node }
+    }
+
+    fn assert_close(a: Vec2<f32>, b: Vec2<f32>) {
+        assert!(a.distance(b) < 1e-4, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn nodes_carry_their_cubic_handles() {
+        let mut path = open(&[
+            PathCmd::Cubic { ctrl0: v(0., 1.), ctrl1: v(1., 1.), end: v(1., 0.) },
+            PathCmd::Quadratic { ctrl: v(2., -1.), end: v(3., 0.) },
+        ]);
+        path.move_point(node(1), v(1., 2.), false);
+        assert_eq!(path.subpaths[0].cmds[1], PathCmd::Cubic { ctrl0: v(0., 1.), ctrl1: v(1., 3.), end: v(1., 2.) });
+        // Quadratic control points are shared with the next node, so they stay.
+        assert_eq!(path.subpaths[0].cmds[2], PathCmd::Quadratic { ctrl: v(2., -1.), end: v(3., 0.) });
+    }
+
+    #[test]
+    fn aligned_handles_turn_together() {
+        let mut path = open(&[
+            PathCmd::Cubic { ctrl0: v(0., 1.), ctrl1: v(1., 1.), end: v(2., 1.) },
+            PathCmd::Cubic { ctrl0: v(4., 1.), ctrl1: v(4., 0.), end: v(5., 0.) },
+        ]);
+        let handle = PathPoint { subpath: 0, cmd: 2, kind: PointKind::Ctrl0 };
+        path.move_point(handle, v(2., 3.), true);
+        assert_close(path.point(PathPoint { subpath: 0, cmd: 1, kind: PointKind::Ctrl1 }).unwrap(), v(2., 0.));
+
+        // Unless they are moved apart.
+        path.move_point(handle, v(3., 3.), false);
+        path.move_point(handle, v(4., 4.), true);
+        assert_close(path.point(PathPoint { subpath: 0, cmd: 1, kind: PointKind::Ctrl1 }).unwrap(), v(2., 0.));
+
+        path.align_tangents(0, 1);
+        let (a, b) = (path.point(PathPoint { subpath: 0, cmd: 1, kind: PointKind::Ctrl1 }).unwrap(), path.point(handle).unwrap());
+        let (u, w) = (a - v(2., 1.), b - v(2., 1.));
+        assert!((u.x * w.y - u.y * w.x).abs() < 1e-4 && u.dot(w) < 0.);
+        assert!((u.magnitude() - 1.).abs() < 1e-4);
+    }
+
+    #[test]
+    fn twin_start_nodes_move_together() {
+        let mut path = Path::circle(v(0., 0.), 1.);
+        path.move_point(node(0), v(2., 0.), false);
+        let s = &path.subpaths[0];
+        assert_eq!(s.start(), v(2., 0.));
+        assert_eq!(s.cmds[4].end(), v(2., 0.));
+        assert_eq!(s.primitive, None);
+        assert_eq!(path.points().iter().filter(|&&(p, _)| p.kind == PointKind::Node).count(), 4);
+    }
+
+    #[test]
+    fn inserting_a_node_keeps_the_curve() {
+        let mut path = open(&[PathCmd::Cubic { ctrl0: v(0., 1.), ctrl1: v(1., 1.), end: v(1., 0.) }]);
+        let before = segment(&path.subpaths[0], 1);
+        let p = path.insert_node(0, 1, 0.25).unwrap();
+        assert_eq!(p, node(1));
+        assert_close(path.point(p).unwrap(), before.evaluate(0.25));
+        assert_close(segment(&path.subpaths[0], 2).evaluate(0.5), before.evaluate(0.625));
+
+        // Including the closing segment.
+        let mut path = Path::rect(v(0., 0.), v(1., 1.));
+        path.insert_node(0, 4, 0.5).unwrap();
+        assert_eq!(path.subpaths[0].cmds[4].end(), v(0., 0.5));
+        assert_eq!(path.subpaths[0].cmds[5].end(), v(0., 0.));
+    }
+
+    #[test]
+    fn deleting_a_node_keeps_the_curve() {
+        let mut path = open(&[PathCmd::Cubic { ctrl0: v(0., 1.), ctrl1: v(1., 1.), end: v(1., 0.) }]);
+        let before = segment(&path.subpaths[0], 1);
+        path.insert_node(0, 1, 0.4);
+        path.delete_node(0, 1);
+        assert_eq!(path.subpaths[0].cmds.len(), 2);
+        let after = segment(&path.subpaths[0], 1);
+        for i in 0 .. 11 {
+            let p = before.evaluate(i as f32 / 10.);
+            assert_close(after.closest_point(p).1, p);
+        }
+
+        // Lines stay lines.
+        let mut path = open(&[PathCmd::Line { end: v(1., 0.) }, PathCmd::Line { end: v(1., 1.) }]);
+        path.delete_node(0, 1);
+        assert_eq!(path.subpaths[0].cmds, vec![PathCmd::Start(v(0., 0.)), PathCmd::Line { end: v(1., 1.) }]);
+
+        // Ends are simply dropped.
+        path.delete_node(0, 0);
+        assert_eq!(path.subpaths[0].cmds, vec![PathCmd::Start(v(1., 1.))]);
+        path.delete_node(0, 0);
+        assert!(path.subpaths.is_empty());
+    }
+
+    #[test]
+    fn deleting_the_start_of_a_closed_subpath() {
+        let mut path = Path::rect(v(0., 0.), v(1., 1.));
+        path.delete_node(0, 0);
+        let s = &path.subpaths[0];
+        assert!(s.is_closed);
+        assert_eq!(s.cmds, vec![
+            PathCmd::Start(v(1., 0.)),
+            PathCmd::Line { end: v(1., 1.) },
+            PathCmd::Line { end: v(0., 1.) },
+            PathCmd::Line { end: v(1., 0.) },
+        ]);
+    }
+
+    #[test]
+    fn converting_segments() {
+        let mut path = open(&[PathCmd::Line { end: v(3., 0.) }]);
+        path.convert_segment(0, 1, SegmentKind::Cubic);
+        assert_eq!(path.subpaths[0].cmds[1], PathCmd::Cubic { ctrl0: v(1., 0.), ctrl1: v(2., 0.), end: v(3., 0.) });
+        path.convert_segment(0, 1, SegmentKind::Quadratic);
+        assert_eq!(path.subpaths[0].cmds[1], PathCmd::Quadratic { ctrl: v(1.5, 0.), end: v(3., 0.) });
+        path.convert_segment(0, 1, SegmentKind::Cubic);
+        assert_eq!(path.subpaths[0].cmds[1], PathCmd::Cubic { ctrl0: v(1., 0.), ctrl1: v(2., 0.), end: v(3., 0.) });
+        path.convert_segment(0, 1, SegmentKind::Line);
+        assert_eq!(path.subpaths[0].cmds[1], PathCmd::Line { end: v(3., 0.) });
+    }
+
+    #[test]
+    fn nearest_point_prefers_nodes() {
+        let path = open(&[PathCmd::Cubic { ctrl0: v(0., 0.), ctrl1: v(1., 1.), end: v(1., 0.) }]);
+        assert_eq!(path.nearest_point(v(0.1, 0.), 0.5), Some(node(0)));
+        assert_eq!(path.nearest_point(v(0.9, 0.9), 0.5), Some(PathPoint { subpath: 0, cmd: 1, kind: PointKind::Ctrl1 }));
+        assert_eq!(path.nearest_point(v(5., 5.), 0.5), None);
+    }
+
+    #[test]
+    fn segment_ends_and_handle_nodes_name_twins_once() {
+        let circle = Path::circle(v(0., 0.), 1.);
+        assert_eq!(circle.segment_ends(0, 4), Some((node(3), node(0))));
+        assert_eq!(circle.segment_ends(0, 5), None);
+        assert_eq!(circle.handle_node(PathPoint { subpath: 0, cmd: 4, kind: PointKind::Ctrl1 }), Some(node(0)));
+        let square = Path::rect(v(0., 0.), v(1., 1.));
+        assert_eq!(square.segment_ends(0, 4), Some((node(3), node(0))));
+        assert_eq!(square.handle_node(node(1)), None);
+    }
+}
@@ -0,0 +1,580 @@
+// Fill triangulation.
+//
+// Closed outlines are turned into triangles on the CPU by ear clipping, so
+// that a fill is a single indexed draw instead of the stencil trick.
+//
+// Outlines are first sorted into outer boundaries and holes according to the
+// fill rule. Each hole is then merged into its outer boundary by a pair of
+// coincident "bridge" edges, which leaves one simple polygon per outer
+// boundary. This is the same approach as mapbox's earcut.
+//
+// Self-intersecting or mutually intersecting outlines are not supported;
+// `triangulate()` returns `None` for them and the stencil should be used instead.
+
+use std::cmp::Ordering;
+use std::ops::Range;
+use v::Vec2;
+use super::FillRule;
+
+// Twice the signed area of triangle `abc`; positive when it turns counter-clockwise.
+fn cross(a: Vec2<f32>, b: Vec2<f32>, c: Vec2<f32>) -> f32 {
+    (b.x - a.x) * (c.y - a.y) - (b.y - a.y) * (c.x - a.x)
+}
+
+// Edges are included.
+fn is_point_in_triangle(p: Vec2<f32>, a: Vec2<f32>, b: Vec2<f32>, c: Vec2<f32>) -> bool {
+    cross(a, b, p) >= 0. && cross(b, c, p) >= 0. && cross(c, a, p) >= 0.
+}
+
+fn signed_area(points: &[Vec2<f32>]) -> f32 {
+    let mut sum = 0.;
+    let mut prev = points[points.len() - 1];
+    for &p in points {
+        sum += prev.x * p.y - p.x * prev.y;
+        prev = p;
+    }
+    sum / 2.
+}
+
+// Whether all points are on a single line, so that they bound nothing.
+// Self-intersecting outlines may have no area and still bound something.
+fn is_flat(points: &[Vec2<f32>]) -> bool {
+    let a = points[0];
+    match points.iter().find(|&&p| p != a) {
+        None => true,
+        Some(&b) => points.iter().all(|&p| cross(a, b, p) == 0.),
+    }
+}
+
+// How many times `points` winds around `p`, counter-clockwise being positive.
+fn winding_number(p: Vec2<f32>, points: &[Vec2<f32>]) -> i32 {
+    let mut winding = 0;
+    let mut prev = points[points.len() - 1];
+    for &next in points {
+        if prev.y <= p.y {
+            if next.y > p.y && cross(prev, next, p) > 0. {
+                winding += 1;
+            }
+        } else if next.y <= p.y && cross(prev, next, p) < 0. {
+            winding -= 1;
+        }
+        prev = next;
+    }
+    winding
+}
+
+// Touching counts as intersecting.
+fn do_segments_intersect(a: Vec2<f32>, b: Vec2<f32>, c: Vec2<f32>, d: Vec2<f32>) -> bool {
+    let sign = |x: f32| if x > 0. { 1 } else if x < 0. { -1 } else { 0 };
+    let on_segment = |p: Vec2<f32>, q: Vec2<f32>, r: Vec2<f32>| {
+        r.x <= p.x.max(q.x) && r.x >= p.x.min(q.x) && r.y <= p.y.max(q.y) && r.y >= p.y.min(q.y)
+    };
+    let (d1, d2) = (sign(cross(c, d, a)), sign(cross(c, d, b)));
+    let (d3, d4) = (sign(cross(a, b, c)), sign(cross(a, b, d)));
+    if d1 * d2 < 0 && d3 * d4 < 0 {
+        return true;
+    }
+    (d1 == 0 && on_segment(c, d, a))
+    || (d2 == 0 && on_segment(c, d, b))
+    || (d3 == 0 && on_segment(a, b, c))
+    || (d4 == 0 && on_segment(a, b, d))
+}
+
+// Sweeps edges from left to right, so that only the ones whose X ranges
+// overlap are tested against each other.
+fn has_intersections(positions: &[Vec2<f32>], contours: &[Range<usize>]) -> bool {
+    let mut edges = vec![];
+    for contour in contours {
+        for i in contour.clone() {
+            let j = if i + 1 == contour.end { contour.start } else { i + 1 };
+            let (a, b) = (positions[i], positions[j]);
+            edges.push((a.x.min(b.x), a.x.max(b.x), i, j));
+        }
+    }
+    edges.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+    for (k, &(_, max_x, a, b)) in edges.iter().enumerate() {
+        for &(min_x, _, c, d) in &edges[k + 1 ..] {
+            if min_x > max_x {
+                break;
+            }
+            // Consecutive edges of a contour share a vertex, which is fine.
+            if a == d || b == c {
+                continue;
+            }
+            if do_segments_intersect(positions[a], positions[b], positions[c], positions[d]) {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Node {
+    // Index into the positions.
+    i: usize,
+    prev: usize,
+    next: usize,
+    is_removed: bool,
+}
+
+// A set of circular doubly-linked lists of vertices, each one being a polygon.
+struct EarClipper<'a> {
+    positions: &'a [Vec2<f32>],
+    nodes: Vec<Node>,
+    triangles: Vec<u32>,
+}
+
+impl<'a> EarClipper<'a> {
+    fn pos(&self, n: usize) -> Vec2<f32> {
+        self.positions[self.nodes[n].i]
+    }
+    fn prev(&self, n: usize) -> usize {
+        self.nodes[n].prev
+    }
+    fn next(&self, n: usize) -> usize {
+        self.nodes[n].next
+    }
+    fn cross(&self, a: usize, b: usize, c: usize) -> f32 {
+        cross(self.pos(a), self.pos(b), self.pos(c))
+    }
+    fn insert_after(&mut self, i: usize, last: Option<usize>) -> usize {
+        let n = self.nodes.len();
+        match last {
+            None => self.nodes.push(Node { i, prev: n, next: n, is_removed: false }),
+            Some(last) => {
+                let next = self.next(last);
+                self.nodes.push(Node { i, prev: last, next, is_removed: false });
+                self.nodes[next].prev = n;
+                self.nodes[last].next = n;
+            },
+        }
+        n
+    }
+    fn remove(&mut self, n: usize) {
+        let Node { prev, next, .. } = self.nodes[n];
+        self.nodes[prev].next = next;
+        self.nodes[next].prev = prev;
+        self.nodes[n].is_removed = true;
+    }
+    // Creates a polygon from a contour, wound counter-clockwise if `ccw` is true,
+    // clockwise otherwise. Returns one of its nodes.
+    fn ring(&mut self, contour: Range<usize>, ccw: bool) -> usize {
+        let is_ccw = signed_area(&self.positions[contour.clone()]) > 0.;
+        let mut last = None;
+        if is_ccw == ccw {
+            for i in contour {
+                last = Some(self.insert_after(i, last));
+            }
+        } else {
+            for i in contour.rev() {
+                last = Some(self.insert_after(i, last));
+            }
+        }
+        last.unwrap()
+    }
+    // Removes duplicate and collinear vertices. Returns a node which is still in the polygon.
+    fn filter(&mut self, start: usize) -> usize {
+        let mut end = start;
+        let mut p = start;
+        loop {
+            let (prev, next) = (self.prev(p), self.next(p));
+            if p != next && (self.pos(p) == self.pos(next) || self.cross(prev, p, next) == 0.) {
+                self.remove(p);
+                p = prev;
+                end = prev;
+                if p == self.next(p) {
+                    break;
+                }
+                continue;
+            }
+            p = next;
+            if p == end {
+                break;
+            }
+        }
+        end
+    }
+    fn is_reflex(&self, n: usize) -> bool {
+        self.cross(self.prev(n), n, self.next(n)) <= 0.
+    }
+    // `reflex` holds the reflex vertices of the polygon, sorted by X.
+    // Only those can get in the way of an ear; convex vertices can't be
+    // inside it unless a reflex one is too.
+    fn is_ear(&self, ear: usize, reflex: &[(f32, usize)]) -> bool {
+        let (a, c) = (self.prev(ear), self.next(ear));
+        let (pa, pb, pc) = (self.pos(a), self.pos(ear), self.pos(c));
+        if cross(pa, pb, pc) <= 0. {
+            return false;
+        }
+        let min = Vec2::partial_min(Vec2::partial_min(pa, pb), pc);
+        let max = Vec2::partial_max(Vec2::partial_max(pa, pb), pc);
+        let first = {
+            let (mut lo, mut hi) = (0, reflex.len());
+            while lo < hi {
+                let mid = (lo + hi) / 2;
+                if reflex[mid].0 < min.x { lo = mid + 1; } else { hi = mid; }
+            }
+            lo
+        };
+        for &(x, p) in &reflex[first..] {
+            if x > max.x {
+                break;
+            }
+            if self.nodes[p].is_removed || !self.is_reflex(p) {
+                continue;
+            }
+            let pp = self.pos(p);
+            // Bridges duplicate some vertices, which touch the triangle but don't get in the way.
+            if pp.y < min.y || pp.y > max.y || pp == pa || pp == pb || pp == pc {
+                continue;
+            }
+            if is_point_in_triangle(pp, pa, pb, pc) {
+                return false;
+            }
+        }
+        true
+    }
+    // Returns false if it got stuck, which happens with self-intersections.
+    fn clip_ears(&mut self, start: usize) -> bool {
+        let mut ear = self.filter(start);
+        // Clipping ears never turns a convex vertex into a reflex one, so this
+        // only needs to be done once.
+        let mut reflex = vec![];
+        let mut p = ear;
+        loop {
+            if self.is_reflex(p) {
+                reflex.push((self.pos(p).x, p));
+            }
+            p = self.next(p);
+            if p == ear {
+                break;
+            }
+        }
+        reflex.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+
+        let mut stop = ear;
+        let mut has_filtered_again = false;
+        while self.prev(ear) != self.next(ear) {
+            let (prev, next) = (self.prev(ear), self.next(ear));
+            if self.is_ear(ear, &reflex) {
+                let (a, b, c) = (self.nodes[prev].i, self.nodes[ear].i, self.nodes[next].i);
+                self.triangles.extend(&[a as u32, b as u32, c as u32]);
+                self.remove(ear);
+                ear = self.next(next);
+                stop = ear;
+                has_filtered_again = false;
+                continue;
+            }
+            ear = next;
+            if ear == stop {
+                // Clipping ears may have made some vertices collinear; try once more without them.
+                if has_filtered_again {
+                    return false;
+                }
+                has_filtered_again = true;
+                ear = self.filter(ear);
+                stop = ear;
+            }
+        }
+        true
+    }
+    fn leftmost(&self, start: usize) -> usize {
+        let mut leftmost = start;
+        let mut p = self.next(start);
+        while p != start {
+            let (a, b) = (self.pos(p), self.pos(leftmost));
+            if a.x < b.x || (a.x == b.x && a.y < b.y) {
+                leftmost = p;
+            }
+            p = self.next(p);
+        }
+        leftmost
+    }
+    // Is the diagonal from `a` to `b` inside the polygon, in the neighbourhood of `a`?
+    fn is_locally_inside(&self, a: usize, b: usize) -> bool {
+        let (prev, next) = (self.prev(a), self.next(a));
+        if self.cross(prev, a, next) > 0. {
+            self.cross(a, b, next) <= 0. && self.cross(a, prev, b) <= 0.
+        } else {
+            self.cross(a, b, prev) > 0. || self.cross(a, next, b) > 0.
+        }
+    }
+    // Finds a vertex of the outer polygon which `hole` (its leftmost vertex)
+    // can be connected to without crossing any edge.
+    fn find_bridge(&self, hole: usize, outer: usize) -> Option<usize> {
+        let h = self.pos(hole);
+        // Cast a ray to the left of the hole and find the closest edge it hits.
+        let mut qx = ::std::f32::NEG_INFINITY;
+        let mut m = None;
+        let mut p = outer;
+        loop {
+            let (a, b) = (self.pos(p), self.pos(self.next(p)));
+            if h.y <= a.y && h.y >= b.y && b.y != a.y {
+                let x = a.x + (h.y - a.y) * (b.x - a.x) / (b.y - a.y);
+                if x <= h.x && x > qx {
+                    qx = x;
+                    m = Some(if a.x < b.x { p } else { self.next(p) });
+                    if x == h.x {
+                        return m;
+                    }
+                }
+            }
+            p = self.next(p);
+            if p == outer {
+                break;
+            }
+        }
+        let mut m = m?;
+        // The edge's endpoint may be hidden by other vertices; if so, pick the
+        // one among them which makes the smallest angle with the ray.
+        let stop = m;
+        let pm = self.pos(m);
+        let mut tan_min = ::std::f32::INFINITY;
+        let mut p = m;
+        loop {
+            let pp = self.pos(p);
+            // The triangle between the hole, the hit point and `m`, wound counter-clockwise.
+            let (a, c) = if h.y < pm.y {
+                (h, Vec2::new(qx, h.y))
+            } else {
+                (Vec2::new(qx, h.y), h)
+            };
+            if h.x >= pp.x && pp.x >= pm.x && h.x != pp.x && is_point_in_triangle(pp, a, pm, c) {
+                let tan = (h.y - pp.y).abs() / (h.x - pp.x);
+                let m_pos = self.pos(m);
+                if self.is_locally_inside(p, hole) && (tan < tan_min || (tan == tan_min && pp.x > m_pos.x)) {
+                    m = p;
+                    tan_min = tan;
+                }
+            }
+            p = self.next(p);
+            if p == stop {
+                break;
+            }
+        }
+        Some(m)
+    }
+    // Links `a` to `b` with two coincident edges, going there and back.
+    fn split(&mut self, a: usize, b: usize) {
+        let (ai, bi) = (self.nodes[a].i, self.nodes[b].i);
+        let (an, bp) = (self.next(a), self.prev(b));
+        let a2 = self.nodes.len();
+        let b2 = a2 + 1;
+        self.nodes.push(Node { i: ai, prev: b2, next: an, is_removed: false });
+        self.nodes.push(Node { i: bi, prev: bp, next: a2, is_removed: false });
+        self.nodes[a].next = b;
+        self.nodes[b].prev = a;
+        self.nodes[an].prev = a2;
+        self.nodes[bp].next = b2;
+    }
+    fn eliminate_holes(&mut self, outer: usize, holes: &[Range<usize>]) -> Option<usize> {
+        let mut lefts = holes.iter().map(|hole| {
+            let ring = self.ring(hole.clone(), false);
+            self.leftmost(ring)
+        }).collect::<Vec<_>>();
+        lefts.sort_by(|&a, &b| {
+            let (a, b) = (self.pos(a), self.pos(b));
+            a.x.partial_cmp(&b.x).unwrap_or(Ordering::Equal).then(a.y.partial_cmp(&b.y).unwrap_or(Ordering::Equal))
+        });
+        let mut outer = outer;
+        for hole in lefts {
+            let bridge = self.find_bridge(hole, outer)?;
+            self.split(bridge, hole);
+            outer = bridge;
+        }
+        Some(outer)
+    }
+}
+
+/// Triangulates the fill of the given polylines (which are implicitly
+/// closed, as with the stencil), according to `fill_rule`.
+///
+/// Returns the positions used by the triangles and their indices, three
+/// per counter-clockwise triangle, or `None` if outlines intersect.
+pub fn triangulate(polylines: &[(Vec<Vec2<f32>>, bool)], fill_rule: FillRule) -> Option<(Vec<Vec2<f32>>, Vec<u32>)> {
+    let mut positions = vec![];
+    let mut contours = vec![];
+    for &(ref points, _is_closed) in polylines {
+        let start = positions.len();
+        for &p in points {
+            if positions.len() == start || positions[positions.len() - 1] != p {
+                positions.push(p);
+            }
+        }
+        while positions.len() > start + 1 && positions[start] == positions[positions.len() - 1] {
+            positions.pop();
+        }
+        if positions.len() - start < 3 || is_flat(&positions[start..]) {
+            positions.truncate(start);
+            continue;
+        }
+        contours.push(start .. positions.len());
+    }
+
+    if has_intersections(&positions, &contours) {
+        return None;
+    }
+
+    // Since outlines don't intersect, each one has a single winding number
+    // on each side, which tells whether it bounds a filled area from the
+    // outside, from the inside (it's a hole), or not at all.
+    let is_filled = |winding: i32| match fill_rule {
+        FillRule::EvenOdd => winding % 2 != 0,
+        FillRule::NonZero => winding != 0,
+    };
+    let mut outers = vec![];
+    let mut holes = vec![];
+    for (i, contour) in contours.iter().enumerate() {
+        let p = positions[contour.start];
+        let outside = contours.iter().enumerate()
+            .filter(|&(j, _)| j != i)
+            .map(|(_, other)| winding_number(p, &positions[other.clone()]))
+            .sum::<i32>();
+        let inside = outside + if signed_area(&positions[contour.clone()]) > 0. { 1 } else { -1 };
+        match (is_filled(inside), is_filled(outside)) {
+            (true, false) => outers.push(i),
+            (false, true) => holes.push(i),
+            _ => (),
+        }
+    }
+
+    // A hole belongs to the smallest outer boundary around it.
+    let mut holes_of_outer = vec![vec![]; outers.len()];
+    for &hole in &holes {
+        let p = positions[contours[hole].start];
+        let parent = outers.iter().enumerate()
+            .filter(|&(_, &outer)| winding_number(p, &positions[contours[outer].clone()]) != 0)
+            .map(|(k, &outer)| (k, signed_area(&positions[contours[outer].clone()]).abs()))
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal))?;
+        holes_of_outer[parent.0].push(contours[hole].clone());
+    }
+
+    let mut clipper = EarClipper {
+        positions: &positions,
+        nodes: vec![],
+        triangles: vec![],
+    };
+    for (&outer, holes) in outers.iter().zip(holes_of_outer.iter()) {
+        let ring = clipper.ring(contours[outer].clone(), true);
+        let ring = clipper.eliminate_holes(ring, holes)?;
+        if !clipper.clip_ears(ring) {
+            return None;
+        }
+    }
+    let triangles = clipper.triangles;
+    Some((positions, triangles))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn polygon(points: &[(f32, f32)]) -> (Vec<Vec2<f32>>, bool) {
+        (points.iter().map(|&(x, y)| Vec2::new(x, y)).collect(), true)
+    }
+
+    fn square(min: f32, max: f32, is_ccw: bool) -> (Vec<Vec2<f32>>, bool) {
+        let mut points = vec![(min, min), (max, min), (max, max), (min, max)];
+        if !is_ccw {
+            points.reverse();
+        }
+        polygon(&points)
+    }
+
+    // Checks that triangles are counter-clockwise, and returns their total area.
+    fn area(triangulation: &(Vec<Vec2<f32>>, Vec<u32>)) -> f32 {
+        let (ref positions, ref indices) = *triangulation;
+        assert_eq!(indices.len() % 3, 0);
+        indices.chunks(3).map(|t| {
+            let area = cross(positions[t[0] as usize], positions[t[1] as usize], positions[t[2] as usize]) / 2.;
+            assert!(area >= 0., "{:?}", t);
+            area
+        }).sum()
+    }
+
+    fn covers(triangulation: &(Vec<Vec2<f32>>, Vec<u32>), x: f32, y: f32) -> bool {
+        let (ref positions, ref indices) = *triangulation;
+        indices.chunks(3).any(|t| is_point_in_triangle(Vec2::new(x, y), positions[t[0] as usize], positions[t[1] as usize], positions[t[2] as usize]))
+    }
+
+    #[test]
+    fn convex_polygons_are_covered_exactly() {
+        let hexagon: Vec<_> = (0..6).map(|i| {
+            let (sin, cos) = (i as f32 * ::std::f32::consts::PI / 3.).sin_cos();
+            (cos, sin)
+        }).collect();
+        let polylines = vec![polygon(&hexagon)];
+        let t = triangulate(&polylines, FillRule::EvenOdd).unwrap();
+        assert_eq!(t.1.len(), 4 * 3);
+        assert!((area(&t) - signed_area(&polylines[0].0)).abs() < 1e-5, "{}", area(&t));
+
+        // Clockwise outlines make counter-clockwise triangles too.
+        let t = triangulate(&[square(0., 2., false)], FillRule::NonZero).unwrap();
+        assert_eq!(area(&t), 4.);
+    }
+
+    #[test]
+    fn concave_polygons_leave_their_notches_empty() {
+        // A "U", open at the top.
+        let u = polygon(&[(0., 0.), (3., 0.), (3., 3.), (2., 3.), (2., 1.), (1., 1.), (1., 3.), (0., 3.)]);
+        let t = triangulate(&[u], FillRule::EvenOdd).unwrap();
+        assert_eq!(t.1.len(), 6 * 3);
+        assert_eq!(area(&t), 7.);
+        assert!(covers(&t, 0.5, 2.5) && covers(&t, 1.5, 0.5) && covers(&t, 2.5, 2.5));
+        assert!(!covers(&t, 1.5, 2.));
+    }
+
+    #[test]
+    fn holes_stay_empty() {
+        // Under either rule, a hole wound the other way is a hole.
+        for &rule in &[FillRule::EvenOdd, FillRule::NonZero] {
+            let t = triangulate(&[square(0., 4., true), square(1., 3., false)], rule).unwrap();
+            assert!((area(&t) - 12.).abs() < 1e-5, "{:?} {}", rule, area(&t));
+            assert!(!covers(&t, 2., 2.) && !covers(&t, 1.5, 2.5));
+            assert!(covers(&t, 0.5, 2.) && covers(&t, 3.5, 3.5));
+        }
+
+        // Wound the same way, it is a hole only under the even-odd rule.
+        let donut = vec![square(0., 4., true), square(1., 3., true)];
+        let t = triangulate(&donut, FillRule::EvenOdd).unwrap();
+        assert!((area(&t) - 12.).abs() < 1e-5);
+        assert!(!covers(&t, 2., 2.));
+        let t = triangulate(&donut, FillRule::NonZero).unwrap();
+        assert!((area(&t) - 16.).abs() < 1e-5);
+        assert!(covers(&t, 2., 2.));
+
+        // An island in the hole is filled again, and several holes can share an outline.
+        let t = triangulate(&[square(0., 6., true), square(1., 5., false), square(2., 4., true)], FillRule::NonZero).unwrap();
+        assert!((area(&t) - (36. - 16. + 4.)).abs() < 1e-4, "{}", area(&t));
+        let holes = [
+            square(0., 6., true),
+            square(1., 2., false),
+            square(4., 5., false),
+            polygon(&[(4., 1.), (4., 2.), (5., 2.), (5., 1.)]),
+        ];
+        let t = triangulate(&holes, FillRule::EvenOdd).unwrap();
+        assert!((area(&t) - 33.).abs() < 1e-4, "{}", area(&t));
+    }
+
+    #[test]
+    fn intersecting_outlines_are_left_to_the_stencil() {
+        // Its area cancels out, but it isn't empty.
+        let bowtie = polygon(&[(0., 0.), (2., 2.), (2., 0.), (0., 2.)]);
+        assert_eq!(triangulate(&[bowtie], FillRule::NonZero), None);
+        assert_eq!(triangulate(&[square(0., 2., true), square(1., 3., true)], FillRule::EvenOdd), None);
+        // Touching counts as intersecting.
+        assert_eq!(triangulate(&[square(0., 2., true), square(2., 3., true)], FillRule::NonZero), None);
+    }
+
+    #[test]
+    fn collinear_and_duplicate_points_are_fine() {
+        let square = polygon(&[(0., 0.), (1., 0.), (1., 0.), (2., 0.), (2., 1.), (2., 2.), (1., 2.), (0., 2.), (0., 1.), (0., 0.)]);
+        let t = triangulate(&[square], FillRule::EvenOdd).unwrap();
+        assert!((area(&t) - 4.).abs() < 1e-6);
+        assert!(covers(&t, 1., 1.));
+        // Outlines without area draw nothing.
+        let line = polygon(&[(0., 0.), (1., 1.), (2., 2.)]);
+        assert_eq!(triangulate(&[line, polygon(&[(0., 0.), (1., 1.)])], FillRule::EvenOdd).map(|t| t.1), Some(vec![]));
+    }
+}
@@ -0,0 +1,822 @@
+// The `.shape` file format.
+//
+// One command per line, made of a keyword followed by its arguments.
+// Blank lines are ignored.
+//
+// Style keys (each one may appear at most once, except the `*_gradient_stop` ones):
+//
+// stroke_thickness t
+// stroke_color r g b a
+// stroke_gradient_stop offset r g b a (repeated, by increasing offset along the subpath)
+// stroke_join miter|round|bevel
+// stroke_cap butt|round|square
+// stroke_miter_limit x
+// stroke_dashes length... (alternating dashes and gaps; omitted when solid)
+// stroke_dash_offset x
+// stroke_taper_start x
+// stroke_taper_end x
+// fill_color r g b a
+// fill_gradient_kind linear|radial|conic
+// fill_gradient_spread pad|repeat|reflect
+// fill_gradient_start_position x y
+// fill_gradient_end_position x y
+// fill_gradient_stop offset r g b a (repeated, by increasing offset)
+// fill_gradient_start_color r g b a (older files; a stop at offset 0, before the others)
+// fill_gradient_end_color r g b a (older files; a stop at offset 1, after the others)
+// fill_rule evenodd|nonzero
+//
+// Path commands (SVG-like, always absolute):
+//
+// M x y = moveto; begins a new subpath
+// L x y = lineto
+// Q cx cy x y = quadratic Bézier curve
+// C c0x c0y c1x c1y x y = cubic Bézier curve
+// Z = closepath (`z` is accepted too)
+//
+// `M`, `L`, `Q` and `C` may be followed by overrides of the style at their
+// end point: `stroke_color r g b a` and/or `stroke_thickness t`, e.g
+// `L 1 0 stroke_thickness 6`.
+//
+// As in SVG, a segment following a `Z` begins a new subpath at the start
+// point of the one that was just closed.
+//
+// Primitives (each one is a whole closed subpath, see `Primitive`):
+//
+// rect min_x min_y max_x max_y
+// rounded_rect min_x min_y max_x max_y radius
+// ellipse center_x center_y radius_x radius_y
+// regular_polygon center_x center_y radius sides rotation_radians
+// star center_x center_y outer_radius inner_radius points rotation_radians
+//
+// Subpaths generated by a primitive are written as such, unless their nodes
+// were edited since.
+//
+// A file which gives no stops (nor `fill_gradient_kind`) keeps the default
+// gradient; otherwise, the gradient only has the stops it gives, possibly none.
+
+use std::io;
+use std::fmt::{self, Display, Formatter};
+use std::error::Error;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use v::{Vec2, Rgba};
+use super::{Path, SubPath, PathCmd, NodeStyle, Style, FillRule, StrokeJoin, StrokeCap, Primitive};
+use super::{GradientKind, GradientSpread, GradientStop};
+
+const STYLE_KEYS: &[&str] = &[
+    "stroke_thickness",
+    "stroke_color",
+    "stroke_join",
+    "stroke_cap",
+    "stroke_miter_limit",
+    "stroke_dashes",
+    "stroke_dash_offset",
+    "stroke_taper_start",
+    "stroke_taper_end",
+    "fill_color",
+    "fill_gradient_kind",
+    "fill_gradient_spread",
+    "fill_gradient_start_color",
+    "fill_gradient_end_color",
+    "fill_gradient_start_position",
+    "fill_gradient_end_position",
+    "fill_rule",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseErrorKind {
+    /// A token was missing or couldn't be parsed as what was expected.
+    Expected { expected: &'static str, found: String },
+    /// The line starts with a keyword that isn't part of the format.
+    UnknownKeyword(String),
+    /// A segment command (`L`, `Q`, `C`) or `Z` appeared before any `M`.
+    SegmentBeforeStart(String),
+    /// The same style key was given twice.
+    DuplicateStyleKey { key: String, first_line: usize },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    /// The file this error comes from, if known. See `in_file()`.
+    pub file: Option<PathBuf>,
+    /// 1-based line number.
+    pub line: usize,
+    /// 1-based column number, in characters.
+    pub column: usize,
+    pub kind: ParseErrorKind,
+}
+
+impl ParseError {
+    pub fn in_file<P: Into<PathBuf>>(mut self, file: P) -> Self {
+        self.file = Some(file.into());
+        self
+    }
+}
+
+impl Display for ParseErrorKind {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            ParseErrorKind::Expected { expected, ref found } => write!(f, "expected {}, found {}", expected, found),
+            ParseErrorKind::UnknownKeyword(ref k) => write!(f, "unknown keyword `{}`", k),
+            ParseErrorKind::SegmentBeforeStart(ref k) => write!(f, "`{}` appears before any `M` command", k),
+            ParseErrorKind::DuplicateStyleKey { ref key, first_line } => write!(f, "duplicate style key `{}` (first given at line {})", key, first_line),
+        }
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self.file {
+            Some(ref file) => write!(f, "{}:", file.display())?,
+            None => write!(f, "<shape>:")?,
+        };
+        write!(f, "{}:{}: {}", self.line, self.column, self.kind)
+    }
+}
+
+impl Error for ParseError {
+    fn description(&self) -> &str {
+        "malformed shape file"
+    }
+}
+
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Token<'a> {
+    column: usize,
+    text: &'a str,
+}
+
+// A single line, split into whitespace-separated tokens.
+#[derive(Debug)]
+struct Line<'a> {
+    number: usize,
+    end_column: usize,
+    tokens: Vec<Token<'a>>,
+    next: usize,
+}
+
+impl<'a> Line<'a> {
+    fn new(number: usize, s: &'a str) -> Self {
+        let mut tokens = vec![];
+        let mut start = None;
+        let mut column = 0;
+        for (i, (byte, c)) in s.char_indices().enumerate() {
+            column = i + 1;
+            match (c.is_whitespace(), start) {
+                (false, None) => start = Some((byte, column)),
+                (true, Some((b, col))) => {
+                    tokens.push(Token { column: col, text: &s[b..byte] });
+                    start = None;
+                },
+                _ => (),
+            };
+        }
+        if let Some((b, col)) = start {
+            tokens.push(Token { column: col, text: &s[b..] });
+        }
+        Self { number, end_column: column + 1, tokens, next: 0 }
+    }
+    fn error(&self, column: usize, kind: ParseErrorKind) -> ParseError {
+        ParseError { file: None, line: self.number, column, kind }
+    }
+    fn keyword(&mut self) -> Option<Token<'a>> {
+        let t = self.tokens.get(self.next).cloned();
+        self.next += 1;
+        t
+    }
+    fn f32(&mut self) -> Result<f32, ParseError> {
+        match self.tokens.get(self.next).cloned() {
+            None => Err(self.error(self.end_column, ParseErrorKind::Expected {
+                expected: "a number", found: "end of line".to_owned(),
+            })),
+            Some(t) => match t.text.parse() {
+                Ok(x) => { self.next += 1; Ok(x) },
+                Err(_) => Err(self.error(t.column, ParseErrorKind::Expected {
+                    expected: "a number", found: format!("`{}`", t.text),
+                })),
+            },
+        }
+    }
+    // A whole number, at least `min`.
+    fn count(&mut self, min: u32, expected: &'static str) -> Result<u32, ParseError> {
+        match self.tokens.get(self.next).cloned() {
+            None => Err(self.error(self.end_column, ParseErrorKind::Expected {
+                expected, found: "end of line".to_owned(),
+            })),
+            Some(t) => match t.text.parse() {
+                Ok(x) if x >= min => { self.next += 1; Ok(x) },
+                _ => Err(self.error(t.column, ParseErrorKind::Expected {
+                    expected, found: format!("`{}`", t.text),
+                })),
+            },
+        }
+    }
+    fn vec2(&mut self) -> Result<Vec2<f32>, ParseError> {
+        let x = self.f32()?;
+        let y = self.f32()?;
+        Ok(Vec2 { x, y })
+    }
+    fn rgba(&mut self) -> Result<Rgba<f32>, ParseError> {
+        let r = self.f32()?;
+        let g = self.f32()?;
+        let b = self.f32()?;
+        let a = self.f32()?;
+        Ok(Rgba { r, g, b, a })
+    }
+    // The remaining numbers on this line, if any.
+    // Dash and gap lengths, which can't all be zero.
+    fn dash_lengths(&mut self) -> Result<Vec<f32>, ParseError> {
+        let first = self.next;
+        let mut v = vec![];
+        while let Some(t) = self.tokens.get(self.next).cloned() {
+            match t.text.parse::<f32>() {
+                Ok(x) if x >= 0. && x.is_finite() => { self.next += 1; v.push(x) },
+                _ => return Err(self.error(t.column, ParseErrorKind::Expected {
+                    expected: "a length", found: format!("`{}`", t.text),
+                })),
+            }
+        }
+        if !v.is_empty() && v.iter().sum::<f32>() <= 0. {
+            let texts = self.tokens[first..].iter().map(|t| t.text).collect::<Vec<_>>();
+            return Err(self.error(self.tokens[first].column, ParseErrorKind::Expected {
+                expected: "a dash pattern which isn't all zeros", found: format!("`{}`", texts.join(" ")),
+            }));
+        }
+        Ok(v)
+    }
+    fn fill_rule(&mut self) -> Result<FillRule, ParseError> {
+        self.name("`evenodd` or `nonzero`", FillRule::from_name)
+    }
+    fn stroke_join(&mut self) -> Result<StrokeJoin, ParseError> {
+        self.name("`miter`, `round` or `bevel`", StrokeJoin::from_name)
+    }
+    fn stroke_cap(&mut self) -> Result<StrokeCap, ParseError> {
+        self.name("`butt`, `round` or `square`", StrokeCap::from_name)
+    }
+    fn gradient_kind(&mut self) -> Result<GradientKind, ParseError> {
+        self.name("`linear`, `radial` or `conic`", GradientKind::from_name)
+    }
+    fn gradient_spread(&mut self) -> Result<GradientSpread, ParseError> {
+        self.name("`pad`, `repeat` or `reflect`", GradientSpread::from_name)
+    }
+    fn name<T>(&mut self, expected: &'static str, from_name: fn(&str) -> Option<T>) -> Result<T, ParseError> {
+        match self.tokens.get(self.next).cloned() {
+            None => Err(self.error(self.end_column, ParseErrorKind::Expected {
+                expected, found: "end of line".to_owned(),
+            })),
+            Some(t) => match from_name(t.text) {
+                Some(x) => { self.next += 1; Ok(x) },
+                None => Err(self.error(t.column, ParseErrorKind::Expected {
+                    expected, found: format!("`{}`", t.text),
+                })),
+            },
+        }
+    }
+    // The style overrides which may follow a path command.
+    fn node_style(&mut self) -> Result<NodeStyle, ParseError> {
+        let mut node = NodeStyle::default();
+        loop {
+            match self.tokens.get(self.next).map(|t| t.text) {
+                Some("stroke_color") if node.stroke_color.is_none() => {
+                    self.next += 1;
+                    node.stroke_color = Some(self.rgba()?);
+                },
+                Some("stroke_thickness") if node.stroke_thickness.is_none() => {
+                    self.next += 1;
+                    node.stroke_thickness = Some(self.f32()?);
+                },
+                _ => return Ok(node),
+            }
+        }
+    }
+    fn end(&self) -> Result<(), ParseError> {
+        match self.tokens.get(self.next) {
+            None => Ok(()),
+            Some(t) => Err(self.error(t.column, ParseErrorKind::Expected {
+                expected: "end of line", found: format!("`{}`", t.text),
+            })),
+        }
+    }
+}
+
+pub fn parse(src: &str) -> Result<(Path, Style), ParseError> {
+    let mut path = Path::default();
+    let mut style = Style::default();
+    let mut style_keys_seen = HashMap::<&str, usize>::new();
+    // `None` until the file says anything about gradient stops.
+    let mut stops: Option<Vec<GradientStop>> = None;
+    let (mut legacy_start_color, mut legacy_end_color) = (None, None);
+
+    for (i, s) in src.lines().enumerate() {
+        let mut line = Line::new(i + 1, s);
+        let keyword = match line.keyword() {
+            Some(k) => k,
+            None => continue,
+        };
+        match keyword.text {
+            "M" | "L" | "Q" | "C" | "Z" | "z" => {
+                if keyword.text != "M" && path.is_empty() {
+                    return Err(line.error(keyword.column, ParseErrorKind::SegmentBeforeStart(keyword.text.to_owned())));
+                }
+            },
+            key if STYLE_KEYS.contains(&key) => {
+                if let Some(&first_line) = style_keys_seen.get(key) {
+                    return Err(line.error(keyword.column, ParseErrorKind::DuplicateStyleKey {
+                        key: key.to_owned(), first_line,
+                    }));
+                }
+                style_keys_seen.insert(key, line.number);
+            },
+            _ => (),
+        };
+        match keyword.text {
+            "M" => {
+                let p = line.vec2()?;
+                path.subpaths.push(SubPath::new(p));
+            },
+            "L" => {
+                let end = line.vec2()?;
+                path.open_subpath().cmds.push(PathCmd::Line { end });
+            },
+            "Q" => {
+                let ctrl = line.vec2()?;
+                let end = line.vec2()?;
+                path.open_subpath().cmds.push(PathCmd::Quadratic { ctrl, end });
+            },
+            "C" => {
+                let ctrl0 = line.vec2()?;
+                let ctrl1 = line.vec2()?;
+                let end = line.vec2()?;
+                path.open_subpath().cmds.push(PathCmd::Cubic { ctrl0, ctrl1, end });
+            },
+            "Z" | "z" => path.current_subpath_mut().unwrap().is_closed = true,
+            "rect" => {
+                let min = line.vec2()?;
+                let max = line.vec2()?;
+                path.subpaths.push(Primitive::Rect { min, max }.to_subpath());
+            },
+            "rounded_rect" => {
+                let min = line.vec2()?;
+                let max = line.vec2()?;
+                let radius = line.f32()?;
+                path.subpaths.push(Primitive::RoundedRect { min, max, radius }.to_subpath());
+            },
+            "ellipse" => {
+                let center = line.vec2()?;
+                let radii = line.vec2()?;
+                path.subpaths.push(Primitive::Ellipse { center, radii }.to_subpath());
+            },
+            "regular_polygon" => {
+                let center = line.vec2()?;
+                let radius = line.f32()?;
+                let sides = line.count(3, "a number of sides, at least 3")?;
+                let rotation_radians = line.f32()?;
+                path.subpaths.push(Primitive::RegularPolygon { center, radius, sides, rotation_radians }.to_subpath());
+            },
+            "star" => {
+                let center = line.vec2()?;
+                let outer_radius = line.f32()?;
+                let inner_radius = line.f32()?;
+                let points = line.count(2, "a number of points, at least 2")?;
+                let rotation_radians = line.f32()?;
+                path.subpaths.push(Primitive::Star { center, outer_radius, inner_radius, points, rotation_radians }.to_subpath());
+            },
+            "stroke_thickness" => style.stroke_thickness = line.f32()?,
+            "stroke_color" => style.stroke_color = line.rgba()?,
+            "stroke_gradient_stop" => {
+                let offset = line.f32()?;
+                let color = line.rgba()?;
+                style.stroke_gradient.push(GradientStop { offset, color });
+            },
+            "stroke_join" => style.stroke_join = line.stroke_join()?,
+            "stroke_cap" => style.stroke_cap = line.stroke_cap()?,
+            "stroke_miter_limit" => style.stroke_miter_limit = line.f32()?,
+            "stroke_dashes" => style.stroke_dashes = line.dash_lengths()?,
+            "stroke_dash_offset" => style.stroke_dash_offset = line.f32()?,
+            "stroke_taper_start" => style.stroke_taper_start = line.f32()?,
+            "stroke_taper_end" => style.stroke_taper_end = line.f32()?,
+            "fill_color" => style.fill_color = line.rgba()?,
+            "fill_gradient_kind" => {
+                style.fill_gradient.kind = line.gradient_kind()?;
+                stops.get_or_insert_with(Vec::new);
+            },
+            "fill_gradient_spread" => style.fill_gradient.spread = line.gradient_spread()?,
+            "fill_gradient_start_color" => {
+                legacy_start_color = Some(line.rgba()?);
+                stops.get_or_insert_with(Vec::new);
+            },
+            "fill_gradient_end_color" => {
+                legacy_end_color = Some(line.rgba()?);
+                stops.get_or_insert_with(Vec::new);
+            },
+            "fill_gradient_stop" => {
+                let offset = line.f32()?;
+                let color = line.rgba()?;
+                stops.get_or_insert_with(Vec::new).push(GradientStop { offset, color });
+            },
+            "fill_gradient_start_position" => style.fill_gradient.start = line.vec2()?,
+            "fill_gradient_end_position" => style.fill_gradient.end = line.vec2()?,
+            "fill_rule" => style.fill_rule = line.fill_rule()?,
+            whoops @ _ => return Err(line.error(keyword.column, ParseErrorKind::UnknownKeyword(whoops.to_owned()))),
+        };
+        match keyword.text {
+            "M" | "L" | "Q" | "C" => {
+                let node = line.node_style()?;
+                if !node.is_empty() {
+                    let subpath = path.current_subpath_mut().unwrap();
+                    let i = subpath.cmds.len() - 1;
+                    *subpath.node_style_mut(i) = node;
+                }
+            },
+            _ => (),
+        };
+        line.end()?;
+    }
+
+    if let Some(mut stops) = stops {
+        if let Some(color) = legacy_start_color {
+            stops.insert(0, GradientStop { offset: 0., color });
+        }
+        if let Some(color) = legacy_end_color {
+            stops.push(GradientStop { offset: 1., color });
+        }
+        style.fill_gradient.stops = stops;
+    }
+
+    Ok((path, style))
+}
+
+pub fn write(f: &mut io::Write, path: &Path, style: &Style) -> io::Result<()> {
+    let &Style {
+        stroke_thickness, stroke_color, ref stroke_gradient, stroke_join, stroke_cap, stroke_miter_limit,
+        ref stroke_dashes, stroke_dash_offset, stroke_taper_start, stroke_taper_end,
+        fill_color, ref fill_gradient, fill_rule,
+    } = style;
+    writeln!(f, "stroke_thickness {}", stroke_thickness)?;
+    writeln!(f, "stroke_color {} {} {} {}", stroke_color.r, stroke_color.g, stroke_color.b, stroke_color.a)?;
+    for stop in stroke_gradient.iter() {
+        let Rgba { r, g, b, a } = stop.color;
+        writeln!(f, "stroke_gradient_stop {} {} {} {} {}", stop.offset, r, g, b, a)?;
+    }
+    writeln!(f, "stroke_join {}", stroke_join.name())?;
+    writeln!(f, "stroke_cap {}", stroke_cap.name())?;
+    writeln!(f, "stroke_miter_limit {}", stroke_miter_limit)?;
+    if !stroke_dashes.is_empty() {
+        let dashes = stroke_dashes.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+        writeln!(f, "stroke_dashes {}", dashes.join(" "))?;
+    }
+    writeln!(f, "stroke_dash_offset {}", stroke_dash_offset)?;
+    writeln!(f, "stroke_taper_start {}", stroke_taper_start)?;
+    writeln!(f, "stroke_taper_end {}", stroke_taper_end)?;
+    writeln!(f, "fill_color {} {} {} {}", fill_color.r, fill_color.g, fill_color.b, fill_color.a)?;
+    writeln!(f, "fill_gradient_kind {}", fill_gradient.kind.name())?;
+    writeln!(f, "fill_gradient_spread {}", fill_gradient.spread.name())?;
+    writeln!(f, "fill_gradient_start_position {} {}", fill_gradient.start.x, fill_gradient.start.y)?;
+    writeln!(f, "fill_gradient_end_position {} {}", fill_gradient.end.x, fill_gradient.end.y)?;
+    for stop in fill_gradient.stops.iter() {
+        let Rgba { r, g, b, a } = stop.color;
+        writeln!(f, "fill_gradient_stop {} {} {} {} {}", stop.offset, r, g, b, a)?;
+    }
+    writeln!(f, "fill_rule {}", fill_rule.name())?;
+    for subpath in path.subpaths.iter() {
+        if let Some(primitive) = subpath.primitive {
+            if primitive.to_subpath() == *subpath {
+                write_primitive(f, &primitive)?;
+                continue;
+            }
+        }
+        for (i, cmd) in subpath.cmds.iter().enumerate() {
+            match *cmd {
+                PathCmd::Start(p) => write!(f, "M {} {}", p.x, p.y)?,
+                PathCmd::Line { end } => write!(f, "L {} {}", end.x, end.y)?,
+                PathCmd::Quadratic { ctrl, end } => write!(f, "Q {} {} {} {}", ctrl.x, ctrl.y, end.x, end.y)?,
+                PathCmd::Cubic { ctrl0, ctrl1, end } => write!(f, "C {} {} {} {} {} {}", ctrl0.x, ctrl0.y, ctrl1.x, ctrl1.y, end.x, end.y)?,
+            };
+            let node = subpath.node_style(i);
+            if let Some(Rgba { r, g, b, a }) = node.stroke_color {
+                write!(f, " stroke_color {} {} {} {}", r, g, b, a)?;
+            }
+            if let Some(t) = node.stroke_thickness {
+                write!(f, " stroke_thickness {}", t)?;
+            }
+            writeln!(f, "")?;
+        }
+        if subpath.is_closed {
+            writeln!(f, "Z")?;
+        }
+    }
+    Ok(())
+}
+
+fn write_primitive(f: &mut io::Write, primitive: &Primitive) -> io::Result<()> {
+    write!(f, "{}", primitive.name())?;
+    match *primitive {
+        Primitive::Rect { min, max } => writeln!(f, " {} {} {} {}", min.x, min.y, max.x, max.y),
+        Primitive::RoundedRect { min, max, radius } => writeln!(f, " {} {} {} {} {}", min.x, min.y, max.x, max.y, radius),
+        Primitive::Ellipse { center, radii } => writeln!(f, " {} {} {} {}", center.x, center.y, radii.x, radii.y),
+        Primitive::RegularPolygon { center, radius, sides, rotation_radians } => {
+            writeln!(f, " {} {} {} {} {}", center.x, center.y, radius, sides, rotation_radians)
+        },
+        Primitive::Star { center, outer_radius, inner_radius, points, rotation_radians } => {
+            writeln!(f, " {} {} {} {} {} {}", center.x, center.y, outer_radius, inner_radius, points, rotation_radians)
+        },
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shape::Gradient;
+
+    fn round_trip(path: &Path, style: &Style) -> (Path, Style) {
+        let mut buf = vec![];
+        write(&mut buf, path, style).unwrap();
+        parse(&String::from_utf8(buf).unwrap()).unwrap()
+    }
+
+    fn error_of(src: &str) -> ParseError {
+        parse(src).unwrap_err()
+    }
+
+    #[test]
+    fn empty_file_gives_defaults() {
+        assert_eq!(parse("").unwrap(), (Path::default(), Style::default()));
+        assert_eq!(parse("\n   \n\t\n").unwrap(), (Path::default(), Style::default()));
+    }
+
+    #[test]
+    fn round_trip_default() {
+        let (path, style) = (Path::default(), Style::default());
+        assert_eq!(round_trip(&path, &style), (path, style));
+    }
+
+    #[test]
+    fn round_trip_all_commands() {
+        let path = Path {
+            subpaths: vec![SubPath {
+                is_closed: true,
+                cmds: vec![
+                    PathCmd::Start(Vec2::new(0.1, -0.2)),
+                    PathCmd::Line { end: Vec2::new(1., 2.) },
+                    PathCmd::Quadratic { ctrl: Vec2::new(3.5, 4.25), end: Vec2::new(-5., 6.) },
+                    PathCmd::Cubic { ctrl0: Vec2::new(0.3, 0.7), ctrl1: Vec2::new(1e-7, 12345.678), end: Vec2::new(-0.0001, 0.) },
+                ],
+                node_styles: vec![],
+                primitive: None,
+            }],
+        };
+        let mut style = Style::default();
+        style.stroke_thickness = 4.8999996;
+        style.stroke_color = Rgba::new(0.085, 0.6205, 0.84999996, 1.);
+        style.stroke_gradient = vec![
+            GradientStop { offset: 0., color: Rgba::new(0.2, 0.1, 0., 1.) },
+            GradientStop { offset: 0.75, color: Rgba::new(0., 0., 0., 0.5) },
+        ];
+        style.stroke_join = StrokeJoin::Round;
+        style.stroke_cap = StrokeCap::Square;
+        style.stroke_miter_limit = 10.5;
+        style.stroke_dashes = vec![4., 2.5, 0.];
+        style.stroke_dash_offset = -1.25;
+        style.stroke_taper_start = 12.;
+        style.stroke_taper_end = 0.5;
+        style.fill_color = Rgba::new(1., 0.8, 0.3, 0.);
+        style.fill_gradient.kind = GradientKind::Radial;
+        style.fill_gradient.spread = GradientSpread::Reflect;
+        style.fill_gradient.start = Vec2::new(-0.79433376, 1.2050099);
+        style.fill_gradient.end = Vec2::new(-0.8699848, -1.4427698);
+        style.fill_gradient.stops = vec![
+            GradientStop { offset: 0., color: Rgba::new(0.1, 0.2, 0.3, 0.4) },
+            GradientStop { offset: 0.35, color: Rgba::new(1., 0.5, 0., 1.) },
+            GradientStop { offset: 0.35, color: Rgba::new(0., 0., 0., 0.) },
+            GradientStop { offset: 1., color: Rgba::new(0.5, 0.6, 0.7, 0.8) },
+        ];
+        style.fill_rule = FillRule::NonZero;
+        assert_eq!(round_trip(&path, &style), (path, style));
+    }
+
+    #[test]
+    fn round_trip_open_path() {
+        let path = Path {
+            subpaths: vec![SubPath {
+                is_closed: false,
+                cmds: vec![
+                    PathCmd::Start(Vec2::new(1., 1.)),
+                    PathCmd::Line { end: Vec2::new(2., 2.) },
+                ],
+                node_styles: vec![],
+                primitive: None,
+            }],
+        };
+        let style = Style::default();
+        assert_eq!(round_trip(&path, &style), (path, style));
+    }
+
+    #[test]
+    fn round_trip_subpaths() {
+        let square = |size: f32| {
+            let mut s = SubPath::new(Vec2::new(-size, -size));
+            s.cmds.push(PathCmd::Line { end: Vec2::new( size, -size) });
+            s.cmds.push(PathCmd::Line { end: Vec2::new( size,  size) });
+            s.cmds.push(PathCmd::Line { end: Vec2::new(-size,  size) });
+            s.is_closed = true;
+            s
+        };
+        let path = Path {
+            subpaths: vec![square(2.), square(1.), SubPath::new(Vec2::new(5., 5.))],
+        };
+        let style = Style::default();
+        assert_eq!(round_trip(&path, &style), (path, style));
+    }
+
+    #[test]
+    fn round_trip_node_styles() {
+        let mut s = SubPath::new(Vec2::new(0., 0.));
+        s.cmds.push(PathCmd::Line { end: Vec2::new(1., 0.) });
+        s.cmds.push(PathCmd::Quadratic { ctrl: Vec2::new(2., 0.), end: Vec2::new(2., 1.) });
+        s.cmds.push(PathCmd::Line { end: Vec2::new(0., 1.) });
+        s.node_style_mut(0).stroke_thickness = Some(0.5);
+        s.node_style_mut(2).stroke_color = Some(Rgba::new(1., 0.25, 0., 0.75));
+        s.node_style_mut(2).stroke_thickness = Some(8.);
+        s.is_closed = true;
+        let path = Path { subpaths: vec![s] };
+        let style = Style::default();
+        assert_eq!(round_trip(&path, &style), (path, style));
+    }
+
+    #[test]
+    fn node_styles_follow_their_command() {
+        let (path, _) = parse("M 0 0 stroke_thickness 3\nL 1 0\nL 1 1 stroke_color 1 0 0 1\n").unwrap();
+        let s = &path.subpaths[0];
+        assert_eq!(s.node_style(0), NodeStyle { stroke_color: None, stroke_thickness: Some(3.) });
+        assert!(s.node_style(1).is_empty());
+        assert_eq!(s.node_style(2), NodeStyle { stroke_color: Some(Rgba::new(1., 0., 0., 1.)), stroke_thickness: None });
+
+        let e = error_of("M 0 0 stroke_thickness 3 stroke_thickness 4\n");
+        assert_eq!((e.line, e.column), (1, 26));
+        assert_eq!(e.kind, ParseErrorKind::Expected { expected: "end of line", found: "`stroke_thickness`".to_owned() });
+    }
+
+    #[test]
+    fn round_trip_no_gradient() {
+        let mut style = Style::default();
+        style.fill_gradient = Gradient::none();
+        assert_eq!(round_trip(&Path::default(), &style), (Path::default(), style));
+    }
+
+    #[test]
+    fn legacy_gradient_colors_become_stops() {
+        let (_, style) = parse("fill_gradient_end_color 0 0 1 1\nfill_gradient_start_color 1 0 0 1\n").unwrap();
+        assert_eq!(style.fill_gradient.stops, vec![
+            GradientStop { offset: 0., color: Rgba::new(1., 0., 0., 1.) },
+            GradientStop { offset: 1., color: Rgba::new(0., 0., 1., 1.) },
+        ]);
+    }
+
+    #[test]
+    fn segment_after_close_starts_new_subpath() {
+        let (path, _) = parse("M 1 2\nL 3 4\nZ\nL 5 6\nZ\n").unwrap();
+        assert_eq!(path.subpaths.len(), 2);
+        assert_eq!(path.subpaths[1].cmds, vec![
+            PathCmd::Start(Vec2::new(1., 2.)),
+            PathCmd::Line { end: Vec2::new(5., 6.) },
+        ]);
+        assert!(path.subpaths[1].is_closed);
+    }
+
+    #[test]
+    fn bad_stroke_join() {
+        let e = error_of("stroke_join square\n");
+        assert_eq!((e.line, e.column), (1, 13));
+        assert_eq!(e.kind, ParseErrorKind::Expected { expected: "`miter`, `round` or `bevel`", found: "`square`".to_owned() });
+    }
+
+    #[test]
+    fn bad_dash_patterns() {
+        let e = error_of("stroke_dashes 2 -1\n");
+        assert_eq!((e.line, e.column), (1, 17));
+        assert_eq!(e.kind, ParseErrorKind::Expected { expected: "a length", found: "`-1`".to_owned() });
+        let e = error_of("stroke_dashes 2 inf\n");
+        assert_eq!((e.line, e.column), (1, 17));
+        let e = error_of("stroke_dashes 0 0.\n");
+        assert_eq!((e.line, e.column), (1, 15));
+        assert_eq!(e.kind, ParseErrorKind::Expected { expected: "a dash pattern which isn't all zeros", found: "`0 0.`".to_owned() });
+        assert_eq!(parse("stroke_dashes 0 2\n").unwrap().1.stroke_dashes, vec![0., 2.]);
+    }
+
+    #[test]
+    fn bad_fill_rule() {
+        let e = error_of("fill_rule odd\n");
+        assert_eq!((e.line, e.column), (1, 11));
+        assert_eq!(e.kind, ParseErrorKind::Expected { expected: "`evenodd` or `nonzero`", found: "`odd`".to_owned() });
+    }
+
+    #[test]
+    fn bundled_shapes_round_trip() {
+        let files = [
+            include_str!("../../res/shapes/default.shape"),
+            include_str!("../../res/shapes/dune.shape"),
+            include_str!("../../res/shapes/mountain.shape"),
+            include_str!("../../res/shapes/pilier.shape"),
+            include_str!("../../res/shapes/sky.shape"),
+        ];
+        for src in files.iter() {
+            let (path, style) = parse(src).unwrap();
+            assert_eq!(round_trip(&path, &style), (path, style));
+        }
+    }
+
+    #[test]
+    fn truncated_number() {
+        let e = error_of("M 0 0\nL 1\n");
+        assert_eq!(e.line, 2);
+        assert_eq!(e.column, 4);
+        assert_eq!(e.kind, ParseErrorKind::Expected { expected: "a number", found: "end of line".to_owned() });
+
+        let e = error_of("stroke_color 1 0.5 0.\n");
+        assert_eq!((e.line, e.column), (1, 22));
+    }
+
+    #[test]
+    fn malformed_number() {
+        let e = error_of("M 0 0\nL 1 2.x5\n");
+        assert_eq!((e.line, e.column), (2, 5));
+        assert_eq!(e.kind, ParseErrorKind::Expected { expected: "a number", found: "`2.x5`".to_owned() });
+    }
+
+    #[test]
+    fn trailing_tokens() {
+        let e = error_of("M 0 0 L 1 2\n");
+        assert_eq!((e.line, e.column), (1, 7));
+        assert_eq!(e.kind, ParseErrorKind::Expected { expected: "end of line", found: "`L`".to_owned() });
+    }
+
+    #[test]
+    fn round_trip_primitives() {
+        let path = Path {
+            subpaths: vec![
+                Primitive::Rect { min: Vec2::new(-1., -2.), max: Vec2::new(3., 4.5) }.to_subpath(),
+                Primitive::RoundedRect { min: Vec2::new(0., 0.), max: Vec2::new(2., 1.), radius: 0.25 }.to_subpath(),
+                Primitive::Ellipse { center: Vec2::new(0.5, -0.5), radii: Vec2::new(2., 0.75) }.to_subpath(),
+                Primitive::RegularPolygon { center: Vec2::zero(), radius: 1.5, sides: 6, rotation_radians: 0.3 }.to_subpath(),
+                Primitive::Star { center: Vec2::new(1., 1.), outer_radius: 1., inner_radius: 0.4, points: 5, rotation_radians: 1.5707964 }.to_subpath(),
+            ],
+        };
+        let style = Style::default();
+        let mut buf = vec![];
+        write(&mut buf, &path, &style).unwrap();
+        let src = String::from_utf8(buf).unwrap();
+        assert!(src.contains("\nregular_polygon 0 0 1.5 6 0.3\n"), "{}", src);
+        assert!(!src.contains("\nM "), "{}", src);
+        assert_eq!(round_trip(&path, &style), (path, style));
+    }
+
+    #[test]
+    fn edited_primitives_are_written_as_nodes() {
+        let mut path = Path::rect(Vec2::zero(), Vec2::one());
+        path.subpaths[0].cmds[2] = PathCmd::Line { end: Vec2::new(2., 1.) };
+        let style = Style::default();
+        let (loaded, _) = round_trip(&path, &style);
+        assert_eq!(loaded.subpaths[0].cmds, path.subpaths[0].cmds);
+        assert_eq!(loaded.subpaths[0].primitive, None);
+    }
+
+    #[test]
+    fn primitive_counts_are_whole_numbers() {
+        let e = error_of("star 0 0 1 0.5 2.5 0\n");
+        assert_eq!((e.line, e.column), (1, 16));
+        let e = error_of("regular_polygon 0 0 1 2 0\n");
+        assert_eq!((e.line, e.column), (1, 23));
+        assert_eq!(e.kind, ParseErrorKind::Expected { expected: "a number of sides, at least 3", found: "`2`".to_owned() });
+    }
+
+    #[test]
+    fn unknown_keyword() {
+        let e = error_of("stroke_thickness 2\n  strok_color 1 1 1 1\n");
+        assert_eq!((e.line, e.column), (2, 3));
+        assert_eq!(e.kind, ParseErrorKind::UnknownKeyword("strok_color".to_owned()));
+    }
+
+    #[test]
+    fn segment_before_start() {
+        for &(src, k) in &[("L 1 2", "L"), ("Q 1 2 3 4", "Q"), ("C 1 2 3 4 5 6", "C"), ("Z", "Z")] {
+            let e = error_of(src);
+            assert_eq!((e.line, e.column), (1, 1));
+            assert_eq!(e.kind, ParseErrorKind::SegmentBeforeStart(k.to_owned()));
+        }
+    }
+
+    #[test]
+    fn duplicate_style_key() {
+        let e = error_of("fill_color 1 1 1 1\nM 0 0\n\nfill_color 0 0 0 1\n");
+        assert_eq!((e.line, e.column), (4, 1));
+        assert_eq!(e.kind, ParseErrorKind::DuplicateStyleKey { key: "fill_color".to_owned(), first_line: 1 });
+    }
+
+    #[test]
+    fn display_includes_location() {
+        let e = error_of("M 0 0\nL 1\n").in_file("res/shapes/oops.shape");
+        assert_eq!(format!("{}", e), "res/shapes/oops.shape:2:4: expected a number, found end of line");
+    }
+}
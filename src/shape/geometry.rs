@@ -0,0 +1,638 @@
+// Geometric queries on paths.
+//
+// These work on the actual curves, not on flattened polylines: each command
+// becomes a `Segment`, i.e a line or a vek Bézier curve which starts at the
+// previous command's end point.
+
+use v::{Vec2, Aabr, LineSegment2, QuadraticBezier2, CubicBezier2};
+use super::{Path, SubPath, PathCmd, FillRule};
+
+/// Recursive subdivisions stop once pieces are this flat, relative to their size.
+const FLATNESS: f32 = 1e-5;
+/// Arc lengths are computed with this relative precision.
+const LENGTH_PRECISION: f32 = 1e-6;
+
+/// A single piece of a subpath.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Segment {
+    Line(LineSegment2<f32>),
+    Quadratic(QuadraticBezier2<f32>),
+    Cubic(CubicBezier2<f32>),
+}
+
+/// Where a point lies on a path.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PathLocation {
+    /// Index into `Path::subpaths`.
+    pub subpath: usize,
+    /// Index into `SubPath::segments()`. Segment `i` is drawn by `cmds[i + 1]`,
+    /// except for the closing segment of closed subpaths.
+    pub segment: usize,
+    /// Where the point is along the segment, from 0 to 1.
+    pub t: f32,
+    pub position: Vec2<f32>,
+}
+
+fn cross(u: Vec2<f32>, v: Vec2<f32>) -> f32 {
+    u.x * v.y - u.y * v.x
+}
+
+/// Where segments `a` and `b` intersect, as a factor along each, if they
+/// do. Touching counts. Collinear segments are never reported.
+pub fn line_intersection(a: LineSegment2<f32>, b: LineSegment2<f32>) -> Option<(f32, f32)> {
+    let (da, db) = (a.end - a.start, b.end - b.start);
+    let denom = cross(da, db);
+    if denom == 0. {
+        return None;
+    }
+    let d = b.start - a.start;
+    let t = cross(d, db) / denom;
+    let u = cross(d, da) / denom;
+    if t >= 0. && t <= 1. && u >= 0. && u <= 1. {
+        Some((t, u))
+    } else {
+        None
+    }
+}
+
+// Gauss-Legendre quadrature over [a, b], with 5 points.
+fn integrate<F: Fn(f32) -> f32>(f: &F, a: f32, b: f32) -> f32 {
+    const NODES: [(f32, f32); 5] = [
+        (0., 0.5688889),
+        (-0.5384693, 0.4786287), (0.5384693, 0.4786287),
+        (-0.9061798, 0.2369269), (0.9061798, 0.2369269),
+    ];
+    let (half, middle) = ((b - a) / 2., (a + b) / 2.);
+    NODES.iter().map(|&(x, w)| w * f(middle + half * x)).sum::<f32>() * half
+}
+
+// Splits the interval in halves until the estimate stops changing.
+fn integrate_adaptive<F: Fn(f32) -> f32>(f: &F, a: f32, b: f32, whole: f32, epsilon: f32, depth: u32) -> f32 {
+    let m = (a + b) / 2.;
+    let (left, right) = (integrate(f, a, m), integrate(f, m, b));
+    if depth == 0 || (left + right - whole).abs() <= epsilon {
+        left + right
+    } else {
+        integrate_adaptive(f, a, m, left, epsilon / 2., depth - 1) + integrate_adaptive(f, m, b, right, epsilon / 2., depth - 1)
+    }
+}
+
+impl Segment {
+    pub fn start(&self) -> Vec2<f32> {
+        match *self {
+            Segment::Line(l) => l.start,
+            Segment::Quadratic(c) => c.start,
+            Segment::Cubic(c) => c.start,
+        }
+    }
+    pub fn end(&self) -> Vec2<f32> {
+        match *self {
+            Segment::Line(l) => l.end,
+            Segment::Quadratic(c) => c.end,
+            Segment::Cubic(c) => c.end,
+        }
+    }
+    pub fn evaluate(&self, t: f32) -> Vec2<f32> {
+        match *self {
+            Segment::Line(l) => l.start + (l.end - l.start) * t,
+            Segment::Quadratic(c) => c.evaluate(t),
+            Segment::Cubic(c) => c.evaluate(t),
+        }
+    }
+    pub fn evaluate_derivative(&self, t: f32) -> Vec2<f32> {
+        match *self {
+            Segment::Line(l) => l.end - l.start,
+            Segment::Quadratic(c) => c.evaluate_derivative(t),
+            Segment::Cubic(c) => c.evaluate_derivative(t),
+        }
+    }
+    /// The normalized direction of the segment at `t`, or zero if it has none
+    /// (i.e all its points are the same).
+    ///
+    /// Where the derivative vanishes (e.g a control point on an end point),
+    /// this is the direction the curve comes from or goes to.
+    pub fn tangent(&self, t: f32) -> Vec2<f32> {
+        let d = self.evaluate_derivative(t);
+        if d.magnitude_squared() > 0. {
+            return d.normalized();
+        }
+        let (a, b) = if t < 0.5 { (t, t + 1e-3) } else { (t - 1e-3, t) };
+        let d = self.evaluate(b) - self.evaluate(a);
+        if d.magnitude_squared() > 0. { d.normalized() } else { Vec2::zero() }
+    }
+    /// Splits this segment in two at `t`.
+    pub fn split(&self, t: f32) -> (Self, Self) {
+        match *self {
+            Segment::Line(l) => {
+                let p = self.evaluate(t);
+                (Segment::Line(LineSegment2 { start: l.start, end: p }), Segment::Line(LineSegment2 { start: p, end: l.end }))
+            },
+            Segment::Quadratic(c) => {
+                let (a, b) = c.split(t);
+                (Segment::Quadratic(a), Segment::Quadratic(b))
+            },
+            Segment::Cubic(c) => {
+                let (a, b) = c.split(t);
+                (Segment::Cubic(a), Segment::Cubic(b))
+            },
+        }
+    }
+    // The start point, the control points if any, and the end point.
+    fn points(&self) -> Vec<Vec2<f32>> {
+        match *self {
+            Segment::Line(l) => vec![l.start, l.end],
+            Segment::Quadratic(c) => vec![c.start, c.ctrl, c.end],
+            Segment::Cubic(c) => vec![c.start, c.ctrl0, c.ctrl1, c.end],
+        }
+    }
+    // How far the control points are from the line between the end points.
+    fn flatness(&self) -> f32 {
+        let (a, b) = (self.start(), self.end());
+        let d = b - a;
+        let length = d.magnitude();
+        self.points().into_iter().map(|p| if length > 0. {
+            cross(d, p - a).abs() / length
+        } else {
+            p.distance(a)
+        }).fold(0., f32::max)
+    }
+    /// The length of the segment from 0 to `t`.
+    pub fn length_to(&self, t: f32) -> f32 {
+        if let Segment::Line(l) = *self {
+            return l.start.distance(l.end) * t;
+        }
+        let speed = |t: f32| self.evaluate_derivative(t).magnitude();
+        let polygon = self.points().windows(2).map(|w| w[0].distance(w[1])).sum::<f32>();
+        integrate_adaptive(&speed, 0., t, integrate(&speed, 0., t), polygon * LENGTH_PRECISION, 16)
+    }
+    pub fn length(&self) -> f32 {
+        self.length_to(1.)
+    }
+    /// The factor at which the length from the start of the segment reaches `length`.
+    pub fn t_at_length(&self, length: f32) -> f32 {
+        let total = self.length();
+        if total <= 0. || length <= 0. {
+            return 0.;
+        }
+        if length >= total {
+            return 1.;
+        }
+        if let Segment::Line(_) = *self {
+            return length / total;
+        }
+        // Newton's method, falling back to bisection when it strays.
+        let (mut low, mut high) = (0_f32, 1_f32);
+        let mut t = length / total;
+        for _ in 0..64 {
+            let error = self.length_to(t) - length;
+            if error.abs() <= total * LENGTH_PRECISION {
+                break;
+            }
+            if error > 0. { high = t; } else { low = t; }
+            let speed = self.evaluate_derivative(t).magnitude();
+            let next = if speed > 0. { t - error / speed } else { -1. };
+            t = if next > low && next < high { next } else { (low + high) / 2. };
+        }
+        t
+    }
+    /// The tightest axis-aligned rectangle which contains the segment.
+    pub fn aabr(&self) -> Aabr<f32> {
+        // vek computes the factors of the extrema; its own `aabr()` mistakes them for coordinates.
+        let mut points = vec![self.start(), self.end()];
+        match *self {
+            Segment::Line(_) => (),
+            Segment::Quadratic(c) => {
+                let ((x0, x1), (y0, y1)) = (c.x_bounds(), c.y_bounds());
+                points.extend([x0, x1, y0, y1].iter().map(|&t| c.evaluate(t)));
+            },
+            Segment::Cubic(c) => {
+                let ((x0, x1), (y0, y1)) = (c.x_bounds(), c.y_bounds());
+                points.extend([x0, x1, y0, y1].iter().map(|&t| c.evaluate(t)));
+            },
+        };
+        let mut aabr = Aabr { min: points[0], max: points[0] };
+        for &p in &points[1..] {
+            aabr.expand_to_contain_point(p);
+        }
+        aabr
+    }
+    /// The factor and position of the point of the segment which is closest to `p`.
+    pub fn closest_point(&self, p: Vec2<f32>) -> (f32, Vec2<f32>) {
+        if let Segment::Line(l) = *self {
+            let d = l.end - l.start;
+            let t = if d == Vec2::zero() { 0. } else { ((p - l.start).dot(d) / d.dot(d)).max(0.).min(1.) };
+            return (t, self.evaluate(t));
+        }
+        // Sample the curve, then refine around the closest sample.
+        const SAMPLES: usize = 32;
+        let (mut t, mut distance) = (0., ::std::f32::INFINITY);
+        for i in 0 .. SAMPLES + 1 {
+            let t_ = i as f32 / SAMPLES as f32;
+            let d = self.evaluate(t_).distance_squared(p);
+            if d < distance {
+                t = t_;
+                distance = d;
+            }
+        }
+        let mut h = 0.5 / SAMPLES as f32;
+        while h > 1e-7 {
+            let (t0, t1) = ((t - h).max(0.), (t + h).min(1.));
+            let (d0, d1) = (self.evaluate(t0).distance_squared(p), self.evaluate(t1).distance_squared(p));
+            if d0 < distance && d0 <= d1 {
+                t = t0;
+                distance = d0;
+            } else if d1 < distance {
+                t = t1;
+                distance = d1;
+            } else {
+                h /= 2.;
+            }
+        }
+        (t, self.evaluate(t))
+    }
+    /// Where this segment and `other` intersect, as a factor along each.
+    /// Touching counts. Overlapping parts give any number of points along them.
+    pub fn intersections(&self, other: &Segment) -> Vec<(f32, f32)> {
+        let mut found = vec![];
+        let a = self.aabr().union(other.aabr());
+        let size = (a.max - a.min).reduce_partial_max().max(::std::f32::MIN_POSITIVE);
+        intersect(self, (0., 1.), other, (0., 1.), size * FLATNESS, 40, &mut found);
+        let mut unique: Vec<(f32, f32)> = vec![];
+        for (t, u) in found {
+            if !unique.iter().any(|&(t_, u_)| (t - t_).abs() <= 1e-4 && (u - u_).abs() <= 1e-4) {
+                unique.push((t, u));
+            }
+        }
+        unique.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        unique
+    }
+    // How many times the segment crosses the ray going from `p` towards +X,
+    // counter-clockwise being positive. The segment includes its start point,
+    // but not its end point.
+    fn winding_number(&self, p: Vec2<f32>) -> i32 {
+        let aabr = self.aabr();
+        if aabr.max.x <= p.x || aabr.min.y > p.y || aabr.max.y < p.y {
+            return 0;
+        }
+        // Split where Y turns around, so that each piece goes up or down.
+        let mut ts = vec![0.];
+        match *self {
+            Segment::Line(_) => (),
+            Segment::Quadratic(c) => ts.extend(c.y_inflection()),
+            Segment::Cubic(c) => if let Some((t0, t1)) = c.y_inflections() {
+                ts.push(t0);
+                ts.extend(t1);
+            },
+        };
+        ts.push(1.);
+        ts.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let mut winding = 0;
+        for w in ts.windows(2) {
+            let (t0, t1) = (w[0], w[1]);
+            let (y0, y1) = (self.evaluate(t0).y, self.evaluate(t1).y);
+            let is_up = y0 <= p.y && y1 > p.y;
+            let is_down = y1 <= p.y && y0 > p.y;
+            if !is_up && !is_down {
+                continue;
+            }
+            // Find where the piece crosses the ray's line.
+            let (mut low, mut high) = (t0, t1);
+            for _ in 0..32 {
+                let m = (low + high) / 2.;
+                if (self.evaluate(m).y <= p.y) == is_up { low = m; } else { high = m; }
+            }
+            if self.evaluate((low + high) / 2.).x > p.x {
+                winding += if is_up { 1 } else { -1 };
+            }
+        }
+        winding
+    }
+}
+
+fn intersect(a: &Segment, ta: (f32, f32), b: &Segment, tb: (f32, f32), epsilon: f32, depth: u32, found: &mut Vec<(f32, f32)>) {
+    let (aabr_a, aabr_b) = (a.aabr(), b.aabr());
+    if aabr_a.min.x > aabr_b.max.x + epsilon || aabr_b.min.x > aabr_a.max.x + epsilon
+    || aabr_a.min.y > aabr_b.max.y + epsilon || aabr_b.min.y > aabr_a.max.y + epsilon {
+        return;
+    }
+    let (is_a_flat, is_b_flat) = (a.flatness() <= epsilon, b.flatness() <= epsilon);
+    if depth == 0 || (is_a_flat && is_b_flat) {
+        let chord = |s: &Segment| LineSegment2 { start: s.start(), end: s.end() };
+        if let Some((t, u)) = line_intersection(chord(a), chord(b)) {
+            found.push((ta.0 + (ta.1 - ta.0) * t, tb.0 + (tb.1 - tb.0) * u));
+        }
+        return;
+    }
+    // Split whichever is the least flat.
+    let mid = |t: (f32, f32)| (t.0 + t.1) / 2.;
+    if is_b_flat || (!is_a_flat && a.flatness() >= b.flatness()) {
+        let (a0, a1) = a.split(0.5);
+        intersect(&a0, (ta.0, mid(ta)), b, tb, epsilon, depth - 1, found);
+        intersect(&a1, (mid(ta), ta.1), b, tb, epsilon, depth - 1, found);
+    } else {
+        let (b0, b1) = b.split(0.5);
+        intersect(a, ta, &b0, (tb.0, mid(tb)), epsilon, depth - 1, found);
+        intersect(a, ta, &b1, (mid(tb), tb.1), epsilon, depth - 1, found);
+    }
+}
+
+impl SubPath {
+    /// The segments of this subpath, in order, including the one which closes it
+    /// if it is closed and doesn't already end at its start point.
+    pub fn segments(&self) -> Vec<Segment> {
+        let mut segments = vec![];
+        let mut start = self.start();
+        for cmd in &self.cmds[1..] {
+            segments.push(match *cmd {
+                PathCmd::Start(p) => Segment::Line(LineSegment2 { start, end: p }),
+                PathCmd::Line { end } => Segment::Line(LineSegment2 { start, end }),
+                PathCmd::Quadratic { ctrl, end } => Segment::Quadratic(QuadraticBezier2 { start, ctrl, end }),
+                PathCmd::Cubic { ctrl0, ctrl1, end } => Segment::Cubic(CubicBezier2 { start, ctrl0, ctrl1, end }),
+            });
+            start = cmd.end();
+        }
+        if self.is_closed && start != self.start() {
+            segments.push(Segment::Line(LineSegment2 { start, end: self.start() }));
+        }
+        segments
+    }
+}
+
+impl Path {
+    /// The total length of all subpaths.
+    pub fn length(&self) -> f32 {
+        self.subpaths.iter().flat_map(|s| s.segments()).map(|s| s.length()).sum()
+    }
+    /// The point at `distance` along the path, and the normalized tangent there.
+    ///
+    /// `distance` goes from 0 at the start of the first subpath to 1 at the
+    /// end of the last one; subpaths are taken one after the other, without
+    /// counting the gaps between them. Returns `None` if the path has no length.
+    pub fn point_and_tangent_at(&self, distance: f32) -> Option<(Vec2<f32>, Vec2<f32>)> {
+        let segments = self.subpaths.iter().flat_map(|s| s.segments()).collect::<Vec<_>>();
+        let lengths = segments.iter().map(|s| s.length()).collect::<Vec<_>>();
+        let total: f32 = lengths.iter().sum();
+        if total <= 0. {
+            return None;
+        }
+        let mut left = distance.max(0.).min(1.) * total;
+        for (segment, &length) in segments.iter().zip(lengths.iter()) {
+            if length <= 0. {
+                continue;
+            }
+            if left <= length {
+                let t = segment.t_at_length(left);
+                return Some((segment.evaluate(t), segment.tangent(t)));
+            }
+            left -= length;
+        }
+        // Only reached through rounding errors.
+        let (segment, _) = segments.iter().zip(lengths.iter()).filter(|&(_, &l)| l > 0.).last().unwrap();
+        Some((segment.end(), segment.tangent(1.)))
+    }
+    /// The point of the path which is closest to `p`, or `None` if the path is empty.
+    pub fn closest_point(&self, p: Vec2<f32>) -> Option<PathLocation> {
+        let mut closest: Option<(f32, PathLocation)> = None;
+        for (i, subpath) in self.subpaths.iter().enumerate() {
+            let segments = subpath.segments();
+            // A lone point.
+            let candidates = if segments.is_empty() {
+                vec![(0, 0., subpath.start())]
+            } else {
+                segments.iter().enumerate().map(|(j, s)| {
+                    let (t, position) = s.closest_point(p);
+                    (j, t, position)
+                }).collect()
+            };
+            for (segment, t, position) in candidates {
+                let distance = position.distance(p);
+                if closest.map(|(d, _)| distance < d).unwrap_or(true) {
+                    closest = Some((distance, PathLocation { subpath: i, segment, t, position }));
+                }
+            }
+        }
+        closest.map(|(_, location)| location)
+    }
+    /// The tightest axis-aligned rectangle which contains the path (not its
+    /// stroke), or `None` if the path is empty.
+    pub fn aabr(&self) -> Option<Aabr<f32>> {
+        let mut aabr: Option<Aabr<f32>> = None;
+        for subpath in &self.subpaths {
+            let start = subpath.start();
+            let mut a = aabr.unwrap_or(Aabr { min: start, max: start });
+            a.expand_to_contain_point(start);
+            for segment in subpath.segments() {
+                a.expand_to_contain(segment.aabr());
+            }
+            aabr = Some(a);
+        }
+        aabr
+    }
+    /// How many times the path winds around `p`, counter-clockwise being
+    /// positive. Subpaths count as closed, as they are when filled.
+    pub fn winding_number(&self, p: Vec2<f32>) -> i32 {
+        let mut winding = 0;
+        for subpath in &self.subpaths {
+            let mut segments = subpath.segments();
+            if !subpath.is_closed {
+                let end = subpath.cmds.last().unwrap().end();
+                segments.push(Segment::Line(LineSegment2 { start: end, end: subpath.start() }));
+            }
+            winding += segments.iter().map(|s| s.winding_number(p)).sum::<i32>();
+        }
+        winding
+    }
+    /// Whether `p` is inside the path's fill.
+    pub fn contains(&self, p: Vec2<f32>, fill_rule: FillRule) -> bool {
+        let winding = self.winding_number(p);
+        match fill_rule {
+            FillRule::EvenOdd => winding % 2 != 0,
+            FillRule::NonZero => winding != 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v(x: f32, y: f32) -> Vec2<f32> {
+        Vec2::new(x, y)
+    }
+
+    fn line(a: Vec2<f32>, b: Vec2<f32>) -> Segment {
+        Segment::Line(LineSegment2 { start: a, end: b })
+    }
+
+    // A closed circle made of 4 cubic curves.
+    fn circle(center: Vec2<f32>, r: f32) -> SubPath {
+        let k = 0.5522847 * r;
+        let p = |x, y| center + v(x, y);
+        let mut s = SubPath::new(p(r, 0.));
+        s.cmds.push(PathCmd::Cubic { ctrl0: p(r, k), ctrl1: p(k, r), end: p(0., r) });
+        s.cmds.push(PathCmd::Cubic { ctrl0: p(-k, r), ctrl1: p(-r, k), end: p(-r, 0.) });
+        s.cmds.push(PathCmd::Cubic { ctrl0: p(-r, -k), ctrl1: p(-k, -r), end: p(0., -r) });
+        s.cmds.push(PathCmd::Cubic { ctrl0: p(k, -r), ctrl1: p(r, -k), end: p(r, 0.) });
+        s.is_closed = true;
+        s
+    }
+
+    fn square(min: f32, max: f32) -> SubPath {
+        let mut s = SubPath::new(v(min, min));
+        s.cmds.push(PathCmd::Line { end: v(max, min) });
+        s.cmds.push(PathCmd::Line { end: v(max, max) });
+        s.cmds.push(PathCmd::Line { end: v(min, max) });
+        s.is_closed = true;
+        s
+    }
+
+    fn polyline_length(s: &Segment, n: usize) -> f32 {
+        (0..n).map(|i| s.evaluate(i as f32 / n as f32).distance(s.evaluate((i + 1) as f32 / n as f32))).sum()
+    }
+
+    fn cubic() -> Segment {
+        Segment::Cubic(CubicBezier2 { start: v(0., 0.), ctrl0: v(0.2, 2.), ctrl1: v(1.5, -1.), end: v(2., 0.5) })
+    }
+
+    #[test]
+    fn length_of_lines_and_curves() {
+        let path = Path { subpaths: vec![square(0., 2.)] };
+        assert_eq!(path.length(), 8.);
+
+        let c = cubic();
+        assert!((c.length() - polyline_length(&c, 10000)).abs() < 1e-4, "{} {}", c.length(), polyline_length(&c, 10000));
+        let q = Segment::Quadratic(QuadraticBezier2 { start: v(0., 0.), ctrl: v(1., 3.), end: v(2., 0.) });
+        assert!((q.length() - polyline_length(&q, 10000)).abs() < 1e-4);
+
+        let circle = Path { subpaths: vec![circle(v(1., 1.), 2.)] };
+        assert!((circle.length() - 4. * ::std::f32::consts::PI).abs() < 1e-2, "{}", circle.length());
+    }
+
+    #[test]
+    fn point_and_tangent_at_distance() {
+        let path = Path { subpaths: vec![square(0., 2.)] };
+        assert_eq!(path.point_and_tangent_at(0.), Some((v(0., 0.), v(1., 0.))));
+        assert_eq!(path.point_and_tangent_at(0.125), Some((v(1., 0.), v(1., 0.))));
+        assert_eq!(path.point_and_tangent_at(0.375), Some((v(2., 1.), v(0., 1.))));
+        assert_eq!(path.point_and_tangent_at(1.), Some((v(0., 0.), v(0., -1.))));
+        assert_eq!(Path::default().point_and_tangent_at(0.5), None);
+
+        // Halfway along a symmetric curve is its middle.
+        let mut s = SubPath::new(v(0., 0.));
+        s.cmds.push(PathCmd::Cubic { ctrl0: v(0., 1.), ctrl1: v(3., 1.), end: v(3., 0.) });
+        let (p, tangent) = Path { subpaths: vec![s] }.point_and_tangent_at(0.5).unwrap();
+        assert!(p.distance(v(1.5, 0.75)) < 1e-4, "{:?}", p);
+        assert!(tangent.distance(v(1., 0.)) < 1e-4, "{:?}", tangent);
+    }
+
+    #[test]
+    fn t_at_length_inverts_length_to() {
+        let c = cubic();
+        for &t in &[0.1, 0.25, 0.5, 0.9] {
+            let found = c.t_at_length(c.length_to(t));
+            assert!((found - t).abs() < 1e-4, "{} {}", t, found);
+        }
+    }
+
+    #[test]
+    fn tangent_where_the_derivative_vanishes() {
+        let c = Segment::Cubic(CubicBezier2 { start: v(0., 0.), ctrl0: v(0., 0.), ctrl1: v(1., 1.), end: v(1., 1.) });
+        let diagonal = v(1., 1.).normalized();
+        assert!(c.tangent(0.).distance(diagonal) < 1e-3);
+        assert!(c.tangent(1.).distance(diagonal) < 1e-3);
+        assert_eq!(line(v(1., 1.), v(1., 1.)).tangent(0.5), Vec2::zero());
+    }
+
+    #[test]
+    fn closest_point() {
+        let path = Path { subpaths: vec![square(0., 2.), circle(v(5., 0.), 1.)] };
+        let l = path.closest_point(v(1., -3.)).unwrap();
+        assert_eq!((l.subpath, l.segment, l.t, l.position), (0, 0, 0.5, v(1., 0.)));
+        // The closing segment of the square.
+        let l = path.closest_point(v(-1., 0.5)).unwrap();
+        assert_eq!((l.subpath, l.segment, l.position), (0, 3, v(0., 0.5)));
+
+        let l = path.closest_point(v(5., 3.)).unwrap();
+        assert_eq!((l.subpath, l.segment), (1, 0));
+        assert!((l.t - 1.).abs() < 1e-4 && l.position.distance(v(5., 1.)) < 1e-4, "{:?}", l);
+        let l = path.closest_point(v(5. + 3., 3.)).unwrap();
+        let expected = v(5., 0.) + v(1., 1.).normalized();
+        assert!(l.position.distance(expected) < 1e-3, "{:?}", l);
+
+        assert_eq!(Path::default().closest_point(v(0., 0.)), None);
+    }
+
+    #[test]
+    fn aabr_accounts_for_curve_extrema() {
+        let path = Path { subpaths: vec![circle(v(1., -1.), 2.)] };
+        let aabr = path.aabr().unwrap();
+        assert!(aabr.min.distance(v(-1., -3.)) < 1e-5 && aabr.max.distance(v(3., 1.)) < 1e-5, "{:?}", aabr);
+
+        // The control points stick out much further than the curve.
+        let c = cubic();
+        let aabr = c.aabr();
+        let (mut min, mut max) = (c.start(), c.start());
+        for i in 0..10001 {
+            let p = c.evaluate(i as f32 / 10000.);
+            min = Vec2::partial_min(min, p);
+            max = Vec2::partial_max(max, p);
+        }
+        assert!(aabr.min.distance(min) < 1e-4 && aabr.max.distance(max) < 1e-4, "{:?} {:?} {:?}", aabr, min, max);
+        assert!(aabr.max.y < 2.);
+
+        assert_eq!(Path::default().aabr(), None);
+    }
+
+    #[test]
+    fn winding_number_and_contains() {
+        let path = Path { subpaths: vec![square(-2., 2.), circle(v(0., 0.), 1.)] };
+        assert_eq!(path.winding_number(v(0., 0.)), 2);
+        assert_eq!(path.winding_number(v(1.5, 1.5)), 1);
+        assert_eq!(path.winding_number(v(3., 0.)), 0);
+        // Just inside and outside the circle, where the polygon of its control points would be wrong.
+        assert_eq!(path.winding_number(v(0.99, 0.)), 2);
+        assert_eq!(path.winding_number(v(0.7, 0.72)), 1);
+        assert_eq!(path.winding_number(v(0.7, 0.7)), 2);
+        assert!(!path.contains(v(0., 0.), FillRule::EvenOdd));
+        assert!(path.contains(v(0., 0.), FillRule::NonZero));
+        assert!(path.contains(v(1.5, 1.5), FillRule::EvenOdd));
+
+        // Open subpaths are filled as if closed; a ray through a vertex counts once.
+        let mut triangle = SubPath::new(v(0., 0.));
+        triangle.cmds.push(PathCmd::Line { end: v(2., 1.) });
+        triangle.cmds.push(PathCmd::Line { end: v(0., 2.) });
+        let path = Path { subpaths: vec![triangle] };
+        assert_eq!(path.winding_number(v(1., 1.)), 1);
+        assert_eq!(path.winding_number(v(-1., 1.)), 0);
+    }
+
+    #[test]
+    fn line_intersections() {
+        let s = |a, b| LineSegment2 { start: a, end: b };
+        assert_eq!(line_intersection(s(v(0., 0.), v(2., 2.)), s(v(0., 2.), v(2., 0.))), Some((0.5, 0.5)));
+        assert_eq!(line_intersection(s(v(0., 0.), v(2., 0.)), s(v(2., 0.), v(2., 1.))), Some((1., 0.)));
+        assert_eq!(line_intersection(s(v(0., 0.), v(0.9, 0.9)), s(v(0., 2.), v(2., 0.))), None);
+        assert_eq!(line_intersection(s(v(0., 0.), v(1., 0.)), s(v(0., 1.), v(1., 1.))), None);
+    }
+
+    #[test]
+    fn curve_intersections() {
+        // A symmetric arch, crossed twice by a horizontal line.
+        let arch = Segment::Quadratic(QuadraticBezier2 { start: v(0., 0.), ctrl: v(1., 2.), end: v(2., 0.) });
+        let found = arch.intersections(&line(v(-1., 0.5), v(3., 0.5)));
+        assert_eq!(found.len(), 2, "{:?}", found);
+        for &(t, u) in &found {
+            let (p, q) = (arch.evaluate(t), line(v(-1., 0.5), v(3., 0.5)).evaluate(u));
+            assert!(p.distance(q) < 1e-4 && (p.y - 0.5).abs() < 1e-4, "{:?} {:?}", p, q);
+        }
+        assert!((found[0].0 + found[1].0 - 1.).abs() < 1e-4);
+
+        let c = cubic();
+        let found = c.intersections(&arch);
+        assert!(!found.is_empty());
+        for &(t, u) in &found {
+            assert!(c.evaluate(t).distance(arch.evaluate(u)) < 1e-4);
+        }
+        assert_eq!(arch.intersections(&line(v(0., 3.), v(2., 3.))), vec![]);
+    }
+}
@@ -0,0 +1,294 @@
+// Gradients, as used by `Style::fill_gradient` and `Style::stroke_gradient`.
+//
+// Fill gradients are evaluated per pixel by the color mesh fragment shader;
+// `Gradient::color_at()` does the same on the CPU and must be kept in sync with it.
+// Stroke gradients are evaluated per vertex, with `color_at_offset()`.
+
+use std::f32::consts::PI;
+use v::{Vec2, Rgba};
+
+/// How many stops the renderer supports. Extra ones are ignored.
+pub const MAX_STOPS: usize = 16;
+
+/// How the offset of a point is computed.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum GradientKind {
+    /// Offset 0 is at `start` and offset 1 at `end`, along the line between them.
+    Linear,
+    /// Offset 0 is at `start` (the center) and offset 1 on the circle going through `end`.
+    Radial,
+    /// Offsets go counter-clockwise around `start` (the center), from 0 in the
+    /// direction of `end` up to 1 after a full turn.
+    Conic,
+}
+
+/// What happens to offsets outside of `[0, 1]`. Same meaning as SVG's `spreadMethod`.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum GradientSpread {
+    /// The colors at both ends extend forever.
+    Pad,
+    Repeat,
+    /// Like `Repeat`, but every other repetition is mirrored.
+    Reflect,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct GradientStop {
+    /// Between 0 and 1.
+    pub offset: f32,
+    pub color: Rgba<f32>,
+}
+
+/// A gradient drawn over `Style::fill_color`, in the shape's local coordinates.
+///
+/// Stops are expected to be sorted by offset; as in SVG, an offset which is
+/// less than the previous one's is treated as equal to it. Without stops,
+/// there is no gradient.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gradient {
+    pub kind: GradientKind,
+    pub spread: GradientSpread,
+    pub start: Vec2<f32>,
+    pub end: Vec2<f32>,
+    pub stops: Vec<GradientStop>,
+}
+
+impl Default for Gradient {
+    fn default() -> Self {
+        Self {
+            kind: GradientKind::Linear,
+            spread: GradientSpread::Pad,
+            start: -Vec2::unit_x(),
+            end: Vec2::unit_x(),
+            stops: vec![
+                GradientStop { offset: 0., color: Rgba::green() },
+                GradientStop { offset: 1., color: Rgba::magenta() },
+            ],
+        }
+    }
+}
+
+impl GradientKind {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            GradientKind::Linear => "linear",
+            GradientKind::Radial => "radial",
+            GradientKind::Conic => "conic",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "linear" => Some(GradientKind::Linear),
+            "radial" => Some(GradientKind::Radial),
+            "conic" => Some(GradientKind::Conic),
+            _ => None,
+        }
+    }
+}
+
+impl GradientSpread {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            GradientSpread::Pad => "pad",
+            GradientSpread::Repeat => "repeat",
+            GradientSpread::Reflect => "reflect",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "pad" => Some(GradientSpread::Pad),
+            "repeat" => Some(GradientSpread::Repeat),
+            "reflect" => Some(GradientSpread::Reflect),
+            _ => None,
+        }
+    }
+}
+
+impl Gradient {
+    /// A gradient without stops, i.e which draws nothing.
+    pub fn none() -> Self {
+        Self { stops: vec![], .. Self::default() }
+    }
+    pub fn is_none(&self) -> bool {
+        self.stops.is_empty()
+    }
+    /// The stops as the renderer sees them: at most `MAX_STOPS`, with offsets
+    /// clamped to `[0, 1]` and never decreasing.
+    pub fn normalized_stops(&self) -> Vec<GradientStop> {
+        normalized_stops(&self.stops[.. self.stops.len().min(MAX_STOPS)])
+    }
+    /// Inserts a stop, keeping stops sorted. A stop which is already at
+    /// this offset is replaced.
+    pub fn set_stop(&mut self, stop: GradientStop) {
+        set_stop(&mut self.stops, stop)
+    }
+    /// Where `p` is along the gradient, before `spread` is applied.
+    pub fn offset_at(&self, p: Vec2<f32>) -> f32 {
+        let d = self.end - self.start;
+        let p = p - self.start;
+        match self.kind {
+            GradientKind::Linear => p.dot(d) / d.dot(d).max(1e-12),
+            GradientKind::Radial => p.magnitude() / d.magnitude().max(1e-6),
+            GradientKind::Conic => {
+                let angle = (d.x * p.y - d.y * p.x).atan2(d.dot(p));
+                let t = angle / (2. * PI);
+                if t < 0. { t + 1. } else { t }
+            },
+        }
+    }
+    /// The color of the gradient at `p`, not blended with anything.
+    pub fn color_at(&self, p: Vec2<f32>) -> Rgba<f32> {
+        let t = self.offset_at(p);
+        let t = match self.spread {
+            GradientSpread::Pad => t.max(0.).min(1.),
+            GradientSpread::Repeat => t - t.floor(),
+            GradientSpread::Reflect => 1. - ((t - 2. * (t / 2.).floor()) - 1.).abs(),
+        };
+        color_at_offset(&self.normalized_stops(), t)
+    }
+}
+
+/// Inserts a stop into sorted `stops`, replacing the one which is already
+/// at this offset, if any.
+pub fn set_stop(stops: &mut Vec<GradientStop>, stop: GradientStop) {
+    match stops.iter().position(|s| s.offset >= stop.offset) {
+        Some(i) if stops[i].offset == stop.offset => stops[i] = stop,
+        Some(i) => stops.insert(i, stop),
+        None => stops.push(stop),
+    }
+}
+
+/// Clamps offsets to `[0, 1]` and makes sure they never decrease.
+pub fn normalized_stops(stops: &[GradientStop]) -> Vec<GradientStop> {
+    let mut min = 0.;
+    stops.iter().map(|stop| {
+        min = stop.offset.max(min).min(1.);
+        GradientStop { offset: min, color: stop.color }
+    }).collect()
+}
+
+/// The color at offset `t` along the given stops, which must be normalized.
+/// Offsets before the first stop or after the last one get its color.
+pub fn color_at_offset(stops: &[GradientStop], t: f32) -> Rgba<f32> {
+    let (first, last) = match (stops.first(), stops.last()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return Rgba::zero(),
+    };
+    if t <= first.offset {
+        return first.color;
+    }
+    for pair in stops.windows(2) {
+        let (a, b) = (pair[0], pair[1]);
+        if t <= b.offset {
+            let s = if b.offset > a.offset { (t - a.offset) / (b.offset - a.offset) } else { 1. };
+            return a.color * (1. - s) + b.color * s;
+        }
+    }
+    last.color
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grey(offset: f32, v: f32) -> GradientStop {
+        GradientStop { offset, color: Rgba::new(v, v, v, 1.) }
+    }
+
+    // From black at 0 to white at 1, along the x axis; so the red channel of
+    // a color is the offset it was taken at.
+    fn ramp(kind: GradientKind, spread: GradientSpread) -> Gradient {
+        Gradient {
+            kind, spread,
+            start: Vec2::zero(),
+            end: Vec2::unit_x(),
+            stops: vec![grey(0., 0.), grey(1., 1.)],
+        }
+    }
+
+    fn assert_near(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-5, "{} is not {}", a, b);
+    }
+
+    #[test]
+    fn colors_are_interpolated_between_stops() {
+        let stops = vec![grey(0.2, 0.), grey(0.6, 1.), grey(0.6, 0.5), grey(1., 0.)];
+        assert_eq!(color_at_offset(&[], 0.5), Rgba::zero());
+        assert_eq!(color_at_offset(&stops, -1.), stops[0].color);
+        assert_eq!(color_at_offset(&stops, 0.2), stops[0].color);
+        assert_near(color_at_offset(&stops, 0.3).r, 0.25);
+        // Stops at the same offset make a hard edge.
+        assert_eq!(color_at_offset(&stops, 0.6), stops[1].color);
+        assert_near(color_at_offset(&stops, 0.8).r, 0.25);
+        assert_eq!(color_at_offset(&stops, 2.), stops[3].color);
+
+        let g = ramp(GradientKind::Linear, GradientSpread::Pad);
+        assert_near(g.color_at(Vec2::new(0.25, 3.)).r, 0.25);
+        assert_eq!(Gradient::none().color_at(Vec2::zero()), Rgba::zero());
+    }
+
+    #[test]
+    fn spread_modes_fold_offsets_into_the_stops() {
+        let spreads = [
+            // At -0.25, 0, 1, 1.25 and 2.
+            (GradientSpread::Pad, [0., 0., 1., 1., 1.]),
+            (GradientSpread::Repeat, [0.75, 0., 0., 0.25, 0.]),
+            (GradientSpread::Reflect, [0.25, 0., 1., 0.75, 0.]),
+        ];
+        for &(spread, expected) in spreads.iter() {
+            let g = ramp(GradientKind::Linear, spread);
+            for (&x, &e) in [-0.25, 0., 1., 1.25, 2.].iter().zip(expected.iter()) {
+                assert_near(g.color_at(Vec2::new(x, 0.)).r, e);
+            }
+        }
+    }
+
+    #[test]
+    fn offsets_of_each_kind() {
+        let g = ramp(GradientKind::Linear, GradientSpread::Pad);
+        assert_near(g.offset_at(Vec2::zero()), 0.);
+        assert_near(g.offset_at(Vec2::new(0.5, 7.)), 0.5);
+        assert_near(g.offset_at(Vec2::unit_x()), 1.);
+        assert_near(g.offset_at(Vec2::new(-1., 0.)), -1.);
+
+        let g = ramp(GradientKind::Radial, GradientSpread::Pad);
+        assert_near(g.offset_at(Vec2::zero()), 0.);
+        assert_near(g.offset_at(Vec2::new(0., -0.5)), 0.5);
+        assert_near(g.offset_at(Vec2::unit_x()), 1.);
+        assert_near(g.offset_at(Vec2::new(-2., 0.)), 2.);
+
+        let g = ramp(GradientKind::Conic, GradientSpread::Pad);
+        assert_near(g.offset_at(Vec2::zero()), 0.);
+        assert_near(g.offset_at(Vec2::unit_x()), 0.);
+        assert_near(g.offset_at(Vec2::unit_y()), 0.25);
+        assert_near(g.offset_at(Vec2::new(-1., 0.)), 0.5);
+        assert_near(g.offset_at(Vec2::new(0., -1.)), 0.75);
+    }
+
+    #[test]
+    fn normalized_stops_are_clamped_and_never_decrease() {
+        let stops = vec![grey(-0.3, 0.), grey(0.5, 0.2), grey(0.2, 0.4), grey(1.5, 1.)];
+        let offsets: Vec<_> = normalized_stops(&stops).iter().map(|s| s.offset).collect();
+        assert_eq!(offsets, vec![0., 0.5, 0.5, 1.]);
+        assert_eq!(normalized_stops(&stops)[2].color, stops[2].color);
+
+        let mut g = Gradient::default();
+        g.stops = (0 .. MAX_STOPS + 3).map(|i| grey(i as f32 / 20., 0.)).collect();
+        assert_eq!(g.normalized_stops().len(), MAX_STOPS);
+    }
+
+    #[test]
+    fn set_stop_replaces_or_inserts_in_order() {
+        let mut stops = vec![grey(0., 0.), grey(1., 1.)];
+        set_stop(&mut stops, grey(0.5, 0.3));
+        set_stop(&mut stops, grey(1., 0.7));
+        assert_eq!(stops, vec![grey(0., 0.), grey(0.5, 0.3), grey(1., 0.7)]);
+        set_stop(&mut stops, grey(0.25, 0.1));
+        let offsets: Vec<_> = stops.iter().map(|s| s.offset).collect();
+        assert_eq!(offsets, vec![0., 0.25, 0.5, 1.]);
+
+        let mut g = Gradient::none();
+        g.set_stop(grey(0.5, 1.));
+        assert_eq!(g.stops, vec![grey(0.5, 1.)]);
+    }
+}
@@ -0,0 +1,792 @@
+pub mod format;
+pub mod svg;
+pub mod stroke;
+pub mod fill;
+pub mod gradient;
+pub mod boolean;
+pub mod geometry;
+pub mod simplify;
+pub mod offset;
+pub mod primitive;
+pub mod edit;
+pub use self::format::{ParseError, ParseErrorKind};
+pub use self::gradient::{Gradient, GradientKind, GradientSpread, GradientStop};
+pub use self::stroke::StrokePoint;
+pub use self::primitive::Primitive;
+
+use std::io;
+use std::fmt::{self, Display, Formatter};
+use std::error::Error;
+use std::ops::Range;
+use v::{Vec2, Vec3, Mat4, Rgba, CubicBezier2, QuadraticBezier2};
+use mesh::color_mesh::Vertex;
+
+/// `Style::stroke_thickness` and dash lengths are in pixels, as seen with
+/// the default 800x480 window and an unscaled camera. This is how many of
+/// these pixels one local unit spans.
+pub const REFERENCE_PIXELS_PER_UNIT: f32 = 240.;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Style {
+    pub stroke_thickness: f32,
+    pub stroke_color: Rgba<f32>,
+    /// Colors along the length of each subpath, from offset 0 at its start
+    /// to 1 at its end. When there are any, they replace `stroke_color`.
+    pub stroke_gradient: Vec<GradientStop>,
+    pub stroke_join: StrokeJoin,
+    pub stroke_cap: StrokeCap,
+    /// Above this ratio of miter length to stroke thickness, miter joins
+    /// become bevel joins. Same meaning as SVG's `stroke-miterlimit`.
+    pub stroke_miter_limit: f32,
+    /// Alternating lengths of dashes and gaps; empty for a solid stroke.
+    pub stroke_dashes: Vec<f32>,
+    /// How far into the dash pattern the stroke starts.
+    pub stroke_dash_offset: f32,
+    /// Over how long the stroke narrows down to nothing at the start and at
+    /// the end of open subpaths, as if drawn with a brush. Zero for no taper.
+    pub stroke_taper_start: f32,
+    pub stroke_taper_end: f32,
+    pub fill_color: Rgba<f32>,
+    pub fill_gradient: Gradient,
+    pub fill_rule: FillRule,
+}
+
+/// How overlapping subpaths (and self-intersections) decide what is inside.
+/// Same meaning as SVG's `fill-rule`.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum FillRule {
+    EvenOdd,
+    NonZero,
+}
+
+/// The shape of the corners of a stroke. Same meaning as SVG's `stroke-linejoin`.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum StrokeJoin {
+    Miter,
+    Round,
+    Bevel,
+}
+
+/// The shape of the ends of open strokes and dashes. Same meaning as SVG's `stroke-linecap`.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum StrokeCap {
+    Butt,
+    Round,
+    Square,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PathCmd {
+    Start(Vec2<f32>),
+    Line { end: Vec2<f32> },
+    Cubic { ctrl0: Vec2<f32>, ctrl1: Vec2<f32>, end: Vec2<f32> },
+    Quadratic { ctrl: Vec2<f32>, end: Vec2<f32> },
+}
+
+/// Overrides of the shape's style at a single node, i.e at the end point
+/// of a `PathCmd`. The stroke blends from one node to the next.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct NodeStyle {
+    pub stroke_color: Option<Rgba<f32>>,
+    pub stroke_thickness: Option<f32>,
+}
+
+/// A sequence of commands, starting with exactly one `PathCmd::Start`.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SubPath {
+    pub is_closed: bool,
+    pub cmds: Vec<PathCmd>,
+    /// The style of each command's end point. This may be shorter than
+    /// `cmds`; the nodes past its end have no overrides.
+    pub node_styles: Vec<NodeStyle>,
+    /// The primitive this subpath was generated from, if any. It is saved
+    /// instead of the nodes for as long as they are still the ones it generates.
+    pub primitive: Option<Primitive>,
+}
+
+/// Any number of subpaths, each with its own `M`...`Z`.
+/// Holes are simply subpaths which the fill rule considers as outside.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Path {
+    pub subpaths: Vec<SubPath>,
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    Parse(ParseError),
+}
+
+impl From<io::Error> for LoadError {
+    fn from(e: io::Error) -> Self {
+        LoadError::Io(e)
+    }
+}
+
+impl From<ParseError> for LoadError {
+    fn from(e: ParseError) -> Self {
+        LoadError::Parse(e)
+    }
+}
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            LoadError::Io(ref e) => write!(f, "I/O error: {}", e),
+            LoadError::Parse(ref e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for LoadError {
+    fn description(&self) -> &str {
+        match *self {
+            LoadError::Io(_) => "I/O error while loading shape",
+            LoadError::Parse(_) => "malformed shape file",
+        }
+    }
+}
+
+/// CPU-side shape data. It knows nothing about OpenGL; the renderer creates
+/// and caches the matching `mesh::shape_mesh::ShapeMesh` on demand.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Shape {
+    pub style: Style,
+    pub path: Path,
+}
+
+/// The vertices needed to draw a `Shape`, computed on the CPU.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Tessellation {
+    /// The outline vertices of all subpaths, one after the other.
+    pub vertices: Vec<Vertex>,
+    /// For each subpath, its range in `vertices`, and whether it is closed.
+    pub subpaths: Vec<(Range<usize>, bool)>,
+    /// Triangle strips covering the stroke, one after the other.
+    pub stroke_vertices: Vec<Vertex>,
+    /// The range of each strip in `stroke_vertices`.
+    pub stroke_strips: Vec<Range<usize>>,
+    /// Whether the fill could be triangulated. If not, `fill_vertices` and
+    /// `fill_indices` are empty and the fill must be drawn with the stencil,
+    /// from `vertices` and `fill_cover_strip`.
+    pub is_fill_triangulated: bool,
+    /// The fill's triangles, in the fill color. The gradient is applied when drawing.
+    pub fill_vertices: Vec<Vertex>,
+    pub fill_indices: Vec<u32>,
+    /// A quad covering the bounds of the outline, in the fill color.
+    pub fill_cover_strip: Vec<Vertex>,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            stroke_thickness: 2.,
+            stroke_color: Rgba::black(),
+            stroke_gradient: vec![],
+            stroke_join: StrokeJoin::Miter,
+            stroke_cap: StrokeCap::Butt,
+            stroke_miter_limit: 4.,
+            stroke_dashes: vec![],
+            stroke_dash_offset: 0.,
+            stroke_taper_start: 0.,
+            stroke_taper_end: 0.,
+            fill_color: Rgba::yellow(),
+            fill_gradient: Gradient::default(),
+            fill_rule: FillRule::EvenOdd,
+        }
+    }
+}
+
+impl FillRule {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            FillRule::EvenOdd => "evenodd",
+            FillRule::NonZero => "nonzero",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "evenodd" => Some(FillRule::EvenOdd),
+            "nonzero" => Some(FillRule::NonZero),
+            _ => None,
+        }
+    }
+}
+
+impl StrokeJoin {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            StrokeJoin::Miter => "miter",
+            StrokeJoin::Round => "round",
+            StrokeJoin::Bevel => "bevel",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "miter" => Some(StrokeJoin::Miter),
+            "round" => Some(StrokeJoin::Round),
+            "bevel" => Some(StrokeJoin::Bevel),
+            _ => None,
+        }
+    }
+}
+
+impl StrokeCap {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            StrokeCap::Butt => "butt",
+            StrokeCap::Round => "round",
+            StrokeCap::Square => "square",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "butt" => Some(StrokeCap::Butt),
+            "round" => Some(StrokeCap::Round),
+            "square" => Some(StrokeCap::Square),
+            _ => None,
+        }
+    }
+}
+
+impl PathCmd {
+    pub fn end(&self) -> Vec2<f32> {
+        match *self {
+            PathCmd::Start(p) => p,
+            PathCmd::Line { end } => end,
+            PathCmd::Cubic { end, .. } => end,
+            PathCmd::Quadratic { end, .. } => end,
+        }
+    }
+}
+
+impl NodeStyle {
+    pub fn is_empty(&self) -> bool {
+        self.stroke_color.is_none() && self.stroke_thickness.is_none()
+    }
+}
+
+impl SubPath {
+    pub fn new(start: Vec2<f32>) -> Self {
+        Self {
+            is_closed: false,
+            cmds: vec![PathCmd::Start(start)],
+            node_styles: vec![],
+            primitive: None,
+        }
+    }
+    pub fn start(&self) -> Vec2<f32> {
+        self.cmds[0].end()
+    }
+    /// The style overrides at the end point of `self.cmds[i]`.
+    pub fn node_style(&self, i: usize) -> NodeStyle {
+        self.node_styles.get(i).cloned().unwrap_or_default()
+    }
+    pub fn node_style_mut(&mut self, i: usize) -> &mut NodeStyle {
+        if self.node_styles.len() <= i {
+            self.node_styles.resize(i + 1, NodeStyle::default());
+        }
+        &mut self.node_styles[i]
+    }
+    /// Approximates this subpath by a polyline, which is at most `tolerance`
+    /// away from the actual curves. Curves are split into as few segments as
+    /// this allows, so small or flat ones only get a few vertices.
+    pub fn generate_vertex_positions(&self, tolerance: f32) -> Vec<Vec2<f32>> {
+        self.generate_vertex_positions_and_nodes(tolerance).0
+    }
+    /// Same as `generate_vertex_positions()`, but also returns, for each
+    /// command, the index of the vertex at its end point.
+    pub fn generate_vertex_positions_and_nodes(&self, tolerance: f32) -> (Vec<Vec2<f32>>, Vec<usize>) {
+        let mut vertices = vec![];
+        let mut nodes = Vec::with_capacity(self.cmds.len());
+        for cmd in &self.cmds {
+            match *cmd {
+                PathCmd::Start(start) => vertices.push(start),
+                PathCmd::Line { end } => vertices.push(end),
+                PathCmd::Quadratic { ctrl, end } => {
+                    let start = *vertices.last().unwrap();
+                    let c = QuadraticBezier2 { start, ctrl, end };
+                    let dd = (start - ctrl * 2. + end).magnitude() * 2.;
+                    let n = segment_count(dd, tolerance);
+                    for i in 1..n {
+                        vertices.push(c.evaluate(i as f32 / n as f32));
+                    }
+                    vertices.push(end);
+                },
+                PathCmd::Cubic { ctrl0, ctrl1, end } => {
+                    let start = *vertices.last().unwrap();
+                    let c = CubicBezier2 { start, ctrl0, ctrl1, end };
+                    let dd0 = (start - ctrl0 * 2. + ctrl1).magnitude();
+                    let dd1 = (ctrl0 - ctrl1 * 2. + end).magnitude();
+                    let n = segment_count(dd0.max(dd1) * 6., tolerance);
+                    for i in 1..n {
+                        vertices.push(c.evaluate(i as f32 / n as f32));
+                    }
+                    vertices.push(end);
+                },
+            };
+            nodes.push(vertices.len() - 1);
+        }
+        (vertices, nodes)
+    }
+    /// Approximates this subpath by a polyline as `generate_vertex_positions()`
+    /// does, along with the stroke's color and half width at each vertex.
+    pub fn generate_stroke_points(&self, tolerance: f32, style: &Style) -> Vec<StrokePoint> {
+        let (positions, nodes) = self.generate_vertex_positions_and_nodes(tolerance);
+
+        // The distance from the start, along the polyline, of each vertex.
+        let mut lengths = Vec::with_capacity(positions.len());
+        let mut length = 0.;
+        for (i, &p) in positions.iter().enumerate() {
+            if i > 0 {
+                length += positions[i - 1].distance(p);
+            }
+            lengths.push(length);
+        }
+        if self.is_closed && !positions.is_empty() {
+            length += positions[positions.len() - 1].distance(positions[0]);
+        }
+
+        let stroke_gradient = gradient::normalized_stops(&style.stroke_gradient);
+        let base_color = |s: f32| if stroke_gradient.is_empty() {
+            style.stroke_color
+        } else {
+            gradient::color_at_offset(&stroke_gradient, if length > 0. { s / length } else { 0. })
+        };
+        let half_width = |thickness: f32| thickness / REFERENCE_PIXELS_PER_UNIT / 2.;
+        let taper = |s: f32| {
+            if self.is_closed {
+                return 1.;
+            }
+            let ramp = |d: f32, taper: f32| if taper > 0. { (d * REFERENCE_PIXELS_PER_UNIT / taper).min(1.) } else { 1. };
+            ramp(s, style.stroke_taper_start) * ramp(length - s, style.stroke_taper_end)
+        };
+
+        // How far each node is from the base style. In between nodes, this
+        // varies linearly with the distance along the path.
+        let deltas = nodes.iter().enumerate().map(|(k, &i)| {
+            let node = self.node_style(k);
+            let color = node.stroke_color.map(|c| c - base_color(lengths[i])).unwrap_or(Rgba::zero());
+            let thickness = node.stroke_thickness.map(|t| t - style.stroke_thickness).unwrap_or(0.);
+            (color, thickness)
+        }).collect::<Vec<_>>();
+
+        let mut k = 0;
+        positions.iter().enumerate().map(|(i, &position)| {
+            while k + 1 < nodes.len() && nodes[k + 1] <= i {
+                k += 1;
+            }
+            let (color_delta, thickness_delta) = match deltas.get(k + 1) {
+                None => deltas[k],
+                Some(&(next_color, next_thickness)) => {
+                    let (a, b) = (lengths[nodes[k]], lengths[nodes[k + 1]]);
+                    let u = if b > a { (lengths[i] - a) / (b - a) } else { 0. };
+                    let (color, thickness) = deltas[k];
+                    (color * (1. - u) + next_color * u, thickness * (1. - u) + next_thickness * u)
+                },
+            };
+            let color = base_color(lengths[i]) + color_delta;
+            StrokePoint {
+                position,
+                half_width: half_width(style.stroke_thickness + thickness_delta).max(0.) * taper(lengths[i]),
+                color: color.map(|x| x.max(0.).min(1.)),
+            }
+        }).collect()
+    }
+}
+
+/// The maximum distance, in pixels, between a curve and the polyline which
+/// approximates it on screen.
+pub const FLATTENING_TOLERANCE_IN_PIXELS: f32 = 0.25;
+/// A single curve is never split into more segments than this.
+pub const MAX_SEGMENTS_PER_CURVE: u32 = 1024;
+
+/// Returns the flattening tolerance, in local units, for a shape of which
+/// each local unit spans `pixels_per_unit` pixels on screen.
+///
+/// It is rounded down to a power of two, so that the required precision only
+/// changes when zooming by a factor of two.
+pub fn flattening_tolerance(pixels_per_unit: f32) -> f32 {
+    let tolerance = FLATTENING_TOLERANCE_IN_PIXELS / pixels_per_unit.abs().max(::std::f32::MIN_POSITIVE);
+    2_f32.powf(tolerance.log2().floor())
+}
+
+// Wang's formula: for a Bézier curve whose second derivative's magnitude is
+// at most `dd`, the number of segments (evenly spaced in `t`) needed for the
+// polyline to stay within `tolerance` of the curve.
+fn segment_count(dd: f32, tolerance: f32) -> u32 {
+    let n = (dd / (8. * tolerance)).sqrt().ceil();
+    if n >= 1. {
+        (n as u32).min(MAX_SEGMENTS_PER_CURVE)
+    } else {
+        1
+    }
+}
+
+impl Path {
+    pub fn is_empty(&self) -> bool {
+        self.subpaths.is_empty()
+    }
+    /// The subpath which is currently being drawn, i.e the last one.
+    pub fn current_subpath_mut(&mut self) -> Option<&mut SubPath> {
+        self.subpaths.last_mut()
+    }
+    /// Returns the subpath that segments should be appended to.
+    /// As in SVG, if the last subpath is closed, a new one begins at its start point.
+    ///
+    /// Panics if there are no subpaths yet.
+    pub fn open_subpath(&mut self) -> &mut SubPath {
+        let start = {
+            let current = self.subpaths.last().unwrap();
+            if !current.is_closed {
+                None
+            } else {
+                Some(current.start())
+            }
+        };
+        if let Some(start) = start {
+            self.subpaths.push(SubPath::new(start));
+        }
+        self.subpaths.last_mut().unwrap()
+    }
+    /// The same path, with each quadratic curve replaced by the equivalent
+    /// cubic one. Node styles are kept.
+    pub fn to_cubics(&self) -> Self {
+        let subpaths = self.subpaths.iter().map(|subpath| {
+            let mut start = subpath.start();
+            let cmds = subpath.cmds.iter().map(|cmd| {
+                let cmd = match *cmd {
+                    PathCmd::Quadratic { ctrl, end } => {
+                        let c = QuadraticBezier2 { start, ctrl, end }.into_cubic();
+                        PathCmd::Cubic { ctrl0: c.ctrl0, ctrl1: c.ctrl1, end }
+                    },
+                    ref cmd => cmd.clone(),
+                };
+                start = cmd.end();
+                cmd
+            }).collect();
+            SubPath { cmds, ..subpath.clone() }
+        }).collect();
+        Self { subpaths }
+    }
+}
+
+fn create_fill_cover_strip_vertices(vertices: &[Vertex], color: Rgba<f32>) -> Vec<Vertex> {
+    let (mut min, mut max) = (Vec3::broadcast(::std::f32::INFINITY), Vec3::broadcast(::std::f32::NEG_INFINITY));
+    for v in vertices {
+        let p = v.position;
+        min = Vec3::partial_min(min, p);
+        max = Vec3::partial_max(max, p);
+    }
+    if vertices.is_empty() {
+        return vec![];
+    }
+    vec![
+        Vertex { position: Vec3::new(min.x, max.y, 0.), color, },
+        Vertex { position: Vec3::new(min.x, min.y, 0.), color, },
+        Vertex { position: Vec3::new(max.x, max.y, 0.), color, },
+        Vertex { position: Vec3::new(max.x, min.y, 0.), color, },
+    ]
+}
+
+// The color seen when `top` is blended over `bottom`, as with
+// `glBlendFunc(GL_SRC_ALPHA, GL_ONE_MINUS_SRC_ALPHA)`.
+fn over(top: Rgba<f32>, bottom: Rgba<f32>) -> Rgba<f32> {
+    let a = top.a + bottom.a * (1. - top.a);
+    if a <= 0. {
+        return Rgba::zero();
+    }
+    let mix = |t: f32, b: f32| (t * top.a + b * bottom.a * (1. - top.a)) / a;
+    Rgba::new(mix(top.r, bottom.r), mix(top.g, bottom.g), mix(top.b, bottom.b), a)
+}
+
+impl Shape {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// `tolerance` is in local units; see `flattening_tolerance()`.
+    pub fn tessellate(&self, tolerance: f32) -> Tessellation {
+        let mut vertices = vec![];
+        let mut subpaths = vec![];
+        let mut stroke_polylines = vec![];
+        for subpath in &self.path.subpaths {
+            let points = subpath.generate_stroke_points(tolerance, &self.style);
+            let start = vertices.len();
+            vertices.extend(points.iter().map(|p| Vertex { position: p.position.into(), color: p.color }));
+            subpaths.push((start .. vertices.len(), subpath.is_closed));
+            stroke_polylines.push((points, subpath.is_closed));
+        }
+        let polylines = stroke_polylines.iter().map(|&(ref points, is_closed)| {
+            (points.iter().map(|p| p.position).collect(), is_closed)
+        }).collect::<Vec<_>>();
+        let (stroke_vertices, stroke_strips) = stroke::tessellate(&stroke_polylines, &self.style, tolerance);
+        let fill_color = self.style.fill_color;
+        let fill = fill::triangulate(&polylines, self.style.fill_rule);
+        let is_fill_triangulated = fill.is_some();
+        let (fill_vertices, fill_indices) = match fill {
+            Some((positions, indices)) => {
+                let vertices = positions.into_iter().map(|p| Vertex { position: p.into(), color: fill_color }).collect();
+                (vertices, indices)
+            },
+            None => (vec![], vec![]),
+        };
+        let fill_cover_strip = create_fill_cover_strip_vertices(&vertices, fill_color);
+        Tessellation {
+            vertices,
+            subpaths,
+            stroke_vertices,
+            stroke_strips,
+            is_fill_triangulated,
+            fill_vertices,
+            fill_indices,
+            fill_cover_strip,
+        }
+    }
+    /// Whether `p`, in local units, is on the shape as drawn: on its fill,
+    /// unless it is fully transparent, or within its stroke.
+    pub fn hit_test(&self, p: Vec2<f32>) -> bool {
+        let style = &self.style;
+        let has_fill = style.fill_color.a > 0. || !style.fill_gradient.stops.is_empty();
+        if has_fill && self.path.contains(p, style.fill_rule) {
+            return true;
+        }
+        let half_width = style.stroke_thickness / REFERENCE_PIXELS_PER_UNIT / 2.;
+        half_width > 0. && self.path.closest_point(p).map_or(false, |location| location.position.distance(p) <= half_width)
+    }
+    pub fn save(&self, f: &mut io::Write) -> io::Result<()> {
+        format::write(f, &self.path, &self.style)
+    }
+    /// Exports this shape as a standalone SVG document.
+    /// `name` becomes the `id` of its `<path>`.
+    pub fn save_svg(&self, f: &mut io::Write, name: &str) -> io::Result<()> {
+        svg::write(f, &[(name, self, Mat4::identity())])
+    }
+    pub fn load(f: &mut io::Read) -> Result<Self, LoadError> {
+        let data = {
+            let mut buf = String::new();
+            f.read_to_string(&mut buf)?;
+            buf
+        };
+        let (path, style) = format::parse(&data)?;
+        Ok(Self { path, style })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn subpath(cmds: &[PathCmd]) -> SubPath {
+        SubPath { is_closed: false, cmds: cmds.to_vec(), node_styles: vec![], primitive: None }
+    }
+
+    fn cubic(scale: f32) -> SubPath {
+        let v = |x, y| Vec2::new(x, y) * scale;
+        subpath(&[
+            PathCmd::Start(v(0., 0.)),
+            PathCmd::Cubic { ctrl0: v(0., 1.), ctrl1: v(1., 1.), end: v(1., 0.) },
+        ])
+    }
+
+    fn quadratic(scale: f32) -> SubPath {
+        let v = |x, y| Vec2::new(x, y) * scale;
+        subpath(&[
+            PathCmd::Start(v(0., 0.)),
+            PathCmd::Quadratic { ctrl: v(0.5, 2.), end: v(1., 0.) },
+        ])
+    }
+
+    fn distance_to_segment(p: Vec2<f32>, a: Vec2<f32>, b: Vec2<f32>) -> f32 {
+        let ab = b - a;
+        let t = if ab == Vec2::zero() { 0. } else { ((p - a).dot(ab) / ab.dot(ab)).max(0.).min(1.) };
+        p.distance(a + ab * t)
+    }
+
+    // Samples the actual curve densely, and returns the largest distance to the polyline.
+    fn max_deviation(subpath: &SubPath, polyline: &[Vec2<f32>]) -> f32 {
+        let mut start = subpath.start();
+        let mut max = 0_f32;
+        for cmd in &subpath.cmds[1..] {
+            let eval: Box<Fn(f32) -> Vec2<f32>> = match *cmd {
+                PathCmd::Quadratic { ctrl, end } => {
+                    let c = QuadraticBezier2 { start, ctrl, end };
+                    Box::new(move |t| c.evaluate(t))
+                },
+                PathCmd::Cubic { ctrl0, ctrl1, end } => {
+                    let c = CubicBezier2 { start, ctrl0, ctrl1, end };
+                    Box::new(move |t| c.evaluate(t))
+                },
+                _ => unreachable!(),
+            };
+            for i in 0..1001 {
+                let p = eval(i as f32 / 1000.);
+                let d = polyline.windows(2).map(|w| distance_to_segment(p, w[0], w[1])).fold(::std::f32::INFINITY, f32::min);
+                max = max.max(d);
+            }
+            start = cmd.end();
+        }
+        max
+    }
+
+    #[test]
+    fn lines_are_not_subdivided() {
+        let s = subpath(&[
+            PathCmd::Start(Vec2::zero()),
+            PathCmd::Line { end: Vec2::new(100., 0.) },
+            PathCmd::Line { end: Vec2::new(100., 100.) },
+        ]);
+        assert_eq!(s.generate_vertex_positions(0.001).len(), 3);
+    }
+
+    #[test]
+    fn curves_end_exactly_at_their_end_point() {
+        for s in &[cubic(3.), quadratic(3.)] {
+            let v = s.generate_vertex_positions(0.01);
+            assert_eq!(v[0], s.start());
+            assert_eq!(*v.last().unwrap(), s.cmds[1].end());
+        }
+    }
+
+    #[test]
+    fn small_curves_get_fewer_vertices() {
+        let tolerance = 0.01;
+        let tiny = cubic(0.001).generate_vertex_positions(tolerance).len();
+        let small = cubic(1.).generate_vertex_positions(tolerance).len();
+        let big = cubic(100.).generate_vertex_positions(tolerance).len();
+        assert_eq!(tiny, 2);
+        assert!(tiny < small && small < big, "{} {} {}", tiny, small, big);
+        // The old fixed subdivision gave 33 vertices per curve, whatever its size.
+        assert!(small < 33);
+    }
+
+    #[test]
+    fn finer_tolerance_gives_more_vertices() {
+        let coarse = quadratic(1.).generate_vertex_positions(0.01).len();
+        let fine = quadratic(1.).generate_vertex_positions(0.01 / 4.).len();
+        // The segment count grows like 1/sqrt(tolerance).
+        assert!(fine > coarse);
+        assert!(fine - 1 <= 2 * (coarse - 1) + 1, "{} {}", coarse, fine);
+    }
+
+    #[test]
+    fn deviation_stays_within_tolerance() {
+        for &scale in &[0.1, 1., 10., 100.] {
+            for &tolerance in &[0.1, 0.01, 0.001] {
+                for s in &[cubic(scale), quadratic(scale)] {
+                    let v = s.generate_vertex_positions(tolerance);
+                    let d = max_deviation(s, &v);
+                    // Leave some room for f32 rounding of large coordinates.
+                    assert!(d <= tolerance + scale * 1e-5, "scale {}, tolerance {}: deviation {}", scale, tolerance, d);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn segment_count_is_capped() {
+        let v = cubic(1e6).generate_vertex_positions(1e-6);
+        assert_eq!(v.len(), MAX_SEGMENTS_PER_CURVE as usize + 1);
+    }
+
+    #[test]
+    fn quadratics_become_the_same_cubics() {
+        let mut s = quadratic(10.);
+        s.cmds.push(PathCmd::Quadratic { ctrl: Vec2::new(25., 20.), end: Vec2::new(0., 5.) });
+        s.is_closed = true;
+        let path = Path { subpaths: vec![s.clone()] };
+        let cubics = path.to_cubics();
+        let c = &cubics.subpaths[0];
+        assert_eq!(c.is_closed, true);
+        assert_eq!(c.cmds.len(), s.cmds.len());
+        for (cmd, original) in c.cmds.iter().zip(&s.cmds) {
+            match (cmd, original) {
+                (&PathCmd::Cubic { .. }, &PathCmd::Quadratic { .. }) |
+                (&PathCmd::Start(_), &PathCmd::Start(_)) => (),
+                _ => panic!("{:?} became {:?}", original, cmd),
+            }
+            assert_eq!(cmd.end(), original.end());
+        }
+        // Each curve goes through the points of the other.
+        let tolerance = 0.001;
+        let error = 1e-4;
+        assert!(max_deviation(&s, &c.generate_vertex_positions(tolerance)) <= tolerance + error);
+        assert!(max_deviation(c, &s.generate_vertex_positions(tolerance)) <= tolerance + error);
+    }
+
+    #[test]
+    fn stroke_varies_with_the_distance_along_the_path() {
+        let mut s = subpath(&[
+            PathCmd::Start(Vec2::zero()),
+            PathCmd::Line { end: Vec2::new(1., 0.) },
+            PathCmd::Line { end: Vec2::new(1., 3.) },
+        ]);
+        let mut style = Style::default();
+        style.stroke_thickness = 4.;
+        style.stroke_gradient = vec![
+            GradientStop { offset: 0., color: Rgba::black() },
+            GradientStop { offset: 1., color: Rgba::white() },
+        ];
+        let points = s.generate_stroke_points(0.01, &style);
+        assert_eq!(points[1].color, Rgba::new(0.25, 0.25, 0.25, 1.));
+        assert_eq!(points[2].color, Rgba::white());
+
+        s.node_style_mut(2).stroke_thickness = Some(8.);
+        let points = s.generate_stroke_points(0.01, &style);
+        let half_width = |t: f32| t / REFERENCE_PIXELS_PER_UNIT / 2.;
+        assert_eq!(points[0].half_width, half_width(4.));
+        assert_eq!(points[1].half_width, half_width(4.));
+        assert_eq!(points[2].half_width, half_width(8.));
+
+        // In between nodes, overrides fade out with the distance along the curve.
+        let mut s = cubic(1.);
+        s.node_style_mut(1).stroke_color = Some(Rgba::red());
+        let points = s.generate_stroke_points(0.001, &Style::default());
+        assert!(points.len() > 2);
+        let total: f32 = points.windows(2).map(|w| w[0].position.distance(w[1].position)).sum();
+        let mut length = 0.;
+        for (i, p) in points.iter().enumerate() {
+            if i > 0 {
+                length += points[i - 1].position.distance(p.position);
+            }
+            assert!((p.color.r - length / total).abs() < 1e-4, "{}: {:?}", i, p.color);
+        }
+    }
+
+    #[test]
+    fn stroke_tapers_at_the_ends_of_open_subpaths() {
+        let mut s = subpath(&[
+            PathCmd::Start(Vec2::zero()),
+            PathCmd::Line { end: Vec2::new(1., 0.) },
+            PathCmd::Line { end: Vec2::new(2., 0.) },
+            PathCmd::Line { end: Vec2::new(3., 0.) },
+            PathCmd::Line { end: Vec2::new(4., 0.) },
+        ]);
+        let mut style = Style::default();
+        style.stroke_thickness = 4.;
+        style.stroke_taper_start = REFERENCE_PIXELS_PER_UNIT * 2.;
+        style.stroke_taper_end = REFERENCE_PIXELS_PER_UNIT;
+        let half_width = 4. / REFERENCE_PIXELS_PER_UNIT / 2.;
+        let widths = |s: &SubPath| s.generate_stroke_points(0.01, &style).iter().map(|p| p.half_width).collect::<Vec<_>>();
+        assert_eq!(widths(&s), vec![0., half_width / 2., half_width, half_width, 0.]);
+
+        s.is_closed = true;
+        assert_eq!(widths(&s), vec![half_width; 5]);
+    }
+
+    #[test]
+    fn tolerance_is_a_power_of_two_within_the_pixel_bound() {
+        for &ppu in &[1., 3., 240., 241., 1000., 123456.] {
+            let t = flattening_tolerance(ppu);
+            assert_eq!(t.log2().fract(), 0.);
+            assert!(t * ppu <= FLATTENING_TOLERANCE_IN_PIXELS);
+            assert!(t * ppu > FLATTENING_TOLERANCE_IN_PIXELS / 2.);
+        }
+    }
+
+    #[test]
+    fn tolerance_only_changes_when_zooming_by_a_factor_of_two() {
+        assert_eq!(flattening_tolerance(300.), flattening_tolerance(400.));
+        assert_eq!(flattening_tolerance(300.), flattening_tolerance(512.));
+        assert_eq!(flattening_tolerance(300.) / 2., flattening_tolerance(600.));
+    }
+}
@@ -0,0 +1,167 @@
+// Offsetting of fills, i.e growing or shrinking them by a given distance,
+// for outlines and shadows.
+//
+// The fill is first turned into non-crossing loops with the inside on their
+// left, via `boolean`. Each loop is then moved sideways by the distance, with
+// round joins where it grows around a corner. Where it shrinks around a
+// corner, the offset edges are joined through the corner itself instead:
+// that makes small loops which wind the wrong way, and which disappear along
+// with everything else that got folded over when the area of positive
+// winding is taken at the end (as in Angus Johnson's Clipper library).
+
+use v::Vec2;
+use super::{Path, SubPath, PathCmd, FillRule};
+use super::boolean::{self, BooleanOp};
+
+// The right-hand normal of `d`, scaled by `distance`.
+fn outward(d: Vec2<f32>, distance: f32) -> Vec2<f32> {
+    Vec2::new(d.y, -d.x) * distance
+}
+
+fn cross(a: Vec2<f32>, b: Vec2<f32>) -> f32 {
+    a.x * b.y - a.y * b.x
+}
+
+// Appends the arc around `center` from `center + from`, turning by `angle`
+// radians, but not its start point.
+fn push_arc(points: &mut Vec<Vec2<f32>>, center: Vec2<f32>, from: Vec2<f32>, angle: f32, tolerance: f32) {
+    let radius = from.magnitude();
+    let max_step = if tolerance < radius {
+        2. * (1. - tolerance / radius).acos()
+    } else {
+        ::std::f32::consts::PI / 2.
+    };
+    let n = (angle.abs() / max_step).ceil().max(1.) as u32;
+    for i in 1 .. n + 1 {
+        let (sin, cos) = (angle * i as f32 / n as f32).sin_cos();
+        points.push(center + Vec2::new(from.x * cos - from.y * sin, from.x * sin + from.y * cos));
+    }
+}
+
+// `points` is a closed loop, without its start point repeated.
+fn offset_loop(points: &[Vec2<f32>], distance: f32, tolerance: f32) -> SubPath {
+    let n = points.len();
+    let direction = |i: usize| (points[(i + 1) % n] - points[i]).normalized();
+    let mut offset = vec![];
+    for i in 0 .. n {
+        let p = points[i];
+        let (d0, d1) = (direction((i + n - 1) % n), direction(i));
+        let (r0, r1) = (outward(d0, distance), outward(d1, distance));
+        let (sin, cos) = (cross(d0, d1), d0.dot(d1));
+        let is_straight = sin.abs() < 1e-6;
+        if is_straight && cos > 0. {
+            offset.push(p + r0);
+        } else if is_straight {
+            // Turning back: go around the end.
+            offset.push(p + r0);
+            push_arc(&mut offset, p, r0, ::std::f32::consts::PI * distance.signum(), tolerance);
+        } else if sin * distance > 0. {
+            offset.push(p + r0);
+            push_arc(&mut offset, p, r0, sin.atan2(cos), tolerance);
+        } else {
+            offset.push(p + r0);
+            offset.push(p);
+            offset.push(p + r1);
+        }
+    }
+    let mut subpath = SubPath::new(offset[0]);
+    subpath.cmds.extend(offset[1..].iter().map(|&end| PathCmd::Line { end }));
+    subpath.is_closed = true;
+    subpath
+}
+
+/// Grows the fill of `path` by `distance` all around, or shrinks it if
+/// `distance` is negative. Corners which grow get rounded.
+///
+/// The result is as described for `boolean::combine()`. `distance` and
+/// `tolerance` are in local units.
+pub fn offset(path: &Path, fill_rule: FillRule, distance: f32, tolerance: f32) -> Path {
+    let normalized = boolean::combine(path, fill_rule, &Path::default(), FillRule::EvenOdd, BooleanOp::Union, tolerance);
+    if distance == 0. {
+        return normalized;
+    }
+    let loops = normalized.subpaths.iter().map(|subpath| {
+        offset_loop(&subpath.generate_vertex_positions(tolerance), distance, tolerance)
+    }).collect();
+    boolean::positive_area(&Path { subpaths: loops }, tolerance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    const TOLERANCE: f32 = 1e-4;
+
+    fn signed_area(subpath: &SubPath) -> f32 {
+        let points = subpath.generate_vertex_positions(TOLERANCE);
+        let mut prev = points[points.len() - 1];
+        points.iter().map(|&p| {
+            let area = (prev.x * p.y - p.x * prev.y) / 2.;
+            prev = p;
+            area
+        }).sum()
+    }
+
+    fn area(path: &Path) -> f32 {
+        path.subpaths.iter().map(signed_area).sum()
+    }
+
+    // Counter-clockwise.
+    fn rect(min: f32, max: f32) -> Path {
+        let mut subpath = SubPath::new(Vec2::broadcast(min));
+        for &(x, y) in &[(max, min), (max, max), (min, max)] {
+            subpath.cmds.push(PathCmd::Line { end: Vec2::new(x, y) });
+        }
+        subpath.is_closed = true;
+        Path { subpaths: vec![subpath] }
+    }
+
+    fn square(side: f32) -> Path {
+        rect(0., side)
+    }
+
+    #[test]
+    fn grown_squares_have_round_corners() {
+        let (s, d) = (2., 0.25);
+        let grown = offset(&square(s), FillRule::NonZero, d, TOLERANCE);
+        assert_eq!(grown.subpaths.len(), 1);
+        let expected = (s + 2. * d) * (s + 2. * d) - (4. - PI) * d * d;
+        // Round corners are polygons inside the actual arcs.
+        let max_error = 2. * PI * d * TOLERANCE;
+        let area = area(&grown);
+        assert!(area <= expected && area >= expected - max_error, "{} {}", area, expected);
+        for p in grown.subpaths[0].generate_vertex_positions(TOLERANCE) {
+            let nearest = Vec2::new(p.x.max(0.).min(s), p.y.max(0.).min(s));
+            assert!((p.distance(nearest) - d).abs() < 1e-4, "{:?}", p);
+        }
+    }
+
+    #[test]
+    fn shrunk_squares_keep_sharp_corners() {
+        let shrunk = offset(&square(2.), FillRule::NonZero, -0.25, TOLERANCE);
+        assert_eq!(shrunk.subpaths.len(), 1);
+        assert!((area(&shrunk) - 1.5 * 1.5).abs() < 1e-5, "{}", area(&shrunk));
+        assert_eq!(shrunk.subpaths[0].cmds.len(), 4);
+
+        // Past half its size, nothing is left.
+        assert_eq!(offset(&square(2.), FillRule::NonZero, -1.01, TOLERANCE), Path::default());
+        assert_eq!(offset(&square(2.), FillRule::NonZero, -3., TOLERANCE), Path::default());
+        assert_eq!(area(&offset(&square(2.), FillRule::NonZero, 0., TOLERANCE)), 4.);
+    }
+
+    #[test]
+    fn holes_shrink_when_fills_grow() {
+        let mut donut = square(4.);
+        donut.subpaths.extend(rect(1., 3.).subpaths);
+        let grown = offset(&donut, FillRule::EvenOdd, 0.5, TOLERANCE);
+        assert_eq!(grown.subpaths.len(), 2);
+        let expected = 5. * 5. - (4. - PI) * 0.25 - 1.;
+        assert!((area(&grown) - expected).abs() < 1e-3, "{} {}", area(&grown), expected);
+
+        // The hole closes up.
+        let grown = offset(&donut, FillRule::EvenOdd, 1.01, TOLERANCE);
+        assert_eq!(grown.subpaths.len(), 1);
+        assert!(area(&grown) > 0.);
+    }
+}
@@ -0,0 +1,150 @@
+// Simple shapes described by a few parameters, rather than by their nodes.
+//
+// Each one generates a single closed subpath, counter-clockwise as are the
+// results of `boolean`. The subpath remembers which primitive it came from,
+// so that the `.shape` format can save the parameters instead (see
+// `SubPath::primitive`).
+
+use std::f32::consts::PI;
+use v::Vec2;
+use super::{Path, SubPath, PathCmd};
+
+/// How far along the tangents the control points of a quarter circle are,
+/// relative to its radius, for the cubic curve to stay closest to it.
+const QUARTER_CIRCLE_KAPPA: f32 = 0.552_284_8;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Primitive {
+    /// An axis-aligned rectangle, from its bottom-left to its top-right corner.
+    Rect { min: Vec2<f32>, max: Vec2<f32> },
+    /// Same as `Rect`, with corners rounded as quarter circles. The radius is
+    /// at most half of the smallest side.
+    RoundedRect { min: Vec2<f32>, max: Vec2<f32>, radius: f32 },
+    /// An axis-aligned ellipse, made of four cubic curves.
+    Ellipse { center: Vec2<f32>, radii: Vec2<f32> },
+    /// A polygon with at least 3 equal sides. `rotation_radians` is the angle of
+    /// its first vertex, counter-clockwise from +X.
+    RegularPolygon { center: Vec2<f32>, radius: f32, sides: u32, rotation_radians: f32 },
+    /// A star with at least 2 tips, at `outer_radius`, joined through
+    /// vertices at `inner_radius`. `rotation_radians` is the angle of its
+    /// first tip, counter-clockwise from +X.
+    Star { center: Vec2<f32>, outer_radius: f32, inner_radius: f32, points: u32, rotation_radians: f32 },
+}
+
+impl Primitive {
+    /// The keyword for this kind of primitive, in the `.shape` format and in editor commands.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Primitive::Rect { .. } => "rect",
+            Primitive::RoundedRect { .. } => "rounded_rect",
+            Primitive::Ellipse { .. } => "ellipse",
+            Primitive::RegularPolygon { .. } => "regular_polygon",
+            Primitive::Star { .. } => "star",
+        }
+    }
+    /// Generates the closed subpath for this primitive, which remembers it.
+    pub fn to_subpath(&self) -> SubPath {
+        let mut subpath = match *self {
+            Primitive::Rect { min, max } => polygon(&[min, Vec2::new(max.x, min.y), max, Vec2::new(min.x, max.y)]),
+            Primitive::RoundedRect { min, max, radius } => rounded_rect(min, max, radius),
+            Primitive::Ellipse { center, radii } => ellipse(center, radii),
+            Primitive::RegularPolygon { center, radius, sides, rotation_radians } => {
+                polygon(&(0 .. sides).map(|i| {
+                    center + direction(rotation_radians + 2. * PI * i as f32 / sides as f32) * radius
+                }).collect::<Vec<_>>())
+            },
+            Primitive::Star { center, outer_radius, inner_radius, points, rotation_radians } => {
+                polygon(&(0 .. points * 2).map(|i| {
+                    let radius = if i % 2 == 0 { outer_radius } else { inner_radius };
+                    center + direction(rotation_radians + PI * i as f32 / points as f32) * radius
+                }).collect::<Vec<_>>())
+            },
+        };
+        subpath.primitive = Some(*self);
+        subpath
+    }
+}
+
+fn direction(angle: f32) -> Vec2<f32> {
+    let (sin, cos) = angle.sin_cos();
+    Vec2::new(cos, sin)
+}
+
+fn polygon(vertices: &[Vec2<f32>]) -> SubPath {
+    let mut subpath = SubPath::new(vertices[0]);
+    subpath.cmds.extend(vertices[1..].iter().map(|&end| PathCmd::Line { end }));
+    subpath.is_closed = true;
+    subpath
+}
+
+// The quarter of an ellipse around `center`, from `center + from` to `center + to`.
+fn quarter_ellipse(center: Vec2<f32>, from: Vec2<f32>, to: Vec2<f32>) -> PathCmd {
+    PathCmd::Cubic {
+        ctrl0: center + from + to * QUARTER_CIRCLE_KAPPA,
+        ctrl1: center + to + from * QUARTER_CIRCLE_KAPPA,
+        end: center + to,
+    }
+}
+
+fn ellipse(center: Vec2<f32>, radii: Vec2<f32>) -> SubPath {
+    let (x, y) = (Vec2::new(radii.x, 0.), Vec2::new(0., radii.y));
+    let mut subpath = SubPath::new(center + x);
+    subpath.cmds.push(quarter_ellipse(center, x, y));
+    subpath.cmds.push(quarter_ellipse(center, y, -x));
+    subpath.cmds.push(quarter_ellipse(center, -x, -y));
+    subpath.cmds.push(quarter_ellipse(center, -y, x));
+    subpath.is_closed = true;
+    subpath
+}
+
+fn rounded_rect(min: Vec2<f32>, max: Vec2<f32>, radius: f32) -> SubPath {
+    let radius = radius.max(0.).min((max.x - min.x).abs() / 2.).min((max.y - min.y).abs() / 2.);
+    if radius <= 0. {
+        return polygon(&[min, Vec2::new(max.x, min.y), max, Vec2::new(min.x, max.y)]);
+    }
+    let (x, y) = (Vec2::new(radius, 0.), Vec2::new(0., radius));
+    // The center of each corner, counter-clockwise from the bottom-right
+    // one, with where its arc starts and ends relative to it.
+    let bottom_left = min + x + y;
+    let corners = [
+        (Vec2::new(max.x, min.y) - x + y, -y, x),
+        (max - x - y, x, y),
+        (Vec2::new(min.x, max.y) + x - y, y, -x),
+        (bottom_left, -x, -y),
+    ];
+    let mut subpath = SubPath::new(bottom_left - y);
+    for &(center, from, to) in corners.iter() {
+        // Sides vanish when the radius is half their length.
+        if subpath.cmds.last().unwrap().end() != center + from {
+            subpath.cmds.push(PathCmd::Line { end: center + from });
+        }
+        subpath.cmds.push(quarter_ellipse(center, from, to));
+    }
+    subpath.is_closed = true;
+    subpath
+}
+
+impl Path {
+    /// A path made of the single subpath generated by `primitive`.
+    pub fn from_primitive(primitive: Primitive) -> Self {
+        Self { subpaths: vec![primitive.to_subpath()] }
+    }
+    pub fn rect(min: Vec2<f32>, max: Vec2<f32>) -> Self {
+        Self::from_primitive(Primitive::Rect { min, max })
+    }
+    pub fn rounded_rect(min: Vec2<f32>, max: Vec2<f32>, radius: f32) -> Self {
+        Self::from_primitive(Primitive::RoundedRect { min, max, radius })
+    }
+    pub fn ellipse(center: Vec2<f32>, radii: Vec2<f32>) -> Self {
+        Self::from_primitive(Primitive::Ellipse { center, radii })
+    }
+    pub fn circle(center: Vec2<f32>, radius: f32) -> Self {
+        Self::ellipse(center, Vec2::broadcast(radius))
+    }
+    pub fn regular_polygon(center: Vec2<f32>, radius: f32, sides: u32, rotation_radians: f32) -> Self {
+        Self::from_primitive(Primitive::RegularPolygon { center, radius, sides, rotation_radians })
+    }
+    pub fn star(center: Vec2<f32>, outer_radius: f32, inner_radius: f32, points: u32, rotation_radians: f32) -> Self {
+        Self::from_primitive(Primitive::Star { center, outer_radius, inner_radius, points, rotation_radians })
+    }
+}
@@ -0,0 +1,355 @@
+// Clean-up of paths drawn by hand.
+//
+// `simplify()` drops the nodes which barely change the outline
+// (Ramer-Douglas-Peucker), and `fit_curves()` replaces runs of nodes by as
+// few cubic curves as stay within a given error of them (Philip J. Schneider,
+// "An Algorithm for Automatically Fitting Digitized Curves", Graphics Gems, 1990).
+//
+// Both flatten curves first, so they accept any path; what comes out is
+// made of lines and cubic curves respectively.
+
+use v::{Vec2, CubicBezier2};
+use super::{Path, SubPath, PathCmd};
+
+/// A run of points is split in two if fitting it takes more than this many tries.
+const MAX_REPARAMETERIZATIONS: usize = 4;
+/// Guards against endless splitting on degenerate input.
+const MAX_FIT_DEPTH: u32 = 32;
+
+fn distance_to_segment(p: Vec2<f32>, a: Vec2<f32>, b: Vec2<f32>) -> f32 {
+    let ab = b - a;
+    let t = if ab == Vec2::zero() { 0. } else { ((p - a).dot(ab) / ab.dot(ab)).max(0.).min(1.) };
+    p.distance(a + ab * t)
+}
+
+// The flattened subpath, without consecutive duplicates. For closed subpaths,
+// the start point is repeated at the end.
+fn polyline(subpath: &SubPath, tolerance: f32) -> Vec<Vec2<f32>> {
+    let mut points = subpath.generate_vertex_positions(tolerance);
+    if subpath.is_closed {
+        let start = points[0];
+        points.push(start);
+    }
+    points.dedup();
+    points
+}
+
+fn mark_kept_points(points: &[Vec2<f32>], epsilon: f32, is_kept: &mut [bool]) {
+    let (a, b) = (points[0], points[points.len() - 1]);
+    let farthest = points[1 .. points.len() - 1].iter().enumerate()
+        .map(|(i, &p)| (i + 1, distance_to_segment(p, a, b)))
+        .fold((0, 0.), |max, x| if x.1 > max.1 { x } else { max });
+    if farthest.1 > epsilon {
+        let i = farthest.0;
+        is_kept[i] = true;
+        mark_kept_points(&points[.. i + 1], epsilon, &mut is_kept[.. i + 1]);
+        mark_kept_points(&points[i ..], epsilon, &mut is_kept[i ..]);
+    }
+}
+
+/// Ramer-Douglas-Peucker: keeps as few of the points as possible, such that
+/// the dropped ones are at most `epsilon` away from the resulting polyline.
+/// The end points are always kept.
+pub fn simplify_polyline(points: &[Vec2<f32>], epsilon: f32) -> Vec<Vec2<f32>> {
+    if points.len() <= 2 {
+        return points.to_vec();
+    }
+    let mut is_kept = vec![false; points.len()];
+    is_kept[0] = true;
+    is_kept[points.len() - 1] = true;
+    mark_kept_points(points, epsilon, &mut is_kept);
+    points.iter().zip(is_kept).filter(|&(_, k)| k).map(|(&p, _)| p).collect()
+}
+
+/// Simplifies each subpath with `simplify_polyline()`, after flattening it
+/// within `tolerance`. Both are in local units.
+pub fn simplify(path: &Path, epsilon: f32, tolerance: f32) -> Path {
+    let subpaths = path.subpaths.iter().map(|subpath| {
+        let mut points = simplify_polyline(&polyline(subpath, tolerance), epsilon);
+        if subpath.is_closed && points.len() > 1 {
+            points.pop();
+        }
+        let mut s = SubPath::new(points[0]);
+        s.cmds.extend(points[1..].iter().map(|&end| PathCmd::Line { end }));
+        s.is_closed = subpath.is_closed;
+        s
+    }).collect();
+    Path { subpaths }
+}
+
+// The Bernstein polynomials of degree 3.
+fn bernstein(t: f32) -> [f32; 4] {
+    let s = 1. - t;
+    [s * s * s, 3. * s * s * t, 3. * s * t * t, t * t * t]
+}
+
+fn second_derivative(c: &CubicBezier2<f32>, t: f32) -> Vec2<f32> {
+    (c.ctrl1 - c.ctrl0 * 2. + c.start) * (6. * (1. - t)) + (c.end - c.ctrl1 * 2. + c.ctrl0) * (6. * t)
+}
+
+// Parameters for the points, proportional to the distance along them.
+fn chord_length_parameters(points: &[Vec2<f32>]) -> Vec<f32> {
+    let mut u = vec![0.];
+    for w in points.windows(2) {
+        let last = u[u.len() - 1];
+        u.push(last + w[0].distance(w[1]));
+    }
+    let total = u[u.len() - 1];
+    u.iter().map(|x| x / total).collect()
+}
+
+// The least-squares fit of a cubic curve to the points at parameters `u`, with
+// given tangents at both ends. `tangent1` points away from the first point;
+// `tangent2` points back from the last one.
+fn fit_cubic_with_tangents(points: &[Vec2<f32>], u: &[f32], tangent1: Vec2<f32>, tangent2: Vec2<f32>) -> CubicBezier2<f32> {
+    let (first, last) = (points[0], points[points.len() - 1]);
+    let (mut c, mut x) = ([[0_f32; 2]; 2], [0_f32; 2]);
+    for (&p, &t) in points.iter().zip(u) {
+        let b = bernstein(t);
+        let a = [tangent1 * b[1], tangent2 * b[2]];
+        c[0][0] += a[0].dot(a[0]);
+        c[0][1] += a[0].dot(a[1]);
+        c[1][1] += a[1].dot(a[1]);
+        let rest = p - (first * (b[0] + b[1]) + last * (b[2] + b[3]));
+        x[0] += a[0].dot(rest);
+        x[1] += a[1].dot(rest);
+    }
+    c[1][0] = c[0][1];
+    let det = c[0][0] * c[1][1] - c[1][0] * c[0][1];
+    let (alpha1, alpha2) = if det == 0. {
+        (0., 0.)
+    } else {
+        ((x[0] * c[1][1] - x[1] * c[0][1]) / det, (c[0][0] * x[1] - c[1][0] * x[0]) / det)
+    };
+    // Control points on the wrong side, or on top of the end points, make
+    // for a bad curve; fall back to Wu and Barsky's heuristic.
+    let length = first.distance(last);
+    let epsilon = 1e-6 * length;
+    let (alpha1, alpha2) = if alpha1 < epsilon || alpha2 < epsilon {
+        (length / 3., length / 3.)
+    } else {
+        (alpha1, alpha2)
+    };
+    CubicBezier2 { start: first, ctrl0: first + tangent1 * alpha1, ctrl1: last + tangent2 * alpha2, end: last }
+}
+
+// The largest distance between the points and the curve at their parameters,
+// and the index of the point where it happens.
+fn max_error(points: &[Vec2<f32>], u: &[f32], curve: &CubicBezier2<f32>) -> (f32, usize) {
+    let mut max = (0., points.len() / 2);
+    for i in 1 .. points.len() - 1 {
+        let d = curve.evaluate(u[i]).distance(points[i]);
+        if d > max.0 {
+            max = (d, i);
+        }
+    }
+    max
+}
+
+// One step of Newton-Raphson for each parameter, towards the point of the
+// curve which is closest to its point.
+fn reparameterize(points: &[Vec2<f32>], u: &[f32], curve: &CubicBezier2<f32>) -> Vec<f32> {
+    points.iter().zip(u).map(|(&p, &t)| {
+        let d = curve.evaluate(t) - p;
+        let d1 = curve.evaluate_derivative(t);
+        let d2 = second_derivative(curve, t);
+        let denominator = d1.dot(d1) + d.dot(d2);
+        if denominator == 0. { t } else { t - d.dot(d1) / denominator }
+    }).collect()
+}
+
+fn fit_cubics_recursive(points: &[Vec2<f32>], tangent1: Vec2<f32>, tangent2: Vec2<f32>, error: f32, depth: u32, curves: &mut Vec<CubicBezier2<f32>>) {
+    let (first, last) = (points[0], points[points.len() - 1]);
+    if points.len() == 2 {
+        let d = first.distance(last) / 3.;
+        curves.push(CubicBezier2 { start: first, ctrl0: first + tangent1 * d, ctrl1: last + tangent2 * d, end: last });
+        return;
+    }
+    let mut u = chord_length_parameters(points);
+    let mut curve = fit_cubic_with_tangents(points, &u, tangent1, tangent2);
+    let (mut max, mut split) = max_error(points, &u, &curve);
+    if max <= error || depth == 0 {
+        curves.push(curve);
+        return;
+    }
+    // Close enough to be worth improving the parameters.
+    if max <= error * 4. {
+        for _ in 0 .. MAX_REPARAMETERIZATIONS {
+            u = reparameterize(points, &u, &curve);
+            curve = fit_cubic_with_tangents(points, &u, tangent1, tangent2);
+            let (m, s) = max_error(points, &u, &curve);
+            max = m;
+            split = s;
+            if max <= error {
+                curves.push(curve);
+                return;
+            }
+        }
+    }
+    // Split at the worst point, keeping the joint smooth.
+    let center = (points[split - 1] - points[split + 1]).normalized();
+    fit_cubics_recursive(&points[.. split + 1], tangent1, center, error, depth - 1, curves);
+    fit_cubics_recursive(&points[split ..], -center, tangent2, error, depth - 1, curves);
+}
+
+/// The single cubic curve which is closest to the points, from the first one
+/// to the last one. `tangent1` is the direction in which it leaves the first
+/// point, and `tangent2` the direction back from the last one; both are normalized.
+pub fn fit_cubic(points: &[Vec2<f32>], tangent1: Vec2<f32>, tangent2: Vec2<f32>) -> CubicBezier2<f32> {
+    let mut u = chord_length_parameters(points);
+    let mut curve = fit_cubic_with_tangents(points, &u, tangent1, tangent2);
+    // With a single curve to get right, it pays to keep going for longer.
+    for _ in 0 .. MAX_REPARAMETERIZATIONS * 8 {
+        u = reparameterize(points, &u, &curve);
+        curve = fit_cubic_with_tangents(points, &u, tangent1, tangent2);
+    }
+    curve
+}
+
+/// Fits a chain of cubic curves through the points, which are at most
+/// `error` away from it. For a closed polyline, the last point must be the
+/// same as the first one, and the chain is smooth there as well.
+///
+/// Consecutive points must be distinct.
+pub fn fit_cubics(points: &[Vec2<f32>], is_closed: bool, error: f32) -> Vec<CubicBezier2<f32>> {
+    let n = points.len();
+    if n < 2 {
+        return vec![];
+    }
+    let (tangent1, tangent2) = if is_closed && n > 2 {
+        let t = (points[1] - points[n - 2]).normalized();
+        (t, -t)
+    } else {
+        ((points[1] - points[0]).normalized(), (points[n - 2] - points[n - 1]).normalized())
+    };
+    let mut curves = vec![];
+    fit_cubics_recursive(points, tangent1, tangent2, error, MAX_FIT_DEPTH, &mut curves);
+    curves
+}
+
+/// Replaces each subpath by a chain of cubic curves, fitted with
+/// `fit_cubics()` after flattening it within `tolerance`. Both are in local units.
+pub fn fit_curves(path: &Path, error: f32, tolerance: f32) -> Path {
+    let subpaths = path.subpaths.iter().map(|subpath| {
+        let points = polyline(subpath, tolerance);
+        let mut s = SubPath::new(points[0]);
+        s.cmds.extend(fit_cubics(&points, subpath.is_closed, error).into_iter().map(|c| PathCmd::Cubic {
+            ctrl0: c.ctrl0, ctrl1: c.ctrl1, end: c.end,
+        }));
+        s.is_closed = subpath.is_closed;
+        s
+    }).collect();
+    Path { subpaths }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    // A wobbly line, as drawn by hand.
+    fn wave(n: usize) -> Vec<Vec2<f32>> {
+        (0..n).map(|i| {
+            let x = i as f32 / (n - 1) as f32 * 4.;
+            Vec2::new(x, (x * 3.).sin() * 0.5 + (x * 17.).sin() * 0.02)
+        }).collect()
+    }
+
+    fn circle(n: usize) -> Vec<Vec2<f32>> {
+        (0 .. n + 1).map(|i| {
+            let (sin, cos) = (2. * PI * (i % n) as f32 / n as f32).sin_cos();
+            Vec2::new(cos, sin)
+        }).collect()
+    }
+
+    fn distance_to_polyline(p: Vec2<f32>, polyline: &[Vec2<f32>]) -> f32 {
+        polyline.windows(2).map(|w| distance_to_segment(p, w[0], w[1])).fold(::std::f32::INFINITY, f32::min)
+    }
+
+    fn distance_to_curves(p: Vec2<f32>, curves: &[CubicBezier2<f32>]) -> f32 {
+        let mut min = ::std::f32::INFINITY;
+        for c in curves {
+            let mut prev = c.start;
+            for i in 1 .. 201 {
+                let next = c.evaluate(i as f32 / 200.);
+                min = min.min(distance_to_segment(p, prev, next));
+                prev = next;
+            }
+        }
+        min
+    }
+
+    #[test]
+    fn simplified_polylines_stay_within_epsilon() {
+        let points = wave(400);
+        for &epsilon in &[0.001, 0.01, 0.05, 0.2] {
+            let simplified = simplify_polyline(&points, epsilon);
+            assert!(simplified.len() < points.len());
+            assert_eq!(simplified[0], points[0]);
+            assert_eq!(simplified[simplified.len() - 1], points[points.len() - 1]);
+            // The kept points are some of the points, in order.
+            let mut rest = points.iter();
+            assert!(simplified.iter().all(|p| rest.any(|q| q == p)));
+            for &p in &points {
+                let d = distance_to_polyline(p, &simplified);
+                assert!(d <= epsilon, "{:?} is {} away, for {}", p, d, epsilon);
+            }
+        }
+        // A larger epsilon keeps fewer points.
+        assert!(simplify_polyline(&points, 0.2).len() < simplify_polyline(&points, 0.01).len());
+        assert_eq!(simplify_polyline(&points, 10.), vec![points[0], points[points.len() - 1]]);
+    }
+
+    #[test]
+    fn simplifying_keeps_what_matters() {
+        let v = |x, y| Vec2::new(x, y);
+        assert_eq!(simplify_polyline(&[v(0., 0.), v(1., 1.)], 1.), vec![v(0., 0.), v(1., 1.)]);
+        let corner = [v(0., 0.), v(1., 0.01), v(2., 0.), v(2., 1.), v(2., 2.)];
+        assert_eq!(simplify_polyline(&corner, 0.1), vec![v(0., 0.), v(2., 0.), v(2., 2.)]);
+        assert_eq!(simplify_polyline(&corner, 0.), vec![v(0., 0.), v(1., 0.01), v(2., 0.), v(2., 2.)]);
+
+        // Closed subpaths stay closed, and keep their start point.
+        let mut square = SubPath::new(v(0., 0.));
+        for &end in &[v(1., 0.), v(2., 0.), v(2., 2.), v(0., 2.)] {
+            square.cmds.push(PathCmd::Line { end });
+        }
+        square.is_closed = true;
+        let simplified = simplify(&Path { subpaths: vec![square] }, 0.01, 0.01);
+        assert!(simplified.subpaths[0].is_closed);
+        assert_eq!(simplified.subpaths[0].generate_vertex_positions(0.01), vec![v(0., 0.), v(2., 0.), v(2., 2.), v(0., 2.)]);
+    }
+
+    #[test]
+    fn fitted_curves_stay_within_the_error() {
+        let points = wave(200);
+        for &error in &[0.005, 0.02, 0.1] {
+            let curves = fit_cubics(&points, false, error);
+            assert!(!curves.is_empty() && curves.len() < points.len() / 4, "{} curves for {}", curves.len(), error);
+            assert_eq!(curves[0].start, points[0]);
+            assert_eq!(curves[curves.len() - 1].end, points[points.len() - 1]);
+            for pair in curves.windows(2) {
+                assert_eq!(pair[0].end, pair[1].start);
+            }
+            for &p in &points {
+                let d = distance_to_curves(p, &curves);
+                assert!(d <= error * 1.001, "{:?} is {} away, for {}", p, d, error);
+            }
+        }
+    }
+
+    #[test]
+    fn closed_fits_are_smooth_where_they_close() {
+        let points = circle(48);
+        let curves = fit_cubics(&points, true, 0.001);
+        let (first, last) = (curves[0], curves[curves.len() - 1]);
+        assert_eq!(first.start, last.end);
+        let (out, back) = ((first.ctrl0 - first.start).normalized(), (last.end - last.ctrl1).normalized());
+        assert!(out.x * back.y - out.y * back.x < 1e-4 && out.dot(back) > 0.99, "{:?} {:?}", out, back);
+        for &p in &points {
+            assert!(distance_to_curves(p, &curves) <= 0.001 * 1.001);
+        }
+        assert!(curves.len() < points.len() / 3, "{}", curves.len());
+
+        assert!(fit_cubics(&points[..1], true, 0.1).is_empty());
+    }
+}
@@ -0,0 +1,478 @@
+// Stroke tessellation.
+//
+// Strokes are turned into triangle strips on the CPU, since wide lines
+// (`glLineWidth()` above 1) are clamped to 1 pixel by core profile drivers.
+//
+// A strip is a sequence of cross sections, i.e pairs of points on the left
+// and on the right of the centerline. Joins are extra cross sections which
+// pivot around the centerline point, and round caps are cross sections which
+// shrink down to the tip.
+//
+// The width and color may vary from one point to the next; each cross section
+// takes those of the point it belongs to.
+
+use std::f32::consts::PI;
+use std::ops::Range;
+use v::{Vec2, Rgba};
+use mesh::color_mesh::Vertex;
+use super::{Style, StrokeJoin, StrokeCap, REFERENCE_PIXELS_PER_UNIT};
+
+/// A single round join or cap is never split into more segments than this.
+const MAX_ARC_SEGMENTS: usize = 64;
+
+/// Dashes and gaps are at least this long, in local units.
+const MIN_DASH_LENGTH: f32 = 1e-4;
+
+/// Paths which would have more dashes than this get a solid stroke instead.
+const MAX_DASHES_PER_PATH: usize = 10_000;
+
+fn left_normal(d: Vec2<f32>) -> Vec2<f32> {
+    Vec2::new(-d.y, d.x)
+}
+
+fn rotated(v: Vec2<f32>, radians: f32) -> Vec2<f32> {
+    let (sin, cos) = radians.sin_cos();
+    Vec2::new(cos * v.x - sin * v.y, sin * v.x + cos * v.y)
+}
+
+/// A point of a polyline to be stroked.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct StrokePoint {
+    pub position: Vec2<f32>,
+    /// Half the stroke's thickness at this point, in local units.
+    pub half_width: f32,
+    pub color: Rgba<f32>,
+}
+
+impl StrokePoint {
+    fn lerp(a: Self, b: Self, t: f32) -> Self {
+        Self {
+            position: a.position * (1. - t) + b.position * t,
+            half_width: a.half_width * (1. - t) + b.half_width * t,
+            color: a.color * (1. - t) + b.color * t,
+        }
+    }
+}
+
+struct Stroker<'a> {
+    style: &'a Style,
+    // Those of the point whose cross sections are being added.
+    half_width: f32,
+    color: Rgba<f32>,
+    tolerance: f32,
+    vertices: Vec<Vertex>,
+    strips: Vec<Range<usize>>,
+    strip_start: usize,
+}
+
+impl<'a> Stroker<'a> {
+    fn begin_strip(&mut self) {
+        self.strip_start = self.vertices.len();
+    }
+    fn end_strip(&mut self) {
+        // At least two cross sections are needed to cover anything.
+        if self.vertices.len() - self.strip_start >= 4 {
+            self.strips.push(self.strip_start .. self.vertices.len());
+        } else {
+            self.vertices.truncate(self.strip_start);
+        }
+    }
+    fn section(&mut self, left: Vec2<f32>, right: Vec2<f32>) {
+        let color = self.color;
+        self.vertices.push(Vertex { position: left.into(), color });
+        self.vertices.push(Vertex { position: right.into(), color });
+    }
+    fn set_point(&mut self, point: &StrokePoint) {
+        self.half_width = point.half_width;
+        self.color = point.color;
+    }
+    // How many segments an arc of the stroke's radius needs, for it to stay within tolerance.
+    fn arc_segment_count(&self, radians: f32) -> usize {
+        let h = self.half_width;
+        if h <= self.tolerance {
+            return 1;
+        }
+        let step = 2. * (1. - self.tolerance / h).acos();
+        ((radians.abs() / step).ceil() as usize).max(1).min(MAX_ARC_SEGMENTS)
+    }
+    // `d` is the direction of the path at `p`; the cap extends away from the path.
+    fn cap(&mut self, p: Vec2<f32>, d: Vec2<f32>, is_start: bool) {
+        let h = self.half_width;
+        let n = left_normal(d) * h;
+        let outwards = (if is_start { -d } else { d }) * h;
+        match self.style.stroke_cap {
+            StrokeCap::Butt => self.section(p + n, p - n),
+            StrokeCap::Square => if is_start {
+                self.section(p + outwards + n, p + outwards - n);
+                self.section(p + n, p - n);
+            } else {
+                self.section(p + n, p - n);
+                self.section(p + outwards + n, p + outwards - n);
+            },
+            StrokeCap::Round => {
+                // From the side (a = pi/2) to the tip (a = 0), or the other way around.
+                let count = self.arc_segment_count(PI / 2.);
+                for i in 0 .. count + 1 {
+                    let i = if is_start { i } else { count - i };
+                    let (sin, cos) = (PI / 2. * i as f32 / count as f32).sin_cos();
+                    self.section(p + outwards * cos + n * sin, p + outwards * cos - n * sin);
+                }
+            },
+        }
+    }
+    // Joins the segment arriving at `p` with direction `d0`, to the one leaving with direction `d1`.
+    fn join(&mut self, p: Vec2<f32>, d0: Vec2<f32>, d1: Vec2<f32>) {
+        let h = self.half_width;
+        let n0 = left_normal(d0);
+        let n1 = left_normal(d1);
+        self.section(p + n0 * h, p - n0 * h);
+
+        let cross = d0.x * d1.y - d0.y * d1.x;
+        let dot = d0.dot(d1);
+        let is_straight = cross.abs() <= 1e-6 && dot > 0.;
+        // The outer corner is on the left when turning right, and vice versa.
+        let side = if cross < 0. { 1. } else { -1. };
+        let pivot = |this: &mut Self, outer: Vec2<f32>| if side > 0. {
+            this.section(outer, p);
+        } else {
+            this.section(p, outer);
+        };
+
+        if !is_straight {
+            match self.style.stroke_join {
+                // Covered by the triangles between the two cross sections.
+                StrokeJoin::Bevel => (),
+                StrokeJoin::Miter => {
+                    let m = n0 + n1;
+                    let cos_half_turn = if m.magnitude() > 1e-6 { m.normalized().dot(n0) } else { 0. };
+                    if cos_half_turn > 0. && 1. / cos_half_turn <= self.style.stroke_miter_limit {
+                        let tip = p + m.normalized() * (side * h / cos_half_turn);
+                        pivot(self, tip);
+                    }
+                },
+                StrokeJoin::Round => {
+                    let turn = cross.atan2(dot);
+                    let count = self.arc_segment_count(turn);
+                    for i in 1..count {
+                        let outer = p + rotated(n0 * side, turn * i as f32 / count as f32) * h;
+                        pivot(self, outer);
+                    }
+                },
+            }
+        }
+
+        self.section(p + n1 * h, p - n1 * h);
+    }
+    fn stroke(&mut self, points: &[StrokePoint], is_closed: bool) {
+        // Zero-length segments have no direction, so drop them.
+        let epsilon = self.tolerance * 1e-3;
+        let mut points = points.to_vec();
+        points.dedup_by(|a, b| a.position.distance(b.position) <= epsilon);
+        if is_closed && points.len() > 1 && points[0].position.distance(points.last().unwrap().position) <= epsilon {
+            points.pop();
+        }
+        let n = points.len();
+        if n == 0 || (n == 1 && is_closed) || points.iter().all(|p| p.half_width <= 0.) {
+            return;
+        }
+
+        self.begin_strip();
+        if n == 1 {
+            // A dot; only visible with round or square caps.
+            self.set_point(&points[0]);
+            self.cap(points[0].position, Vec2::unit_x(), true);
+            self.cap(points[0].position, Vec2::unit_x(), false);
+            self.end_strip();
+            return;
+        }
+
+        let direction = |i: usize| (points[(i + 1) % n].position - points[i].position).normalized();
+        if is_closed {
+            let d = direction(0);
+            self.set_point(&points[0]);
+            let normal = left_normal(d) * self.half_width;
+            self.section(points[0].position + normal, points[0].position - normal);
+            for i in 1..n {
+                self.set_point(&points[i]);
+                self.join(points[i].position, direction(i - 1), direction(i));
+            }
+            self.set_point(&points[0]);
+            self.join(points[0].position, direction(n - 1), d);
+        } else {
+            self.set_point(&points[0]);
+            self.cap(points[0].position, direction(0), true);
+            for i in 1 .. n-1 {
+                self.set_point(&points[i]);
+                self.join(points[i].position, direction(i - 1), direction(i));
+            }
+            self.set_point(&points[n - 1]);
+            self.cap(points[n - 1].position, direction(n - 2), false);
+        }
+        self.end_strip();
+    }
+}
+
+// Splits a polyline along a dash pattern, returning the "on" parts as open
+// polylines, or `None` if the pattern doesn't actually make dashes, or would
+// make more than `max_dashes` of them.
+fn dash(polyline: &[StrokePoint], is_closed: bool, pattern: &[f32], offset: f32, max_dashes: usize) -> Option<Vec<Vec<StrokePoint>>> {
+    let mut pattern = pattern.to_vec();
+    // As in SVG, an odd number of lengths is repeated to yield an even number.
+    if pattern.len() % 2 == 1 {
+        pattern.extend_from_slice(&pattern.clone());
+    }
+    if polyline.is_empty() || pattern.is_empty() || pattern.iter().sum::<f32>() <= 0. || pattern.iter().any(|x| x.is_nan() || *x < 0.) {
+        return None;
+    }
+    for x in pattern.iter_mut() {
+        *x = x.max(MIN_DASH_LENGTH);
+    }
+    let total: f32 = pattern.iter().sum();
+
+    let mut points = polyline.to_vec();
+    if is_closed {
+        points.push(polyline[0]);
+    }
+    // This also keeps the loops below from stalling on lengths which are
+    // too small to make a difference to the distance along the polyline.
+    let length: f32 = points.windows(2).map(|w| w[0].position.distance(w[1].position)).sum();
+    if (length / total).ceil() * (pattern.len() / 2) as f32 > max_dashes as f32 {
+        return None;
+    }
+
+    // Where we are in the pattern: an index, and how much is left of it.
+    let mut i = 0;
+    let mut left = pattern[0];
+    let mut skip = ((offset % total) + total) % total;
+    while skip > 0. {
+        if skip >= left {
+            skip -= left;
+            i = (i + 1) % pattern.len();
+            left = pattern[i];
+        } else {
+            left -= skip;
+            skip = 0.;
+        }
+    }
+
+    let mut runs = vec![];
+    let mut current = if i % 2 == 0 { vec![points[0]] } else { vec![] };
+    for w in points.windows(2) {
+        let (a, b) = (w[0], w[1]);
+        let length = a.position.distance(b.position);
+        let mut remaining = length;
+        if remaining <= 0. {
+            continue;
+        }
+        while remaining > left {
+            remaining -= left;
+            let point = StrokePoint::lerp(a, b, 1. - remaining / length);
+            if i % 2 == 0 {
+                current.push(point);
+                runs.push(::std::mem::replace(&mut current, vec![]));
+            } else {
+                current = vec![point];
+            }
+            i = (i + 1) % pattern.len();
+            left = pattern[i];
+        }
+        left -= remaining;
+        if i % 2 == 0 {
+            current.push(b);
+        }
+    }
+    if i % 2 == 0 && !current.is_empty() {
+        runs.push(current);
+    }
+    Some(runs)
+}
+
+/// Tessellates the stroke of the given polylines (each with whether it is
+/// closed) into triangle strips.
+///
+/// Returns the vertices of all strips one after the other, and the range of
+/// each strip. `tolerance` is in local units, and bounds the error of round
+/// joins and caps. Widths and colors are taken from the points, not from `style`.
+pub fn tessellate(polylines: &[(Vec<StrokePoint>, bool)], style: &Style, tolerance: f32) -> (Vec<Vertex>, Vec<Range<usize>>) {
+    let mut stroker = Stroker {
+        style,
+        half_width: 0.,
+        color: Rgba::zero(),
+        tolerance,
+        vertices: vec![],
+        strips: vec![],
+        strip_start: 0,
+    };
+    let dashes = style.stroke_dashes.iter().map(|&x| x / REFERENCE_PIXELS_PER_UNIT).collect::<Vec<_>>();
+    let dash_offset = style.stroke_dash_offset / REFERENCE_PIXELS_PER_UNIT;
+    let mut dashes_left = MAX_DASHES_PER_PATH;
+    for &(ref points, is_closed) in polylines {
+        match dash(points, is_closed, &dashes, dash_offset, dashes_left) {
+            None => stroker.stroke(points, is_closed),
+            Some(runs) => {
+                dashes_left -= runs.len().min(dashes_left);
+                for run in runs {
+                    stroker.stroke(&run, false);
+                }
+            },
+        }
+    }
+    (stroker.vertices, stroker.strips)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v(x: f32, y: f32) -> Vec2<f32> {
+        Vec2::new(x, y)
+    }
+
+    fn points(positions: &[(f32, f32)]) -> Vec<StrokePoint> {
+        positions.iter().map(|&(x, y)| StrokePoint { position: v(x, y), half_width: 0.5, color: Rgba::black() }).collect()
+    }
+
+    fn positions(vertices: &[Vertex]) -> Vec<Vec2<f32>> {
+        vertices.iter().map(|v| Vec2::new(v.position.x, v.position.y)).collect()
+    }
+
+    fn style(join: StrokeJoin, cap: StrokeCap) -> Style {
+        Style { stroke_join: join, stroke_cap: cap, .. Style::default() }
+    }
+
+    // The start and end of each dash along the X axis, to 4 decimal places.
+    fn dash_extents(runs: &[Vec<StrokePoint>]) -> Vec<(f32, f32)> {
+        let round = |p: &StrokePoint| (p.position.x * 1e4).round() / 1e4;
+        runs.iter().map(|run| (round(&run[0]), round(run.last().unwrap()))).collect()
+    }
+
+    #[test]
+    fn straight_segments_are_as_wide_as_the_stroke() {
+        let line = vec![(points(&[(0., 0.), (2., 0.)]), false)];
+        let (vertices, strips) = tessellate(&line, &style(StrokeJoin::Miter, StrokeCap::Butt), 0.01);
+        assert_eq!(strips, vec![0..4]);
+        assert_eq!(positions(&vertices), vec![v(0., 0.5), v(0., -0.5), v(2., 0.5), v(2., -0.5)]);
+
+        let (vertices, _) = tessellate(&line, &style(StrokeJoin::Miter, StrokeCap::Square), 0.01);
+        assert_eq!(positions(&vertices), vec![
+            v(-0.5, 0.5), v(-0.5, -0.5), v(0., 0.5), v(0., -0.5),
+            v(2., 0.5), v(2., -0.5), v(2.5, 0.5), v(2.5, -0.5),
+        ]);
+
+        let (vertices, _) = tessellate(&line, &style(StrokeJoin::Miter, StrokeCap::Round), 0.01);
+        let vertices = positions(&vertices);
+        assert!(vertices.len() > 8);
+        let (first, last) = (vertices[0], vertices[vertices.len() - 1]);
+        assert!(first.distance(v(-0.5, 0.)) < 1e-6 && last.distance(v(2.5, 0.)) < 1e-6, "{:?} {:?}", first, last);
+        for p in vertices {
+            let center = if p.x < 1. { v(0., 0.) } else { v(2., 0.) };
+            assert!(p.x >= 0. && p.x <= 2. || (p.distance(center) - 0.5).abs() < 1e-5, "{:?}", p);
+        }
+    }
+
+    #[test]
+    fn miters_become_bevels_above_the_limit() {
+        // A right angle has a miter ratio of sqrt(2).
+        let corner = vec![(points(&[(0., 0.), (1., 0.), (1., 1.)]), false)];
+        let mut miter = style(StrokeJoin::Miter, StrokeCap::Butt);
+        miter.stroke_miter_limit = 1.5;
+        let (vertices, _) = tessellate(&corner, &miter, 0.01);
+        let vertices = positions(&vertices);
+        assert_eq!(vertices.len(), 10);
+        // Turning left, the outer corner is on the right.
+        assert!(vertices.iter().any(|p| p.distance(v(1.5, -0.5)) < 1e-6), "{:?}", vertices);
+
+        miter.stroke_miter_limit = 1.4;
+        let (vertices, _) = tessellate(&corner, &miter, 0.01);
+        assert_eq!(vertices.len(), 8);
+        let (bevel, _) = tessellate(&corner, &style(StrokeJoin::Bevel, StrokeCap::Butt), 0.01);
+        assert_eq!(positions(&vertices), positions(&bevel));
+
+        // Straight on, there is no join at all.
+        let straight = vec![(points(&[(0., 0.), (1., 0.), (2., 0.)]), false)];
+        assert_eq!(tessellate(&straight, &miter, 0.01).0.len(), 8);
+    }
+
+    #[test]
+    fn round_joins_stay_on_the_circle() {
+        let corner = vec![(points(&[(0., 0.), (1., 0.), (1., -1.)]), false)];
+        let (vertices, _) = tessellate(&corner, &style(StrokeJoin::Round, StrokeCap::Butt), 0.001);
+        let vertices = positions(&vertices);
+        assert!(vertices.len() > 10);
+        let corner = v(1., 0.);
+        for p in &vertices[4 .. vertices.len() - 4] {
+            let d = p.distance(corner);
+            assert!(d < 1e-6 || (d - 0.5).abs() < 1e-5, "{:?}", p);
+        }
+    }
+
+    #[test]
+    fn closed_polylines_join_their_ends() {
+        let square = vec![(points(&[(0., 0.), (1., 0.), (1., 1.), (0., 1.), (0., 0.)]), true)];
+        let (vertices, strips) = tessellate(&square, &style(StrokeJoin::Bevel, StrokeCap::Round), 0.01);
+        // One cross section to begin with, then two per corner.
+        assert_eq!(strips, vec![0..18]);
+        let vertices = positions(&vertices);
+        assert_eq!(vertices[0], vertices[16]);
+        assert!(tessellate(&[(points(&[(1., 1.)]), true)], &Style::default(), 0.01).1.is_empty());
+    }
+
+    #[test]
+    fn dashes_follow_the_pattern_and_offset() {
+        let line = points(&[(0., 0.), (4., 0.), (10., 0.)]);
+        let runs = dash(&line, false, &[2., 1.], 0.5, MAX_DASHES_PER_PATH).unwrap();
+        assert_eq!(dash_extents(&runs), vec![(0., 1.5), (2.5, 4.5), (5.5, 7.5), (8.5, 10.)]);
+        // Dashes keep the corners they go through.
+        assert_eq!(runs[1].len(), 3);
+
+        let runs = dash(&line, false, &[2., 1.], -1., MAX_DASHES_PER_PATH).unwrap();
+        assert_eq!(dash_extents(&runs), vec![(1., 3.), (4., 6.), (7., 9.)]);
+
+        // An odd number of lengths is repeated.
+        let runs = dash(&line, false, &[3.], 7., MAX_DASHES_PER_PATH).unwrap();
+        assert_eq!(dash_extents(&runs), vec![(0., 2.), (5., 8.)]);
+
+        assert_eq!(dash(&line, false, &[], 0., MAX_DASHES_PER_PATH), None);
+        assert_eq!(dash(&line, false, &[0., 0.], 0., MAX_DASHES_PER_PATH), None);
+        assert_eq!(dash(&line, false, &[2., -1.], 0., MAX_DASHES_PER_PATH), None);
+    }
+
+    #[test]
+    fn dashes_of_closed_polylines_go_around() {
+        let square = points(&[(0., 0.), (1., 0.), (1., 1.), (0., 1.)]);
+        let runs = dash(&square, true, &[1.5, 0.5], 0., MAX_DASHES_PER_PATH).unwrap();
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[1][0].position, v(1., 1.));
+        assert_eq!(runs[1].last().unwrap().position, v(0., 0.5));
+
+        // Dash lengths are in pixels.
+        let mut dashed = style(StrokeJoin::Miter, StrokeCap::Butt);
+        dashed.stroke_dashes = vec![1.5 * REFERENCE_PIXELS_PER_UNIT, 0.5 * REFERENCE_PIXELS_PER_UNIT];
+        let (_, strips) = tessellate(&[(square, true)], &dashed, 0.01);
+        assert_eq!(strips.len(), 2);
+    }
+
+    #[test]
+    fn tiny_dashes_are_clamped_or_make_a_solid_stroke() {
+        let line = points(&[(0., 0.), (1., 0.)]);
+        // Zero-length dashes are still there, e.g for round caps to make dots.
+        let runs = dash(&line, false, &[0., 0.25], 0., MAX_DASHES_PER_PATH).unwrap();
+        assert_eq!(dash_extents(&runs), vec![(0., 0.0001), (0.2501, 0.2502), (0.5002, 0.5003), (0.7503, 0.7504)]);
+        assert_eq!(dash(&line, false, &[1e-9, 1e-9], 0., MAX_DASHES_PER_PATH).unwrap().len(), 5000);
+        let long_line = points(&[(0., 0.), (10., 0.)]);
+        assert_eq!(dash(&long_line, false, &[1e-9, 1e-9], 0., MAX_DASHES_PER_PATH), None);
+        assert_eq!(dash(&line, false, &[0.25, 0.25], 0., 1), None);
+
+        // Far from the origin, such lengths wouldn't even move along the line.
+        let far = points(&[(1e6, 0.), (2e6, 0.)]);
+        let mut dashed = style(StrokeJoin::Miter, StrokeCap::Butt);
+        dashed.stroke_dashes = vec![1e-9, 1e-9];
+        assert_eq!(tessellate(&[(far, false)], &dashed, 0.01).1.len(), 1);
+
+        // The limit is for the whole path.
+        let lines: Vec<_> = (0 .. 3).map(|i| (points(&[(0., i as f32), (4000., i as f32)]), false)).collect();
+        dashed.stroke_dashes = vec![0.5 * REFERENCE_PIXELS_PER_UNIT];
+        let (_, strips) = tessellate(&lines, &dashed, 0.01);
+        assert_eq!(strips.len(), 2 * 4000 + 1);
+    }
+}
@@ -0,0 +1,1648 @@
+// SVG import and export.
+//
+// Import covers what our Inkscape drawings actually use: `<path>` elements
+// (the whole `d` syntax, arcs being converted to cubic Béziers), `<g>`
+// groups, `transform`s, presentation attributes and `style`, and
+// `<linearGradient>`s and `<radialGradient>`s. Other elements are skipped
+// with a warning.
+//
+// Each `<path>` becomes a `Shape`. All shapes from one document share the
+// same frame: the document's viewBox is centered on the origin, its largest
+// side spans -1 to 1, and Y goes up.
+//
+// Export writes standalone documents in world units, which the importer
+// reads back.
+
+use std::io;
+use std::fmt::{self, Display, Formatter};
+use std::error::Error;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::f32::consts::PI;
+use v::{Vec2, Vec3, Mat4, Rgba};
+use super::{Shape, Path, SubPath, PathCmd, FillRule, StrokeJoin, StrokeCap, over};
+use super::{Gradient, GradientKind, GradientSpread, GradientStop};
+use super::gradient::MAX_STOPS as MAX_GRADIENT_STOPS;
+use super::REFERENCE_PIXELS_PER_UNIT as PIXELS_PER_UNIT;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SvgErrorKind {
+    /// The document isn't well-formed XML (as far as we can tell).
+    Xml(String),
+    /// A `d` attribute is malformed. `offset` is in characters.
+    PathData { offset: usize, msg: String },
+    /// An attribute or style property has a value we can't make sense of.
+    BadAttribute { name: String, value: String },
+    /// The document has no `<path>` to import.
+    NoPaths,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SvgError {
+    /// The file this error comes from, if known. See `in_file()`.
+    pub file: Option<PathBuf>,
+    /// 1-based line number of the offending element.
+    pub line: usize,
+    pub kind: SvgErrorKind,
+}
+
+#[derive(Debug)]
+pub enum ImportError {
+    Io(io::Error),
+    Svg(SvgError),
+}
+
+impl SvgError {
+    pub fn in_file<P: Into<PathBuf>>(mut self, file: P) -> Self {
+        self.file = Some(file.into());
+        self
+    }
+}
+
+impl Display for SvgErrorKind {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            SvgErrorKind::Xml(ref msg) => write!(f, "malformed XML: {}", msg),
+            SvgErrorKind::PathData { offset, ref msg } => write!(f, "path data, at character {}: {}", offset, msg),
+            SvgErrorKind::BadAttribute { ref name, ref value } => write!(f, "invalid value `{}` for `{}`", value, name),
+            SvgErrorKind::NoPaths => write!(f, "no <path> element to import"),
+        }
+    }
+}
+
+impl Display for SvgError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self.file {
+            Some(ref file) => write!(f, "{}:", file.display())?,
+            None => write!(f, "<svg>:")?,
+        };
+        write!(f, "{}: {}", self.line, self.kind)
+    }
+}
+
+impl Error for SvgError {
+    fn description(&self) -> &str {
+        "malformed or unsupported SVG file"
+    }
+}
+
+impl From<io::Error> for ImportError {
+    fn from(e: io::Error) -> Self {
+        ImportError::Io(e)
+    }
+}
+
+impl From<SvgError> for ImportError {
+    fn from(e: SvgError) -> Self {
+        ImportError::Svg(e)
+    }
+}
+
+impl Display for ImportError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            ImportError::Io(ref e) => write!(f, "I/O error: {}", e),
+            ImportError::Svg(ref e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for ImportError {
+    fn description(&self) -> &str {
+        match *self {
+            ImportError::Io(_) => "I/O error while importing SVG",
+            ImportError::Svg(_) => "malformed or unsupported SVG file",
+        }
+    }
+}
+
+
+//
+// XML
+//
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum TagKind {
+    Open,
+    Close,
+    Empty,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Tag {
+    line: usize,
+    kind: TagKind,
+    /// Without the `svg:` prefix, if any.
+    name: String,
+    /// Attribute names are kept as-is, e.g `xlink:href`.
+    attrs: HashMap<String, String>,
+}
+
+fn xml_error(line: usize, msg: &str) -> SvgError {
+    SvgError { file: None, line, kind: SvgErrorKind::Xml(msg.to_owned()) }
+}
+
+fn bad_attribute(line: usize, name: &str, value: &str) -> SvgError {
+    SvgError { file: None, line, kind: SvgErrorKind::BadAttribute { name: name.to_owned(), value: value.to_owned() } }
+}
+
+fn skip_whitespace(s: &str) -> &str {
+    let i = s.find(|c: char| !c.is_whitespace()).unwrap_or(s.len());
+    &s[i..]
+}
+
+fn count_lines(s: &str) -> usize {
+    s.chars().filter(|&c| c == '\n').count()
+}
+
+fn unescape(s: &str) -> String {
+    if !s.contains('&') {
+        return s.to_owned();
+    }
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(amp) = rest.find('&') {
+        out += &rest[..amp];
+        rest = &rest[amp..];
+        let semi = match rest.find(';') {
+            Some(i) => i,
+            None => break,
+        };
+        let entity = &rest[1..semi];
+        let c = match entity {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ if entity.starts_with("#x") => u32::from_str_radix(&entity[2..], 16).ok().and_then(::std::char::from_u32),
+            _ if entity.starts_with('#') => entity[1..].parse().ok().and_then(::std::char::from_u32),
+            _ => None,
+        };
+        match c {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[semi+1..];
+            },
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            },
+        }
+    }
+    out += rest;
+    out
+}
+
+fn parse_attributes(mut s: &str, line: usize) -> Result<HashMap<String, String>, SvgError> {
+    let mut attrs = HashMap::new();
+    loop {
+        s = skip_whitespace(s);
+        if s.is_empty() {
+            return Ok(attrs);
+        }
+        let eq = s.find('=').ok_or_else(|| xml_error(line, "attribute without a value"))?;
+        let name = s[..eq].trim();
+        s = skip_whitespace(&s[eq+1..]);
+        let quote = match s.chars().next() {
+            Some(q @ '"') | Some(q @ '\'') => q,
+            _ => return Err(xml_error(line, "unquoted attribute value")),
+        };
+        let len = s[1..].find(quote).ok_or_else(|| xml_error(line, "unterminated attribute value"))?;
+        attrs.insert(name.to_owned(), unescape(&s[1 .. 1+len]));
+        s = &s[1+len+1 ..];
+    }
+}
+
+// Splits the document into its tags, ignoring text, comments, and the like.
+fn tokenize_xml(src: &str) -> Result<Vec<Tag>, SvgError> {
+    let mut tags = vec![];
+    let mut rest = src;
+    let mut line = 1;
+    while let Some(lt) = rest.find('<') {
+        line += count_lines(&rest[..lt]);
+        rest = &rest[lt..];
+
+        let skipped = [("<!--", "-->"), ("<![CDATA[", "]]>"), ("<?", "?>"), ("<!", ">")];
+        if let Some(&(_, end)) = skipped.iter().find(|&&(start, _)| rest.starts_with(start)) {
+            let len = rest.find(end).ok_or_else(|| xml_error(line, "unterminated markup"))? + end.len();
+            line += count_lines(&rest[..len]);
+            rest = &rest[len..];
+            continue;
+        }
+
+        let mut quote = None;
+        let mut end = None;
+        for (i, c) in rest.char_indices().skip(1) {
+            match quote {
+                Some(q) => if c == q {
+                    quote = None;
+                },
+                None => match c {
+                    '"' | '\'' => quote = Some(c),
+                    '>' => {
+                        end = Some(i);
+                        break;
+                    },
+                    _ => (),
+                },
+            }
+        }
+        let end = end.ok_or_else(|| xml_error(line, "unterminated tag"))?;
+        let mut body = &rest[1..end];
+        let kind = if body.starts_with('/') {
+            body = &body[1..];
+            TagKind::Close
+        } else if body.ends_with('/') {
+            body = &body[..body.len()-1];
+            TagKind::Empty
+        } else {
+            TagKind::Open
+        };
+        let name_len = body.find(char::is_whitespace).unwrap_or(body.len());
+        let name = &body[..name_len];
+        if name.is_empty() {
+            return Err(xml_error(line, "tag without a name"));
+        }
+        let name = if name.starts_with("svg:") { &name[4..] } else { name };
+        let attrs = parse_attributes(&body[name_len..], line)?;
+        tags.push(Tag { line, kind, name: name.to_owned(), attrs });
+
+        line += count_lines(&rest[..end]);
+        rest = &rest[end+1 ..];
+    }
+    Ok(tags)
+}
+
+
+//
+// Attribute values
+//
+
+// Parses a number, converting units to pixels. Percentages are turned into
+// fractions, and font-relative units assume the default 16px font.
+fn parse_number(s: &str) -> Option<f32> {
+    let s = s.trim();
+    let digits = |from: usize| from + s[from..].find(|c: char| !c.is_digit(10)).unwrap_or(s.len() - from);
+    let mut len = digits(if s.starts_with('+') || s.starts_with('-') { 1 } else { 0 });
+    if s[len..].starts_with('.') {
+        len = digits(len + 1);
+    }
+    // An exponent needs digits, so that the `e` of `em` and `ex` is a unit.
+    if s[len..].starts_with('e') || s[len..].starts_with('E') {
+        let sign = if s[len+1 ..].starts_with('+') || s[len+1 ..].starts_with('-') { 1 } else { 0 };
+        let end = digits(len + 1 + sign);
+        if end > len + 1 + sign {
+            len = end;
+        }
+    }
+    let x: f32 = s[..len].parse().ok()?;
+    let factor = match s[len..].trim() {
+        "" | "px" => 1.,
+        "%" => 0.01,
+        "pt" => 96. / 72.,
+        "pc" => 16.,
+        "mm" => 96. / 25.4,
+        "cm" => 96. / 2.54,
+        "in" => 96.,
+        "em" => 16.,
+        "ex" => 8.,
+        _ => return None,
+    };
+    Some(x * factor)
+}
+
+fn parse_numbers(s: &str) -> Option<Vec<f32>> {
+    s.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|s| !s.is_empty())
+        .map(parse_number)
+        .collect()
+}
+
+fn parse_color(s: &str) -> Option<Rgba<f32>> {
+    let s = s.trim();
+    if s.starts_with('#') {
+        let digits = s[1..].chars().map(|c| c.to_digit(16).map(|x| x as f32)).collect::<Option<Vec<_>>>()?;
+        let byte = |i: usize| (digits[i] * 16. + digits[i+1]) / 255.;
+        return match digits.len() {
+            3 => Some(Rgba::new(digits[0] / 15., digits[1] / 15., digits[2] / 15., 1.)),
+            6 => Some(Rgba::new(byte(0), byte(2), byte(4), 1.)),
+            _ => None,
+        };
+    }
+    if s.starts_with("rgb(") && s.ends_with(')') {
+        let mut channels = vec![];
+        for c in s[4 .. s.len()-1].split(',') {
+            let c = c.trim();
+            channels.push(if c.ends_with('%') {
+                parse_number(c)?
+            } else {
+                c.parse::<f32>().ok()? / 255.
+            });
+        }
+        if channels.len() != 3 {
+            return None;
+        }
+        return Some(Rgba::new(channels[0], channels[1], channels[2], 1.));
+    }
+    let rgb = match s {
+        "black" => (0, 0, 0),
+        "white" => (255, 255, 255),
+        "red" => (255, 0, 0),
+        "lime" => (0, 255, 0),
+        "green" => (0, 128, 0),
+        "blue" => (0, 0, 255),
+        "yellow" => (255, 255, 0),
+        "cyan" | "aqua" => (0, 255, 255),
+        "magenta" | "fuchsia" => (255, 0, 255),
+        "gray" | "grey" => (128, 128, 128),
+        "silver" => (192, 192, 192),
+        "maroon" => (128, 0, 0),
+        "olive" => (128, 128, 0),
+        "navy" => (0, 0, 128),
+        "purple" => (128, 0, 128),
+        "teal" => (0, 128, 128),
+        "orange" => (255, 165, 0),
+        _ => return None,
+    };
+    Some(Rgba::new(rgb.0 as f32 / 255., rgb.1 as f32 / 255., rgb.2 as f32 / 255., 1.))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Paint {
+    None,
+    Color(Rgba<f32>),
+    /// The `id` of a paint server, e.g a gradient.
+    Url(String),
+}
+
+fn parse_paint(s: &str) -> Option<Paint> {
+    let s = s.trim();
+    if s == "none" {
+        return Some(Paint::None);
+    }
+    if s.starts_with("url(") {
+        let end = s.find(')')?;
+        let id = s[4..end].trim().trim_matches(|c| c == '"' || c == '\'');
+        if !id.starts_with('#') {
+            return None;
+        }
+        return Some(Paint::Url(id[1..].to_owned()));
+    }
+    parse_color(s).map(Paint::Color)
+}
+
+// Presentation attributes, overridden by the properties in `style`.
+fn properties(tag: &Tag) -> HashMap<&str, &str> {
+    let mut props: HashMap<&str, &str> = tag.attrs.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+    if let Some(style) = tag.attrs.get("style") {
+        for decl in style.split(';') {
+            if let Some(colon) = decl.find(':') {
+                props.insert(decl[..colon].trim(), decl[colon+1 ..].trim());
+            }
+        }
+    }
+    props
+}
+
+
+//
+// Transforms
+//
+
+// The SVG `matrix(a b c d e f)`, mapping (x, y) to (ax + cy + e, bx + dy + f).
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Affine {
+    a: f32, b: f32, c: f32, d: f32, e: f32, f: f32,
+}
+
+impl Affine {
+    fn identity() -> Self {
+        Affine { a: 1., b: 0., c: 0., d: 1., e: 0., f: 0. }
+    }
+    fn translate(x: f32, y: f32) -> Self {
+        Affine { e: x, f: y, .. Self::identity() }
+    }
+    fn scale(x: f32, y: f32) -> Self {
+        Affine { a: x, d: y, .. Self::identity() }
+    }
+    fn rotate(radians: f32) -> Self {
+        let (sin, cos) = radians.sin_cos();
+        Affine { a: cos, b: sin, c: -sin, d: cos, e: 0., f: 0. }
+    }
+    /// The transform which applies `rhs` first, then `self`.
+    fn then(&self, rhs: &Self) -> Self {
+        Affine {
+            a: self.a * rhs.a + self.c * rhs.b,
+            b: self.b * rhs.a + self.d * rhs.b,
+            c: self.a * rhs.c + self.c * rhs.d,
+            d: self.b * rhs.c + self.d * rhs.d,
+            e: self.a * rhs.e + self.c * rhs.f + self.e,
+            f: self.b * rhs.e + self.d * rhs.f + self.f,
+        }
+    }
+    fn apply(&self, p: Vec2<f32>) -> Vec2<f32> {
+        Vec2::new(self.a * p.x + self.c * p.y + self.e, self.b * p.x + self.d * p.y + self.f)
+    }
+    /// How much lengths are scaled, on average.
+    fn scale_factor(&self) -> f32 {
+        (self.a * self.d - self.b * self.c).abs().sqrt()
+    }
+}
+
+fn parse_transform(s: &str) -> Option<Affine> {
+    let mut m = Affine::identity();
+    let mut rest = s;
+    loop {
+        rest = skip_whitespace(rest.trim_matches(','));
+        if rest.is_empty() {
+            return Some(m);
+        }
+        let open = rest.find('(')?;
+        let close = rest.find(')')?;
+        let name = rest[..open].trim();
+        let args = parse_numbers(&rest[open+1 .. close])?;
+        let t = match (name, args.len()) {
+            ("matrix", 6) => Affine { a: args[0], b: args[1], c: args[2], d: args[3], e: args[4], f: args[5] },
+            ("translate", 1) => Affine::translate(args[0], 0.),
+            ("translate", 2) => Affine::translate(args[0], args[1]),
+            ("scale", 1) => Affine::scale(args[0], args[0]),
+            ("scale", 2) => Affine::scale(args[0], args[1]),
+            ("rotate", 1) => Affine::rotate(args[0].to_radians()),
+            ("rotate", 3) => Affine::translate(args[1], args[2])
+                .then(&Affine::rotate(args[0].to_radians()))
+                .then(&Affine::translate(-args[1], -args[2])),
+            ("skewX", 1) => Affine { c: args[0].to_radians().tan(), .. Affine::identity() },
+            ("skewY", 1) => Affine { b: args[0].to_radians().tan(), .. Affine::identity() },
+            _ => return None,
+        };
+        m = m.then(&t);
+        rest = &rest[close+1 ..];
+    }
+}
+
+
+//
+// Path data
+//
+
+struct PathData {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+type PathDataError = (usize, String);
+
+impl PathData {
+    fn new(d: &str) -> Self {
+        Self { chars: d.chars().collect(), pos: 0 }
+    }
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).cloned()
+    }
+    fn skip_separators(&mut self) {
+        while self.peek().map(|c| c.is_whitespace() || c == ',').unwrap_or(false) {
+            self.pos += 1;
+        }
+    }
+    fn error<T>(&self, msg: &str) -> Result<T, PathDataError> {
+        Err((self.pos, msg.to_owned()))
+    }
+    fn is_at_end(&mut self) -> bool {
+        self.skip_separators();
+        self.peek().is_none()
+    }
+    fn is_at_number(&mut self) -> bool {
+        self.skip_separators();
+        self.peek().map(|c| c.is_digit(10) || "+-.".contains(c)).unwrap_or(false)
+    }
+    fn command(&mut self) -> Result<char, PathDataError> {
+        self.skip_separators();
+        match self.peek() {
+            Some(c) if "MmLlHhVvCcSsQqTtAaZz".contains(c) => {
+                self.pos += 1;
+                Ok(c)
+            },
+            _ => self.error("expected a command"),
+        }
+    }
+    fn number(&mut self) -> Result<f32, PathDataError> {
+        self.skip_separators();
+        let start = self.pos;
+        let digits = |this: &mut Self| {
+            let from = this.pos;
+            while this.peek().map(|c| c.is_digit(10)).unwrap_or(false) {
+                this.pos += 1;
+            }
+            this.pos > from
+        };
+        if let Some('+') | Some('-') = self.peek() {
+            self.pos += 1;
+        }
+        let mut has_digits = digits(self);
+        if self.peek() == Some('.') {
+            self.pos += 1;
+            has_digits |= digits(self);
+        }
+        if !has_digits {
+            self.pos = start;
+            return self.error("expected a number");
+        }
+        if let Some('e') | Some('E') = self.peek() {
+            let mantissa_end = self.pos;
+            self.pos += 1;
+            if let Some('+') | Some('-') = self.peek() {
+                self.pos += 1;
+            }
+            if !digits(self) {
+                self.pos = mantissa_end;
+            }
+        }
+        let s: String = self.chars[start .. self.pos].iter().cloned().collect();
+        match s.parse() {
+            Ok(x) => Ok(x),
+            Err(_) => {
+                self.pos = start;
+                self.error("expected a number")
+            },
+        }
+    }
+    fn vec2(&mut self) -> Result<Vec2<f32>, PathDataError> {
+        let x = self.number()?;
+        let y = self.number()?;
+        Ok(Vec2::new(x, y))
+    }
+    // Arc flags are a single digit, and need no separator after them.
+    fn flag(&mut self) -> Result<bool, PathDataError> {
+        self.skip_separators();
+        match self.peek() {
+            Some('0') => { self.pos += 1; Ok(false) },
+            Some('1') => { self.pos += 1; Ok(true) },
+            _ => self.error("expected a flag (0 or 1)"),
+        }
+    }
+}
+
+// Converts an elliptical arc, given as in SVG, to cubic Béziers of at most 90° each.
+// See the "Elliptical arc implementation notes" of the SVG spec.
+fn arc_to_cubics(from: Vec2<f32>, radii: Vec2<f32>, x_axis_rotation_degrees: f32, large_arc: bool, sweep: bool, to: Vec2<f32>) -> Vec<PathCmd> {
+    if from == to {
+        return vec![];
+    }
+    let (mut rx, mut ry) = (radii.x.abs(), radii.y.abs());
+    if rx == 0. || ry == 0. {
+        return vec![PathCmd::Line { end: to }];
+    }
+    let (sin, cos) = x_axis_rotation_degrees.to_radians().sin_cos();
+    let rotate = |v: Vec2<f32>| Vec2::new(cos * v.x - sin * v.y, sin * v.x + cos * v.y);
+
+    let half = (from - to) / 2.;
+    let p = Vec2::new(cos * half.x + sin * half.y, -sin * half.x + cos * half.y);
+    let lambda = (p.x * p.x) / (rx * rx) + (p.y * p.y) / (ry * ry);
+    if lambda > 1. {
+        rx *= lambda.sqrt();
+        ry *= lambda.sqrt();
+    }
+    let num = rx * rx * ry * ry - rx * rx * p.y * p.y - ry * ry * p.x * p.x;
+    let den = rx * rx * p.y * p.y + ry * ry * p.x * p.x;
+    let mut coef = (num / den).max(0.).sqrt();
+    if large_arc == sweep {
+        coef = -coef;
+    }
+    let center_prime = Vec2::new(coef * rx * p.y / ry, -coef * ry * p.x / rx);
+    let center = rotate(center_prime) + (from + to) / 2.;
+
+    let angle = |u: Vec2<f32>, v: Vec2<f32>| (u.x * v.y - u.y * v.x).atan2(u.dot(v));
+    let u = Vec2::new((p.x - center_prime.x) / rx, (p.y - center_prime.y) / ry);
+    let v = Vec2::new((-p.x - center_prime.x) / rx, (-p.y - center_prime.y) / ry);
+    let theta = angle(Vec2::unit_x(), u);
+    let mut delta = angle(u, v);
+    if !sweep && delta > 0. {
+        delta -= 2. * PI;
+    } else if sweep && delta < 0. {
+        delta += 2. * PI;
+    }
+
+    let point = |t: f32| center + rotate(Vec2::new(rx * t.cos(), ry * t.sin()));
+    let tangent = |t: f32| rotate(Vec2::new(-rx * t.sin(), ry * t.cos()));
+    let n = (delta.abs() / (PI / 2.)).ceil().max(1.) as usize;
+    let step = delta / n as f32;
+    let k = 4. / 3. * (step / 4.).tan();
+    (0..n).map(|i| {
+        let a = theta + step * i as f32;
+        let b = a + step;
+        let start = point(a);
+        let end = if i == n-1 { to } else { point(b) };
+        PathCmd::Cubic {
+            ctrl0: start + tangent(a) * k,
+            ctrl1: end - tangent(b) * k,
+            end,
+        }
+    }).collect()
+}
+
+/// Parses the value of a `d` attribute.
+/// Errors are given as a character offset and a message.
+fn parse_path_data(d: &str) -> Result<Path, PathDataError> {
+    let mut path = Path::default();
+    let mut p = PathData::new(d);
+    if p.is_at_end() {
+        return Ok(path);
+    }
+    let mut cmd = p.command()?;
+    if cmd != 'M' && cmd != 'm' {
+        return Err((0, "path data must begin with a moveto".to_owned()));
+    }
+
+    let mut current = Vec2::zero();
+    // The last control point, for the smooth variants of curves.
+    let mut last_cubic_ctrl = None;
+    let mut last_quadratic_ctrl = None;
+
+    loop {
+        let origin = if cmd.is_lowercase() { current } else { Vec2::zero() };
+        let mut cubic_ctrl = None;
+        let mut quadratic_ctrl = None;
+        match cmd.to_ascii_uppercase() {
+            'M' => {
+                current = origin + p.vec2()?;
+                path.subpaths.push(SubPath::new(current));
+                // Subsequent pairs are implicit linetos.
+                cmd = if cmd == 'm' { 'l' } else { 'L' };
+            },
+            'Z' => {
+                path.current_subpath_mut().unwrap().is_closed = true;
+                current = path.current_subpath_mut().unwrap().start();
+            },
+            c => {
+                let segments = match c {
+                    'L' => vec![PathCmd::Line { end: origin + p.vec2()? }],
+                    'H' => vec![PathCmd::Line { end: Vec2::new(origin.x + p.number()?, current.y) }],
+                    'V' => vec![PathCmd::Line { end: Vec2::new(current.x, origin.y + p.number()?) }],
+                    'C' | 'S' => {
+                        let ctrl0 = if c == 'C' {
+                            origin + p.vec2()?
+                        } else {
+                            last_cubic_ctrl.map(|ctrl| current * 2. - ctrl).unwrap_or(current)
+                        };
+                        let ctrl1 = origin + p.vec2()?;
+                        let end = origin + p.vec2()?;
+                        cubic_ctrl = Some(ctrl1);
+                        vec![PathCmd::Cubic { ctrl0, ctrl1, end }]
+                    },
+                    'Q' | 'T' => {
+                        let ctrl = if c == 'Q' {
+                            origin + p.vec2()?
+                        } else {
+                            last_quadratic_ctrl.map(|ctrl| current * 2. - ctrl).unwrap_or(current)
+                        };
+                        let end = origin + p.vec2()?;
+                        quadratic_ctrl = Some(ctrl);
+                        vec![PathCmd::Quadratic { ctrl, end }]
+                    },
+                    'A' => {
+                        let radii = p.vec2()?;
+                        let rotation = p.number()?;
+                        let large_arc = p.flag()?;
+                        let sweep = p.flag()?;
+                        let end = origin + p.vec2()?;
+                        arc_to_cubics(current, radii, rotation, large_arc, sweep, end)
+                    },
+                    _ => unreachable!(),
+                };
+                // A segment may not change the current point (e.g a degenerate arc).
+                if let Some(last) = segments.last() {
+                    current = last.end();
+                    path.open_subpath().cmds.extend(segments.iter().cloned());
+                }
+            },
+        };
+        last_cubic_ctrl = cubic_ctrl;
+        last_quadratic_ctrl = quadratic_ctrl;
+
+        if p.is_at_end() {
+            return Ok(path);
+        }
+        if cmd == 'Z' || cmd == 'z' || !p.is_at_number() {
+            cmd = p.command()?;
+        }
+    }
+}
+
+
+//
+// Styles and gradients
+//
+
+// What we need from the cascade of presentation attributes.
+#[derive(Debug, Clone, PartialEq)]
+struct Presentation {
+    fill: Paint,
+    fill_opacity: f32,
+    fill_rule: FillRule,
+    stroke: Paint,
+    stroke_opacity: f32,
+    stroke_width: f32,
+    stroke_join: StrokeJoin,
+    stroke_cap: StrokeCap,
+    stroke_miter_limit: f32,
+    stroke_dashes: Vec<f32>,
+    stroke_dash_offset: f32,
+    /// Not inherited in SVG, but group opacity multiplies down to leaves.
+    opacity: f32,
+    display: bool,
+}
+
+impl Default for Presentation {
+    fn default() -> Self {
+        Self {
+            fill: Paint::Color(Rgba::black()),
+            fill_opacity: 1.,
+            fill_rule: FillRule::NonZero,
+            stroke: Paint::None,
+            stroke_opacity: 1.,
+            stroke_width: 1.,
+            stroke_join: StrokeJoin::Miter,
+            stroke_cap: StrokeCap::Butt,
+            stroke_miter_limit: 4.,
+            stroke_dashes: vec![],
+            stroke_dash_offset: 0.,
+            opacity: 1.,
+            display: true,
+        }
+    }
+}
+
+impl Presentation {
+    fn inherit(&self, tag: &Tag) -> Result<Self, SvgError> {
+        let mut p = self.clone();
+        p.opacity = 1.;
+        for (&name, &value) in properties(tag).iter() {
+            if value == "inherit" {
+                continue;
+            }
+            let bad = || bad_attribute(tag.line, name, value);
+            match name {
+                "fill" => p.fill = parse_paint(value).ok_or_else(bad)?,
+                "stroke" => p.stroke = parse_paint(value).ok_or_else(bad)?,
+                "fill-opacity" => p.fill_opacity = parse_number(value).ok_or_else(bad)?,
+                "stroke-opacity" => p.stroke_opacity = parse_number(value).ok_or_else(bad)?,
+                "stroke-width" => p.stroke_width = parse_number(value).ok_or_else(bad)?,
+                "stroke-linejoin" => p.stroke_join = match value {
+                    // SVG 2 additions, which we don't do.
+                    "miter-clip" | "arcs" => StrokeJoin::Miter,
+                    _ => StrokeJoin::from_name(value).ok_or_else(bad)?,
+                },
+                "stroke-linecap" => p.stroke_cap = StrokeCap::from_name(value).ok_or_else(bad)?,
+                "stroke-miterlimit" => p.stroke_miter_limit = parse_number(value).ok_or_else(bad)?,
+                "stroke-dasharray" => p.stroke_dashes = match value {
+                    "none" => vec![],
+                    _ => parse_numbers(value).ok_or_else(bad)?,
+                },
+                "stroke-dashoffset" => p.stroke_dash_offset = parse_number(value).ok_or_else(bad)?,
+                "opacity" => p.opacity = parse_number(value).ok_or_else(bad)?,
+                "fill-rule" => p.fill_rule = FillRule::from_name(value).ok_or_else(bad)?,
+                "display" => p.display = value != "none",
+                _ => (),
+            };
+        }
+        p.opacity *= self.opacity;
+        p.display &= self.display;
+        Ok(p)
+    }
+}
+
+// A `<linearGradient>` or `<radialGradient>` as written; `None` fields are inherited from `href`.
+#[derive(Debug, Clone, PartialEq)]
+struct SvgGradient {
+    kind: GradientKind,
+    href: Option<String>,
+    // `x1 y1 x2 y2` for linear gradients, `cx cy r` for radial ones.
+    start: [Option<f32>; 2],
+    end: [Option<f32>; 2],
+    radius: Option<f32>,
+    spread: Option<GradientSpread>,
+    is_in_user_space: Option<bool>,
+    transform: Option<Affine>,
+    stops: Vec<(f32, Rgba<f32>)>,
+}
+
+impl SvgGradient {
+    fn from_tag(tag: &Tag) -> Result<Self, SvgError> {
+        let number = |name: &str| match tag.attrs.get(name) {
+            None => Ok(None),
+            Some(v) => parse_number(v).map(Some).ok_or_else(|| bad_attribute(tag.line, name, v)),
+        };
+        let kind = if tag.name == "radialGradient" { GradientKind::Radial } else { GradientKind::Linear };
+        let (start, end, radius) = match kind {
+            GradientKind::Radial => {
+                if tag.attrs.contains_key("fx") || tag.attrs.contains_key("fy") {
+                    warn!("SVG import: line {}: Focal points of radial gradients are not supported; using the center instead", tag.line);
+                }
+                ([number("cx")?, number("cy")?], [None, None], number("r")?)
+            },
+            _ => ([number("x1")?, number("y1")?], [number("x2")?, number("y2")?], None),
+        };
+        let href = tag.attrs.get("xlink:href").or(tag.attrs.get("href"));
+        Ok(Self {
+            kind,
+            href: href.map(|href| if href.starts_with('#') { &href[1..] } else { href }.to_owned()),
+            start, end, radius,
+            spread: match tag.attrs.get("spreadMethod") {
+                None => None,
+                Some(v) => Some(GradientSpread::from_name(v).ok_or_else(|| bad_attribute(tag.line, "spreadMethod", v))?),
+            },
+            is_in_user_space: tag.attrs.get("gradientUnits").map(|units| units == "userSpaceOnUse"),
+            transform: match tag.attrs.get("gradientTransform") {
+                None => None,
+                Some(v) => Some(parse_transform(v).ok_or_else(|| bad_attribute(tag.line, "gradientTransform", v))?),
+            },
+            stops: vec![],
+        })
+    }
+    fn add_stop(&mut self, tag: &Tag) -> Result<(), SvgError> {
+        let props = properties(tag);
+        let get = |name: &str, default: &'static str| *props.get(name).unwrap_or(&default);
+        let bad = |name: &str, value: &str| bad_attribute(tag.line, name, value);
+        let offset = get("offset", "0");
+        let offset = parse_number(offset).ok_or_else(|| bad("offset", offset))?;
+        let color = get("stop-color", "black");
+        let mut color = parse_color(color).ok_or_else(|| bad("stop-color", color))?;
+        let opacity = get("stop-opacity", "1");
+        color.a = parse_number(opacity).ok_or_else(|| bad("stop-opacity", opacity))?;
+        // Offsets are clamped to be increasing.
+        let min = self.stops.last().map(|s| s.0).unwrap_or(0.);
+        self.stops.push((offset.max(min).min(1.), color));
+        Ok(())
+    }
+    // Follows `href`s to fill in the missing fields.
+    // Only attributes which both kinds of gradients have are inherited across kinds.
+    fn resolve(&self, all: &HashMap<String, SvgGradient>) -> Self {
+        let mut g = self.clone();
+        let mut href = self.href.clone();
+        // Bounded, in case of cycles.
+        for _ in 0..16 {
+            let parent = match href.and_then(|id| all.get(&id)) {
+                Some(parent) => parent,
+                None => break,
+            };
+            if parent.kind == g.kind {
+                for i in 0..2 {
+                    g.start[i] = g.start[i].or(parent.start[i]);
+                    g.end[i] = g.end[i].or(parent.end[i]);
+                }
+                g.radius = g.radius.or(parent.radius);
+            }
+            g.spread = g.spread.or(parent.spread);
+            g.is_in_user_space = g.is_in_user_space.or(parent.is_in_user_space);
+            g.transform = g.transform.or(parent.transform);
+            if g.stops.is_empty() {
+                g.stops = parent.stops.clone();
+            }
+            href = parent.href.clone();
+        }
+        g
+    }
+}
+
+// A fill, in document space.
+#[derive(Debug, Clone, PartialEq)]
+enum Fill {
+    None,
+    Solid(Rgba<f32>),
+    Gradient(Gradient),
+}
+
+// Bounds of a path in document units, within a tenth of a pixel.
+fn bounds(path: &Path) -> Option<(Vec2<f32>, Vec2<f32>)> {
+    let mut points = path.subpaths.iter().flat_map(|s| s.generate_vertex_positions(0.1));
+    let first = points.next()?;
+    Some(points.fold((first, first), |(min, max), p| (Vec2::partial_min(min, p), Vec2::partial_max(max, p))))
+}
+
+fn with_alpha(mut color: Rgba<f32>, alpha: f32) -> Rgba<f32> {
+    color.a *= alpha;
+    color
+}
+
+// `local_path` is the path before `ctm` is applied, as needed for `objectBoundingBox` units.
+//
+// Radial gradients stay circular: under a non-uniform transform, their
+// radius is measured along the transformed X axis.
+fn resolve_fill(pres: &Presentation, ctm: &Affine, local_path: &Path, gradients: &HashMap<String, SvgGradient>, line: usize) -> Fill {
+    let alpha = pres.fill_opacity * pres.opacity;
+    let id = match pres.fill {
+        Paint::None => return Fill::None,
+        Paint::Color(c) => return Fill::Solid(with_alpha(c, alpha)),
+        Paint::Url(ref id) => id,
+    };
+    let g = match gradients.get(id) {
+        Some(g) => g.resolve(gradients),
+        None => {
+            warn!("SVG import: line {}: Paint server `{}` is missing or unsupported; using black instead", line, id);
+            return Fill::Solid(with_alpha(Rgba::black(), alpha));
+        },
+    };
+    let last = match g.stops.last() {
+        Some(&last) => last,
+        None => return Fill::None,
+    };
+    if g.stops.len() == 1 {
+        return Fill::Solid(with_alpha(last.1, alpha));
+    }
+    let (start, end) = match g.kind {
+        GradientKind::Radial => {
+            let center = Vec2::new(g.start[0].unwrap_or(0.5), g.start[1].unwrap_or(0.5));
+            (center, center + Vec2::new(g.radius.unwrap_or(0.5), 0.))
+        },
+        _ => (
+            Vec2::new(g.start[0].unwrap_or(0.), g.start[1].unwrap_or(0.)),
+            Vec2::new(g.end[0].unwrap_or(1.), g.end[1].unwrap_or(0.)),
+        ),
+    };
+    if start == end {
+        return Fill::Solid(with_alpha(last.1, alpha));
+    }
+    if g.stops.len() > MAX_GRADIENT_STOPS {
+        warn!("SVG import: line {}: Gradient `{}` has {} stops; only the first {} ones are drawn", line, id, g.stops.len(), MAX_GRADIENT_STOPS);
+    }
+    let mut to_document = ctm.then(&g.transform.unwrap_or(Affine::identity()));
+    if !g.is_in_user_space.unwrap_or(false) {
+        let (min, max) = match bounds(local_path) {
+            Some(b) => b,
+            None => return Fill::None,
+        };
+        let size = max - min;
+        if size.x == 0. || size.y == 0. {
+            return Fill::Solid(with_alpha(last.1, alpha));
+        }
+        to_document = ctm.then(&Affine::translate(min.x, min.y)).then(&Affine::scale(size.x, size.y))
+            .then(&g.transform.unwrap_or(Affine::identity()));
+    }
+    Fill::Gradient(Gradient {
+        kind: g.kind,
+        spread: g.spread.unwrap_or(GradientSpread::Pad),
+        start: to_document.apply(start),
+        end: to_document.apply(end),
+        stops: g.stops.iter().map(|&(offset, color)| GradientStop { offset, color: with_alpha(color, alpha) }).collect(),
+    })
+}
+
+
+//
+// Import
+//
+
+// A path in document space, not yet mapped to our world units.
+struct Imported {
+    name: String,
+    path: Path,
+    fill: Fill,
+    stroke_color: Rgba<f32>,
+    /// The presentation it comes from, for the other stroke properties.
+    pres: Presentation,
+    /// How much the path was scaled when put in document space.
+    scale: f32,
+}
+
+fn map_points(path: &mut Path, f: &Fn(Vec2<f32>) -> Vec2<f32>) {
+    for subpath in &mut path.subpaths {
+        for cmd in &mut subpath.cmds {
+            *cmd = match *cmd {
+                PathCmd::Start(p) => PathCmd::Start(f(p)),
+                PathCmd::Line { end } => PathCmd::Line { end: f(end) },
+                PathCmd::Quadratic { ctrl, end } => PathCmd::Quadratic { ctrl: f(ctrl), end: f(end) },
+                PathCmd::Cubic { ctrl0, ctrl1, end } => PathCmd::Cubic { ctrl0: f(ctrl0), ctrl1: f(ctrl1), end: f(end) },
+            };
+        }
+    }
+}
+
+fn sanitize_name(id: &str) -> String {
+    id.chars().map(|c| if c.is_alphanumeric() || c == '_' || c == '-' { c } else { '_' }).collect()
+}
+
+/// Converts each `<path>` of an SVG document into a `Shape`.
+///
+/// Shapes are named `<prefix>_<id>`, or `<prefix>_<n>` for paths without an `id`.
+pub fn import(src: &str, prefix: &str) -> Result<Vec<(String, Shape)>, SvgError> {
+    let tags = tokenize_xml(src)?;
+
+    let mut gradients = HashMap::new();
+    {
+        let mut current: Option<(String, SvgGradient)> = None;
+        for tag in &tags {
+            match (tag.name.as_str(), tag.kind) {
+                ("linearGradient", TagKind::Close) | ("radialGradient", TagKind::Close) => if let Some((id, g)) = current.take() {
+                    gradients.insert(id, g);
+                },
+                ("linearGradient", kind) | ("radialGradient", kind) => {
+                    let id = tag.attrs.get("id").cloned().unwrap_or_default();
+                    let g = SvgGradient::from_tag(tag)?;
+                    if kind == TagKind::Empty {
+                        gradients.insert(id, g);
+                    } else {
+                        current = Some((id, g));
+                    }
+                },
+                ("stop", TagKind::Open) | ("stop", TagKind::Empty) => if let Some((_, ref mut g)) = current {
+                    g.add_stop(tag)?;
+                },
+                _ => (),
+            };
+        }
+    }
+
+    // (viewBox origin, viewBox size), if known.
+    let mut view_box = None;
+    let mut imported = vec![];
+    // For each open element: its transform, presentation, and whether it is rendered.
+    let mut stack = vec![(Affine::identity(), Presentation::default(), true)];
+    let not_rendered = ["defs", "clipPath", "mask", "pattern", "marker", "symbol", "linearGradient", "radialGradient"];
+    let unsupported = ["rect", "circle", "ellipse", "line", "polyline", "polygon", "use", "text", "image"];
+
+    for tag in &tags {
+        if tag.kind == TagKind::Close {
+            stack.pop();
+            if stack.is_empty() {
+                return Err(xml_error(tag.line, "unbalanced closing tag"));
+            }
+            continue;
+        }
+        let (parent_ctm, parent_pres, parent_is_rendered) = stack.last().cloned().unwrap();
+        let ctm = match tag.attrs.get("transform") {
+            None => parent_ctm,
+            Some(v) => parent_ctm.then(&parse_transform(v).ok_or_else(|| bad_attribute(tag.line, "transform", v))?),
+        };
+        let pres = parent_pres.inherit(tag)?;
+        let is_rendered = parent_is_rendered && pres.display && !not_rendered.contains(&tag.name.as_str());
+
+        if tag.name == "svg" && view_box.is_none() {
+            if let Some(v) = tag.attrs.get("viewBox") {
+                let n = parse_numbers(v).ok_or_else(|| bad_attribute(tag.line, "viewBox", v))?;
+                if n.len() != 4 {
+                    return Err(bad_attribute(tag.line, "viewBox", v));
+                }
+                view_box = Some((Vec2::new(n[0], n[1]), Vec2::new(n[2], n[3])));
+            } else if let (Some(w), Some(h)) = (tag.attrs.get("width"), tag.attrs.get("height")) {
+                if let (Some(w), Some(h)) = (parse_number(w), parse_number(h)) {
+                    view_box = Some((Vec2::zero(), Vec2::new(w, h)));
+                }
+            }
+        }
+
+        if is_rendered && tag.name == "path" {
+            let d = tag.attrs.get("d").map(String::as_str).unwrap_or("");
+            let local_path = parse_path_data(d).map_err(|(offset, msg)| SvgError {
+                file: None, line: tag.line, kind: SvgErrorKind::PathData { offset, msg },
+            })?;
+            if !local_path.is_empty() {
+                let fill = resolve_fill(&pres, &ctm, &local_path, &gradients, tag.line);
+                let stroke_color = match pres.stroke {
+                    Paint::None => Rgba::zero(),
+                    Paint::Color(c) => with_alpha(c, pres.stroke_opacity * pres.opacity),
+                    Paint::Url(ref id) => {
+                        warn!("SVG import: line {}: Paint servers aren't supported for strokes (`{}`); using black instead", tag.line, id);
+                        with_alpha(Rgba::black(), pres.stroke_opacity * pres.opacity)
+                    },
+                };
+                let name = match tag.attrs.get("id") {
+                    Some(id) => format!("{}_{}", prefix, sanitize_name(id)),
+                    None => format!("{}_{}", prefix, imported.len() + 1),
+                };
+                let mut path = local_path;
+                map_points(&mut path, &|p| ctm.apply(p));
+                imported.push(Imported {
+                    name, path, fill, stroke_color,
+                    pres: pres.clone(),
+                    scale: ctm.scale_factor(),
+                });
+            }
+        } else if is_rendered && unsupported.contains(&tag.name.as_str()) {
+            warn!("SVG import: line {}: Skipping unsupported <{}> element", tag.line, tag.name);
+        }
+
+        if tag.kind == TagKind::Open {
+            stack.push((ctm, pres, is_rendered));
+        }
+    }
+
+    if imported.is_empty() {
+        return Err(SvgError { file: None, line: 1, kind: SvgErrorKind::NoPaths });
+    }
+
+    let (origin, size) = match view_box {
+        Some(b) => b,
+        None => {
+            let mut all = Path::default();
+            for i in &imported {
+                all.subpaths.extend(i.path.subpaths.iter().cloned());
+            }
+            let (min, max) = bounds(&all).unwrap();
+            (min, max - min)
+        },
+    };
+    let center = origin + size / 2.;
+    let half_extent = match size.x.max(size.y) / 2. {
+        h if h > 0. => h,
+        _ => 1.,
+    };
+    let to_world = |p: Vec2<f32>| Vec2::new(p.x - center.x, center.y - p.y) / half_extent;
+
+    Ok(imported.into_iter().map(|i| {
+        let Imported { name, mut path, fill, stroke_color, pres, scale } = i;
+        map_points(&mut path, &to_world);
+        let mut shape = Shape { path, .. Shape::default() };
+        {
+            let to_pixels = |length: f32| length * scale / half_extent * PIXELS_PER_UNIT;
+            let style = &mut shape.style;
+            style.fill_rule = pres.fill_rule;
+            style.stroke_color = stroke_color;
+            style.stroke_thickness = to_pixels(pres.stroke_width);
+            style.stroke_join = pres.stroke_join;
+            style.stroke_cap = pres.stroke_cap;
+            style.stroke_miter_limit = pres.stroke_miter_limit;
+            style.stroke_dashes = pres.stroke_dashes.iter().map(|&x| to_pixels(x)).collect();
+            style.stroke_dash_offset = to_pixels(pres.stroke_dash_offset);
+            // The gradient is drawn over the fill color, which must then be transparent.
+            let (color, gradient) = match fill {
+                Fill::None => (Rgba::zero(), Gradient::none()),
+                Fill::Solid(c) => (c, Gradient::none()),
+                Fill::Gradient(g) => (Rgba::zero(), Gradient {
+                    start: to_world(g.start),
+                    end: to_world(g.end),
+                    .. g
+                }),
+            };
+            style.fill_color = color;
+            style.fill_gradient = gradient;
+        }
+        (name, shape)
+    }).collect())
+}
+
+
+
+//
+// Export
+//
+
+fn hex(c: Rgba<f32>) -> String {
+    let byte = |x: f32| (x.max(0.).min(1.) * 255.).round() as u8;
+    format!("#{:02x}{:02x}{:02x}", byte(c.r), byte(c.g), byte(c.b))
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn path_data(path: &Path) -> String {
+    let mut d = vec![];
+    for subpath in &path.subpaths {
+        for cmd in &subpath.cmds {
+            d.push(match *cmd {
+                PathCmd::Start(p) => format!("M {} {}", p.x, p.y),
+                PathCmd::Line { end } => format!("L {} {}", end.x, end.y),
+                PathCmd::Quadratic { ctrl, end } => format!("Q {} {} {} {}", ctrl.x, ctrl.y, end.x, end.y),
+                PathCmd::Cubic { ctrl0, ctrl1, end } => format!("C {} {} {} {} {} {}", ctrl0.x, ctrl0.y, ctrl1.x, ctrl1.y, end.x, end.y),
+            });
+        }
+        if subpath.is_closed {
+            d.push("Z".to_owned());
+        }
+    }
+    d.join(" ")
+}
+
+fn write_shape(f: &mut io::Write, index: usize, name: &str, shape: &Shape, model: &Mat4<f32>) -> io::Result<()> {
+    let style = &shape.style;
+    let c = &model.cols;
+    writeln!(f, r#"    <g transform="matrix({} {} {} {} {} {})">"#,
+        c.x.x, c.x.y, c.y.x, c.y.y, c.w.x, c.w.y)?;
+
+    // The gradient is drawn over the fill color; what we see is both combined.
+    let gradient = &style.fill_gradient;
+    let stops = gradient.normalized_stops();
+    let solid = |color: Rgba<f32>| format!(r#"fill="{}" fill-opacity="{}""#, hex(color), color.a);
+    let fill = if stops.is_empty() || gradient.start == gradient.end {
+        solid(style.fill_color)
+    } else if gradient.kind == GradientKind::Conic {
+        warn!("SVG export: `{}` has a conic gradient, which SVG can't express; it is exported as its first color", name);
+        solid(over(stops[0].color, style.fill_color))
+    } else {
+        let (p0, p1) = (gradient.start, gradient.end);
+        match gradient.kind {
+            GradientKind::Radial => writeln!(f, r#"      <radialGradient id="gradient{}" gradientUnits="userSpaceOnUse" spreadMethod="{}" cx="{}" cy="{}" r="{}">"#,
+                index, gradient.spread.name(), p0.x, p0.y, p0.distance(p1))?,
+            _ => writeln!(f, r#"      <linearGradient id="gradient{}" gradientUnits="userSpaceOnUse" spreadMethod="{}" x1="{}" y1="{}" x2="{}" y2="{}">"#,
+                index, gradient.spread.name(), p0.x, p0.y, p1.x, p1.y)?,
+        };
+        for stop in &stops {
+            let color = over(stop.color, style.fill_color);
+            writeln!(f, r#"        <stop offset="{}" stop-color="{}" stop-opacity="{}"/>"#, stop.offset, hex(color), color.a)?;
+        }
+        match gradient.kind {
+            GradientKind::Radial => writeln!(f, r#"      </radialGradient>"#)?,
+            _ => writeln!(f, r#"      </linearGradient>"#)?,
+        };
+        format!(r#"fill="url(#gradient{})""#, index)
+    };
+    let has_node_styles = shape.path.subpaths.iter().any(|s| s.node_styles.iter().any(|n| !n.is_empty()));
+    let has_taper = style.stroke_taper_start > 0. || style.stroke_taper_end > 0.;
+    if !style.stroke_gradient.is_empty() || has_node_styles || has_taper {
+        warn!("SVG export: `{}` has a stroke which varies along its length, which SVG can't express; it is exported with its plain stroke color and thickness", name);
+    }
+    let stroke = if style.stroke_color.a <= 0. {
+        r#"stroke="none""#.to_owned()
+    } else {
+        let mut stroke = format!(r#"stroke="{}" stroke-opacity="{}" stroke-width="{}" stroke-linejoin="{}" stroke-linecap="{}" stroke-miterlimit="{}""#,
+            hex(style.stroke_color), style.stroke_color.a, style.stroke_thickness / PIXELS_PER_UNIT,
+            style.stroke_join.name(), style.stroke_cap.name(), style.stroke_miter_limit);
+        if !style.stroke_dashes.is_empty() {
+            let dashes = style.stroke_dashes.iter().map(|x| (x / PIXELS_PER_UNIT).to_string()).collect::<Vec<_>>();
+            stroke += &format!(r#" stroke-dasharray="{}" stroke-dashoffset="{}""#, dashes.join(" "), style.stroke_dash_offset / PIXELS_PER_UNIT);
+        }
+        stroke
+    };
+    writeln!(f, r#"      <path id="{}" {} fill-rule="{}" {} d="{}"/>"#,
+        escape(name), fill, style.fill_rule.name(), stroke, path_data(&shape.path))?;
+    writeln!(f, "    </g>")
+}
+
+/// Writes a standalone SVG document showing the given shapes, each with
+/// its model matrix, in order (the last one is on top).
+///
+/// One world unit spans `REFERENCE_PIXELS_PER_UNIT` pixels, so that the
+/// document looks the same size as in the game.
+pub fn write(f: &mut io::Write, shapes: &[(&str, &Shape, Mat4<f32>)]) -> io::Result<()> {
+    let mut bounds: Option<(Vec2<f32>, Vec2<f32>)> = None;
+    for &(_, shape, ref m) in shapes {
+        let max_scale = Vec2::new(m.cols.x.x, m.cols.x.y).magnitude().max(Vec2::new(m.cols.y.x, m.cols.y.y).magnitude());
+        let margin = Vec2::broadcast(shape.style.stroke_thickness / 2. / PIXELS_PER_UNIT * max_scale);
+        for subpath in &shape.path.subpaths {
+            for p in subpath.generate_vertex_positions(1. / PIXELS_PER_UNIT) {
+                let p = m.mul_point(Vec3::from(p));
+                let p = Vec2::new(p.x, p.y);
+                bounds = Some(match bounds {
+                    None => (p - margin, p + margin),
+                    Some((min, max)) => (Vec2::partial_min(min, p - margin), Vec2::partial_max(max, p + margin)),
+                });
+            }
+        }
+    }
+    let (min, max) = bounds.unwrap_or((-Vec2::one(), Vec2::one()));
+    let size = max - min;
+
+    writeln!(f, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(f, r#"<svg xmlns="http://www.w3.org/2000/svg" version="1.1" width="{}" height="{}" viewBox="{} {} {} {}">"#,
+        size.x * PIXELS_PER_UNIT, size.y * PIXELS_PER_UNIT, min.x, -max.y, size.x, size.y)?;
+    writeln!(f, "  <!-- World units; Y goes up. -->")?;
+    writeln!(f, r#"  <g transform="scale(1 -1)">"#)?;
+    for (i, &(name, shape, ref model)) in shapes.iter().enumerate() {
+        write_shape(f, i, name, shape, model)?;
+    }
+    writeln!(f, "  </g>")?;
+    writeln!(f, "</svg>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v(x: f32, y: f32) -> Vec2<f32> {
+        Vec2::new(x, y)
+    }
+
+    fn is_near(a: Vec2<f32>, b: Vec2<f32>) -> bool {
+        a.distance(b) < 1e-4
+    }
+
+    fn cmds(d: &str) -> Vec<PathCmd> {
+        parse_path_data(d).unwrap().subpaths.iter().flat_map(|s| s.cmds.iter().cloned()).collect()
+    }
+
+    fn error_kind(src: &str) -> (usize, SvgErrorKind) {
+        let e = import(src, "t").unwrap_err();
+        (e.line, e.kind)
+    }
+
+    #[test]
+    fn xml_skips_markup_and_unescapes_attributes() {
+        let src = "<?xml version='1.0'?>\n<!-- <path/> -->\n<svg:svg a=\"x &amp; &#65;&#x42; &bogus;\">\n<![CDATA[ <g> ]]></svg:svg>";
+        let tags = tokenize_xml(src).unwrap();
+        assert_eq!(tags.len(), 2);
+        assert_eq!((tags[0].line, tags[0].kind, tags[0].name.as_str()), (3, TagKind::Open, "svg"));
+        assert_eq!(tags[0].attrs["a"], "x & AB &bogus;");
+        assert_eq!((tags[1].line, tags[1].kind, tags[1].name.as_str()), (4, TagKind::Close, "svg"));
+
+        let tags = tokenize_xml("<path d='M 0 0 > 1'/>").unwrap();
+        assert_eq!(tags[0].kind, TagKind::Empty);
+        assert_eq!(tags[0].attrs["d"], "M 0 0 > 1");
+    }
+
+    #[test]
+    fn malformed_xml_is_an_error() {
+        assert_eq!(tokenize_xml("\n<svg a=b>").unwrap_err(), xml_error(2, "unquoted attribute value"));
+        assert_eq!(tokenize_xml("<svg a=\"b>").unwrap_err(), xml_error(1, "unterminated tag"));
+        assert_eq!(tokenize_xml("<svg a>").unwrap_err(), xml_error(1, "attribute without a value"));
+        assert_eq!(tokenize_xml("<!-- ").unwrap_err(), xml_error(1, "unterminated markup"));
+        assert_eq!(tokenize_xml("< a='b'>").unwrap_err(), xml_error(1, "tag without a name"));
+    }
+
+    #[test]
+    fn numbers_are_converted_to_pixels() {
+        assert_eq!(parse_number(" 1.5e1 "), Some(15.));
+        assert_eq!(parse_number("12px"), Some(12.));
+        assert_eq!(parse_number("50%"), Some(0.5));
+        assert_eq!(parse_number("1in"), Some(96.));
+        assert_eq!(parse_number("72pt"), Some(96.));
+        assert_eq!(parse_number("2em"), Some(32.));
+        assert_eq!(parse_number("1ex"), Some(8.));
+        assert_eq!(parse_number("-.5E+1em"), Some(-80.));
+        assert_eq!(parse_number("1e"), None);
+        assert_eq!(parse_number("1furlong"), None);
+        assert_eq!(parse_number("px"), None);
+        assert_eq!(parse_numbers("1, 2 3,4"), Some(vec![1., 2., 3., 4.]));
+        assert_eq!(parse_numbers("1 x"), None);
+    }
+
+    #[test]
+    fn colors() {
+        assert_eq!(parse_color("#f80"), Some(Rgba::new(1., 8. / 15., 0., 1.)));
+        assert_eq!(parse_color(" #FF0080 "), Some(Rgba::new(1., 0., 128. / 255., 1.)));
+        assert_eq!(parse_color("rgb(255, 0, 50%)"), Some(Rgba::new(1., 0., 0.5, 1.)));
+        assert_eq!(parse_color("teal"), Some(Rgba::new(0., 128. / 255., 128. / 255., 1.)));
+        assert_eq!(parse_color("#ff00"), None);
+        assert_eq!(parse_color("#ggg"), None);
+        assert_eq!(parse_color("#aé"), None);
+        assert_eq!(parse_color("#ééé"), None);
+        assert_eq!(parse_color("rgb(1, 2)"), None);
+        assert_eq!(parse_color("chartreuse"), None);
+        assert_eq!(parse_paint("none"), Some(Paint::None));
+        assert_eq!(parse_paint("url('#g')"), Some(Paint::Url("g".to_owned())));
+        assert_eq!(parse_paint("url(g)"), None);
+    }
+
+    #[test]
+    fn transform_lists_apply_from_right_to_left() {
+        let t = parse_transform("translate(10, 20) scale(2)").unwrap();
+        assert_eq!(t.apply(v(1., 1.)), v(12., 22.));
+        let t = parse_transform("scale(2),translate(10)").unwrap();
+        assert_eq!(t.apply(v(1., 1.)), v(22., 2.));
+        assert!(is_near(parse_transform("rotate(90 10 0)").unwrap().apply(v(20., 0.)), v(10., 10.)));
+        assert!(is_near(parse_transform("skewX(45)").unwrap().apply(v(0., 1.)), v(1., 1.)));
+        assert_eq!(parse_transform("matrix(1 2 3 4 5 6)").unwrap().apply(v(1., 1.)), v(9., 12.));
+        assert_eq!(parse_transform("").unwrap(), Affine::identity());
+        assert_eq!(parse_transform("translate(1 2 3)"), None);
+        assert_eq!(parse_transform("frobnicate(1)"), None);
+        assert_eq!(parse_transform("scale(2"), None);
+    }
+
+    #[test]
+    fn relative_commands_are_made_absolute() {
+        assert_eq!(cmds("m 10 10 l 10 0 h 5 v 5 z"), cmds("M 10 10 L 20 10 L 25 10 L 25 15 Z"));
+        // Pairs after a moveto are linetos, and numbers need no separators.
+        assert_eq!(cmds("M0,0 1,1-2-2.5.5.5"), cmds("M 0 0 L 1 1 L -2 -2.5 L 0.5 0.5"));
+        assert_eq!(cmds("m 1 1 2 2"), cmds("M 1 1 L 3 3"));
+        let path = parse_path_data("M 0 0 L 1 0 Z m 1 1 l 1 0").unwrap();
+        assert_eq!(path.subpaths.len(), 2);
+        assert!(path.subpaths[0].is_closed);
+        assert_eq!(path.subpaths[1].cmds, vec![PathCmd::Start(v(1., 1.)), PathCmd::Line { end: v(2., 1.) }]);
+        assert_eq!(parse_path_data("").unwrap(), Path::default());
+    }
+
+    #[test]
+    fn smooth_curves_reflect_the_previous_control_point() {
+        assert_eq!(cmds("M 0 0 C 0 10 10 10 10 0 s 10 -10 10 0"), vec![
+            PathCmd::Start(v(0., 0.)),
+            PathCmd::Cubic { ctrl0: v(0., 10.), ctrl1: v(10., 10.), end: v(10., 0.) },
+            PathCmd::Cubic { ctrl0: v(10., -10.), ctrl1: v(20., -10.), end: v(20., 0.) },
+        ]);
+        assert_eq!(cmds("M 0 0 Q 5 10 10 0 T 20 0"), vec![
+            PathCmd::Start(v(0., 0.)),
+            PathCmd::Quadratic { ctrl: v(5., 10.), end: v(10., 0.) },
+            PathCmd::Quadratic { ctrl: v(15., -10.), end: v(20., 0.) },
+        ]);
+        // Without a previous curve of the same kind, the control point is the current point.
+        assert_eq!(cmds("M 0 0 L 5 0 S 10 5 10 0")[2], PathCmd::Cubic { ctrl0: v(5., 0.), ctrl1: v(10., 5.), end: v(10., 0.) });
+        assert_eq!(cmds("M 0 0 C 0 1 1 1 1 0 T 2 0")[2], PathCmd::Quadratic { ctrl: v(1., 0.), end: v(2., 0.) });
+    }
+
+    #[test]
+    fn arcs_become_cubics_on_the_ellipse() {
+        let c = cmds("M 0 0 A 10 10 0 0 1 20 0");
+        assert_eq!(c.len(), 3);
+        let mid = c[1].end();
+        assert!((mid.distance(v(10., 0.)) - 10.).abs() < 1e-4, "{:?}", mid);
+        assert_eq!(c[2].end(), v(20., 0.));
+        // Sweeping the other way goes through the other side.
+        let other = cmds("M 0 0 A 10 10 0 0 0 20 0")[1].end();
+        assert!(is_near(other, v(mid.x, -mid.y)), "{:?} {:?}", mid, other);
+        // Radii too small for the end points are scaled up.
+        assert!(is_near(cmds("M 0 0 a 1 1 0 0 1 20 0")[1].end(), mid));
+        // The large arc is 300° here, so it takes 4 curves.
+        assert_eq!(cmds("M 0 0 A 20 20 0 1 1 20 0").len(), 5);
+        assert_eq!(cmds("M 0 0 A 0 10 0 0 1 20 0")[1], PathCmd::Line { end: v(20., 0.) });
+        assert_eq!(cmds("M 0 0 A 10 10 0 0 1 0 0").len(), 1);
+        // Flags need no separators.
+        assert_eq!(cmds("M 0 0 A 5 5 0 1020 0").last().unwrap().end(), v(20., 0.));
+    }
+
+    #[test]
+    fn malformed_path_data_is_an_error() {
+        assert_eq!(parse_path_data("L 0 0").unwrap_err().0, 0);
+        assert_eq!(parse_path_data("M 0 0 L 1 x").unwrap_err(), (10, "expected a number".to_owned()));
+        assert_eq!(parse_path_data("M 0 0 A 1 1 0 2 0 1 1").unwrap_err(), (14, "expected a flag (0 or 1)".to_owned()));
+        assert_eq!(parse_path_data("M 0 0 X").unwrap_err(), (6, "expected a command".to_owned()));
+        assert_eq!(parse_path_data("M 0 0 L").unwrap_err(), (7, "expected a number".to_owned()));
+    }
+
+    #[test]
+    fn import_maps_the_view_box_and_inherits_styles() {
+        let src = r#"<svg viewBox="0 0 100 50">
+            <g fill="blue" stroke-width="3" transform="translate(50 25)">
+                <path id="a b" style="fill: #fff; stroke: rgb(0, 50%, 255); stroke-width: 1em" d="M 0 0 L 10 0 L 10 10 Z"/>
+                <path opacity="0.5" fill-opacity="50%" stroke="red" fill-rule="evenodd" d="M 0 0 L 1 1"/>
+                <path display="none" d="M 0 0 L 1 1"/>
+                <rect width="1" height="1"/>
+            </g>
+            <defs><path d="M 0 0 L 1 1"/></defs>
+        </svg>"#;
+        let shapes = import(src, "t").unwrap();
+        assert_eq!(shapes.len(), 2);
+
+        let (ref name, ref shape) = shapes[0];
+        assert_eq!(name, "t_a_b");
+        assert_eq!(shape.path.subpaths[0].cmds[..3].to_vec(), vec![
+            PathCmd::Start(v(0., 0.)),
+            PathCmd::Line { end: v(0.2, 0.) },
+            PathCmd::Line { end: v(0.2, -0.2) },
+        ]);
+        assert_eq!(shape.style.fill_color, Rgba::white());
+        assert_eq!(shape.style.stroke_color, Rgba::new(0., 0.5, 1., 1.));
+        assert_eq!(shape.style.stroke_thickness, 16. / 50. * PIXELS_PER_UNIT);
+
+        let (ref name, ref shape) = shapes[1];
+        assert_eq!(name, "t_2");
+        assert_eq!(shape.style.fill_color, Rgba::new(0., 0., 1., 0.25));
+        assert_eq!(shape.style.stroke_color, Rgba::new(1., 0., 0., 0.5));
+        assert_eq!(shape.style.stroke_thickness, 3. / 50. * PIXELS_PER_UNIT);
+        assert_eq!(shape.style.fill_rule, FillRule::EvenOdd);
+    }
+
+    #[test]
+    fn import_without_a_view_box_fits_the_paths() {
+        let shapes = import(r#"<svg><path d="M 10 10 L 30 10 L 30 20"/></svg>"#, "t").unwrap();
+        assert_eq!(shapes[0].1.path.subpaths[0].cmds, vec![
+            PathCmd::Start(v(-1., 0.5)),
+            PathCmd::Line { end: v(1., 0.5) },
+            PathCmd::Line { end: v(1., -0.5) },
+        ]);
+        let shapes = import(r#"<svg width="4in" height="2in"><path d="M 0 0 L 384 192"/></svg>"#, "t").unwrap();
+        assert_eq!(shapes[0].1.path.subpaths[0].cmds[1], PathCmd::Line { end: v(1., -0.5) });
+    }
+
+    #[test]
+    fn gradients_are_inherited_and_mapped_to_the_bounding_box() {
+        let src = r##"<svg viewBox="0 0 100 100">
+            <defs>
+                <linearGradient id="base" spreadMethod="reflect">
+                    <stop offset="0" stop-color="red"/>
+                    <stop offset="100%" style="stop-color: #00f; stop-opacity: 0.5"/>
+                </linearGradient>
+                <linearGradient id="g" xlink:href="#base" x1="0" y1="0" x2="0" y2="1"/>
+                <radialGradient id="r" href="#base" gradientUnits="userSpaceOnUse" cx="50" cy="50" r="25"/>
+            </defs>
+            <path fill="url(#g)" d="M 0 0 H 100 V 100 H 0 Z"/>
+            <path fill="url(#r)" fill-opacity="0.5" d="M 0 0 H 100 V 100 H 0 Z"/>
+            <path fill="url(#missing)" d="M 0 0 H 100 V 100 H 0 Z"/>
+        </svg>"##;
+        let shapes = import(src, "t").unwrap();
+        let stops = vec![
+            GradientStop { offset: 0., color: Rgba::new(1., 0., 0., 1.) },
+            GradientStop { offset: 1., color: Rgba::new(0., 0., 1., 0.5) },
+        ];
+
+        let style = &shapes[0].1.style;
+        assert_eq!(style.fill_color, Rgba::zero());
+        assert_eq!(style.fill_gradient, Gradient {
+            kind: GradientKind::Linear,
+            spread: GradientSpread::Reflect,
+            start: v(-1., 1.),
+            end: v(-1., -1.),
+            stops: stops.clone(),
+        });
+
+        let gradient = &shapes[1].1.style.fill_gradient;
+        assert_eq!((gradient.kind, gradient.start, gradient.end), (GradientKind::Radial, v(0., 0.), v(0.5, 0.)));
+        assert_eq!(gradient.stops[1].color, Rgba::new(0., 0., 1., 0.25));
+
+        assert_eq!(shapes[2].1.style.fill_color, Rgba::black());
+        assert_eq!(shapes[2].1.style.fill_gradient, Gradient::none());
+    }
+
+    // Counter-clockwise.
+    fn rect(min: Vec2<f32>, max: Vec2<f32>) -> Path {
+        let mut subpath = SubPath::new(min);
+        for &p in &[v(max.x, min.y), max, v(min.x, max.y)] {
+            subpath.cmds.push(PathCmd::Line { end: p });
+        }
+        subpath.is_closed = true;
+        Path { subpaths: vec![subpath] }
+    }
+
+    fn is_near_color(a: Rgba<f32>, b: Rgba<f32>) -> bool {
+        (a - b).into_array().iter().all(|x| x.abs() <= 0.5 / 255.)
+    }
+
+    #[test]
+    fn exported_shapes_are_imported_back() {
+        // The stroke reaches -1 and 1, so that world units survive import.
+        let mut shape = Shape { path: rect(v(-0.95, -0.5), v(0.95, 0.5)), .. Shape::default() };
+        {
+            let style = &mut shape.style;
+            style.stroke_thickness = 24.;
+            style.stroke_color = Rgba::new(1., 0.2, 0., 0.5);
+            style.stroke_join = StrokeJoin::Bevel;
+            style.stroke_cap = StrokeCap::Square;
+            style.stroke_miter_limit = 2.;
+            style.stroke_dashes = vec![12., 6.];
+            style.stroke_dash_offset = 3.;
+            style.fill_rule = FillRule::EvenOdd;
+            style.fill_color = Rgba::zero();
+            style.fill_gradient = Gradient {
+                kind: GradientKind::Linear,
+                spread: GradientSpread::Repeat,
+                start: v(-0.5, 0.),
+                end: v(0.5, 0.25),
+                stops: vec![
+                    GradientStop { offset: 0., color: Rgba::new(1., 0., 0., 1.) },
+                    GradientStop { offset: 0.4, color: Rgba::new(0., 0.6, 1., 0.2) },
+                ],
+            };
+        }
+        let mut buf = vec![];
+        write(&mut buf, &[("s", &shape, Mat4::identity())]).unwrap();
+        let mut imported = import(&String::from_utf8(buf).unwrap(), "t").unwrap();
+        assert_eq!(imported.len(), 1);
+        let (name, imported) = imported.pop().unwrap();
+        assert_eq!(name, "t_s");
+
+        let points = |shape: &Shape| shape.path.subpaths[0].generate_vertex_positions(0.001);
+        let (a, b) = (points(&shape), points(&imported));
+        assert_eq!(a.len(), b.len());
+        assert!(a.iter().zip(b.iter()).all(|(&a, &b)| is_near(a, b)), "{:?} {:?}", a, b);
+        assert!(imported.path.subpaths[0].is_closed);
+
+        let (style, expected) = (&imported.style, &shape.style);
+        assert!((style.stroke_thickness - expected.stroke_thickness).abs() < 1e-3, "{}", style.stroke_thickness);
+        assert!(is_near_color(style.stroke_color, expected.stroke_color), "{:?}", style.stroke_color);
+        assert_eq!((style.stroke_join, style.stroke_cap, style.stroke_miter_limit), (StrokeJoin::Bevel, StrokeCap::Square, 2.));
+        assert!(style.stroke_dashes.iter().zip(&[12., 6.]).all(|(a, b)| (a - b).abs() < 1e-3), "{:?}", style.stroke_dashes);
+        assert!((style.stroke_dash_offset - 3.).abs() < 1e-3);
+        assert_eq!(style.fill_rule, FillRule::EvenOdd);
+        assert_eq!(style.fill_color, Rgba::zero());
+
+        let (gradient, expected) = (&style.fill_gradient, &expected.fill_gradient);
+        assert_eq!((gradient.kind, gradient.spread), (GradientKind::Linear, GradientSpread::Repeat));
+        assert!(is_near(gradient.start, expected.start) && is_near(gradient.end, expected.end), "{:?}", gradient);
+        assert_eq!(gradient.stops.len(), 2);
+        for (stop, expected) in gradient.stops.iter().zip(&expected.stops) {
+            assert_eq!(stop.offset, expected.offset);
+            assert!(is_near_color(stop.color, expected.color), "{:?}", stop.color);
+        }
+    }
+
+    #[test]
+    fn export_writes_model_matrices_in_order() {
+        let mut shape = Shape { path: rect(v(0., 0.), v(1., 1.)), .. Shape::default() };
+        shape.style.stroke_thickness = 0.;
+        let mut model = Mat4::scaling_3d(Vec3::new(2., 3., 1.));
+        model.cols.w = ::v::Vec4::new(4., -5., 0., 1.);
+        let mut buf = vec![];
+        write(&mut buf, &[("back", &shape, Mat4::identity()), ("front", &shape, model)]).unwrap();
+        let src = String::from_utf8(buf).unwrap();
+        let back = src.find(r#"<g transform="matrix(1 0 0 1 0 0)">"#).unwrap();
+        let front = src.find(r#"<g transform="matrix(2 0 0 3 4 -5)">"#).unwrap();
+        assert!(back < src.find(r#"id="back""#).unwrap());
+        assert!(back < front && front < src.find(r#"id="front""#).unwrap());
+        // From (0, -5) to (6, 1), with no stroke to make it larger.
+        assert!(src.contains(r#"viewBox="0 -1 6 6""#), "{}", src);
+    }
+
+    #[test]
+    fn import_errors() {
+        assert_eq!(error_kind("<svg><g/></svg>"), (1, SvgErrorKind::NoPaths));
+        assert_eq!(error_kind("<svg></svg></g>"), (1, SvgErrorKind::Xml("unbalanced closing tag".to_owned())));
+        assert_eq!(error_kind("<svg>\n<path fill=\"#aé\" d=\"M 0 0 L 1 1\"/></svg>"),
+            (2, SvgErrorKind::BadAttribute { name: "fill".to_owned(), value: "#aé".to_owned() }));
+        assert_eq!(error_kind("<svg><path style=\"stroke-width: 1furlong\" d=\"M 0 0 L 1 1\"/></svg>"),
+            (1, SvgErrorKind::BadAttribute { name: "stroke-width".to_owned(), value: "1furlong".to_owned() }));
+        assert_eq!(error_kind("<svg>\n\n<path d=\"M 0 0 L\"/></svg>"),
+            (3, SvgErrorKind::PathData { offset: 7, msg: "expected a number".to_owned() }));
+        assert_eq!(error_kind("<svg viewBox=\"0 0 1\"><path d=\"M 0 0 L 1 1\"/></svg>"),
+            (1, SvgErrorKind::BadAttribute { name: "viewBox".to_owned(), value: "0 0 1".to_owned() }));
+        // Font-relative lengths are fine.
+        assert!(import("<svg><path stroke-width=\"0.5em\" d=\"M 0 0 L 1 1\"/></svg>", "t").is_ok());
+    }
+}
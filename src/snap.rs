@@ -0,0 +1,246 @@
+// Snapping of positions in the editor, to the grid, to nodes, to angles
+// around a previous node, and to guide lines.
+//
+// Each kind can be turned on and off separately. When several of them are
+// close enough, the most specific one wins: nodes, then guides, then
+// angles, then the grid.
+
+use std::f32::consts::PI;
+use v::Vec2;
+
+/// The grid drawn by the editor has lines this far apart...
+pub const GRID_STEPS: [f32; 2] = [0.1, 1.];
+/// ...of which the finest one whose lines are at least this many screen pixels apart is snapped to.
+pub const MIN_GRID_SPACING: f32 = 16.;
+
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum SnapKind {
+    Grid,
+    Node,
+    Angle,
+    Guide,
+}
+
+impl SnapKind {
+    pub const ALL: [SnapKind; 4] = [SnapKind::Grid, SnapKind::Node, SnapKind::Angle, SnapKind::Guide];
+
+    /// The keyword for this kind, in editor commands.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            SnapKind::Grid => "grid",
+            SnapKind::Node => "nodes",
+            SnapKind::Angle => "angles",
+            SnapKind::Guide => "guides",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "grid" => Some(SnapKind::Grid),
+            "nodes" => Some(SnapKind::Node),
+            "angles" => Some(SnapKind::Angle),
+            "guides" => Some(SnapKind::Guide),
+            _ => None,
+        }
+    }
+}
+
+/// A line placed by the user, across the whole world.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Guide {
+    Horizontal { y: f32 },
+    Vertical { x: f32 },
+}
+
+impl Guide {
+    /// The point of the guide which is closest to `p`.
+    pub fn closest_point(&self, p: Vec2<f32>) -> Vec2<f32> {
+        match *self {
+            Guide::Horizontal { y } => Vec2::new(p.x, y),
+            Guide::Vertical { x } => Vec2::new(x, p.y),
+        }
+    }
+}
+
+/// Where a position got snapped to, and why.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Snap {
+    Grid(Vec2<f32>),
+    Node(Vec2<f32>),
+    /// On a line from `origin`, at a multiple of the angle step.
+    Angle { origin: Vec2<f32>, position: Vec2<f32> },
+    /// On `guides[guide]`, or where it crosses another one.
+    Guide { guide: usize, position: Vec2<f32> },
+}
+
+impl Snap {
+    pub fn position(&self) -> Vec2<f32> {
+        match *self {
+            Snap::Grid(p) | Snap::Node(p) => p,
+            Snap::Angle { position, .. } | Snap::Guide { position, .. } => position,
+        }
+    }
+    pub fn kind(&self) -> SnapKind {
+        match *self {
+            Snap::Grid(_) => SnapKind::Grid,
+            Snap::Node(_) => SnapKind::Node,
+            Snap::Angle { .. } => SnapKind::Angle,
+            Snap::Guide { .. } => SnapKind::Guide,
+        }
+    }
+}
+
+/// What is around the position being snapped. All positions are in world space.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SnapTargets<'a> {
+    pub nodes: &'a [Vec2<f32>],
+    /// The previous node, for angle snapping.
+    pub origin: Option<Vec2<f32>>,
+    pub grid_step: f32,
+    /// Targets farther than this are ignored.
+    pub max_distance: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapping {
+    /// Indexed by `SnapKind as usize`.
+    is_enabled: [bool; 4],
+    pub guides: Vec<Guide>,
+    pub angle_step_radians: f32,
+}
+
+impl Default for Snapping {
+    fn default() -> Self {
+        Self {
+            is_enabled: [true; 4],
+            guides: vec![],
+            angle_step_radians: 15_f32.to_radians(),
+        }
+    }
+}
+
+/// The step of the grid to snap to, given the current zoom level.
+pub fn grid_step(pixels_per_unit: f32) -> f32 {
+    for &step in GRID_STEPS.iter() {
+        if step * pixels_per_unit >= MIN_GRID_SPACING {
+            return step;
+        }
+    }
+    GRID_STEPS[GRID_STEPS.len() - 1]
+}
+
+impl Snapping {
+    pub fn is_enabled(&self, kind: SnapKind) -> bool {
+        self.is_enabled[kind as usize]
+    }
+    pub fn set_enabled(&mut self, kind: SnapKind, is_enabled: bool) {
+        self.is_enabled[kind as usize] = is_enabled;
+    }
+    /// Where `p` snaps to, if anywhere.
+    pub fn snap(&self, p: Vec2<f32>, targets: &SnapTargets) -> Option<Snap> {
+        let is_near = |q: Vec2<f32>| q.distance(p) <= targets.max_distance;
+        if self.is_enabled(SnapKind::Node) {
+            let nearest = targets.nodes.iter().cloned().filter(|&q| is_near(q))
+                .fold(None, |nearest: Option<Vec2<f32>>, q| match nearest {
+                    Some(n) if n.distance(p) <= q.distance(p) => Some(n),
+                    _ => Some(q),
+                });
+            if let Some(q) = nearest {
+                return Some(Snap::Node(q));
+            }
+        }
+        if self.is_enabled(SnapKind::Guide) {
+            let mut near = self.guides.iter().enumerate()
+                .map(|(i, guide)| (i, guide.closest_point(p)))
+                .filter(|&(_, q)| is_near(q));
+            if let Some((guide, q)) = near.next() {
+                // Where a horizontal and a vertical guide cross.
+                let crossing = near.map(|(i, _)| self.guides[i])
+                    .find(|&other| !same_direction(other, self.guides[guide]))
+                    .map(|other| other.closest_point(q))
+                    .filter(|&c| is_near(c));
+                return Some(Snap::Guide { guide, position: crossing.unwrap_or(q) });
+            }
+        }
+        if let (true, Some(origin)) = (self.is_enabled(SnapKind::Angle), targets.origin) {
+            let d = p - origin;
+            if d != Vec2::zero() && self.angle_step_radians > 0. {
+                let angle = (d.y.atan2(d.x) / self.angle_step_radians).round() * self.angle_step_radians;
+                let (sin, cos) = (angle % (2. * PI)).sin_cos();
+                let direction = Vec2::new(cos, sin);
+                let position = origin + direction * d.dot(direction).max(0.);
+                if is_near(position) {
+                    return Some(Snap::Angle { origin, position });
+                }
+            }
+        }
+        if self.is_enabled(SnapKind::Grid) && targets.grid_step > 0. {
+            let position = (p / targets.grid_step).map(|x| x.round()) * targets.grid_step;
+            if is_near(position) {
+                return Some(Snap::Grid(position));
+            }
+        }
+        None
+    }
+}
+
+fn same_direction(a: Guide, b: Guide) -> bool {
+    match (a, b) {
+        (Guide::Horizontal { .. }, Guide::Horizontal { .. }) => true,
+        (Guide::Vertical { .. }, Guide::Vertical { .. }) => true,
+        _ => false,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn targets<'a>(nodes: &'a [Vec2<f32>], origin: Option<Vec2<f32>>) -> SnapTargets<'a> {
+        SnapTargets { nodes, origin, grid_step: 1., max_distance: 0.2 }
+    }
+
+    #[test]
+    fn nodes_win_over_the_grid() {
+        let snapping = Snapping::default();
+        let nodes = [Vec2::new(0.1, 0.1), Vec2::new(3., 3.)];
+        assert_eq!(snapping.snap(Vec2::new(0.05, 0.), &targets(&nodes, None)), Some(Snap::Node(nodes[0])));
+        assert_eq!(snapping.snap(Vec2::new(1.9, 2.1), &targets(&nodes, None)), Some(Snap::Grid(Vec2::new(2., 2.))));
+        assert_eq!(snapping.snap(Vec2::new(1.5, 2.5), &targets(&nodes, None)), None);
+    }
+
+    #[test]
+    fn disabled_kinds_are_skipped() {
+        let mut snapping = Snapping::default();
+        snapping.set_enabled(SnapKind::Node, false);
+        let nodes = [Vec2::new(0.1, 0.1)];
+        assert_eq!(snapping.snap(Vec2::new(0.05, 0.), &targets(&nodes, None)), Some(Snap::Grid(Vec2::zero())));
+        snapping.set_enabled(SnapKind::Grid, false);
+        assert_eq!(snapping.snap(Vec2::new(0.05, 0.), &targets(&nodes, None)), None);
+    }
+
+    #[test]
+    fn angles_snap_around_the_origin() {
+        let snapping = Snapping::default();
+        let origin = Vec2::new(0.5, 0.5);
+        let snap = snapping.snap(origin + Vec2::new(2., 0.58), &targets(&[], Some(origin))).unwrap();
+        assert_eq!(snap.kind(), SnapKind::Angle);
+        let d = snap.position() - origin;
+        assert!((d.y.atan2(d.x) - 15_f32.to_radians()).abs() < 1e-5);
+    }
+
+    #[test]
+    fn guides_snap_to_their_crossing() {
+        let mut snapping = Snapping::default();
+        snapping.guides = vec![Guide::Horizontal { y: 0.55 }, Guide::Vertical { x: 0.45 }];
+        let snap = snapping.snap(Vec2::new(0.4, 0.6), &targets(&[], None));
+        assert_eq!(snap, Some(Snap::Guide { guide: 0, position: Vec2::new(0.45, 0.55) }));
+    }
+
+    #[test]
+    fn grid_step_follows_the_zoom() {
+        assert_eq!(grid_step(240.), 0.1);
+        assert_eq!(grid_step(100.), 1.);
+        assert_eq!(grid_step(1.), 1.);
+    }
+}
@@ -2,7 +2,7 @@ use v::{self, Vec2, Vec3, Mat4};
 
 pub type Xform3D = v::Transform<f32, f32, f32>;
 
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Xform2D {
     pub position: Vec3<f32>,
     pub rotation_z_radians: f32,
@@ -41,5 +41,25 @@ impl Xform2D {
     pub fn model_matrix(&self) -> Mat4<f32> {
         Mat4::scaling_3d(self.scale).rotated_z(self.rotation_z_radians).translated_3d(self.position)
     }
+    /// Where the point `p` of the shape ends up in the world; same as `model_matrix()`, in 2D.
+    pub fn transform_point(&self, p: Vec2<f32>) -> Vec2<f32> {
+        (p * self.scale).rotated_z(self.rotation_z_radians) + Vec2::from(self.position)
+    }
+    /// Goes from `self` at `t` = 0 to `to` at `t` = 1, component by component.
+    pub fn lerp(&self, to: &Self, t: f32) -> Self {
+        Self {
+            position: self.position + (to.position - self.position) * t,
+            rotation_z_radians: self.rotation_z_radians + (to.rotation_z_radians - self.rotation_z_radians) * t,
+            scale: self.scale + (to.scale - self.scale) * t,
+        }
+    }
+    /// The inverse of `transform_point()`. Axes which are scaled to zero map to zero.
+    pub fn inverse_transform_point(&self, p: Vec2<f32>) -> Vec2<f32> {
+        let p = (p - Vec2::from(self.position)).rotated_z(-self.rotation_z_radians);
+        Vec2::new(
+            if self.scale.x == 0. { 0. } else { p.x / self.scale.x },
+            if self.scale.y == 0. { 0. } else { p.y / self.scale.y },
+        )
+    }
 }
 